@@ -0,0 +1,72 @@
+// The shape of `Document::to_json()` on the Rust side, which src/schema.json describes too
+
+export interface Document {
+  version: 1;
+  title: string | null;
+  attributes: Record<string, string | null>;
+  blocks: Block[];
+}
+
+export type Block =
+  | { type: 'section'; level: number; title: string; id: string | null; number: string | null; blocks: Block[] }
+  | { type: 'paragraph'; content: Inline[] }
+  | { type: 'admonition'; kind: 'note' | 'tip' | 'important' | 'warning' | 'caution'; content: Inline[]; blocks: Block[] }
+  | { type: 'delimited'; kind: 'listing' | 'example' | 'literal'; content: string; language: string | null; title: string | null }
+  | { type: 'delimited'; kind: 'quote'; author: string | null; citation: string | null; content: string; language: string | null; title: string | null }
+  | { type: 'sidebar'; title: string | null; roles: string[]; blocks: Block[] }
+  | { type: 'table'; title: string | null; header: boolean; rows: TableCell[][] }
+  | { type: 'image'; path: string; alt: string | null; width: string | null; height: string | null; title: string | null }
+  | { type: 'list'; kind: 'unordered' | 'ordered'; items: ListItem[] }
+  | { type: 'list'; kind: 'description'; style: 'stacked' | 'horizontal' | 'qanda' | 'glossary'; items: DescriptionItem[] }
+  | { type: 'metadata'; kind: 'title'; title: string }
+  | { type: 'metadata'; kind: 'attribute'; values: string[] }
+  | { type: 'metadata'; kind: 'anchor'; id: string }
+  | { type: 'metadata'; kind: 'attribute-entry'; name: string; value: string | null }
+  | { type: 'metadata'; kind: 'attribute-unset'; name: string }
+  | { type: 'toc'; levels: number | null }
+  | { type: 'page-break' }
+  | { type: 'comment'; text: string; delimited: boolean };
+
+export interface TableCell {
+  content: Inline[];
+  colspan: number;
+  rowspan: number;
+  align: 'left' | 'center' | 'right' | null;
+}
+
+export interface ListItem {
+  content: Inline[];
+  blocks: Block[];
+}
+
+export interface DescriptionItem {
+  term: string;
+  description: Inline[] | null;
+  blocks: Block[];
+}
+
+export type Inline =
+  | { type: 'text'; text: string }
+  | {
+      type: 'formatted';
+      kind: 'strong' | 'emphasis' | 'monospace' | 'literal-monospace' | 'superscript' | 'subscript' | 'mark';
+      content: Inline[];
+      id: string | null;
+      roles: string[];
+    }
+  | { type: 'link'; url: string; text: string | null }
+  | { type: 'image'; path: string; attributes: string | null }
+  | { type: 'xref'; target: string; text: string | null }
+  | { type: 'term'; term: string; target: string | null }
+  | { type: 'linebreak' }
+  | { type: 'passthrough'; content: string };
+
+export interface HtmlOptions {
+  // Restricts link and image targets for documents from untrusted authors
+  sanitize?: boolean;
+}
+
+export function parse(input: string): Document;
+export function convertToHtml(input: string, options?: HtmlOptions): string;
+export function convertToLatex(input: string): string;
+export function formatAsciidoc(input: string): string;
@@ -0,0 +1,21 @@
+'use strict';
+
+const wasm = require('./pkg/adoc.js');
+
+function parse(input) {
+  return JSON.parse(wasm.parseToJson(input));
+}
+
+function convertToHtml(input, options = {}) {
+  return wasm.convertToHtml(input, Boolean(options.sanitize));
+}
+
+function convertToLatex(input) {
+  return wasm.convertToLatex(input);
+}
+
+function formatAsciidoc(input) {
+  return wasm.formatAsciidoc(input);
+}
+
+module.exports = { parse, convertToHtml, convertToLatex, formatAsciidoc };
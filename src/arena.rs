@@ -0,0 +1,564 @@
+use bumpalo::Bump;
+
+use crate::ast;
+use crate::ast::{AdmonitionKind, CellAlignment, DelimitedBlockKind, DescriptionListStyle, FormattedTextKind, ListKind, escape_html, quote_html, xref_href};
+use crate::context::{self, TocEntry};
+use crate::parser::glossary_id;
+
+// Borrowed mirror of the `ast` types whose nodes and strings all live in one bump arena,
+// so a whole tree is released at once when the arena is reset or dropped. A tree gets here
+// by copying a parsed `ast::Document` with `Document::alloc_in`; parsing itself still
+// allocates each node on the heap, so this saves nothing while parsing, only in how long
+// the kept tree's memory lives and how it is freed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Document<'a> {
+    pub header: Option<Header<'a>>,
+    pub body: &'a [Block<'a>],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Header<'a> {
+    pub title: &'a str,
+    pub attributes: &'a [Attribute<'a>],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attribute<'a> {
+    pub name: &'a str,
+    pub value: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block<'a> {
+    Section {
+        level: usize,
+        title: &'a str,
+        id: Option<&'a str>,
+        number: Option<&'a str>,
+        blocks: &'a [Block<'a>],
+    },
+    Paragraph {
+        content: &'a [InlineElement<'a>],
+    },
+    Admonition {
+        kind: AdmonitionKind,
+        content: &'a [InlineElement<'a>],
+        blocks: &'a [Block<'a>],
+    },
+    DelimitedBlock {
+        kind: DelimitedBlockKind,
+        content: &'a str,
+        language: Option<&'a str>,
+        title: Option<&'a str>,
+    },
+    Sidebar {
+        title: Option<&'a str>,
+        roles: &'a [&'a str],
+        blocks: &'a [Block<'a>],
+    },
+    List {
+        kind: ListKind,
+        items: &'a [ListItem<'a>],
+    },
+    Table {
+        title: Option<&'a str>,
+        header: bool,
+        rows: &'a [&'a [TableCell<'a>]],
+    },
+    Image {
+        path: &'a str,
+        alt: Option<&'a str>,
+        width: Option<&'a str>,
+        height: Option<&'a str>,
+        title: Option<&'a str>,
+    },
+    BlockMetadata {
+        kind: BlockMetadataKind<'a>,
+    },
+    TableOfContents {
+        levels: Option<usize>,
+    },
+    PageBreak,
+    Comment {
+        text: &'a str,
+        delimited: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TableCell<'a> {
+    pub content: &'a [InlineElement<'a>],
+    pub colspan: usize,
+    pub rowspan: usize,
+    pub align: Option<CellAlignment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListItem<'a> {
+    Unordered {
+        content: &'a [InlineElement<'a>],
+        blocks: &'a [Block<'a>],
+    },
+    Ordered {
+        content: &'a [InlineElement<'a>],
+        blocks: &'a [Block<'a>],
+    },
+    Description {
+        term: &'a str,
+        description: Option<&'a [InlineElement<'a>]>,
+        blocks: &'a [Block<'a>],
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockMetadataKind<'a> {
+    Title(&'a str),
+    Attribute(&'a [&'a str]),
+    Anchor(&'a str),
+    AttributeEntry(Attribute<'a>),
+    AttributeUnset(&'a str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InlineElement<'a> {
+    Text(&'a str),
+    Formatted {
+        kind: FormattedTextKind,
+        content: &'a [InlineElement<'a>],
+        id: Option<&'a str>,
+        roles: &'a [&'a str],
+    },
+    Macro {
+        kind: MacroKind<'a>,
+    },
+    LineBreak,
+    Passthrough(&'a str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MacroKind<'a> {
+    Link {
+        url: &'a str,
+        text: Option<&'a str>,
+    },
+    Image {
+        path: &'a str,
+        attributes: Option<&'a str>,
+    },
+    CrossReference {
+        target: &'a str,
+        text: Option<&'a str>,
+    },
+    Term {
+        term: &'a str,
+        target: Option<&'a str>,
+    },
+}
+
+impl<'a> Document<'a> {
+    // Copies every node and string of `document` into `arena`
+    pub fn alloc_in(arena: &'a Bump, document: &ast::Document) -> Document<'a> {
+        Document {
+            header: document.header.as_ref().map(|header| Header {
+                title: arena.alloc_str(&header.title),
+                attributes: arena.alloc_slice_fill_iter(header.attributes.iter().map(|attribute| Attribute {
+                    name: arena.alloc_str(&attribute.name),
+                    value: attribute.value.as_deref().map(|value| &*arena.alloc_str(value)),
+                })),
+            }),
+            body: alloc_blocks(arena, &document.body),
+        }
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&'a str> {
+        let header = self.header.as_ref()?;
+        header.attributes.iter()
+            .find(|attribute| attribute.name == name)
+            .map(|attribute| attribute.value.unwrap_or(""))
+    }
+    
+    // Renders into a single buffer instead of building a string per node
+    pub fn to_html(&self) -> String {
+        let options = self.html_options();
+        let mut html = String::new();
+        if let Some(header) = &self.header {
+            html.push_str(&ast::header_html(header.title, &|name| self.attribute(name), &options));
+        }
+        write_blocks_html(self.body, &options, &mut html);
+        let entries = self.header.iter().flat_map(|header| header.attributes).map(|attribute| (attribute.name, attribute.value.unwrap_or("")));
+        let revisions = context::revisions(entries, &|name| self.attribute(name));
+        html.push_str(&ast::revision_history_html(&revisions, self.attribute("revhistory")));
+        html.push_str(&ast::footer_html(&|name| self.attribute(name), &options));
+        html
+    }
+    
+    // The header attributes `ast::HtmlOptions::for_document` reads
+    fn html_options(&self) -> ast::HtmlOptions {
+        ast::HtmlOptions {
+            section_anchors: self.attribute("sectanchors").is_some(),
+            section_links: self.attribute("sectlinks").is_some(),
+            toc_levels: self.attribute("toclevels").and_then(|levels| levels.parse().ok()).unwrap_or(ast::DEFAULT_TOCLEVELS),
+            ..Default::default()
+        }
+    }
+}
+
+fn alloc_blocks<'a>(arena: &'a Bump, blocks: &[ast::Block]) -> &'a [Block<'a>] {
+    arena.alloc_slice_fill_iter(blocks.iter().map(|block| alloc_block(arena, block)))
+}
+
+fn alloc_block<'a>(arena: &'a Bump, block: &ast::Block) -> Block<'a> {
+    match block {
+        ast::Block::Section { level, title, id, number, blocks } => Block::Section {
+            level: *level,
+            title: arena.alloc_str(title),
+            id: id.as_deref().map(|id| &*arena.alloc_str(id)),
+            number: number.as_deref().map(|number| &*arena.alloc_str(number)),
+            blocks: alloc_blocks(arena, blocks),
+        },
+        ast::Block::Paragraph { content } => Block::Paragraph { content: alloc_inlines(arena, content) },
+        ast::Block::Admonition { kind, content, blocks } => Block::Admonition {
+            kind: *kind,
+            content: alloc_inlines(arena, content),
+            blocks: alloc_blocks(arena, blocks),
+        },
+        ast::Block::DelimitedBlock { kind, content, language, title } => Block::DelimitedBlock {
+            kind: kind.clone(),
+            content: arena.alloc_str(content),
+            language: language.as_deref().map(|language| &*arena.alloc_str(language)),
+            title: title.as_deref().map(|title| &*arena.alloc_str(title)),
+        },
+        ast::Block::Sidebar { title, roles, blocks } => Block::Sidebar {
+            title: title.as_deref().map(|title| &*arena.alloc_str(title)),
+            roles: arena.alloc_slice_fill_iter(roles.iter().map(|role| &*arena.alloc_str(role))),
+            blocks: alloc_blocks(arena, blocks),
+        },
+        ast::Block::List { kind, items } => Block::List {
+            kind: kind.clone(),
+            items: arena.alloc_slice_fill_iter(items.iter().map(|item| alloc_list_item(arena, item))),
+        },
+        ast::Block::Table { title, header, rows } => Block::Table {
+            title: title.as_deref().map(|title| &*arena.alloc_str(title)),
+            header: *header,
+            rows: arena.alloc_slice_fill_iter(rows.iter().map(|row| {
+                &*arena.alloc_slice_fill_iter(row.iter().map(|cell| TableCell {
+                    content: alloc_inlines(arena, &cell.content),
+                    colspan: cell.colspan,
+                    rowspan: cell.rowspan,
+                    align: cell.align,
+                }))
+            })),
+        },
+        ast::Block::BlockMetadata { kind } => Block::BlockMetadata {
+            kind: match kind {
+                ast::BlockMetadataKind::Title(title) => BlockMetadataKind::Title(arena.alloc_str(title)),
+                ast::BlockMetadataKind::Attribute(values) => BlockMetadataKind::Attribute(
+                    arena.alloc_slice_fill_iter(values.iter().map(|value| &*arena.alloc_str(value))),
+                ),
+                ast::BlockMetadataKind::Anchor(anchor) => BlockMetadataKind::Anchor(arena.alloc_str(anchor)),
+                ast::BlockMetadataKind::AttributeEntry(attribute) => BlockMetadataKind::AttributeEntry(Attribute {
+                    name: arena.alloc_str(&attribute.name),
+                    value: attribute.value.as_deref().map(|value| &*arena.alloc_str(value)),
+                }),
+                ast::BlockMetadataKind::AttributeUnset(name) => BlockMetadataKind::AttributeUnset(arena.alloc_str(name)),
+            },
+        },
+        ast::Block::Image { path, alt, width, height, title } => {
+            let optional = |value: &Option<String>| value.as_deref().map(|value| &*arena.alloc_str(value));
+            Block::Image { path: arena.alloc_str(path), alt: optional(alt), width: optional(width), height: optional(height), title: optional(title) }
+        }
+        ast::Block::TableOfContents { levels } => Block::TableOfContents { levels: *levels },
+        ast::Block::PageBreak => Block::PageBreak,
+        ast::Block::Comment { text, delimited } => Block::Comment { text: arena.alloc_str(text), delimited: *delimited },
+    }
+}
+
+fn alloc_list_item<'a>(arena: &'a Bump, item: &ast::ListItem) -> ListItem<'a> {
+    match item {
+        ast::ListItem::Unordered { content, blocks } => ListItem::Unordered {
+            content: alloc_inlines(arena, content),
+            blocks: alloc_blocks(arena, blocks),
+        },
+        ast::ListItem::Ordered { content, blocks } => ListItem::Ordered {
+            content: alloc_inlines(arena, content),
+            blocks: alloc_blocks(arena, blocks),
+        },
+        ast::ListItem::Description { term, description, blocks } => ListItem::Description {
+            term: arena.alloc_str(term),
+            description: description.as_deref().map(|description| alloc_inlines(arena, description)),
+            blocks: alloc_blocks(arena, blocks),
+        },
+    }
+}
+
+fn alloc_inlines<'a>(arena: &'a Bump, elements: &[ast::InlineElement]) -> &'a [InlineElement<'a>] {
+    arena.alloc_slice_fill_iter(elements.iter().map(|element| alloc_inline(arena, element)))
+}
+
+fn alloc_inline<'a>(arena: &'a Bump, element: &ast::InlineElement) -> InlineElement<'a> {
+    let alloc_optional = |value: &Option<String>| value.as_deref().map(|value| &*arena.alloc_str(value));
+    match element {
+        ast::InlineElement::Text(text) => InlineElement::Text(arena.alloc_str(text)),
+        ast::InlineElement::Formatted { kind, content, attributes } => InlineElement::Formatted {
+            kind: kind.clone(),
+            content: alloc_inlines(arena, content),
+            id: alloc_optional(&attributes.id),
+            roles: arena.alloc_slice_fill_iter(attributes.roles.iter().map(|role| &*arena.alloc_str(role))),
+        },
+        ast::InlineElement::Macro { kind } => InlineElement::Macro {
+            kind: match kind {
+                ast::MacroKind::Link { url, text } => MacroKind::Link {
+                    url: arena.alloc_str(url),
+                    text: alloc_optional(text),
+                },
+                ast::MacroKind::Image { path, attributes } => MacroKind::Image {
+                    path: arena.alloc_str(path),
+                    attributes: alloc_optional(attributes),
+                },
+                ast::MacroKind::CrossReference { target, text } => MacroKind::CrossReference {
+                    target: arena.alloc_str(target),
+                    text: alloc_optional(text),
+                },
+                ast::MacroKind::Term { term, target } => MacroKind::Term {
+                    term: arena.alloc_str(term),
+                    target: alloc_optional(target),
+                },
+            },
+        },
+        ast::InlineElement::LineBreak => InlineElement::LineBreak,
+        ast::InlineElement::Passthrough(content) => InlineElement::Passthrough(arena.alloc_str(content)),
+    }
+}
+
+// A table of contents lists the sections among its sibling blocks, as in `ast`
+fn write_blocks_html(blocks: &[Block], options: &ast::HtmlOptions, html: &mut String) {
+    for block in blocks {
+        match block {
+            Block::TableOfContents { levels } => {
+                let entries = toc_entries(blocks, levels.unwrap_or(options.toc_levels));
+                html.push_str(&ast::toc_html(&entries, options));
+            }
+            _ => block.write_html_in(options, html),
+        }
+    }
+}
+
+fn toc_entries(blocks: &[Block], toc_levels: usize) -> Vec<TocEntry> {
+    blocks.iter()
+        .filter_map(|block| match block {
+            Block::Section { level, title, id, number, blocks } if *level <= toc_levels + 1 => Some(TocEntry {
+                id: id.unwrap_or_default().to_string(),
+                title: title.to_string(),
+                number: number.map(str::to_string),
+                level: *level,
+                children: toc_entries(blocks, toc_levels),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+impl Block<'_> {
+    pub fn write_html(&self, html: &mut String) {
+        self.write_html_in(&ast::HtmlOptions::default(), html);
+    }
+
+    fn write_html_in(&self, options: &ast::HtmlOptions, html: &mut String) {
+        match self {
+            Block::Section { level, title, id, number, blocks } => {
+                html.push_str(&ast::section_heading_html(*level, title, *id, *number, options));
+                write_blocks_html(blocks, options, html);
+            }
+            Block::Paragraph { content } => {
+                html.push_str("<p>");
+                write_inlines_html(content, html);
+                html.push_str("</p>\n");
+            }
+            Block::Admonition { kind, content, blocks } => {
+                let mut content_html = String::new();
+                write_inlines_html(content, &mut content_html);
+                write_blocks_html(blocks, options, &mut content_html);
+                html.push_str(&ast::admonition_html(*kind, &content_html));
+            }
+            Block::DelimitedBlock { kind, content, language, title } => {
+                let content = escape_html(content);
+                match kind {
+                    DelimitedBlockKind::Listing => {
+                        let code = match language {
+                            Some(lang) => format!("<pre><code class=\"language-{}\">{}</code></pre>\n", escape_html(lang), content),
+                            None => format!("<pre><code>{}</code></pre>\n", content),
+                        };
+                        html.push_str(&ast::listing_html(*title, &code));
+                    }
+                    DelimitedBlockKind::Example => html.push_str(&format!("<div class=\"example\">{}</div>\n", content)),
+                    DelimitedBlockKind::Literal => html.push_str(&format!("<pre>{}</pre>\n", content)),
+                    DelimitedBlockKind::Quote(attribution) => html.push_str(&quote_html(&content, attribution)),
+                }
+            }
+            Block::Sidebar { title, roles, blocks } => {
+                html.push_str(&ast::sidebar_start_html(*title, roles));
+                write_blocks_html(blocks, options, html);
+                html.push_str("</aside>\n");
+            }
+            Block::List { kind, items } => {
+                let (open, close) = match kind {
+                    ListKind::Unordered => ("<ul>\n", "</ul>\n"),
+                    ListKind::Ordered => ("<ol>\n", "</ol>\n"),
+                    ListKind::Description(DescriptionListStyle::Stacked) => ("<dl>\n", "</dl>\n"),
+                    ListKind::Description(DescriptionListStyle::Horizontal) => ("<table class=\"hdlist\">\n", "</table>\n"),
+                    ListKind::Description(DescriptionListStyle::Qanda) => ("<ol class=\"qanda\">\n", "</ol>\n"),
+                    ListKind::Description(DescriptionListStyle::Glossary) => ("<dl class=\"glossary\">\n", "</dl>\n"),
+                };
+                html.push_str(open);
+                for item in *items {
+                    match (kind, item) {
+                        (ListKind::Description(DescriptionListStyle::Horizontal), ListItem::Description { term, description, blocks }) => {
+                            html.push_str(&format!("<tr>\n<td class=\"hdlist1\">{}</td>\n<td class=\"hdlist2\">", escape_html(term)));
+                            if let Some(description) = description {
+                                write_inlines_html(description, html);
+                            }
+                            write_attached_blocks_html(blocks, html);
+                            html.push_str("</td>\n</tr>\n");
+                        }
+                        (ListKind::Description(DescriptionListStyle::Qanda), ListItem::Description { term, description, blocks }) => {
+                            html.push_str(&format!("<li>\n<p><em>{}</em></p>\n", escape_html(term)));
+                            if let Some(description) = description {
+                                html.push_str("<p>");
+                                write_inlines_html(description, html);
+                                html.push_str("</p>\n");
+                            }
+                            for block in *blocks {
+                                block.write_html(html);
+                            }
+                            html.push_str("</li>\n");
+                        }
+                        (ListKind::Description(DescriptionListStyle::Glossary), ListItem::Description { term, description, blocks }) => {
+                            html.push_str(&format!("<dt id=\"{}\">{}</dt>\n", escape_html(&glossary_id(term)), escape_html(term)));
+                            if description.is_some() || !blocks.is_empty() {
+                                html.push_str("<dd>");
+                                if let Some(description) = description {
+                                    write_inlines_html(description, html);
+                                }
+                                write_attached_blocks_html(blocks, html);
+                                html.push_str("</dd>\n");
+                            }
+                        }
+                        _ => item.write_html(html),
+                    }
+                }
+                html.push_str(close);
+            }
+            Block::Table { title, header, rows } => {
+                html.push_str(&ast::table_start_html(*title));
+                for (index, row) in rows.iter().enumerate() {
+                    let cells: Vec<(String, String)> = row.iter()
+                        .map(|cell| {
+                            let mut content = String::new();
+                            write_inlines_html(cell.content, &mut content);
+                            (ast::table_cell_attributes_html(cell.colspan, cell.rowspan, cell.align), content)
+                        })
+                        .collect();
+                    html.push_str(&ast::table_row_html(index, *header, &cells, rows.len()));
+                }
+                html.push_str("</table>\n");
+            }
+            Block::Image { path, alt, width, height, title } => {
+                let alt = escape_html(ast::image_alt(path, *alt));
+                let image = format!("<img src=\"{}\" alt=\"{}\"{}>", escape_html(path), alt, ast::image_size_html(*width, *height));
+                html.push_str(&ast::image_block_html(&image, *title));
+            }
+            Block::PageBreak => html.push_str(ast::PAGE_BREAK_HTML),
+            Block::BlockMetadata { .. } | Block::TableOfContents { .. } | Block::Comment { .. } => {}
+        }
+    }
+}
+
+impl ListItem<'_> {
+    pub fn write_html(&self, html: &mut String) {
+        match self {
+            ListItem::Unordered { content, blocks } | ListItem::Ordered { content, blocks } => {
+                html.push_str("<li>");
+                write_inlines_html(content, html);
+                write_attached_blocks_html(blocks, html);
+                html.push_str("</li>\n");
+            }
+            ListItem::Description { term, description, blocks } => {
+                html.push_str(&format!("<dt>{}</dt>\n", escape_html(term)));
+                if description.is_some() || !blocks.is_empty() {
+                    html.push_str("<dd>");
+                    if let Some(description) = description {
+                        write_inlines_html(description, html);
+                    }
+                    write_attached_blocks_html(blocks, html);
+                    html.push_str("</dd>\n");
+                }
+            }
+        }
+    }
+}
+
+fn write_attached_blocks_html(blocks: &[Block], html: &mut String) {
+    if !blocks.is_empty() {
+        html.push('\n');
+        for block in blocks {
+            block.write_html(html);
+        }
+    }
+}
+
+impl InlineElement<'_> {
+    pub fn write_html(&self, html: &mut String) {
+        match self {
+            InlineElement::Text(text) => html.push_str(&escape_html(text)),
+            InlineElement::Formatted { kind, content, id, roles } => {
+                let tag = match kind {
+                    FormattedTextKind::Strong => "strong",
+                    FormattedTextKind::Emphasis => "em",
+                    FormattedTextKind::Monospace | FormattedTextKind::LiteralMonospace => "code",
+                    FormattedTextKind::Superscript => "sup",
+                    FormattedTextKind::Subscript => "sub",
+                    FormattedTextKind::Mark if id.is_none() && roles.is_empty() => "mark",
+                    FormattedTextKind::Mark => ast::change_tag(roles).unwrap_or("span"),
+                };
+                html.push('<');
+                html.push_str(tag);
+                if let Some(id) = id {
+                    html.push_str(&format!(" id=\"{}\"", escape_html(id)));
+                }
+                if !roles.is_empty() {
+                    html.push_str(&format!(" class=\"{}\"", escape_html(&roles.join(" "))));
+                }
+                html.push('>');
+                write_inlines_html(content, html);
+                html.push_str(&format!("</{}>", tag));
+            }
+            InlineElement::Macro { kind } => match kind {
+                MacroKind::Link { url, text } => {
+                    html.push_str(&format!("<a href=\"{}\">{}</a>", escape_html(url), escape_html(text.unwrap_or(url))));
+                }
+                MacroKind::Image { path, attributes } => {
+                    let alt = attributes.map(escape_html).unwrap_or_else(|| "Image".to_string());
+                    html.push_str(&format!("<img src=\"{}\" alt=\"{}\">", escape_html(path), alt));
+                }
+                MacroKind::CrossReference { target, text } => {
+                    let href = escape_html(&xref_href(target));
+                    html.push_str(&format!("<a href=\"{}\">{}</a>", href, escape_html(text.unwrap_or(target))));
+                }
+                MacroKind::Term { term, target: Some(target) } => {
+                    html.push_str(&format!("<a class=\"term\" href=\"#{}\">{}</a>", escape_html(target), escape_html(term)));
+                }
+                MacroKind::Term { term, target: None } => {
+                    html.push_str(&format!("<span class=\"term\">{}</span>", escape_html(term)));
+                }
+            },
+            InlineElement::LineBreak => html.push_str("<br>\n"),
+            InlineElement::Passthrough(content) => html.push_str(content),
+        }
+    }
+}
+
+fn write_inlines_html(elements: &[InlineElement], html: &mut String) {
+    for element in elements {
+        element.write_html(html);
+    }
+}
@@ -1,23 +1,747 @@
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::context::{PageNavigation, Revision, TocEntry, breadcrumbs_html, page_links_html, sidebar_html, toc_entries};
+use crate::include::is_uri;
+use crate::parser::{glossary_id, is_attribute_name};
+#[cfg(feature = "std")]
+use crate::datetime;
+use crate::intern::{Symbol, SymbolPool};
+use crate::prelude::*;
+use crate::theme::Theme;
+use crate::trace;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Document {
     pub header: Option<Header>,
     pub body: Vec<Block>,
 }
 
+pub(crate) const DEFAULT_TOCLEVELS: usize = 2;
+
+// The schemes untrusted documents may link to unless `allowed_schemes` says otherwise
+const SAFE_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+#[derive(Clone)]
+pub struct HtmlOptions {
+    pub sanitize: bool,
+    // Checked only when sanitizing; relative URLs are always allowed
+    pub allowed_schemes: Vec<String>,
+    pub url_rewriter: Option<Arc<dyn UrlRewriter>>,
+    // An empty `<a class="anchor">` before each heading's text, which stylesheets draw as a §
+    pub section_anchors: bool,
+    // Heading text wrapped in a link to the section itself
+    pub section_links: bool,
+    // How many section levels `toc::[]` lists unless the macro sets `levels=`
+    pub toc_levels: usize,
+    // `loading="lazy"` on images, so those off screen are fetched as they scroll into view
+    pub lazy_loading: bool,
+    // `decoding="async"` on images, so decoding them doesn't hold up the rest of the page
+    pub async_decoding: bool,
+    // Asked for each image's size, so the page can keep room for it before it loads
+    pub image_sizer: Option<Arc<dyn ImageSizer>>,
+    // A whole page with a `<head>` carrying the title and social metadata, rather than
+    // markup to place inside one
+    pub standalone: bool,
+    // Breadcrumbs and links to neighbouring pages, for documents of a multi-file build
+    pub navigation: PageNavigation,
+    // Replaces the footer's text. `{name}` references the document's attributes, and
+    // `{generator}` the name and version of this crate.
+    pub footer_template: Option<String>,
+    // A built-in theme by name, a stylesheet's path or URL, or `none`, in place of the
+    // document's `:stylesheet:`
+    pub stylesheet: Option<String>,
+    // For paper, as when printing to PDF from a browser: links to other sites are followed by
+    // their URL, navigation between pages is left out and the print theme is the default
+    pub print: bool,
+    // What becomes of raw HTML in inline passthroughs. Sanitizing never lets it through as is.
+    pub passthrough: PassthroughPolicy,
+    // Links to other sites open in a new tab with `rel="noopener noreferrer"`, and carry
+    // `external_link_class` too when it is set, for stylesheets to mark them with an icon
+    pub external_links: bool,
+    pub external_link_class: Option<String>,
+    // Sees the HTML of each block as it is rendered, to wrap or rewrite it
+    pub block_hook: Option<Arc<dyn BlockHook>>,
+    // A script at the end of standalone pages that makes the copy buttons of titled
+    // listings work
+    pub copy_button_script: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PassthroughPolicy {
+    // Written into the page as is, which only suits documents from trusted authors
+    #[default]
+    Verbatim,
+    // Shown as the text it was written as, tags and all
+    Escape,
+    // Tags are dropped and only their text is kept
+    Strip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UrlContext {
+    Link,
+    Image,
+    CrossReference,
+}
+
+// A generated href or src, plus extra attributes for its element such as `rel`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlTarget {
+    pub context: UrlContext,
+    pub url: String,
+    pub attributes: Vec<(String, String)>,
+}
+
+// Sees every href and src before it is written. Returning None drops the link or image and
+// keeps its text. Closures taking and returning a UrlTarget implement this too.
+pub trait UrlRewriter: Send + Sync {
+    fn rewrite(&self, target: UrlTarget) -> Option<UrlTarget>;
+}
+
+impl<F> UrlRewriter for F
+where
+    F: Fn(UrlTarget) -> Option<UrlTarget> + Send + Sync,
+{
+    fn rewrite(&self, target: UrlTarget) -> Option<UrlTarget> {
+        self(target)
+    }
+}
+
+// Given each block with the HTML rendered for it, returns what takes its place, as when code
+// blocks get a copy button or headings a permalink. A section's heading is passed on its own,
+// ahead of the blocks inside it, which are passed one by one. Closures taking the block and
+// its HTML implement this too.
+pub trait BlockHook: Send + Sync {
+    fn render(&self, block: &Block, html: String) -> String;
+}
+
+impl<F> BlockHook for F
+where
+    F: Fn(&Block, String) -> String + Send + Sync,
+{
+    fn render(&self, block: &Block, html: String) -> String {
+        self(block, html)
+    }
+}
+
+fn hooked_html(block: &Block, html: String, options: &HtmlOptions) -> String {
+    match &options.block_hook {
+        Some(hook) => hook.render(block, html),
+        None => html,
+    }
+}
+
+// Width and height in pixels of an image, by its path as written in the document
+pub trait ImageSizer: Send + Sync {
+    fn image_size(&self, path: &str) -> Option<(u32, u32)>;
+}
+
+impl<F> ImageSizer for F
+where
+    F: Fn(&str) -> Option<(u32, u32)> + Send + Sync,
+{
+    fn image_size(&self, path: &str) -> Option<(u32, u32)> {
+        self(path)
+    }
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        HtmlOptions {
+            sanitize: false,
+            allowed_schemes: SAFE_URL_SCHEMES.iter().map(|scheme| scheme.to_string()).collect(),
+            url_rewriter: None,
+            section_anchors: false,
+            section_links: false,
+            toc_levels: DEFAULT_TOCLEVELS,
+            lazy_loading: false,
+            async_decoding: false,
+            image_sizer: None,
+            standalone: false,
+            navigation: PageNavigation::default(),
+            footer_template: None,
+            stylesheet: None,
+            print: false,
+            passthrough: PassthroughPolicy::Verbatim,
+            external_links: false,
+            external_link_class: None,
+            block_hook: None,
+            copy_button_script: false,
+        }
+    }
+}
+
+impl HtmlOptions {
+    // For user-submitted documents: every link and image target must be relative or use one
+    // of `allowed_schemes`, so `javascript:` and `data:` URLs never reach an href or src, and
+    // event handler attributes are stripped even if a rewriter adds them. Text and attribute
+    // values are always escaped, so no raw HTML gets through either.
+    pub fn untrusted() -> Self {
+        HtmlOptions { sanitize: true, passthrough: PassthroughPolicy::Escape, ..Default::default() }
+    }
+
+    fn passthrough_policy(&self) -> PassthroughPolicy {
+        match self.passthrough {
+            PassthroughPolicy::Verbatim if self.sanitize => PassthroughPolicy::Escape,
+            policy => policy,
+        }
+    }
+    
+    // `:sectanchors:` and `:sectlinks:` in the document header switch the heading links on,
+    // and `:toclevels:` sets the depth of its tables of contents
+    pub(crate) fn for_document(&self, document: &Document) -> HtmlOptions {
+        let mut options = self.clone();
+        options.section_anchors |= document.attribute("sectanchors").is_some();
+        options.section_links |= document.attribute("sectlinks").is_some();
+        if let Some(levels) = document.attribute("toclevels").and_then(|levels| levels.parse().ok()) {
+            options.toc_levels = levels;
+        }
+        if options.print {
+            options.navigation = PageNavigation::default();
+        }
+        options
+    }
+    
+    // A width or height given in the markup takes the place of what the sizer measures
+    fn image_attributes_html(&self, path: &str, width: Option<&str>, height: Option<&str>) -> String {
+        let mut html = String::new();
+        if width.is_some() || height.is_some() {
+            html.push_str(&image_size_html(width, height));
+        } else if let Some((width, height)) = self.image_sizer.as_ref().and_then(|sizer| sizer.image_size(path)) {
+            html.push_str(&format!(" width=\"{}\" height=\"{}\"", width, height));
+        }
+        if self.lazy_loading {
+            html.push_str(" loading=\"lazy\"");
+        }
+        if self.async_decoding {
+            html.push_str(" decoding=\"async\"");
+        }
+        html
+    }
+    
+    // Whether a URL the document gives outside its body, such as a stylesheet, may be used
+    fn permits_url(&self, url: &str) -> bool {
+        !self.sanitize || has_allowed_scheme(url, &self.allowed_schemes)
+    }
+    
+    // The rewriter runs first so the sanitizer also vets the URLs it produces
+    fn resolve_url(&self, context: UrlContext, url: &str) -> Option<UrlTarget> {
+        let mut target = UrlTarget { context, url: url.to_string(), attributes: Vec::new() };
+        if let Some(rewriter) = &self.url_rewriter {
+            target = rewriter.rewrite(target)?;
+        }
+        target.attributes.retain(|(name, _)| is_attribute_name(name));
+        
+        if self.sanitize {
+            if !self.permits_url(&target.url) {
+                return None;
+            }
+            target.attributes.retain(|(name, _)| !name.to_ascii_lowercase().starts_with("on"));
+        }
+        // Only absolute URLs lead off the site; relative ones and fragments are left alone
+        if self.external_links && context != UrlContext::Image && is_uri(&target.url) {
+            target.add_attribute("rel", "noopener noreferrer");
+            target.add_attribute("target", "_blank");
+            if let Some(class) = &self.external_link_class {
+                target.add_attribute("class", class);
+            }
+        }
+        Some(target)
+    }
+}
+
+impl UrlTarget {
+    // Joins `value` to the words of an attribute already set, as a rewriter may have set `rel`
+    fn add_attribute(&mut self, name: &str, value: &str) {
+        match self.attributes.iter_mut().find(|(existing, _)| existing == name) {
+            Some((_, existing)) if name == "target" => *existing = value.to_string(),
+            Some((_, existing)) => {
+                for word in value.split_whitespace() {
+                    if !existing.split_whitespace().any(|existing| existing == word) {
+                        existing.push(' ');
+                        existing.push_str(word);
+                    }
+                }
+            }
+            None => self.attributes.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    fn attributes_html(&self) -> String {
+        self.attributes.iter()
+            .map(|(name, value)| format!(" {}=\"{}\"", name, escape_html(value)))
+            .collect()
+    }
+}
+
 impl Document {
     pub fn to_html(&self) -> String {
-        let mut html = String::new();
+        self.to_html_with(&HtmlOptions::default())
+    }
+
+    pub fn to_html_with(&self, options: &HtmlOptions) -> String {
+        trace::span!(DEBUG, "render", backend = "html");
+        let options = &options.for_document(self);
+        let mut html = sidebar_html(&options.navigation);
+        html.push_str(&breadcrumbs_html(&options.navigation));
         
         if let Some(header) = &self.header {
-            html.push_str(&format!("<h1>{}</h1>\n", escape_html(&header.title)));
+            html.push_str(&header_html(&header.title, &|name| header.attribute(name), options));
         }
         
         for block in &self.body {
-            html.push_str(&block.to_html());
+            html.push_str(&block_among_html(block, &self.body, options));
         }
         
+        html.push_str(&revision_history_html(&self.revisions(), self.attribute("revhistory")));
+        html.push_str(&page_links_html(&options.navigation));
+        html.push_str(&footer_html(&|name| self.attribute(name), options));
+        if options.standalone {
+            let (start, end) = self.page_html(options);
+            return format!("{}{}{}", start, html, end);
+        }
         html
     }
+    
+    // What goes before and after the body of a standalone page
+    fn page_html(&self, options: &HtmlOptions) -> (String, String) {
+        let title = self.header.as_ref().map(|header| header.title.as_str()).unwrap_or_default();
+        let attribute = |name: &str| self.attribute(name);
+        let head = social_meta_html(title, &attribute, options) + &stylesheet_html(&attribute, options, false);
+        let script = if options.copy_button_script { COPY_BUTTON_SCRIPT } else { "" };
+        (page_start_html(title, &head, &body_class(&attribute)), format!("{}{}", script, PAGE_END_HTML))
+    }
+    
+    // The built-in theme styling the document's pages and where a linked copy of it belongs,
+    // relative to them. None when the pages use a stylesheet of their own or none at all.
+    pub fn theme(&self, options: &HtmlOptions) -> Option<(Theme, String)> {
+        let attribute = |name: &str| self.attribute(name);
+        let theme = Theme::from_name(stylesheet_name(&attribute, options))?;
+        Some((theme, theme_path(&attribute, theme)))
+    }
+    
+    // The roles given by `[.role]` above the title, `:role:` or `:docrole:`, in that order
+    pub fn roles(&self) -> Vec<&str> {
+        document_roles(&|name| self.attribute(name))
+    }
+    
+    // Streams the rendered document so only one block's markup is held in memory at a time
+    #[cfg(feature = "std")]
+    pub fn write_html(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.write_html_with(out, &HtmlOptions::default())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn write_html_with(&self, out: &mut dyn Write, options: &HtmlOptions) -> io::Result<()> {
+        trace::span!(DEBUG, "render", backend = "html");
+        let options = &options.for_document(self);
+        let page = options.standalone.then(|| self.page_html(options));
+        if let Some((start, _)) = &page {
+            out.write_all(start.as_bytes())?;
+        }
+        out.write_all(sidebar_html(&options.navigation).as_bytes())?;
+        out.write_all(breadcrumbs_html(&options.navigation).as_bytes())?;
+        if let Some(header) = &self.header {
+            out.write_all(header_html(&header.title, &|name| header.attribute(name), options).as_bytes())?;
+        }
+        
+        for block in &self.body {
+            write_block_among_html(block, &self.body, out, options)?;
+        }
+        
+        out.write_all(revision_history_html(&self.revisions(), self.attribute("revhistory")).as_bytes())?;
+        out.write_all(page_links_html(&options.navigation).as_bytes())?;
+        out.write_all(footer_html(&|name| self.attribute(name), options).as_bytes())?;
+        if let Some((_, end)) = page {
+            out.write_all(end.as_bytes())?;
+        }
+        Ok(())
+    }
+    
+    // Fills `docdatetime` for the footer's "Last updated" line, preferring SOURCE_DATE_EPOCH
+    // over the source's modification time so builds stay reproducible. Under
+    // `:reproducible:` it is left unset.
+    #[cfg(feature = "std")]
+    pub fn set_last_updated(&mut self, modified: SystemTime) {
+        let Some(header) = &mut self.header else { return };
+        if header.attribute("docdatetime").is_some() || header.attribute("reproducible").is_some() {
+            return;
+        }
+        let secs = datetime::source_date_epoch()
+            .unwrap_or_else(|| modified.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+        header.attributes.push(Attribute {
+            name: Symbol::from("docdatetime"),
+            value: Some(datetime::format_docdatetime(secs)),
+        });
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.header.as_ref()?.attribute(name)
+    }
+
+    pub fn walk_inlines(&self, f: &mut dyn FnMut(&InlineElement)) {
+        walk_block_inlines(&self.body, f);
+    }
+
+    pub fn walk_inlines_mut(&mut self, f: &mut dyn FnMut(&mut InlineElement)) {
+        walk_block_inlines_mut(&mut self.body, f);
+    }
+}
+
+// Title, authors and revision details in Asciidoctor's header block, unless `:noheader:` is set
+pub(crate) fn header_html<'a>(title: &str, attribute: &dyn Fn(&str) -> Option<&'a str>, options: &HtmlOptions) -> String {
+    if attribute("noheader").is_some() {
+        return String::new();
+    }
+    let value = |name: &str| attribute(name).filter(|value| !value.is_empty());
+    
+    let authors = author_names(attribute);
+    let mut details = String::new();
+    for (index, author) in authors.iter().enumerate() {
+        let suffix = if index == 0 { String::new() } else { format!("_{}", index + 1) };
+        details.push_str(&format!("<span id=\"author{}\" class=\"author\">{}</span><br>\n", suffix, escape_html(author)));
+        if let Some(email) = value(&format!("email{}", suffix)) {
+            let link = match options.resolve_url(UrlContext::Link, &format!("mailto:{}", email)) {
+                Some(target) => format!("<a href=\"{}\"{}>{}</a>", escape_html(&target.url), target.attributes_html(), escape_html(email)),
+                None => escape_html(email),
+            };
+            details.push_str(&format!("<span id=\"email{}\" class=\"email\">{}</span><br>\n", suffix, link));
+        }
+    }
+    if let Some(revnumber) = value("revnumber") {
+        let separator = if value("revdate").is_some() { "," } else { "" };
+        details.push_str(&format!("<span id=\"revnumber\">version {}{}</span>\n", escape_html(revnumber), separator));
+    }
+    if let Some(revdate) = value("revdate") {
+        details.push_str(&format!("<span id=\"revdate\">{}</span>\n", escape_html(revdate)));
+    }
+    if let Some(revremark) = value("revremark") {
+        details.push_str(&format!("<br><span id=\"revremark\">{}</span>\n", escape_html(revremark)));
+    }
+    
+    let mut html = format!("<div id=\"header\">\n<h1>{}</h1>\n", escape_html(title));
+    if !details.is_empty() {
+        html.push_str(&format!("<div class=\"details\">\n{}</div>\n", details));
+    }
+    html.push_str("</div>\n");
+    html
+}
+
+// `authors` lists several authors separated by semicolons, otherwise `author` names one
+pub(crate) fn author_names<'a>(attribute: &dyn Fn(&str) -> Option<&'a str>) -> Vec<&'a str> {
+    match attribute("authors").filter(|value| !value.is_empty()) {
+        Some(authors) => authors.split(';').map(str::trim).filter(|author| !author.is_empty()).collect(),
+        None => attribute("author").filter(|value| !value.is_empty()).into_iter().collect(),
+    }
+}
+
+// With `:revhistory:` set, a table of the revisions after the body, under the attribute's
+// value as its heading
+pub(crate) fn revision_history_html(revisions: &[Revision], revhistory: Option<&str>) -> String {
+    let Some(title) = revhistory else { return String::new() };
+    if revisions.is_empty() {
+        return String::new();
+    }
+    let title = if title.is_empty() { "Revision History" } else { title };
+    let cell = |value: &Option<String>| format!("<td>{}</td>", value.as_deref().map(escape_html).unwrap_or_default());
+    let rows: String = revisions.iter()
+        .map(|revision| format!("<tr>{}{}{}</tr>\n", cell(&revision.number), cell(&revision.date), cell(&revision.remark)))
+        .collect();
+    format!(
+        "<h2 id=\"_revision_history\">{}</h2>\n<table class=\"revhistory\">\n<thead>\n<tr><th>Version</th><th>Date</th><th>Remark</th></tr>\n</thead>\n<tbody>\n{}</tbody>\n</table>\n",
+        escape_html(title),
+        rows,
+    )
+}
+
+pub(crate) const GENERATOR: &str = concat!("adoc ", env!("CARGO_PKG_VERSION"));
+
+// Version and last-updated footer, unless `:nofooter:` is set or there is nothing to show.
+// `:reproducible:` leaves out the time so the page only changes with its content. The labels
+// follow `:version-label:` and `:last-update-label:`, `:footer-text:` adds a line of its own,
+// and a standalone page ends by naming its generator.
+pub(crate) fn footer_html<'a>(attribute: &dyn Fn(&str) -> Option<&'a str>, options: &HtmlOptions) -> String {
+    if attribute("nofooter").is_some() {
+        return String::new();
+    }
+    let value = |name: &str| attribute(name).filter(|value| !value.is_empty());
+    
+    let text = match &options.footer_template {
+        Some(template) => footer_template_html(template, attribute),
+        None => {
+            let mut lines = Vec::new();
+            if let Some(revnumber) = value("revnumber") {
+                lines.push(format!("{} {}", escape_html(value("version-label").unwrap_or("Version")), escape_html(revnumber)));
+            }
+            if let Some(updated) = value("docdatetime").filter(|_| attribute("reproducible").is_none()) {
+                lines.push(format!("{} {}", escape_html(value("last-update-label").unwrap_or("Last updated")), escape_html(updated)));
+            }
+            if let Some(footer_text) = value("footer-text") {
+                lines.push(escape_html(footer_text));
+            }
+            if options.standalone {
+                lines.push(format!("Generated by {}", GENERATOR));
+            }
+            if lines.is_empty() {
+                return String::new();
+            }
+            lines.join("<br>\n") + "\n"
+        }
+    };
+    if text.trim().is_empty() {
+        return String::new();
+    }
+    format!("<div id=\"footer\">\n<div id=\"footer-text\">\n{}</div>\n</div>\n", text)
+}
+
+// The template is HTML of the caller's own, so only the values put into it are escaped.
+// References to attributes the document does not set are left as written.
+fn footer_template_html<'a>(template: &str, attribute: &dyn Fn(&str) -> Option<&'a str>) -> String {
+    let mut html = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        html.push_str(&rest[..start]);
+        let Some(name) = after.find('}').map(|end| &after[..end]).filter(|name| is_attribute_name(name)) else {
+            html.push('{');
+            rest = after;
+            continue;
+        };
+        match name {
+            "generator" => html.push_str(GENERATOR),
+            _ => match attribute(name) {
+                Some(value) => html.push_str(&escape_html(value)),
+                None => html.push_str(&rest[start..start + name.len() + 2]),
+            },
+        }
+        rest = &after[name.len() + 1..];
+    }
+    html.push_str(rest);
+    if !html.ends_with('\n') {
+        html.push('\n');
+    }
+    html
+}
+
+pub(crate) fn page_start_html(title: &str, head: &str, body_class: &str) -> String {
+    let class = if body_class.is_empty() { String::new() } else { format!(" class=\"{}\"", escape_html(body_class)) };
+    format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n{}</head>\n<body{}>\n", escape_html(title), head, class)
+}
+
+pub(crate) fn document_roles<'a>(attribute: &dyn Fn(&str) -> Option<&'a str>) -> Vec<&'a str> {
+    let mut roles: Vec<&str> = Vec::new();
+    for role in ["role", "docrole"].into_iter().filter_map(attribute).flat_map(str::split_whitespace) {
+        if !roles.contains(&role) {
+            roles.push(role);
+        }
+    }
+    roles
+}
+
+// The doctype then the document's roles, as Asciidoctor sets them, so a theme can style one
+// kind of page without touching the others
+pub(crate) fn body_class<'a>(attribute: &dyn Fn(&str) -> Option<&'a str>) -> String {
+    let doctype = attribute("doctype").filter(|doctype| !doctype.is_empty()).unwrap_or("article");
+    let mut classes = vec![doctype];
+    classes.extend(document_roles(attribute));
+    classes.join(" ")
+}
+
+// The page's styles: a built-in theme is embedded, or linked as `adoc-<name>.css` under
+// `:stylesdir:` when `:linkcss:` is set or `linked` asks for it, as pages sharing one copy do.
+// Any other `:stylesheet:` is linked as given, unless sanitizing rules out its scheme; `none`
+// leaves the page unstyled, and with nothing set the default theme is used.
+pub(crate) fn stylesheet_html<'a>(attribute: &dyn Fn(&str) -> Option<&'a str>, options: &HtmlOptions, linked: bool) -> String {
+    let name = stylesheet_name(attribute, options);
+    if name == "none" {
+        return String::new();
+    }
+    let link = |href: &str| format!("<link rel=\"stylesheet\" href=\"{}\">\n", escape_html(href));
+    match Theme::from_name(name) {
+        Some(theme) if linked || attribute("linkcss").is_some() => link(&theme_path(attribute, theme)),
+        Some(theme) => format!("<style>\n{}</style>\n", theme.css()),
+        None if options.permits_url(name) => link(name),
+        None => String::new(),
+    }
+}
+
+fn stylesheet_name<'a: 'b, 'b>(attribute: &dyn Fn(&str) -> Option<&'a str>, options: &'b HtmlOptions) -> &'b str {
+    options.stylesheet.as_deref()
+        .or_else(|| attribute("stylesheet"))
+        .filter(|name| !name.is_empty())
+        .unwrap_or(if options.print { Theme::Print.name() } else { Theme::Default.name() })
+}
+
+fn theme_path<'a>(attribute: &dyn Fn(&str) -> Option<&'a str>, theme: Theme) -> String {
+    match attribute("stylesdir").map(|dir| dir.trim_end_matches('/')).filter(|dir| !dir.is_empty()) {
+        Some(dir) => format!("{}/{}", dir, theme.file_name()),
+        None => theme.file_name(),
+    }
+}
+
+// Browsers start a new page here when printing and show nothing on screen
+pub(crate) const PAGE_BREAK_HTML: &str = "<div style=\"break-after: page\"></div>\n";
+
+pub(crate) const PAGE_END_HTML: &str = "</body>\n</html>\n";
+
+// Copies the code under a titled listing's button to the clipboard
+const COPY_BUTTON_SCRIPT: &str = "<script>
+document.addEventListener(\"click\", function (event) {
+  var button = event.target.closest(\".copy-button\");
+  if (!button) return;
+  var code = button.closest(\".listingblock\").querySelector(\"pre\");
+  navigator.clipboard.writeText(code.innerText);
+});
+</script>
+";
+
+// A titled listing gets a bar above the code with the title and a button for copying the
+// code, which does nothing without a script such as the one `copy_button_script` adds
+pub(crate) fn listing_html(title: Option<&str>, code: &str) -> String {
+    match title {
+        Some(title) => format!(
+            "<div class=\"listingblock\">\n<div class=\"code-header\">\n<span class=\"filename\">{}</span>\n\
+<button type=\"button\" class=\"copy-button\" aria-label=\"Copy code\">Copy</button>\n</div>\n{}</div>\n",
+            escape_html(title), code,
+        ),
+        None => code.to_string(),
+    }
+}
+
+// `:description:` and `:author:` as meta tags. With a description or an `:og-image:` the page
+// also gets Open Graph and Twitter card tags, so links to it unfurl with a preview. An image
+// whose scheme sanitizing disallows is left out.
+pub(crate) fn social_meta_html<'a>(title: &str, attribute: &dyn Fn(&str) -> Option<&'a str>, options: &HtmlOptions) -> String {
+    let value = |name: &str| attribute(name).filter(|value| !value.is_empty());
+    let meta = |key: &str, name: &str, content: &str| format!("<meta {}=\"{}\" content=\"{}\">\n", key, name, escape_html(content));
+    let description = value("description");
+    let image = value("og-image").filter(|image| options.permits_url(image));
+    
+    let mut html = String::new();
+    if let Some(description) = description {
+        html.push_str(&meta("name", "description", description));
+    }
+    let authors = author_names(attribute);
+    if !authors.is_empty() {
+        html.push_str(&meta("name", "author", &authors.join(", ")));
+    }
+    if description.is_none() && image.is_none() {
+        return html;
+    }
+    
+    html.push_str(&meta("property", "og:type", "article"));
+    if !title.is_empty() {
+        html.push_str(&meta("property", "og:title", title));
+    }
+    if let Some(description) = description {
+        html.push_str(&meta("property", "og:description", description));
+    }
+    if let Some(image) = image {
+        html.push_str(&meta("property", "og:image", image));
+    }
+    html.push_str(&meta("name", "twitter:card", if image.is_some() { "summary_large_image" } else { "summary" }));
+    if !title.is_empty() {
+        html.push_str(&meta("name", "twitter:title", title));
+    }
+    if let Some(description) = description {
+        html.push_str(&meta("name", "twitter:description", description));
+    }
+    if let Some(image) = image {
+        html.push_str(&meta("name", "twitter:image", image));
+    }
+    html
+}
+
+pub(crate) fn walk_block_inlines(blocks: &[Block], f: &mut dyn FnMut(&InlineElement)) {
+    for block in blocks {
+        match block {
+            Block::Section { blocks, .. } | Block::Sidebar { blocks, .. } => walk_block_inlines(blocks, f),
+            Block::Paragraph { content } => walk_inline_elements(content, f),
+            Block::Admonition { content, blocks, .. } => {
+                walk_inline_elements(content, f);
+                walk_block_inlines(blocks, f);
+            }
+            Block::Table { rows, .. } => {
+                for cell in rows.iter().flatten() {
+                    walk_inline_elements(&cell.content, f);
+                }
+            }
+            Block::List { items, .. } => {
+                for item in items {
+                    match item {
+                        ListItem::Unordered { content, blocks } | ListItem::Ordered { content, blocks } => {
+                            walk_inline_elements(content, f);
+                            walk_block_inlines(blocks, f);
+                        }
+                        ListItem::Description { description, blocks, .. } => {
+                            if let Some(content) = description {
+                                walk_inline_elements(content, f);
+                            }
+                            walk_block_inlines(blocks, f);
+                        }
+                    }
+                }
+            }
+            Block::DelimitedBlock { .. } | Block::Image { .. } | Block::BlockMetadata { .. } | Block::TableOfContents { .. } | Block::PageBreak | Block::Comment { .. } => {}
+        }
+    }
+}
+
+fn walk_inline_elements(elements: &[InlineElement], f: &mut dyn FnMut(&InlineElement)) {
+    for element in elements {
+        f(element);
+        if let InlineElement::Formatted { content, .. } = element {
+            walk_inline_elements(content, f);
+        }
+    }
+}
+
+pub(crate) fn walk_block_inlines_mut(blocks: &mut [Block], f: &mut dyn FnMut(&mut InlineElement)) {
+    for block in blocks {
+        match block {
+            Block::Section { blocks, .. } | Block::Sidebar { blocks, .. } => walk_block_inlines_mut(blocks, f),
+            Block::Paragraph { content } => walk_inline_elements_mut(content, f),
+            Block::Admonition { content, blocks, .. } => {
+                walk_inline_elements_mut(content, f);
+                walk_block_inlines_mut(blocks, f);
+            }
+            Block::Table { rows, .. } => {
+                for cell in rows.iter_mut().flatten() {
+                    walk_inline_elements_mut(&mut cell.content, f);
+                }
+            }
+            Block::List { items, .. } => {
+                for item in items {
+                    match item {
+                        ListItem::Unordered { content, blocks } | ListItem::Ordered { content, blocks } => {
+                            walk_inline_elements_mut(content, f);
+                            walk_block_inlines_mut(blocks, f);
+                        }
+                        ListItem::Description { description, blocks, .. } => {
+                            if let Some(content) = description {
+                                walk_inline_elements_mut(content, f);
+                            }
+                            walk_block_inlines_mut(blocks, f);
+                        }
+                    }
+                }
+            }
+            Block::DelimitedBlock { .. } | Block::Image { .. } | Block::BlockMetadata { .. } | Block::TableOfContents { .. } | Block::PageBreak | Block::Comment { .. } => {}
+        }
+    }
+}
+
+fn walk_inline_elements_mut(elements: &mut [InlineElement], f: &mut dyn FnMut(&mut InlineElement)) {
+    for element in elements {
+        f(element);
+        if let InlineElement::Formatted { content, .. } = element {
+            walk_inline_elements_mut(content, f);
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -26,9 +750,18 @@ pub struct Header {
     pub attributes: Vec<Attribute>,
 }
 
+impl Header {
+    // Attributes set without a value read as an empty string
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter()
+            .find(|attribute| attribute.name == name)
+            .map(|attribute| attribute.value.as_deref().unwrap_or(""))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Attribute {
-    pub name: String,
+    pub name: Symbol,
     pub value: Option<String>,
 }
 
@@ -37,23 +770,163 @@ pub enum Block {
     Section {
         level: usize,
         title: String,
+        id: Option<String>,
+        number: Option<String>,
         blocks: Vec<Block>,
     },
     Paragraph {
         content: Vec<InlineElement>,
     },
+    // A paragraph starting with its label, as in `NOTE: text`, holds its text in `content`.
+    // `[NOTE]` over an example block holds the blocks inside it instead.
+    Admonition {
+        kind: AdmonitionKind,
+        content: Vec<InlineElement>,
+        blocks: Vec<Block>,
+    },
     DelimitedBlock {
         kind: DelimitedBlockKind,
         content: String,
-        language: Option<String>,
+        language: Option<Symbol>,
+        // The file name or `.Title` of a listing, shown in a bar above the code
+        title: Option<String>,
     },
     List {
         kind: ListKind,
         items: Vec<ListItem>,
     },
+    // `****`, holding blocks of its own. The `.Title` and `[.role]` lines above it are
+    // taken into the block.
+    Sidebar {
+        title: Option<String>,
+        roles: Vec<Symbol>,
+        blocks: Vec<Block>,
+    },
+    // `|===`, holding rows of cells. The `.Title` line above it is taken into the block; an
+    // attribute list above it stays in the body.
+    Table {
+        title: Option<String>,
+        // Whether the first row heads the columns
+        header: bool,
+        rows: Vec<Vec<TableCell>>,
+    },
+    // `image::path[alt, width, height]` on a line of its own. The `.Title` line above it, or
+    // `title=` in its list, gives the caption under the image.
+    Image {
+        path: String,
+        alt: Option<String>,
+        width: Option<String>,
+        height: Option<String>,
+        title: Option<String>,
+    },
     BlockMetadata {
         kind: BlockMetadataKind,
     },
+    // `toc::[]`, listing the sections beside it: the whole document at the top level, or
+    // the subsections of the section it sits in
+    TableOfContents {
+        levels: Option<usize>,
+    },
+    // `<<<`, where printed output starts a new page
+    PageBreak,
+    // A `//` line, with the text after the slashes as written, or a `////` block with the
+    // lines between its delimiters. Only kept when `ParseOptions::comments` is set. HTML
+    // leaves them out; LaTeX carries them over as `%` comments, which don't print either.
+    Comment {
+        text: String,
+        delimited: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdmonitionKind {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+impl AdmonitionKind {
+    pub const ALL: [AdmonitionKind; 5] = [
+        AdmonitionKind::Note,
+        AdmonitionKind::Tip,
+        AdmonitionKind::Important,
+        AdmonitionKind::Warning,
+        AdmonitionKind::Caution,
+    ];
+    
+    // As written in the source, `NOTE`
+    pub fn label(self) -> &'static str {
+        match self {
+            AdmonitionKind::Note => "NOTE",
+            AdmonitionKind::Tip => "TIP",
+            AdmonitionKind::Important => "IMPORTANT",
+            AdmonitionKind::Warning => "WARNING",
+            AdmonitionKind::Caution => "CAUTION",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<AdmonitionKind> {
+        AdmonitionKind::ALL.into_iter().find(|kind| kind.label() == label)
+    }
+    
+    // The class name, `note`
+    pub fn name(self) -> &'static str {
+        match self {
+            AdmonitionKind::Note => "note",
+            AdmonitionKind::Tip => "tip",
+            AdmonitionKind::Important => "important",
+            AdmonitionKind::Warning => "warning",
+            AdmonitionKind::Caution => "caution",
+        }
+    }
+    
+    // The title shown beside the content, `Note`
+    pub fn caption(self) -> &'static str {
+        match self {
+            AdmonitionKind::Note => "Note",
+            AdmonitionKind::Tip => "Tip",
+            AdmonitionKind::Important => "Important",
+            AdmonitionKind::Warning => "Warning",
+            AdmonitionKind::Caution => "Caution",
+        }
+    }
+}
+
+// A cell covers `colspan` columns and `rowspan` rows from where it starts, as a spec like
+// `2.3+^|` in front of it says
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableCell {
+    pub content: Vec<InlineElement>,
+    pub colspan: usize,
+    pub rowspan: usize,
+    pub align: Option<CellAlignment>,
+}
+
+impl TableCell {
+    pub fn new(content: Vec<InlineElement>) -> TableCell {
+        TableCell { content, colspan: 1, rowspan: 1, align: None }
+    }
+
+}
+
+// `<`, `^` or `>` in a cell spec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+impl CellAlignment {
+    pub fn name(self) -> &'static str {
+        match self {
+            CellAlignment::Left => "left",
+            CellAlignment::Center => "center",
+            CellAlignment::Right => "right",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -61,38 +934,60 @@ pub enum DelimitedBlockKind {
     Listing,
     Example,
     Literal,
-    Sidebar,
-    Quote,
+    Quote(Attribution),
+}
+
+// Who a quote is by and where it comes from, as in `[quote, author, citation]`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Attribution {
+    pub author: Option<String>,
+    pub citation: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ListKind {
     Unordered,
     Ordered,
-    Description,
+    Description(DescriptionListStyle),
+}
+
+// Chosen by a `[horizontal]`, `[qanda]` or `[glossary]` attribute list above a description list
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DescriptionListStyle {
+    #[default]
+    Stacked,
+    Horizontal,
+    Qanda,
+    Glossary,
 }
 
+// Each item has its principal text and the blocks attached to it: blocks joined with a `+`
+// continuation, indented literal paragraphs and, last, any list nested under the item
 #[derive(Debug, Clone, PartialEq)]
 pub enum ListItem {
     Unordered {
-        level: usize,
         content: Vec<InlineElement>,
+        blocks: Vec<Block>,
     },
     Ordered {
-        level: usize,
         content: Vec<InlineElement>,
+        blocks: Vec<Block>,
     },
     Description {
         term: String,
         description: Option<Vec<InlineElement>>,
+        blocks: Vec<Block>,
     },
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum BlockMetadataKind {
     Title(String),
-    Attribute(Vec<String>),
+    Attribute(Vec<Symbol>),
     Anchor(String),
+    // `:name: value` and `:name!:` lines in the body take effect from that point on
+    AttributeEntry(Attribute),
+    AttributeUnset(Symbol),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -101,11 +996,14 @@ pub enum InlineElement {
     Formatted {
         kind: FormattedTextKind,
         content: Vec<InlineElement>,
+        attributes: InlineAttributes,
     },
     Macro {
         kind: MacroKind,
     },
     LineBreak,
+    // `+++text+++` or `pass:[text]`, raw HTML written into the page as `passthrough` allows
+    Passthrough(String),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -113,8 +1011,110 @@ pub enum FormattedTextKind {
     Strong,
     Emphasis,
     Monospace,
+    // `` `+text+` ``, monospace whose text is kept exactly as written
+    LiteralMonospace,
     Superscript,
     Subscript,
+    // `#text#`, which is only read with an attribute list in front of it and then renders as
+    // a span carrying the id and roles
+    Mark,
+}
+
+// The id and roles of an attribute list in front of formatted text, as in `[#id.role]*text*`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InlineAttributes {
+    pub id: Option<String>,
+    pub roles: Vec<Symbol>,
+}
+
+impl InlineAttributes {
+    // Reads the shorthand `#id.role.role` form and the named `id=` and `role=` entries. The
+    // `ins` and `del` styles, as in `[del]` or `[del.note]`, are taken as roles of the same
+    // name.
+    pub fn parse(text: &str) -> Option<Self> {
+        Self::parse_in(text, &mut SymbolPool::new())
+    }
+    
+    // Takes the roles from `symbols`
+    pub fn parse_in(text: &str, symbols: &mut SymbolPool) -> Option<Self> {
+        let mut attributes = InlineAttributes::default();
+        for entry in text.split(',').map(str::trim) {
+            let style = ["ins", "del"].into_iter()
+                .find(|style| entry.strip_prefix(style).is_some_and(|rest| rest.is_empty() || rest.starts_with(['#', '.'])));
+            let entry = match style {
+                Some(style) => {
+                    attributes.roles.push(symbols.symbol(style));
+                    &entry[style.len()..]
+                }
+                None => entry,
+            };
+            if style.is_some() && entry.is_empty() {
+                continue;
+            } else if let Some(id) = entry.strip_prefix("id=") {
+                attributes.id = Some(id.trim_matches('"').to_string());
+            } else if let Some(roles) = entry.strip_prefix("role=") {
+                attributes.roles.extend(roles.trim_matches('"').split_whitespace().map(|role| symbols.symbol(role)));
+            } else if entry.starts_with('#') || entry.starts_with('.') {
+                let mut rest = entry;
+                if let Some(id) = rest.strip_prefix('#') {
+                    let end = id.find('.').unwrap_or(id.len());
+                    attributes.id = Some(id[..end].to_string());
+                    rest = &id[end..];
+                }
+                attributes.roles.extend(rest.split('.').filter(|role| !role.is_empty()).map(|role| symbols.symbol(role)));
+            } else {
+                return None;
+            }
+        }
+        let valid = attributes.id.as_deref().is_none_or(is_inline_name) && attributes.roles.iter().all(|role| is_inline_name(role));
+        (valid && !attributes.is_empty()).then_some(attributes)
+    }
+    
+    // In front of monospace, `[source,js]` (or `[,js]`) also names the language, which
+    // becomes the `language-js` role a listing's code element has
+    pub fn parse_code(text: &str) -> Option<Self> {
+        Self::parse_code_in(text, &mut SymbolPool::new())
+    }
+
+    pub fn parse_code_in(text: &str, symbols: &mut SymbolPool) -> Option<Self> {
+        let language = text.split_once(',')
+            .filter(|(style, _)| matches!(style.trim(), "" | "source"))
+            .map(|(_, language)| language.trim());
+        match language {
+            Some(language) if is_inline_name(language) => Some(InlineAttributes { id: None, roles: vec![symbols.symbol(&format!("language-{}", language))] }),
+            _ => Self::parse_in(text, symbols),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.id.is_none() && self.roles.is_empty()
+    }
+
+    pub(crate) fn to_html(&self) -> String {
+        let mut html = String::new();
+        if let Some(id) = &self.id {
+            html.push_str(&format!(" id=\"{}\"", escape_html(id)));
+        }
+        if !self.roles.is_empty() {
+            let roles: Vec<&str> = self.roles.iter().map(Symbol::as_str).collect();
+            html.push_str(&format!(" class=\"{}\"", escape_html(&roles.join(" "))));
+        }
+        html
+    }
+}
+
+// Marked text with an `added` or `ins` role is shown as inserted, and with `deleted` or
+// `del` as deleted, so a document can track its own edits: `[.deleted]#old#[.added]#new#`
+pub(crate) fn change_tag(roles: &[impl AsRef<str>]) -> Option<&'static str> {
+    roles.iter().find_map(|role| match role.as_ref() {
+        "added" | "ins" => Some("ins"),
+        "deleted" | "del" => Some("del"),
+        _ => None,
+    })
+}
+
+fn is_inline_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -131,35 +1131,78 @@ pub enum MacroKind {
         target: String,
         text: Option<String>,
     },
+    // `term:[API]`, with the id of the glossary entry for the term when the document has one
+    Term {
+        term: String,
+        target: Option<String>,
+    },
 }
 
 impl Block {
+    #[cfg(feature = "std")]
+    pub fn write_html(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.write_html_with(out, &HtmlOptions::default())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn write_html_with(&self, out: &mut dyn Write, options: &HtmlOptions) -> io::Result<()> {
+        match self {
+            Block::Section { level, title, id, number, blocks } => {
+                out.write_all(hooked_html(self, section_heading_html(*level, title, id.as_deref(), number.as_deref(), options), options).as_bytes())?;
+                for block in blocks {
+                    write_block_among_html(block, blocks, out, options)?;
+                }
+                Ok(())
+            }
+            _ => out.write_all(self.to_html_with(options).as_bytes()),
+        }
+    }
+
     pub fn to_html(&self) -> String {
+        self.to_html_with(&HtmlOptions::default())
+    }
+
+    pub fn to_html_with(&self, options: &HtmlOptions) -> String {
+        let html = self.html_with(options);
         match self {
-            Block::Section { level, title, blocks } => {
-                let heading_level = (*level).min(6);
-                let mut html = format!("<h{}>{}</h{}>\n", heading_level, escape_html(title), heading_level);
+            // The hook has seen the heading and each block inside already
+            Block::Section { .. } => html,
+            _ => hooked_html(self, html, options),
+        }
+    }
+
+    fn html_with(&self, options: &HtmlOptions) -> String {
+        match self {
+            Block::Section { level, title, id, number, blocks } => {
+                let mut html = hooked_html(self, section_heading_html(*level, title, id.as_deref(), number.as_deref(), options), options);
                 for block in blocks {
-                    html.push_str(&block.to_html());
+                    html.push_str(&block_among_html(block, blocks, options));
                 }
                 html
             }
             Block::Paragraph { content } => {
-                format!("<p>{}</p>\n", inline_elements_to_html(content))
+                format!("<p>{}</p>\n", inline_elements_to_html(content, options))
             }
-            Block::DelimitedBlock { kind, content, language } => {
+            Block::Admonition { kind, content, blocks } => {
+                let mut html = inline_elements_to_html(content, options);
+                for block in blocks {
+                    html.push_str(&block_among_html(block, blocks, options));
+                }
+                admonition_html(*kind, &html)
+            }
+            Block::DelimitedBlock { kind, content, language, title } => {
                 match kind {
                     DelimitedBlockKind::Listing => {
-                        if let Some(lang) = language {
+                        let code = if let Some(lang) = language {
                             format!("<pre><code class=\"language-{}\">{}</code></pre>\n", escape_html(lang), escape_html(content))
                         } else {
                             format!("<pre><code>{}</code></pre>\n", escape_html(content))
-                        }
+                        };
+                        listing_html(title.as_deref(), &code)
                     },
                     DelimitedBlockKind::Example => format!("<div class=\"example\">{}</div>\n", escape_html(content)),
                     DelimitedBlockKind::Literal => format!("<pre>{}</pre>\n", escape_html(content)),
-                    DelimitedBlockKind::Sidebar => format!("<aside>{}</aside>\n", escape_html(content)),
-                    DelimitedBlockKind::Quote => format!("<blockquote>{}</blockquote>\n", escape_html(content)),
+                    DelimitedBlockKind::Quote(attribution) => quote_html(&escape_html(content), attribution),
                 }
             }
             Block::List { kind, items } => {
@@ -167,7 +1210,7 @@ impl Block {
                     ListKind::Unordered => {
                         let mut html = String::from("<ul>\n");
                         for item in items {
-                            html.push_str(&item.to_html());
+                            html.push_str(&item.to_html_with(options));
                         }
                         html.push_str("</ul>\n");
                         html
@@ -175,36 +1218,193 @@ impl Block {
                     ListKind::Ordered => {
                         let mut html = String::from("<ol>\n");
                         for item in items {
-                            html.push_str(&item.to_html());
+                            html.push_str(&item.to_html_with(options));
                         }
                         html.push_str("</ol>\n");
                         html
                     }
-                    ListKind::Description => {
-                        let mut html = String::from("<dl>\n");
-                        for item in items {
-                            html.push_str(&item.to_html());
-                        }
-                        html.push_str("</dl>\n");
-                        html
-                    }
+                    ListKind::Description(style) => description_list_html(*style, items, options),
                 }
             }
-            Block::BlockMetadata { .. } => String::new(),
+            Block::Sidebar { title, roles, blocks } => {
+                let mut html = sidebar_start_html(title.as_deref(), roles);
+                for block in blocks {
+                    html.push_str(&block_among_html(block, blocks, options));
+                }
+                html.push_str("</aside>\n");
+                html
+            }
+            Block::Table { title, header, rows } => {
+                let mut html = table_start_html(title.as_deref());
+                for (index, row) in rows.iter().enumerate() {
+                    let cells: Vec<(String, String)> = row.iter()
+                        .map(|cell| (table_cell_attributes_html(cell.colspan, cell.rowspan, cell.align), inline_elements_to_html(&cell.content, options)))
+                        .collect();
+                    html.push_str(&table_row_html(index, *header, &cells, rows.len()));
+                }
+                html.push_str("</table>\n");
+                html
+            }
+            Block::Image { path, alt, width, height, title } => {
+                let alt = escape_html(image_alt(path, alt.as_deref()));
+                let image = match options.resolve_url(UrlContext::Image, path) {
+                    Some(target) => format!(
+                        "<img src=\"{}\" alt=\"{}\"{}{}>",
+                        escape_html(&target.url), alt, target.attributes_html(), options.image_attributes_html(path, width.as_deref(), height.as_deref()),
+                    ),
+                    None => format!("<span class=\"alt\">{}</span>", alt),
+                };
+                image_block_html(&image, title.as_deref())
+            }
+            Block::PageBreak => PAGE_BREAK_HTML.to_string(),
+            Block::Comment { .. } => String::new(),
+            // Needs the blocks around it, see `block_among_html`
+            Block::BlockMetadata { .. } | Block::TableOfContents { .. } => String::new(),
+        }
+    }
+}
+
+// A table of contents lists the sections among its sibling blocks
+fn block_among_html(block: &Block, siblings: &[Block], options: &HtmlOptions) -> String {
+    match block {
+        Block::TableOfContents { levels } => {
+            let entries = toc_entries(siblings, levels.unwrap_or(options.toc_levels));
+            hooked_html(block, toc_html(&entries, options), options)
+        }
+        _ => block.to_html_with(options),
+    }
+}
+
+#[cfg(feature = "std")]
+fn write_block_among_html(block: &Block, siblings: &[Block], out: &mut dyn Write, options: &HtmlOptions) -> io::Result<()> {
+    match block {
+        Block::TableOfContents { .. } => out.write_all(block_among_html(block, siblings, options).as_bytes()),
+        _ => block.write_html_with(out, options),
+    }
+}
+
+pub(crate) fn toc_html(entries: &[TocEntry], options: &HtmlOptions) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+    format!("<div class=\"toc\">\n<div class=\"toctitle\">Table of Contents</div>\n{}</div>\n", toc_list_html(entries, options))
+}
+
+// Asciidoctor numbers the list classes from `sectlevel1` for `==` sections
+fn toc_list_html(entries: &[TocEntry], options: &HtmlOptions) -> String {
+    let Some(first) = entries.first() else { return String::new() };
+    let mut html = format!("<ul class=\"sectlevel{}\">\n", first.level.saturating_sub(1));
+    for entry in entries {
+        let number = entry.number.as_ref().map(|number| format!("{} ", escape_html(number))).unwrap_or_default();
+        let text = format!("{}{}", number, escape_html(&entry.title));
+        match options.resolve_url(UrlContext::CrossReference, &format!("#{}", entry.id)) {
+            Some(target) => html.push_str(&format!("<li><a href=\"{}\"{}>{}</a>", escape_html(&target.url), target.attributes_html(), text)),
+            None => html.push_str(&format!("<li>{}", text)),
+        }
+        if !entry.children.is_empty() {
+            html.push('\n');
+            html.push_str(&toc_list_html(&entry.children, options));
+        }
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ul>\n");
+    html
+}
+
+// Horizontal lists set terms beside their descriptions in a two-column table, question
+// and answer lists number each question and glossaries anchor each term
+fn description_list_html(style: DescriptionListStyle, items: &[ListItem], options: &HtmlOptions) -> String {
+    let (open, close) = match style {
+        DescriptionListStyle::Stacked => ("<dl>\n", "</dl>\n"),
+        DescriptionListStyle::Horizontal => ("<table class=\"hdlist\">\n", "</table>\n"),
+        DescriptionListStyle::Qanda => ("<ol class=\"qanda\">\n", "</ol>\n"),
+        DescriptionListStyle::Glossary => ("<dl class=\"glossary\">\n", "</dl>\n"),
+    };
+    let mut html = String::from(open);
+    for item in items {
+        match (style, item) {
+            (DescriptionListStyle::Horizontal, ListItem::Description { term, description, blocks }) => {
+                let description = description.as_deref().map(|description| inline_elements_to_html(description, options)).unwrap_or_default();
+                html.push_str(&format!(
+                    "<tr>\n<td class=\"hdlist1\">{}</td>\n<td class=\"hdlist2\">{}{}</td>\n</tr>\n",
+                    escape_html(term),
+                    description,
+                    attached_blocks_html(blocks, options),
+                ));
+            }
+            (DescriptionListStyle::Qanda, ListItem::Description { term, description, blocks }) => {
+                html.push_str(&format!("<li>\n<p><em>{}</em></p>\n", escape_html(term)));
+                if let Some(description) = description {
+                    html.push_str(&format!("<p>{}</p>\n", inline_elements_to_html(description, options)));
+                }
+                for block in blocks {
+                    html.push_str(&block.to_html_with(options));
+                }
+                html.push_str("</li>\n");
+            }
+            (DescriptionListStyle::Glossary, ListItem::Description { term, description, blocks }) => {
+                html.push_str(&format!("<dt id=\"{}\">{}</dt>\n", escape_html(&glossary_id(term)), escape_html(term)));
+                if description.is_some() || !blocks.is_empty() {
+                    let description = description.as_deref().map(|description| inline_elements_to_html(description, options)).unwrap_or_default();
+                    html.push_str(&format!("<dd>{}{}</dd>\n", description, attached_blocks_html(blocks, options)));
+                }
+            }
+            _ => html.push_str(&item.to_html_with(options)),
         }
     }
+    html.push_str(close);
+    html
+}
+
+pub(crate) fn section_heading_html(level: usize, title: &str, id: Option<&str>, number: Option<&str>, options: &HtmlOptions) -> String {
+    let heading_level = level.min(6);
+    let id_attr = id.map(|id| format!(" id=\"{}\"", escape_html(id))).unwrap_or_default();
+    let number = number.map(|number| format!("{} ", escape_html(number))).unwrap_or_default();
+    let mut text = format!("{}{}", number, escape_html(title));
+    
+    let target = id
+        .filter(|_| options.section_anchors || options.section_links)
+        .and_then(|id| options.resolve_url(UrlContext::CrossReference, &format!("#{}", id)));
+    if let Some(target) = target {
+        let href = escape_html(&target.url);
+        let attributes = target.attributes_html();
+        if options.section_links {
+            text = format!("<a class=\"link\" href=\"{}\"{}>{}</a>", href, attributes, text);
+        }
+        if options.section_anchors {
+            text = format!("<a class=\"anchor\" href=\"{}\"{}></a>{}", href, attributes, text);
+        }
+    }
+    format!("<h{}{}>{}</h{}>\n", heading_level, id_attr, text, heading_level)
 }
 
 impl ListItem {
+    pub fn blocks(&self) -> &[Block] {
+        match self {
+            ListItem::Unordered { blocks, .. } | ListItem::Ordered { blocks, .. } | ListItem::Description { blocks, .. } => blocks,
+        }
+    }
+
+    pub(crate) fn blocks_mut(&mut self) -> &mut Vec<Block> {
+        match self {
+            ListItem::Unordered { blocks, .. } | ListItem::Ordered { blocks, .. } | ListItem::Description { blocks, .. } => blocks,
+        }
+    }
+
     pub fn to_html(&self) -> String {
+        self.to_html_with(&HtmlOptions::default())
+    }
+
+    pub fn to_html_with(&self, options: &HtmlOptions) -> String {
         match self {
-            ListItem::Unordered { content, .. } | ListItem::Ordered { content, .. } => {
-                format!("<li>{}</li>\n", inline_elements_to_html(content))
+            ListItem::Unordered { content, blocks } | ListItem::Ordered { content, blocks } => {
+                format!("<li>{}{}</li>\n", inline_elements_to_html(content, options), attached_blocks_html(blocks, options))
             }
-            ListItem::Description { term, description } => {
+            ListItem::Description { term, description, blocks } => {
                 let mut html = format!("<dt>{}</dt>\n", escape_html(term));
-                if let Some(desc) = description {
-                    html.push_str(&format!("<dd>{}</dd>\n", inline_elements_to_html(desc)));
+                if description.is_some() || !blocks.is_empty() {
+                    let description = description.as_deref().map(|description| inline_elements_to_html(description, options)).unwrap_or_default();
+                    html.push_str(&format!("<dd>{}{}</dd>\n", description, attached_blocks_html(blocks, options)));
                 }
                 html
             }
@@ -212,46 +1412,260 @@ impl ListItem {
     }
 }
 
+// Attached blocks start on a line of their own after the item's text
+fn attached_blocks_html(blocks: &[Block], options: &HtmlOptions) -> String {
+    if blocks.is_empty() {
+        return String::new();
+    }
+    let html: String = blocks.iter().map(|block| block.to_html_with(options)).collect();
+    format!("\n{}", html)
+}
+
 impl InlineElement {
     pub fn to_html(&self) -> String {
+        self.to_html_with(&HtmlOptions::default())
+    }
+    
+    // Links and images whose target the options do not permit are rendered as their text
+    pub fn to_html_with(&self, options: &HtmlOptions) -> String {
         match self {
             InlineElement::Text(text) => escape_html(text),
-            InlineElement::Formatted { kind, content } => {
-                let inner = inline_elements_to_html(content);
-                match kind {
-                    FormattedTextKind::Strong => format!("<strong>{}</strong>", inner),
-                    FormattedTextKind::Emphasis => format!("<em>{}</em>", inner),
-                    FormattedTextKind::Monospace => format!("<code>{}</code>", inner),
-                    FormattedTextKind::Superscript => format!("<sup>{}</sup>", inner),
-                    FormattedTextKind::Subscript => format!("<sub>{}</sub>", inner),
-                }
+            InlineElement::Formatted { kind, content, attributes } => {
+                let tag = match kind {
+                    FormattedTextKind::Strong => "strong",
+                    FormattedTextKind::Emphasis => "em",
+                    FormattedTextKind::Monospace | FormattedTextKind::LiteralMonospace => "code",
+                    FormattedTextKind::Superscript => "sup",
+                    FormattedTextKind::Subscript => "sub",
+                    FormattedTextKind::Mark if attributes.is_empty() => "mark",
+                    FormattedTextKind::Mark => change_tag(&attributes.roles).unwrap_or("span"),
+                };
+                format!("<{}{}>{}</{}>", tag, attributes.to_html(), inline_elements_to_html(content, options), tag)
             }
             InlineElement::Macro { kind } => {
                 match kind {
                     MacroKind::Link { url, text } => {
                         let link_text = text.as_ref().map(|t| escape_html(t)).unwrap_or_else(|| escape_html(url));
-                        format!("<a href=\"{}\">{}</a>", escape_html(url), link_text)
+                        match options.resolve_url(UrlContext::Link, url) {
+                            Some(target) if options.print && text.as_ref().is_some_and(|text| *text != target.url) && is_uri(&target.url) => format!(
+                                "<a href=\"{}\"{}>{}</a> <span class=\"url\">({})</span>",
+                                escape_html(&target.url), target.attributes_html(), link_text, escape_html(&target.url),
+                            ),
+                            Some(target) => format!("<a href=\"{}\"{}>{}</a>", escape_html(&target.url), target.attributes_html(), link_text),
+                            None => link_text,
+                        }
                     }
                     MacroKind::Image { path, attributes } => {
                         let alt = attributes.as_ref().map(|a| escape_html(a)).unwrap_or_else(|| "Image".to_string());
-                        format!("<img src=\"{}\" alt=\"{}\">", escape_html(path), alt)
+                        match options.resolve_url(UrlContext::Image, path) {
+                            Some(target) => format!("<img src=\"{}\" alt=\"{}\"{}{}>", escape_html(&target.url), alt, target.attributes_html(), options.image_attributes_html(path, None, None)),
+                            None => alt,
+                        }
                     }
                     MacroKind::CrossReference { target, text } => {
                         let link_text = text.as_ref().map(|t| escape_html(t)).unwrap_or_else(|| escape_html(target));
-                        format!("<a href=\"#{}\">{}</a>", escape_html(target), link_text)
+                        match options.resolve_url(UrlContext::CrossReference, &xref_href(target)) {
+                            Some(target) => format!("<a href=\"{}\"{}>{}</a>", escape_html(&target.url), target.attributes_html(), link_text),
+                            None => link_text,
+                        }
+                    }
+                    MacroKind::Term { term, target } => {
+                        let resolved = target.as_ref().and_then(|target| options.resolve_url(UrlContext::CrossReference, &format!("#{}", target)));
+                        match resolved {
+                            Some(target) => format!("<a class=\"term\" href=\"{}\"{}>{}</a>", escape_html(&target.url), target.attributes_html(), escape_html(term)),
+                            None => format!("<span class=\"term\">{}</span>", escape_html(term)),
+                        }
                     }
                 }
             }
             InlineElement::LineBreak => "<br>\n".to_string(),
+            InlineElement::Passthrough(content) => match options.passthrough_policy() {
+                PassthroughPolicy::Verbatim => content.clone(),
+                PassthroughPolicy::Escape => escape_html(content),
+                PassthroughPolicy::Strip => escape_html(&strip_tags(content)),
+            },
+        }
+    }
+}
+
+// Targets such as `other.adoc#id` point into another document of the same site
+pub(crate) fn xref_href(target: &str) -> String {
+    let (path, fragment) = match target.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (target, None),
+    };
+    
+    match path.strip_suffix(".adoc").or_else(|| path.strip_suffix(".md")) {
+        Some(stem) => match fragment {
+            Some(fragment) if !fragment.is_empty() => format!("{}.html#{}", stem, fragment),
+            _ => format!("{}.html", stem),
+        },
+        None => format!("#{}", target),
+    }
+}
+
+fn inline_elements_to_html(elements: &[InlineElement], options: &HtmlOptions) -> String {
+    elements.iter().map(|e| e.to_html_with(options)).collect::<String>()
+}
+
+// Browsers skip whitespace and control characters inside a scheme, so `java\tscript:` is
+// normalized before the check. A colon after the first `/`, `?` or `#` is part of a
+// relative URL.
+fn has_allowed_scheme(url: &str, allowed_schemes: &[String]) -> bool {
+    let normalized: String = url.chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    match normalized.split_once(':') {
+        Some((scheme, _)) if !scheme.contains(['/', '?', '#']) => {
+            allowed_schemes.iter().any(|allowed| allowed.eq_ignore_ascii_case(scheme))
         }
+        _ => true,
     }
 }
 
-fn inline_elements_to_html(elements: &[InlineElement]) -> String {
-    elements.iter().map(|e| e.to_html()).collect::<String>()
+// The title leads the sidebar's content, and the roles are its classes
+pub(crate) fn sidebar_start_html(title: Option<&str>, roles: &[impl AsRef<str>]) -> String {
+    let mut html = String::from("<aside");
+    if !roles.is_empty() {
+        let roles: Vec<&str> = roles.iter().map(AsRef::as_ref).collect();
+        html.push_str(&format!(" class=\"{}\"", escape_html(&roles.join(" "))));
+    }
+    html.push_str(">\n");
+    if let Some(title) = title {
+        html.push_str(&format!("<div class=\"title\">{}</div>\n", escape_html(title)));
+    }
+    html
+}
+
+// Set as Asciidoctor does, the caption in a cell beside the content, so stylesheets written
+// for it apply
+pub(crate) fn admonition_html(kind: AdmonitionKind, content: &str) -> String {
+    format!(
+        "<div class=\"admonitionblock {}\">\n<table>\n<tr>\n<td class=\"icon\">\n<div class=\"title\">{}</div>\n</td>\n<td class=\"content\">\n{}\n</td>\n</tr>\n</table>\n</div>\n",
+        kind.name(), kind.caption(), content.strip_suffix('\n').unwrap_or(content),
+    )
+}
+
+// Asciidoctor's imageblock, with the title as a caption under the image
+pub(crate) fn image_block_html(image: &str, title: Option<&str>) -> String {
+    let caption = title.map(|title| format!("<div class=\"title\">{}</div>\n", escape_html(title))).unwrap_or_default();
+    format!("<div class=\"imageblock\">\n<div class=\"content\">\n{}\n</div>\n{}</div>\n", image, caption)
+}
+
+// Without alt text the file name stands in, less its extension
+pub(crate) fn image_alt<'a>(path: &'a str, alt: Option<&'a str>) -> &'a str {
+    alt.unwrap_or_else(|| {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        name.rsplit_once('.').map_or(name, |(stem, _)| stem)
+    })
+}
+
+pub(crate) fn image_size_html(width: Option<&str>, height: Option<&str>) -> String {
+    let mut html = String::new();
+    if let Some(width) = width {
+        html.push_str(&format!(" width=\"{}\"", escape_html(width)));
+    }
+    if let Some(height) = height {
+        html.push_str(&format!(" height=\"{}\"", escape_html(height)));
+    }
+    html
+}
+
+pub(crate) fn table_start_html(title: Option<&str>) -> String {
+    match title {
+        Some(title) => format!("<table>\n<caption>{}</caption>\n", escape_html(title)),
+        None => String::from("<table>\n"),
+    }
+}
+
+pub(crate) fn table_cell_attributes_html(colspan: usize, rowspan: usize, align: Option<CellAlignment>) -> String {
+    let mut html = String::new();
+    if colspan > 1 {
+        html.push_str(&format!(" colspan=\"{}\"", colspan));
+    }
+    if rowspan > 1 {
+        html.push_str(&format!(" rowspan=\"{}\"", rowspan));
+    }
+    if let Some(align) = align {
+        html.push_str(&format!(" style=\"text-align: {}\"", align.name()));
+    }
+    html
+}
+
+// A header row is set apart in `thead` with `th` cells, and the rows after it in `tbody`.
+// `cells` holds the attributes and the content of each cell as HTML.
+pub(crate) fn table_row_html(index: usize, header: bool, cells: &[(String, String)], row_count: usize) -> String {
+    let heading = header && index == 0;
+    let tag = if heading { "th" } else { "td" };
+    let mut html = String::new();
+    if heading {
+        html.push_str("<thead>\n");
+    } else if index == usize::from(header) {
+        html.push_str("<tbody>\n");
+    }
+    html.push_str("<tr>\n");
+    for (attributes, content) in cells {
+        html.push_str(&format!("<{}{}>{}</{}>\n", tag, attributes, content, tag));
+    }
+    html.push_str("</tr>\n");
+    if heading {
+        html.push_str("</thead>\n");
+    } else if index + 1 == row_count {
+        html.push_str("</tbody>\n");
+    }
+    html
+}
+
+// An attributed quote is set in a figure, with the author and citation in its caption
+pub(crate) fn quote_html(content: &str, attribution: &Attribution) -> String {
+    let caption = match (&attribution.author, &attribution.citation) {
+        (None, None) => return format!("<blockquote>{}</blockquote>\n", content),
+        (Some(author), None) => escape_html(author),
+        (None, Some(citation)) => format!("<cite>{}</cite>", escape_html(citation)),
+        (Some(author), Some(citation)) => format!("{}, <cite>{}</cite>", escape_html(author), escape_html(citation)),
+    };
+    format!("<figure class=\"quote\">\n<blockquote>{}</blockquote>\n<figcaption>&#8212; {}</figcaption>\n</figure>\n", content, caption)
+}
+
+pub(crate) fn plain_text(elements: &[InlineElement]) -> String {
+    let mut text = String::new();
+    for element in elements {
+        match element {
+            InlineElement::Text(value) => text.push_str(value),
+            InlineElement::Formatted { content, .. } => text.push_str(&plain_text(content)),
+            InlineElement::Macro { kind } => match kind {
+                MacroKind::Link { url, text: link_text } => text.push_str(link_text.as_deref().unwrap_or(url)),
+                MacroKind::Image { attributes, .. } => text.push_str(attributes.as_deref().unwrap_or("")),
+                MacroKind::CrossReference { target, text: xref_text } => text.push_str(xref_text.as_deref().unwrap_or(target)),
+                MacroKind::Term { term, .. } => text.push_str(term),
+            },
+            InlineElement::LineBreak => text.push('\n'),
+            InlineElement::Passthrough(content) => text.push_str(&strip_tags(content)),
+        }
+    }
+    text
+}
+
+// The text of raw HTML with its tags taken out
+pub(crate) fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        match rest[start..].find('>') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    text.push_str(rest);
+    text
 }
 
-fn escape_html(text: &str) -> String {
+pub(crate) fn escape_html(text: &str) -> String {
     text.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
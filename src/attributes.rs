@@ -0,0 +1,65 @@
+use crate::ast::Attribute;
+#[cfg(feature = "attribute-files")]
+use crate::error::ConfigError;
+use crate::intern::Symbol;
+use std::env;
+
+// Attributes from variables named `<prefix><NAME>`. The rest of the variable name is
+// lowercased with underscores turned into hyphens, so `DOCS_PRODUCT_NAME` with the prefix
+// `DOCS_` sets `product-name`.
+pub fn from_env(prefix: &str) -> Vec<Attribute> {
+    let mut attributes: Vec<Attribute> = env::vars()
+        .filter_map(|(key, value)| {
+            let name = key.strip_prefix(prefix)?.to_lowercase().replace('_', "-");
+            (!name.is_empty()).then(|| attribute(&name, value))
+        })
+        .collect();
+    attributes.sort_by(|a, b| a.name.as_str().cmp(b.name.as_str()));
+    attributes
+}
+
+// A flat JSON object. Strings and numbers become values, `true` sets the attribute
+// without a value and `false` or `null` leaves it out.
+#[cfg(feature = "attribute-files")]
+pub fn from_json(source: &str) -> Result<Vec<Attribute>, ConfigError> {
+    use serde_json::Value;
+    
+    let Value::Object(entries) = serde_json::from_str(source).map_err(ConfigError::new)? else {
+        return Err(ConfigError::new("attributes must be a JSON object"));
+    };
+    let mut attributes = Vec::new();
+    for (name, value) in entries {
+        match value {
+            Value::String(value) => attributes.push(attribute(&name, value)),
+            Value::Number(number) => attributes.push(attribute(&name, number.to_string())),
+            Value::Bool(true) => attributes.push(Attribute { name: Symbol::new(&name), value: None }),
+            Value::Bool(false) | Value::Null => {}
+            _ => return Err(ConfigError::new(format!("attribute '{}' must be a string, number or boolean", name))),
+        }
+    }
+    Ok(attributes)
+}
+
+// A TOML table without nested tables, read like the JSON object
+#[cfg(feature = "attribute-files")]
+pub fn from_toml(source: &str) -> Result<Vec<Attribute>, ConfigError> {
+    use toml::Value;
+    
+    let mut attributes = Vec::new();
+    for (name, value) in source.parse::<toml::Table>().map_err(ConfigError::new)? {
+        match value {
+            Value::String(value) => attributes.push(attribute(&name, value)),
+            Value::Integer(number) => attributes.push(attribute(&name, number.to_string())),
+            Value::Float(number) => attributes.push(attribute(&name, number.to_string())),
+            Value::Boolean(true) => attributes.push(Attribute { name: Symbol::new(&name), value: None }),
+            Value::Boolean(false) => {}
+            _ => return Err(ConfigError::new(format!("attribute '{}' must be a string, number or boolean", name))),
+        }
+    }
+    Ok(attributes)
+}
+
+// An empty value is the same as setting the attribute without one, as in the header
+fn attribute(name: &str, value: String) -> Attribute {
+    Attribute { name: Symbol::new(name), value: Some(value).filter(|value| !value.is_empty()) }
+}
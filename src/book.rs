@@ -0,0 +1,163 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ast::*;
+use crate::intern::Symbol;
+use crate::parser::{generate_id, number_sections, unique_id};
+use crate::workspace::{join_path, parent_dir};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Book {
+    header: Header,
+    chapters: Vec<(String, Document)>,
+}
+
+impl Book {
+    pub fn new(title: impl Into<String>) -> Self {
+        Book {
+            header: Header { title: title.into(), attributes: Vec::new() },
+            chapters: Vec::new(),
+        }
+    }
+    
+    // Attributes set on the book take precedence over those of its chapters
+    pub fn set_attribute(&mut self, name: impl Into<Symbol>, value: Option<String>) {
+        let name = name.into();
+        self.header.attributes.retain(|attribute| attribute.name != name);
+        self.header.attributes.push(Attribute { name, value });
+    }
+
+    pub fn add_chapter(&mut self, name: impl Into<String>, document: Document) {
+        self.chapters.push((name.into(), document));
+    }
+
+    pub fn assemble(&self) -> Document {
+        let mut header = self.header.clone();
+        if header.attribute("doctype").is_none() {
+            header.attributes.push(Attribute { name: Symbol::new("doctype"), value: Some("book".to_string()) });
+        }
+        
+        // Every id is renamed up front so xrefs between chapters can be rebased in one pass
+        let mut used_ids = BTreeSet::new();
+        let mut chapter_ids = Vec::new();
+        let mut id_maps = Vec::new();
+        for (_, document) in &self.chapters {
+            if let Some(chapter_header) = &document.header {
+                for attribute in &chapter_header.attributes {
+                    if header.attribute(&attribute.name).is_none() {
+                        header.attributes.push(attribute.clone());
+                    }
+                }
+            }
+            
+            let chapter_id = document.header.as_ref()
+                .map(|h| unique_id(generate_id(&h.title, "_", "_"), "_", &mut used_ids));
+            let mut ids = HashMap::new();
+            collect_ids(&document.body, &mut |id| {
+                ids.insert(id.to_string(), unique_id(id.to_string(), "_", &mut used_ids));
+            });
+            chapter_ids.push(chapter_id);
+            id_maps.push(ids);
+        }
+        
+        let mut body = Vec::new();
+        for (index, (name, document)) in self.chapters.iter().enumerate() {
+            let mut blocks = document.body.clone();
+            let offset = if document.header.is_some() { 1 } else { 0 };
+            rebase_blocks(&mut blocks, offset, &id_maps[index]);
+            walk_block_inlines_mut(&mut blocks, &mut |element| {
+                if let InlineElement::Macro { kind: MacroKind::CrossReference { target, .. } } = element
+                    && let Some(rebased) = self.rebase_target(name, target, &chapter_ids, &id_maps)
+                {
+                    *target = rebased;
+                }
+            });
+            
+            match &document.header {
+                Some(chapter_header) => body.push(Block::Section {
+                    level: 2,
+                    title: chapter_header.title.clone(),
+                    id: chapter_ids[index].clone(),
+                    number: None,
+                    blocks,
+                }),
+                None => body.extend(blocks),
+            }
+        }
+        
+        // Chapters were numbered on their own, so numbers restart from the book's settings
+        number_sections(&mut body, &header.attributes);
+        Document { header: Some(header), body }
+    }
+
+    fn rebase_target(
+        &self,
+        name: &str,
+        target: &str,
+        chapter_ids: &[Option<String>],
+        id_maps: &[HashMap<String, String>],
+    ) -> Option<String> {
+        let (path, fragment) = match target.split_once('#') {
+            Some((path, "")) => (path, None),
+            Some((path, fragment)) => (path, Some(fragment)),
+            None if target.ends_with(".adoc") || target.ends_with(".md") => (target, None),
+            None => ("", Some(target)),
+        };
+        let document_name = if path.is_empty() { name.to_string() } else { join_path(parent_dir(name), path) };
+        let index = self.chapters.iter().position(|(chapter, _)| *chapter == document_name)?;
+        
+        match fragment {
+            Some(id) => id_maps[index].get(id).cloned(),
+            None => chapter_ids[index].clone(),
+        }
+    }
+}
+
+fn collect_ids(blocks: &[Block], f: &mut dyn FnMut(&str)) {
+    for block in blocks {
+        match block {
+            Block::Section { id, blocks, .. } => {
+                if let Some(id) = id {
+                    f(id);
+                }
+                collect_ids(blocks, f);
+            }
+            Block::Sidebar { blocks, .. } | Block::Admonition { blocks, .. } => collect_ids(blocks, f),
+            Block::List { items, .. } => {
+                for item in items {
+                    collect_ids(item.blocks(), f);
+                }
+            }
+            Block::BlockMetadata { kind: BlockMetadataKind::Anchor(id) } => f(id),
+            _ => {}
+        }
+    }
+}
+
+fn rebase_blocks(blocks: &mut [Block], offset: usize, ids: &HashMap<String, String>) {
+    for block in blocks {
+        match block {
+            Block::Section { level, id, blocks, .. } => {
+                *level += offset;
+                if let Some(id) = id
+                    && let Some(rebased) = ids.get(id)
+                {
+                    *id = rebased.clone();
+                }
+                rebase_blocks(blocks, offset, ids);
+            }
+            // The same blocks `collect_ids` looks in, so every id it renamed is renamed here
+            Block::Sidebar { blocks, .. } | Block::Admonition { blocks, .. } => rebase_blocks(blocks, offset, ids),
+            Block::List { items, .. } => {
+                for item in items {
+                    rebase_blocks(item.blocks_mut(), offset, ids);
+                }
+            }
+            Block::BlockMetadata { kind: BlockMetadataKind::Anchor(id) } => {
+                if let Some(rebased) = ids.get(id) {
+                    *id = rebased.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+}
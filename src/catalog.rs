@@ -0,0 +1,105 @@
+use crate::ast::*;
+use crate::parser::glossary_id;
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogEntry {
+    pub document: String,
+    pub id: String,
+    pub title: Option<String>,
+    pub level: Option<usize>,
+}
+
+// A term from a `[glossary]` list, with its definition as plain text
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlossaryEntry {
+    pub document: String,
+    pub id: String,
+    pub term: String,
+    pub definition: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Catalog {
+    entries: Vec<CatalogEntry>,
+    glossary: Vec<GlossaryEntry>,
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Catalog::default()
+    }
+
+    pub fn add_document(&mut self, name: &str, document: &Document) {
+        collect_entries(name, &document.body, &mut self.entries, &mut self.glossary);
+    }
+
+    pub fn entries(&self) -> &[CatalogEntry] {
+        &self.entries
+    }
+
+    pub fn document_entries<'a>(&'a self, document: &'a str) -> impl Iterator<Item = &'a CatalogEntry> {
+        self.entries.iter().filter(move |entry| entry.document == document)
+    }
+
+    pub fn get(&self, document: &str, id: &str) -> Option<&CatalogEntry> {
+        self.entries.iter().find(|entry| entry.document == document && entry.id == id)
+    }
+    
+    // Every glossary term in the order documents were added, for building a term index
+    pub fn glossary(&self) -> &[GlossaryEntry] {
+        &self.glossary
+    }
+    
+    // Terms are matched without regard to case, as `term:[]` matches them
+    pub fn glossary_term(&self, term: &str) -> Option<&GlossaryEntry> {
+        self.glossary.iter().find(|entry| entry.term.eq_ignore_ascii_case(term.trim()))
+    }
+}
+
+fn collect_entries(name: &str, blocks: &[Block], entries: &mut Vec<CatalogEntry>, glossary: &mut Vec<GlossaryEntry>) {
+    for block in blocks {
+        match block {
+            Block::Section { level, title, id, blocks, .. } => {
+                if let Some(id) = id {
+                    entries.push(CatalogEntry {
+                        document: name.to_string(),
+                        id: id.clone(),
+                        title: Some(title.clone()),
+                        level: Some(*level),
+                    });
+                }
+                collect_entries(name, blocks, entries, glossary);
+            }
+            Block::BlockMetadata { kind: BlockMetadataKind::Anchor(id) } => {
+                entries.push(CatalogEntry {
+                    document: name.to_string(),
+                    id: id.clone(),
+                    title: None,
+                    level: None,
+                });
+            }
+            // Glossary terms are anchored, so they can be the target of cross references too
+            Block::List { kind: ListKind::Description(DescriptionListStyle::Glossary), items } => {
+                for item in items {
+                    if let ListItem::Description { term, description, .. } = item {
+                        let id = glossary_id(term);
+                        entries.push(CatalogEntry {
+                            document: name.to_string(),
+                            id: id.clone(),
+                            title: Some(term.clone()),
+                            level: None,
+                        });
+                        glossary.push(GlossaryEntry {
+                            document: name.to_string(),
+                            id,
+                            term: term.clone(),
+                            definition: description.as_deref().map(plain_text),
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::context::{NavLink, PageNavigation, breadcrumbs_html, sidebar_html};
+use crate::trace;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub file_name: String,
+    pub title: String,
+    pub html: String,
+}
+
+pub(crate) struct ChunkPlan {
+    pub(crate) file_name: String,
+    pub(crate) title: String,
+    pub(crate) parent: Option<usize>,
+    pub(crate) blocks: Vec<Block>,
+}
+
+impl Document {
+    // Splits the document into one page per section down to `depth` (1 = top-level sections),
+    // preceded by an index page holding the preamble and a table of contents
+    pub fn to_chunked_html(&self, depth: usize) -> Vec<Chunk> {
+        self.to_chunked_html_with(depth, &HtmlOptions::default())
+    }
+    
+    // In a multi-file build the site's sidebar and the document's breadcrumbs head every page, and the first and
+    // last pages link on to the documents before and after it
+    pub fn to_chunked_html_with(&self, depth: usize, options: &HtmlOptions) -> Vec<Chunk> {
+        trace::span!(DEBUG, "render", backend = "chunked", depth);
+        let title = self.header.as_ref().map(|h| h.title.clone()).unwrap_or_default();
+        let plans = plan_document_chunks(self, depth, "html");
+        let options = HtmlOptions { standalone: false, ..options.for_document(self) };
+        let attribute = |name: &str| self.attribute(name);
+        // The pages share one linked copy of a built-in theme rather than each embedding it
+        let head = |title: &str| social_meta_html(title, &attribute, &options) + &stylesheet_html(&attribute, &options, true);
+        
+        let mut chunks = Vec::new();
+        for (index, plan) in plans.iter().enumerate() {
+            let mut body = sidebar_html(&options.navigation);
+            body.push_str(&breadcrumbs_html(&options.navigation));
+            if index == 0 && !title.is_empty() {
+                body.push_str(&format!("<h1>{}</h1>\n", escape_html(&title)));
+            }
+            for block in &plan.blocks {
+                body.push_str(&block.to_html_with(&options));
+            }
+            if index == 0 {
+                body.push_str(&toc_html(&plans));
+            }
+            body.push_str(&navigation_html(&plans, index, &options.navigation));
+            
+            chunks.push(Chunk {
+                file_name: plan.file_name.clone(),
+                title: plan.title.clone(),
+                html: format!("{}{}{}", page_start_html(&plan.title, &head(&plan.title), &body_class(&attribute)), body, PAGE_END_HTML),
+            });
+        }
+        
+        chunks
+    }
+}
+
+// The first plan is the index page; xrefs are rewritten to name the page holding their target
+pub(crate) fn plan_document_chunks(document: &Document, depth: usize, extension: &str) -> Vec<ChunkPlan> {
+    let title = document.header.as_ref().map(|h| h.title.clone()).unwrap_or_default();
+    let mut plans = vec![ChunkPlan {
+        file_name: format!("index.{}", extension),
+        title,
+        parent: None,
+        blocks: Vec::new(),
+    }];
+    plan_chunks(&document.body, depth.max(1), extension, 0, &mut plans);
+    
+    let mut targets = HashMap::new();
+    for plan in &plans {
+        collect_targets(&plan.blocks, &plan.file_name, &mut targets);
+    }
+    for plan in plans.iter_mut() {
+        rewrite_xrefs(&mut plan.blocks, &targets);
+    }
+    
+    plans
+}
+
+fn plan_chunks(blocks: &[Block], depth: usize, extension: &str, parent: usize, plans: &mut Vec<ChunkPlan>) {
+    for block in blocks {
+        match block {
+            Block::Section { level, title, id, number, blocks: children } if section_depth(*level) <= depth => {
+                let chunk_children: Vec<Block> = children.iter()
+                    .filter(|child| !is_chunk_section(child, depth))
+                    .cloned()
+                    .collect();
+                let file_name = chunk_file_name(id.as_deref().unwrap_or("section"), extension, plans);
+                plans.push(ChunkPlan {
+                    file_name,
+                    title: title.clone(),
+                    parent: Some(parent),
+                    blocks: vec![Block::Section {
+                        level: *level,
+                        title: title.clone(),
+                        id: id.clone(),
+                        number: number.clone(),
+                        blocks: chunk_children,
+                    }],
+                });
+                let index = plans.len() - 1;
+                plan_chunks(children, depth, extension, index, plans);
+            }
+            _ if parent == 0 => plans[0].blocks.push(block.clone()),
+            _ => {}
+        }
+    }
+}
+
+fn section_depth(level: usize) -> usize {
+    level.saturating_sub(1)
+}
+
+fn is_chunk_section(block: &Block, depth: usize) -> bool {
+    matches!(block, Block::Section { level, .. } if section_depth(*level) <= depth)
+}
+
+fn chunk_file_name(id: &str, extension: &str, plans: &[ChunkPlan]) -> String {
+    let id = &chunk_stem(id);
+    let mut file_name = format!("{}.{}", id, extension);
+    let mut counter = 2;
+    while plans.iter().any(|plan| plan.file_name == file_name) {
+        file_name = format!("{}-{}.{}", id, counter, extension);
+        counter += 1;
+    }
+    file_name
+}
+
+// Ids are written in the document, so only characters safe in a file name are kept, and the
+// name can neither climb out of the output directory nor start with a dot
+fn chunk_stem(id: &str) -> String {
+    let mut stem = String::new();
+    for c in id.chars() {
+        let c = if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' };
+        if c != '.' || !(stem.is_empty() || stem.ends_with('.')) {
+            stem.push(c);
+        }
+    }
+    match stem.trim_end_matches('.') {
+        "" => "section".to_string(),
+        stem => stem.to_string(),
+    }
+}
+
+fn collect_targets(blocks: &[Block], file_name: &str, targets: &mut HashMap<String, (String, Option<String>)>) {
+    for block in blocks {
+        match block {
+            Block::Section { title, id, blocks, .. } => {
+                if let Some(id) = id {
+                    targets.insert(id.clone(), (file_name.to_string(), Some(title.clone())));
+                }
+                collect_targets(blocks, file_name, targets);
+            }
+            Block::BlockMetadata { kind: BlockMetadataKind::Anchor(id) } => {
+                targets.insert(id.clone(), (file_name.to_string(), None));
+            }
+            _ => {}
+        }
+    }
+}
+
+// Internal references have to name the page their target ends up on
+fn rewrite_xrefs(blocks: &mut [Block], targets: &HashMap<String, (String, Option<String>)>) {
+    walk_block_inlines_mut(blocks, &mut |element| {
+        let InlineElement::Macro { kind: MacroKind::CrossReference { target, text } } = element else {
+            return;
+        };
+        if let Some((file_name, title)) = targets.get(target.as_str()) {
+            let text = text.clone().or_else(|| title.clone()).or_else(|| Some(target.clone()));
+            *element = InlineElement::Macro {
+                kind: MacroKind::Link { url: format!("{}#{}", file_name, target), text },
+            };
+        }
+    });
+}
+
+fn toc_html(plans: &[ChunkPlan]) -> String {
+    let mut html = String::from("<nav class=\"toc\">\n");
+    html.push_str(&toc_entries_html(plans, 0));
+    html.push_str("</nav>\n");
+    html
+}
+
+fn toc_entries_html(plans: &[ChunkPlan], parent: usize) -> String {
+    let children: Vec<(usize, &ChunkPlan)> = plans.iter()
+        .enumerate()
+        .filter(|(_, plan)| plan.parent == Some(parent))
+        .collect();
+    if children.is_empty() {
+        return String::new();
+    }
+    
+    let mut html = String::from("<ul>\n");
+    for (index, child) in children {
+        html.push_str(&format!("<li><a href=\"{}\">{}</a>", escape_html(&child.file_name), escape_html(&child.title)));
+        let nested = toc_entries_html(plans, index);
+        if !nested.is_empty() {
+            html.push('\n');
+            html.push_str(&nested);
+        }
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ul>\n");
+    html
+}
+
+fn navigation_html(plans: &[ChunkPlan], index: usize, site: &PageNavigation) -> String {
+    let mut html = String::from("<nav class=\"chunk-nav\">\n");
+    if index > 0 {
+        html.push_str(&nav_link("prev", &plans[index - 1].file_name, &plans[index - 1].title));
+    } else if let Some(NavLink { title, href: Some(href) }) = &site.previous {
+        html.push_str(&nav_link("prev", href, title));
+    }
+    if let Some(parent) = plans[index].parent {
+        html.push_str(&nav_link("up", &plans[parent].file_name, &plans[parent].title));
+    }
+    if index + 1 < plans.len() {
+        html.push_str(&nav_link("next", &plans[index + 1].file_name, &plans[index + 1].title));
+    } else if let Some(NavLink { title, href: Some(href) }) = &site.next {
+        html.push_str(&nav_link("next", href, title));
+    }
+    html.push_str("</nav>\n");
+    html
+}
+
+fn nav_link(rel: &str, href: &str, title: &str) -> String {
+    format!("<a rel=\"{}\" href=\"{}\">{}</a>\n", rel, escape_html(href), escape_html(title))
+}
@@ -0,0 +1,70 @@
+use crate::diagnostics::{StructureRules, Vocabulary};
+#[cfg(feature = "config")]
+use crate::error::ConfigError;
+
+// The name the command-line tool looks for in the working directory
+pub const CONFIG_FILE: &str = "adoc.toml";
+
+// Project settings shared by everyone working on the documents
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub vocabulary: Vocabulary,
+    pub structure: StructureRules,
+}
+
+// [vocabulary]
+// banned = ["simply", "obviously"]
+// case = ["GitHub", "JavaScript"]
+//
+// [vocabulary.preferred]
+// e-mail = "email"
+//
+// [structure]
+// max-list-depth = 3
+#[cfg(feature = "config")]
+pub fn from_toml(source: &str) -> Result<Config, ConfigError> {
+    use toml::{Table, Value};
+    
+    let table = source.parse::<Table>().map_err(ConfigError::new)?;
+    let mut config = Config::default();
+    match table.get("structure") {
+        None => {}
+        Some(Value::Table(structure)) => match structure.get("max-list-depth") {
+            None => {}
+            Some(Value::Integer(depth)) if *depth > 0 => config.structure.max_list_depth = *depth as usize,
+            Some(_) => return Err(ConfigError::new("'structure.max-list-depth' must be a positive integer")),
+        },
+        Some(_) => return Err(ConfigError::new("'structure' must be a table")),
+    }
+    let Some(vocabulary) = table.get("vocabulary") else {
+        return Ok(config);
+    };
+    let Value::Table(vocabulary) = vocabulary else {
+        return Err(ConfigError::new("'vocabulary' must be a table"));
+    };
+    
+    let strings = |key: &str| -> Result<Vec<String>, ConfigError> {
+        match vocabulary.get(key) {
+            None => Ok(Vec::new()),
+            Some(Value::Array(values)) => values.iter()
+                .map(|value| value.as_str().map(str::to_string).ok_or_else(|| ConfigError::new(format!("'vocabulary.{}' must list strings", key))))
+                .collect(),
+            Some(_) => Err(ConfigError::new(format!("'vocabulary.{}' must be an array", key))),
+        }
+    };
+    config.vocabulary.banned = strings("banned")?;
+    config.vocabulary.case = strings("case")?;
+    match vocabulary.get("preferred") {
+        None => {}
+        Some(Value::Table(preferred)) => {
+            for (avoid, prefer) in preferred {
+                let Value::String(prefer) = prefer else {
+                    return Err(ConfigError::new(format!("'vocabulary.preferred.{}' must be a string", avoid)));
+                };
+                config.vocabulary.preferred.push((avoid.clone(), prefer.clone()));
+            }
+        }
+        Some(_) => return Err(ConfigError::new("'vocabulary.preferred' must be a table")),
+    }
+    Ok(config)
+}
@@ -0,0 +1,312 @@
+use crate::ast::*;
+use crate::prelude::*;
+use crate::json::json_string;
+
+// The pieces of a rendered document a custom page layout is built from, in the spirit of
+// Asciidoctor's header and footer templates
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderContext {
+    pub title: Option<String>,
+    pub attributes: Vec<(String, String)>,
+    pub authors: Vec<Author>,
+    pub revision: Revision,
+    pub toc: Vec<TocEntry>,
+    // The body blocks without the header and footer
+    pub body_html: String,
+    // Every section in document order, each rendered with its subsections
+    pub sections: Vec<SectionContext>,
+    pub navigation: PageNavigation,
+    // The doctype and document roles, for the class of `<body>`
+    pub body_class: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Author {
+    pub name: String,
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Revision {
+    pub number: Option<String>,
+    pub date: Option<String>,
+    pub remark: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TocEntry {
+    pub id: String,
+    pub title: String,
+    pub number: Option<String>,
+    pub level: usize,
+    pub children: Vec<TocEntry>,
+}
+
+// Where a document sits in the reading order of a multi-file build. Empty for a document
+// converted on its own.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageNavigation {
+    pub previous: Option<NavLink>,
+    pub next: Option<NavLink>,
+    // From the outermost entry down to the document's parent
+    pub breadcrumbs: Vec<NavLink>,
+    // The whole site's navigation tree, shown beside every page
+    pub sidebar: Vec<NavItem>,
+}
+
+// An href relative to the page it appears on, or none for an entry that only groups others
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavLink {
+    pub title: String,
+    pub href: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavItem {
+    pub title: String,
+    pub href: Option<String>,
+    // Set on the entry for the page the tree is shown on
+    pub current: bool,
+    pub children: Vec<NavItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionContext {
+    pub id: String,
+    pub title: String,
+    pub level: usize,
+    pub html: String,
+}
+
+impl Document {
+    pub fn render_context(&self) -> RenderContext {
+        self.render_context_with(&HtmlOptions::default())
+    }
+
+    pub fn render_context_with(&self, options: &HtmlOptions) -> RenderContext {
+        let options = &options.for_document(self);
+        let value = |name: &str| self.attribute(name).filter(|value| !value.is_empty()).map(str::to_string);
+        
+        let authors = author_names(&|name| self.attribute(name)).into_iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let suffix = if index == 0 { String::new() } else { format!("_{}", index + 1) };
+                Author { name: name.to_string(), email: value(&format!("email{}", suffix)) }
+            })
+            .collect();
+        let mut sections = Vec::new();
+        collect_sections(&self.body, options, &mut sections);
+        
+        RenderContext {
+            title: self.header.as_ref().map(|header| header.title.clone()),
+            attributes: self.header.as_ref()
+                .map(|header| header.attributes.iter()
+                    .map(|attribute| (attribute.name.to_string(), attribute.value.clone().unwrap_or_default()))
+                    .collect())
+                .unwrap_or_default(),
+            authors,
+            revision: Revision { number: value("revnumber"), date: value("revdate"), remark: value("revremark") },
+            toc: toc_entries(&self.body, options.toc_levels),
+            body_html: self.body.iter().map(|block| block.to_html_with(options)).collect(),
+            sections,
+            navigation: options.navigation.clone(),
+            body_class: body_class(&|name| self.attribute(name)),
+        }
+    }
+}
+
+impl Document {
+    // Every `:revision:` entry in the header in the order written, or else the one revision
+    // `revnumber`, `revdate` and `revremark` describe
+    pub fn revisions(&self) -> Vec<Revision> {
+        let entries = self.header.iter()
+            .flat_map(|header| &header.attributes)
+            .map(|attribute| (attribute.name.as_str(), attribute.value.as_deref().unwrap_or("")));
+        revisions(entries, &|name| self.attribute(name))
+    }
+}
+
+pub(crate) fn revisions<'a>(entries: impl Iterator<Item = (&'a str, &'a str)>, attribute: &dyn Fn(&str) -> Option<&'a str>) -> Vec<Revision> {
+    let revisions: Vec<Revision> = entries
+        .filter(|(name, _)| *name == "revision")
+        .map(|(_, line)| parse_revision_line(line))
+        .collect();
+    if !revisions.is_empty() {
+        return revisions;
+    }
+    let value = |name: &str| attribute(name).filter(|value| !value.is_empty()).map(str::to_string);
+    let revision = Revision { number: value("revnumber"), date: value("revdate"), remark: value("revremark") };
+    if revision == Revision::default() { Vec::new() } else { vec![revision] }
+}
+
+// A revision line, `v1.2, 2024-03-01: Fixed typos`, under the authors or in a `:revision:`
+// entry. Without a comma it is only a
+// version when it starts with `v`, otherwise only a date.
+pub(crate) fn parse_revision_line(line: &str) -> Revision {
+    let non_empty = |value: &str| Some(value.trim().to_string()).filter(|value| !value.is_empty());
+    let (rest, remark) = match line.split_once(':') {
+        Some((rest, remark)) => (rest, non_empty(remark)),
+        None => (line, None),
+    };
+    let version = |number: &str| {
+        let number = number.trim();
+        non_empty(number.strip_prefix(['v', 'V']).filter(|rest| rest.starts_with(|c: char| c.is_ascii_digit())).unwrap_or(number))
+    };
+    match rest.split_once(',') {
+        Some((number, date)) => Revision { number: version(number), date: non_empty(date), remark },
+        None if rest.trim_start().starts_with(['v', 'V']) => Revision { number: version(rest), date: None, remark },
+        None => Revision { number: None, date: non_empty(rest), remark },
+    }
+}
+
+impl RenderContext {
+    // For template engines outside Rust; absent values are null
+    pub fn to_json(&self) -> String {
+        let optional = |value: &Option<String>| value.as_deref().map(json_string).unwrap_or_else(|| "null".to_string());
+        let attributes: Vec<String> = self.attributes.iter()
+            .map(|(name, value)| format!("{}: {}", json_string(name), json_string(value)))
+            .collect();
+        let authors: Vec<String> = self.authors.iter()
+            .map(|author| format!("{{\"name\": {}, \"email\": {}}}", json_string(&author.name), optional(&author.email)))
+            .collect();
+        let sections: Vec<String> = self.sections.iter()
+            .map(|section| format!(
+                "    {{\"id\": {}, \"title\": {}, \"level\": {}, \"html\": {}}}",
+                json_string(&section.id), json_string(&section.title), section.level, json_string(&section.html),
+            ))
+            .collect();
+        let link = |link: &NavLink| format!(
+            "{{\"title\": {}, \"href\": {}}}",
+            json_string(&link.title), optional(&link.href),
+        );
+        let optional_link = |value: &Option<NavLink>| value.as_ref().map(link).unwrap_or_else(|| "null".to_string());
+        let breadcrumbs: Vec<String> = self.navigation.breadcrumbs.iter().map(link).collect();
+        format!(
+            "{{\n  \"title\": {},\n  \"attributes\": {{{}}},\n  \"authors\": [{}],\n  \"revision\": {{\"number\": {}, \"date\": {}, \"remark\": {}}},\n  \"toc\": {},\n  \"body\": {},\n  \"sections\": [{}],\n  \"navigation\": {{\"previous\": {}, \"next\": {}, \"breadcrumbs\": [{}], \"sidebar\": {}}},\n  \"body_class\": {}\n}}\n",
+            optional(&self.title),
+            attributes.join(", "),
+            authors.join(", "),
+            optional(&self.revision.number),
+            optional(&self.revision.date),
+            optional(&self.revision.remark),
+            toc_json(&self.toc),
+            json_string(&self.body_html),
+            if sections.is_empty() { String::new() } else { format!("\n{}\n  ", sections.join(",\n")) },
+            optional_link(&self.navigation.previous),
+            optional_link(&self.navigation.next),
+            breadcrumbs.join(", "),
+            sidebar_json(&self.navigation.sidebar),
+            json_string(&self.body_class),
+        )
+    }
+}
+
+// Levels count the `=` signs, so `toclevels` 2 reaches down to `===` sections
+pub(crate) fn toc_entries(blocks: &[Block], toclevels: usize) -> Vec<TocEntry> {
+    blocks.iter()
+        .filter_map(|block| match block {
+            Block::Section { level, title, id, number, blocks } if *level <= toclevels + 1 => Some(TocEntry {
+                id: id.clone().unwrap_or_default(),
+                title: title.clone(),
+                number: number.clone(),
+                level: *level,
+                children: toc_entries(blocks, toclevels),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn toc_json(entries: &[TocEntry]) -> String {
+    let entries: Vec<String> = entries.iter()
+        .map(|entry| format!(
+            "{{\"id\": {}, \"title\": {}, \"number\": {}, \"level\": {}, \"children\": {}}}",
+            json_string(&entry.id),
+            json_string(&entry.title),
+            entry.number.as_deref().map(json_string).unwrap_or_else(|| "null".to_string()),
+            entry.level,
+            toc_json(&entry.children),
+        ))
+        .collect();
+    format!("[{}]", entries.join(", "))
+}
+
+fn sidebar_json(items: &[NavItem]) -> String {
+    let items: Vec<String> = items.iter()
+        .map(|item| format!(
+            "{{\"title\": {}, \"href\": {}, \"current\": {}, \"children\": {}}}",
+            json_string(&item.title),
+            item.href.as_deref().map(json_string).unwrap_or_else(|| "null".to_string()),
+            item.current,
+            sidebar_json(&item.children),
+        ))
+        .collect();
+    format!("[{}]", items.join(", "))
+}
+
+// Breadcrumbs above a page and links to the pages before and after it below, each left out
+// when there is nothing to show
+pub(crate) fn breadcrumbs_html(navigation: &PageNavigation) -> String {
+    if navigation.breadcrumbs.is_empty() {
+        return String::new();
+    }
+    let items: String = navigation.breadcrumbs.iter()
+        .map(|link| match &link.href {
+            Some(href) => format!("<li><a href=\"{}\">{}</a></li>\n", escape_html(href), escape_html(&link.title)),
+            None => format!("<li>{}</li>\n", escape_html(&link.title)),
+        })
+        .collect();
+    format!("<nav class=\"breadcrumbs\">\n<ol>\n{}</ol>\n</nav>\n", items)
+}
+
+// Nested lists, with the current page marked for styling and for screen readers
+pub(crate) fn sidebar_html(navigation: &PageNavigation) -> String {
+    fn items_html(items: &[NavItem]) -> String {
+        let mut html = String::from("<ul>\n");
+        for item in items {
+            html.push_str(if item.current { "<li class=\"current\">" } else { "<li>" });
+            match &item.href {
+                Some(href) if item.current => html.push_str(&format!("<a href=\"{}\" aria-current=\"page\">{}</a>", escape_html(href), escape_html(&item.title))),
+                Some(href) => html.push_str(&format!("<a href=\"{}\">{}</a>", escape_html(href), escape_html(&item.title))),
+                None => html.push_str(&format!("<span>{}</span>", escape_html(&item.title))),
+            }
+            if !item.children.is_empty() {
+                html.push('\n');
+                html.push_str(&items_html(&item.children));
+            }
+            html.push_str("</li>\n");
+        }
+        html.push_str("</ul>\n");
+        html
+    }
+    if navigation.sidebar.is_empty() {
+        return String::new();
+    }
+    format!("<nav class=\"sidebar\">\n{}</nav>\n", items_html(&navigation.sidebar))
+}
+
+pub(crate) fn page_links_html(navigation: &PageNavigation) -> String {
+    let link = |rel: &str, link: &Option<NavLink>| match link {
+        Some(NavLink { title, href: Some(href) }) => format!("<a rel=\"{}\" href=\"{}\">{}</a>\n", rel, escape_html(href), escape_html(title)),
+        _ => String::new(),
+    };
+    let links = format!("{}{}", link("prev", &navigation.previous), link("next", &navigation.next));
+    if links.is_empty() {
+        return String::new();
+    }
+    format!("<nav class=\"page-nav\">\n{}</nav>\n", links)
+}
+
+fn collect_sections(blocks: &[Block], options: &HtmlOptions, sections: &mut Vec<SectionContext>) {
+    for block in blocks {
+        if let Block::Section { level, title, id, blocks: children, .. } = block {
+            sections.push(SectionContext {
+                id: id.clone().unwrap_or_default(),
+                title: title.clone(),
+                level: *level,
+                html: block.to_html_with(options),
+            });
+            collect_sections(children, options, sections);
+        }
+    }
+}
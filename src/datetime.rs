@@ -0,0 +1,52 @@
+use crate::prelude::*;
+
+// Reproducible builds pin the clock through SOURCE_DATE_EPOCH
+#[cfg(feature = "std")]
+pub(crate) fn build_timestamp() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    
+    if let Some(epoch) = source_date_epoch() {
+        return epoch;
+    }
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn source_date_epoch() -> Option<u64> {
+    std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|value| value.trim().parse().ok())
+}
+
+// Formats seconds since the Unix epoch as an ISO 8601 UTC timestamp
+#[cfg(feature = "epub")]
+pub(crate) fn format_utc_timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let rem = secs % 86400;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+// Asciidoctor's `docdatetime` form, e.g. 2024-01-15 10:20:30 +0000
+#[cfg(feature = "std")]
+pub(crate) fn format_docdatetime(secs: u64) -> String {
+    let (date, time) = format_date_time(secs);
+    format!("{} {}", date, time)
+}
+
+// The date and the time apart, as `localdate` and `localtime` hold them
+pub(crate) fn format_date_time(secs: u64) -> (String, String) {
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let rem = secs % 86400;
+    (format!("{:04}-{:02}-{:02}", year, month, day), format!("{:02}:{:02}:{:02} +0000", rem / 3600, rem % 3600 / 60, rem % 60))
+}
+
+fn civil_from_days(days: i64) -> (i64, u64, u64) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = (z - era * 146097) as u64;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe as i64 + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -0,0 +1,498 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::ast::*;
+use crate::catalog::Catalog;
+use crate::config::Config;
+use crate::parser::{AsciiDocParser, ParseOptions};
+use crate::position::DocumentSymbol;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+// A registry entry: the stable code and name a diagnostic is known by, and what to do about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LintRule {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub severity: Severity,
+    pub summary: &'static str,
+    pub help: &'static str,
+}
+
+// In the same order as `DiagnosticKind`. Codes are never reused once published.
+pub const RULES: &[LintRule] = &[
+    LintRule {
+        code: "ADOC001",
+        name: "unterminated-block",
+        severity: Severity::Error,
+        summary: "a delimited block is opened but never closed",
+        help: "add a closing delimiter line matching the opening one",
+    },
+    LintRule {
+        code: "ADOC002",
+        name: "syntax-error",
+        severity: Severity::Error,
+        summary: "the document could not be parsed",
+        help: "check the line for markup the parser does not recognize",
+    },
+    LintRule {
+        code: "ADOC003",
+        name: "unresolved-include",
+        severity: Severity::Warning,
+        summary: "an include directive names a file that could not be read",
+        help: "fix the path, which is relative to the including file, or create the file",
+    },
+    LintRule {
+        code: "ADOC004",
+        name: "section-out-of-sequence",
+        severity: Severity::Warning,
+        summary: "a section skips a level below the section above it",
+        help: "use one more `=` than the parent section, or add the missing level",
+    },
+    LintRule {
+        code: "ADOC005",
+        name: "duplicate-id",
+        severity: Severity::Warning,
+        summary: "two sections or anchors have the same id",
+        help: "give one of them a different id so references reach the intended target",
+    },
+    LintRule {
+        code: "ADOC006",
+        name: "unresolved-xref",
+        severity: Severity::Warning,
+        summary: "a cross reference points at an id that does not exist in the document",
+        help: "fix the target or add an anchor such as `[[id]]` where it should point",
+    },
+    LintRule {
+        code: "ADOC007",
+        name: "banned-term",
+        severity: Severity::Warning,
+        summary: "prose uses a word the project vocabulary rules out",
+        help: "rephrase the sentence without it",
+    },
+    LintRule {
+        code: "ADOC008",
+        name: "preferred-term",
+        severity: Severity::Warning,
+        summary: "prose uses a term the project vocabulary replaces with another",
+        help: "use the preferred term instead",
+    },
+    LintRule {
+        code: "ADOC009",
+        name: "term-case",
+        severity: Severity::Warning,
+        summary: "a name from the project vocabulary is capitalized differently",
+        help: "write the name exactly as the vocabulary lists it",
+    },
+    LintRule {
+        code: "ADOC010",
+        name: "list-too-deep",
+        severity: Severity::Warning,
+        summary: "a list is nested deeper than the project allows",
+        help: "flatten the list, or split the deepest items into a list or section of their own",
+    },
+    LintRule {
+        code: "ADOC011",
+        name: "empty-section",
+        severity: Severity::Warning,
+        summary: "a section has a title but no content or subsections",
+        help: "write the section or remove its title",
+    },
+    LintRule {
+        code: "ADOC012",
+        name: "attribute-conflict",
+        severity: Severity::Warning,
+        summary: "documents of one build set the same header attribute to different values",
+        help: "set the attribute to one value everywhere, or once for the whole build",
+    },
+    LintRule {
+        code: "ADOC013",
+        name: "attribute-cycle",
+        severity: Severity::Warning,
+        summary: "an attribute's value refers back to itself through other attributes, or nests references too deeply",
+        help: "set one of the attributes in the chain without referring to the others",
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    UnterminatedBlock,
+    SyntaxError,
+    UnresolvedInclude,
+    SectionOutOfSequence,
+    DuplicateId,
+    UnresolvedXref,
+    BannedTerm,
+    PreferredTerm,
+    TermCase,
+    ListTooDeep,
+    EmptySection,
+    AttributeConflict,
+    AttributeCycle,
+}
+
+impl DiagnosticKind {
+    pub fn rule(self) -> &'static LintRule {
+        &RULES[self as usize]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    // 1-based, when the finding can be traced to a line
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn code(&self) -> &'static str {
+        self.kind.rule().code
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.kind.rule().name
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.kind.rule().severity
+    }
+
+    pub fn help(&self) -> &'static str {
+        self.kind.rule().help
+    }
+}
+
+// `ADOC001: unterminated listing block`
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message)
+    }
+}
+
+// Words and names a project rules on, matched as whole words in prose. ASCII letters match
+// without regard to case.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Vocabulary {
+    pub banned: Vec<String>,
+    // Each term to avoid with the one to use instead
+    pub preferred: Vec<(String, String)>,
+    // Product and other names that must be capitalized exactly like this
+    pub case: Vec<String>,
+}
+
+// Limits on how a document is put together
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureRules {
+    // How many lists may be nested inside one another, counting the outermost
+    pub max_list_depth: usize,
+}
+
+pub const DEFAULT_MAX_LIST_DEPTH: usize = 4;
+
+impl Default for StructureRules {
+    fn default() -> Self {
+        StructureRules { max_list_depth: DEFAULT_MAX_LIST_DEPTH }
+    }
+}
+
+// Finds a rule by its code or its name
+pub fn lookup(code: &str) -> Option<&'static LintRule> {
+    RULES.iter().find(|rule| rule.code.eq_ignore_ascii_case(code) || rule.name == code)
+}
+
+// Findings for one document, ordered by line
+pub fn check(input: &str, options: &ParseOptions) -> Vec<Diagnostic> {
+    check_with_vocabulary(input, options, &Vocabulary::default())
+}
+
+pub fn check_with_vocabulary(input: &str, options: &ParseOptions, vocabulary: &Vocabulary) -> Vec<Diagnostic> {
+    check_with_config(input, options, &Config { vocabulary: vocabulary.clone(), ..Config::default() })
+}
+
+pub fn check_with_config(input: &str, options: &ParseOptions, config: &Config) -> Vec<Diagnostic> {
+    let mut diagnostics = check_lines(input);
+    
+    if options.include_resolver.is_some() {
+        let graph = AsciiDocParser::include_graph("", input, options);
+        for edge in graph.edges.iter().filter(|edge| edge.depth == 0 && !edge.resolved) {
+            diagnostics.push(Diagnostic {
+                kind: DiagnosticKind::UnresolvedInclude,
+                line: Some(edge.line),
+                message: format!("include target not found: {}", edge.target),
+            });
+        }
+    }
+    
+    match AsciiDocParser::parse_document_with_options(input, options) {
+        Ok(document) => {
+            check_document(input, &document, &mut diagnostics);
+            check_attributes(input, &document, &mut diagnostics);
+            check_structure(input, &document, &config.structure, &mut diagnostics);
+            check_terms(input, &document, &config.vocabulary, &mut diagnostics);
+        }
+        // An unclosed block is the usual reason parsing fails, and is already reported
+        Err(_) if diagnostics.iter().any(|diagnostic| diagnostic.kind == DiagnosticKind::UnterminatedBlock) => {}
+        Err(e) => {
+            let message = e.message.lines().last().unwrap_or_default().trim_start_matches(['=', ' ']).to_string();
+            diagnostics.push(Diagnostic { kind: DiagnosticKind::SyntaxError, line: Some(e.line), message });
+        }
+    }
+    
+    let suppressed = suppressions(input);
+    diagnostics.retain(|diagnostic| !suppressed.iter().any(|suppression| suppression.covers(diagnostic)));
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line.unwrap_or(usize::MAX));
+    diagnostics
+}
+
+const DISABLE_NEXT_LINE: &str = "adoc-lint-disable-next-line";
+
+// `// adoc-lint-disable-next-line duplicate-id, ADOC006` silences those rules, or every
+// rule when none are named, on the line below the comment
+struct Suppression<'a> {
+    line: usize,
+    rules: Vec<&'a str>,
+}
+
+impl Suppression<'_> {
+    fn covers(&self, diagnostic: &Diagnostic) -> bool {
+        diagnostic.line == Some(self.line)
+            && (self.rules.is_empty() || self.rules.iter().any(|rule| lookup(rule) == Some(diagnostic.kind.rule())))
+    }
+}
+
+fn suppressions(input: &str) -> Vec<Suppression<'_>> {
+    input.lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let rules = line.trim().strip_prefix("//")?.trim_start().strip_prefix(DISABLE_NEXT_LINE)?;
+            if !rules.is_empty() && !rules.starts_with([' ', '\t']) {
+                return None;
+            }
+            Some(Suppression {
+                line: index + 2,
+                rules: rules.split([',', ' ', '\t']).filter(|rule| !rule.is_empty()).collect(),
+            })
+        })
+        .collect()
+}
+
+const BLOCK_DELIMITERS: &[(&str, &str)] = &[
+    ("----", "listing"),
+    ("====", "example"),
+    ("....", "literal"),
+    ("****", "sidebar"),
+    ("____", "quote"),
+];
+
+// Delimited blocks and section levels, which only the source lines show
+fn check_lines(input: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    // Innermost last
+    let mut open: Vec<(&str, &str, usize)> = Vec::new();
+    let mut previous_level = 1;
+    
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim_end();
+        if let Some(&(delimiter, name, _)) = open.last() {
+            if line == delimiter {
+                open.pop();
+            } else if !matches!(name, "listing" | "literal" | "comment") && is_literal_delimiter(line) {
+                // Only a literal block nests, inside an example, sidebar or quote block
+                open.push((line, "literal", index + 1));
+            }
+            continue;
+        }
+        if let Some(&(_, name)) = BLOCK_DELIMITERS.iter().find(|(delimiter, _)| line == *delimiter) {
+            open.push((line, name, index + 1));
+            continue;
+        }
+        if is_literal_delimiter(line) {
+            open.push((line, "literal", index + 1));
+            continue;
+        }
+        if is_comment_delimiter(line) {
+            open.push((line, "comment", index + 1));
+            continue;
+        }
+        
+        let level = line.chars().take_while(|&c| c == '=').count();
+        if level > 0 && line[level..].starts_with(' ') && index > 0 {
+            if level > previous_level + 1 {
+                diagnostics.push(Diagnostic {
+                    kind: DiagnosticKind::SectionOutOfSequence,
+                    line: Some(index + 1),
+                    message: format!("section title out of sequence: expected level {}, got level {}", previous_level, level - 1),
+                });
+            }
+            previous_level = level;
+        }
+    }
+    
+    // A comment block may run to the end of the document
+    if let Some(&(_, name, line)) = open.iter().rev().find(|(_, name, _)| *name != "comment") {
+        diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::UnterminatedBlock,
+            line: Some(line),
+            message: format!("unterminated {} block", name),
+        });
+    }
+    diagnostics
+}
+
+// Four or more dots, closed only by a line of the same length
+fn is_literal_delimiter(line: &str) -> bool {
+    line.len() >= 4 && line.bytes().all(|c| c == b'.')
+}
+
+// Four or more slashes, closed only by a line of the same length
+fn is_comment_delimiter(line: &str) -> bool {
+    line.len() >= 4 && line.bytes().all(|c| c == b'/')
+}
+
+fn check_document(input: &str, document: &Document, diagnostics: &mut Vec<Diagnostic>) {
+    let mut catalog = Catalog::new();
+    catalog.add_document("", document);
+    
+    let mut ids = HashSet::new();
+    for entry in catalog.entries() {
+        if !ids.insert(entry.id.as_str()) {
+            diagnostics.push(Diagnostic {
+                kind: DiagnosticKind::DuplicateId,
+                line: last_line_containing(input, &[&format!("[[{}]]", entry.id), &format!("[#{}", entry.id)]),
+                message: format!("id assigned more than once: {}", entry.id),
+            });
+        }
+    }
+    
+    // References into other documents are checked across a workspace instead
+    document.walk_inlines(&mut |element| {
+        if let InlineElement::Macro { kind: MacroKind::CrossReference { target, .. } } = element
+            && !target.contains('#')
+            && !target.ends_with(".adoc")
+            && !ids.contains(target.as_str())
+        {
+            diagnostics.push(Diagnostic {
+                kind: DiagnosticKind::UnresolvedXref,
+                line: first_line_containing(input, &[&format!("<<{}", target), &format!("xref:{}", target)]),
+                message: format!("cross reference target not found: {}", target),
+            });
+        }
+    });
+}
+
+// Reported on the entry that closes the cycle, since until then every reference could resolve
+fn check_attributes(input: &str, document: &Document, diagnostics: &mut Vec<Diagnostic>) {
+    for definition in document.attribute_table(input) {
+        if let Some(chain) = definition.cycle {
+            let (last, before) = chain.split_last().unwrap_or((&definition.name, &[]));
+            let problem = if before.contains(last) { "attribute reference cycle" } else { "attribute references nested too deeply" };
+            diagnostics.push(Diagnostic {
+                kind: DiagnosticKind::AttributeCycle,
+                line: Some(definition.span.start.line),
+                message: format!("{}: {}", problem, chain.join(" -> ")),
+            });
+        }
+    }
+}
+
+// What only the parsed tree shows: how deep lists go and which sections were left empty
+fn check_structure(input: &str, document: &Document, rules: &StructureRules, diagnostics: &mut Vec<Diagnostic>) {
+    check_list_depth(input, &document.body, 0, rules.max_list_depth, diagnostics);
+
+    fn empty_sections(symbols: &[DocumentSymbol], diagnostics: &mut Vec<Diagnostic>) {
+        for symbol in symbols {
+            // The span of a section runs to the end of its last block
+            if symbol.span == symbol.heading {
+                diagnostics.push(Diagnostic {
+                    kind: DiagnosticKind::EmptySection,
+                    line: Some(symbol.heading.start.line),
+                    message: format!("section has no content: {}", symbol.title),
+                });
+            }
+            empty_sections(&symbol.children, diagnostics);
+        }
+    }
+    empty_sections(&document.symbols(input), diagnostics);
+}
+
+// A list deeper than allowed is reported once, at its first item, and not again for the lists
+// nested inside it
+fn check_list_depth(input: &str, blocks: &[Block], depth: usize, max_depth: usize, diagnostics: &mut Vec<Diagnostic>) {
+    for block in blocks {
+        match block {
+            Block::Section { blocks, .. } | Block::Sidebar { blocks, .. } => check_list_depth(input, blocks, depth, max_depth, diagnostics),
+            Block::List { items, .. } if depth + 1 > max_depth => {
+                let text = match items.first() {
+                    Some(ListItem::Unordered { content, .. } | ListItem::Ordered { content, .. }) => plain_text(content),
+                    Some(ListItem::Description { term, .. }) => term.clone(),
+                    None => String::new(),
+                };
+                let first_line = text.lines().next().unwrap_or_default().trim();
+                diagnostics.push(Diagnostic {
+                    kind: DiagnosticKind::ListTooDeep,
+                    line: (!first_line.is_empty()).then(|| first_line_containing(input, &[first_line])).flatten(),
+                    message: format!("list nested {} levels deep, more than {}", depth + 1, max_depth),
+                });
+            }
+            Block::List { items, .. } => {
+                for item in items {
+                    check_list_depth(input, item.blocks(), depth + 1, max_depth, diagnostics);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn check_terms(input: &str, document: &Document, vocabulary: &Vocabulary, diagnostics: &mut Vec<Diagnostic>) {
+    if vocabulary == &Vocabulary::default() {
+        return;
+    }
+    for run in document.prose_runs(input) {
+        let mut push = |kind, message| diagnostics.push(Diagnostic { kind, line: Some(run.span.start.line), message });
+        for term in &vocabulary.banned {
+            for found in find_term(&run.text, term) {
+                push(DiagnosticKind::BannedTerm, format!("'{}' is not allowed", found));
+            }
+        }
+        for (avoid, prefer) in &vocabulary.preferred {
+            for found in find_term(&run.text, avoid) {
+                push(DiagnosticKind::PreferredTerm, format!("use '{}' instead of '{}'", prefer, found));
+            }
+        }
+        for name in &vocabulary.case {
+            for found in find_term(&run.text, name).into_iter().filter(|found| found != name) {
+                push(DiagnosticKind::TermCase, format!("write '{}' as '{}'", found, name));
+            }
+        }
+    }
+}
+
+// Occurrences of `term` in `text` not joined to letters or digits on either side
+fn find_term<'a>(text: &'a str, term: &str) -> Vec<&'a str> {
+    if term.is_empty() {
+        return Vec::new();
+    }
+    text.char_indices()
+        .filter_map(|(start, _)| {
+            let found = text.get(start..start + term.len()).filter(|found| found.eq_ignore_ascii_case(term))?;
+            let before = text[..start].chars().next_back().is_some_and(char::is_alphanumeric);
+            let after = text[start + term.len()..].chars().next().is_some_and(char::is_alphanumeric);
+            (!before && !after).then_some(found)
+        })
+        .collect()
+}
+
+fn first_line_containing(input: &str, needles: &[&str]) -> Option<usize> {
+    input.lines().position(|line| needles.iter().any(|needle| line.contains(needle))).map(|index| index + 1)
+}
+
+fn last_line_containing(input: &str, needles: &[&str]) -> Option<usize> {
+    let lines: Vec<&str> = input.lines().collect();
+    lines.iter().rposition(|line| needles.iter().any(|needle| line.contains(needle))).map(|index| index + 1)
+}
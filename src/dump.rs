@@ -0,0 +1,265 @@
+use alloc::collections::BTreeMap;
+use core::fmt::Write;
+
+use crate::ast::*;
+use crate::position::{Node, SourceNode, Span, source_tree};
+use crate::prelude::*;
+
+impl Document {
+    // The tree as indented lines, one node to a line with its fields after its name, for
+    // snapshot tests and bug reports. Given the text the document was parsed from, blocks,
+    // list items and formatted text end with where they are in it, as `@line:column-line:column`.
+    pub fn dump(&self, source: Option<&str>) -> String {
+        let mut spans = BTreeMap::new();
+        if let Some(source) = source {
+            collect_spans(&source_tree(self, source), &mut spans);
+        }
+        let mut dumper = Dumper { spans, out: String::new() };
+        if let Some(header) = &self.header {
+            dumper.line(0, format!("Header {:?}", header.title), None);
+            for attribute in &header.attributes {
+                dumper.line(1, format!("Attribute {}", entry(attribute)), None);
+            }
+        }
+        for block in &self.body {
+            dumper.block(block, 0);
+        }
+        dumper.out
+    }
+}
+
+// Nodes are told apart by their address, which no two nodes of one kind share
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum NodeKey {
+    Block(usize),
+    ListItem(usize),
+    Inline(usize),
+}
+
+impl NodeKey {
+    fn of(node: Node) -> NodeKey {
+        match node {
+            Node::Block(block) => NodeKey::Block(block as *const Block as usize),
+            Node::ListItem(item) => NodeKey::ListItem(item as *const ListItem as usize),
+            Node::Inline(element) => NodeKey::Inline(element as *const InlineElement as usize),
+        }
+    }
+}
+
+fn collect_spans(nodes: &[SourceNode], spans: &mut BTreeMap<NodeKey, Span>) {
+    for node in nodes {
+        spans.insert(NodeKey::of(node.node), node.span);
+        collect_spans(&node.children, spans);
+    }
+}
+
+struct Dumper {
+    spans: BTreeMap<NodeKey, Span>,
+    out: String,
+}
+
+impl Dumper {
+    fn line(&mut self, depth: usize, text: String, node: Option<Node>) {
+        self.out.push_str(&"  ".repeat(depth));
+        self.out.push_str(&text);
+        if let Some(span) = node.and_then(|node| self.spans.get(&NodeKey::of(node))) {
+            let _ = write!(self.out, " @{}:{}-{}:{}", span.start.line, span.start.column, span.end.line, span.end.column);
+        }
+        self.out.push('\n');
+    }
+
+    fn block(&mut self, block: &Block, depth: usize) {
+        let node = Some(Node::Block(block));
+        match block {
+            Block::Section { level, title, id, number, blocks } => {
+                let mut text = format!("Section level={} title={:?}", level, title);
+                optional(&mut text, "id", id);
+                optional(&mut text, "number", number);
+                self.line(depth, text, node);
+                self.blocks(blocks, depth + 1);
+            }
+            Block::Paragraph { content } => {
+                self.line(depth, "Paragraph".to_string(), node);
+                self.inlines(content, depth + 1);
+            }
+            Block::Admonition { kind, content, blocks } => {
+                self.line(depth, format!("Admonition kind={}", kind.name()), node);
+                self.inlines(content, depth + 1);
+                self.blocks(blocks, depth + 1);
+            }
+            Block::DelimitedBlock { kind, content, language, title } => {
+                let mut text = match kind {
+                    DelimitedBlockKind::Listing => "Listing".to_string(),
+                    DelimitedBlockKind::Example => "Example".to_string(),
+                    DelimitedBlockKind::Literal => "Literal".to_string(),
+                    DelimitedBlockKind::Quote(attribution) => {
+                        let mut text = "Quote".to_string();
+                        optional(&mut text, "author", &attribution.author);
+                        optional(&mut text, "citation", &attribution.citation);
+                        text
+                    }
+                };
+                if let Some(language) = language {
+                    let _ = write!(text, " language={:?}", language.as_str());
+                }
+                optional(&mut text, "title", title);
+                let _ = write!(text, " content={:?}", content);
+                self.line(depth, text, node);
+            }
+            Block::List { kind, items } => {
+                let kind = match kind {
+                    ListKind::Unordered => "unordered".to_string(),
+                    ListKind::Ordered => "ordered".to_string(),
+                    ListKind::Description(style) => format!("description style={:?}", style).to_lowercase(),
+                };
+                self.line(depth, format!("List kind={}", kind), node);
+                for item in items {
+                    self.item(item, depth + 1);
+                }
+            }
+            Block::Sidebar { title, roles, blocks } => {
+                let mut text = "Sidebar".to_string();
+                optional(&mut text, "title", title);
+                if !roles.is_empty() {
+                    let _ = write!(text, " roles={:?}", roles);
+                }
+                self.line(depth, text, node);
+                self.blocks(blocks, depth + 1);
+            }
+            Block::Table { title, header, rows } => {
+                let mut text = format!("Table header={}", header);
+                optional(&mut text, "title", title);
+                self.line(depth, text, node);
+                for row in rows {
+                    self.line(depth + 1, "Row".to_string(), None);
+                    for cell in row {
+                        let mut text = "Cell".to_string();
+                        if cell.colspan != 1 {
+                            let _ = write!(text, " colspan={}", cell.colspan);
+                        }
+                        if cell.rowspan != 1 {
+                            let _ = write!(text, " rowspan={}", cell.rowspan);
+                        }
+                        if let Some(align) = cell.align {
+                            let _ = write!(text, " align={}", format!("{:?}", align).to_lowercase());
+                        }
+                        self.line(depth + 2, text, None);
+                        self.inlines(&cell.content, depth + 3);
+                    }
+                }
+            }
+            Block::BlockMetadata { kind } => {
+                let text = match kind {
+                    BlockMetadataKind::Title(title) => format!("Title {:?}", title),
+                    BlockMetadataKind::Attribute(entries) => {
+                        let entries: Vec<&str> = entries.iter().map(|entry| entry.as_str()).collect();
+                        format!("AttributeList {:?}", entries)
+                    }
+                    BlockMetadataKind::Anchor(anchor) => format!("Anchor {:?}", anchor),
+                    BlockMetadataKind::AttributeEntry(attribute) => format!("Attribute {}", entry(attribute)),
+                    BlockMetadataKind::AttributeUnset(name) => format!("AttributeUnset name={:?}", name.as_str()),
+                };
+                self.line(depth, text, node);
+            }
+            Block::TableOfContents { levels } => {
+                let mut text = "TableOfContents".to_string();
+                if let Some(levels) = levels {
+                    let _ = write!(text, " levels={}", levels);
+                }
+                self.line(depth, text, node);
+            }
+            Block::Image { path, alt, width, height, title } => {
+                let mut text = format!("Image path={:?}", path);
+                optional(&mut text, "alt", alt);
+                optional(&mut text, "width", width);
+                optional(&mut text, "height", height);
+                optional(&mut text, "title", title);
+                self.line(depth, text, node);
+            }
+            Block::PageBreak => self.line(depth, "PageBreak".to_string(), node),
+            Block::Comment { text, delimited } => {
+                let kind = if *delimited { "block" } else { "line" };
+                self.line(depth, format!("Comment kind={} text={:?}", kind, text), node);
+            }
+        }
+    }
+
+    fn blocks(&mut self, blocks: &[Block], depth: usize) {
+        for block in blocks {
+            self.block(block, depth);
+        }
+    }
+
+    fn item(&mut self, item: &ListItem, depth: usize) {
+        let node = Some(Node::ListItem(item));
+        match item {
+            ListItem::Unordered { content, .. } | ListItem::Ordered { content, .. } => {
+                self.line(depth, "Item".to_string(), node);
+                self.inlines(content, depth + 1);
+            }
+            ListItem::Description { term, description, .. } => {
+                self.line(depth, format!("Item term={:?}", term), node);
+                self.inlines(description.as_deref().unwrap_or_default(), depth + 1);
+            }
+        }
+        self.blocks(item.blocks(), depth + 1);
+    }
+
+    fn inlines(&mut self, elements: &[InlineElement], depth: usize) {
+        for element in elements {
+            let node = Some(Node::Inline(element));
+            match element {
+                InlineElement::Text(text) => self.line(depth, format!("Text {:?}", text), node),
+                InlineElement::Formatted { kind, content, attributes } => {
+                    let mut text = format!("{:?}", kind);
+                    optional(&mut text, "id", &attributes.id);
+                    if !attributes.roles.is_empty() {
+                        let _ = write!(text, " roles={:?}", attributes.roles);
+                    }
+                    self.line(depth, text, node);
+                    self.inlines(content, depth + 1);
+                }
+                InlineElement::Macro { kind } => {
+                    let text = match kind {
+                        MacroKind::Link { url, text } => {
+                            let mut line = format!("Link url={:?}", url);
+                            optional(&mut line, "text", text);
+                            line
+                        }
+                        MacroKind::Image { path, attributes } => {
+                            let mut line = format!("Image path={:?}", path);
+                            optional(&mut line, "attributes", attributes);
+                            line
+                        }
+                        MacroKind::CrossReference { target, text } => {
+                            let mut line = format!("CrossReference target={:?}", target);
+                            optional(&mut line, "text", text);
+                            line
+                        }
+                        MacroKind::Term { term, target } => {
+                            let mut line = format!("Term term={:?}", term);
+                            optional(&mut line, "target", target);
+                            line
+                        }
+                    };
+                    self.line(depth, text, node);
+                }
+                InlineElement::LineBreak => self.line(depth, "LineBreak".to_string(), node),
+                InlineElement::Passthrough(html) => self.line(depth, format!("Passthrough {:?}", html), node),
+            }
+        }
+    }
+}
+
+fn optional(text: &mut String, name: &str, value: &Option<String>) {
+    if let Some(value) = value {
+        let _ = write!(text, " {}={:?}", name, value);
+    }
+}
+
+fn entry(attribute: &Attribute) -> String {
+    match &attribute.value {
+        Some(value) => format!("name={:?} value={:?}", attribute.name.as_str(), value),
+        None => format!("name={:?}", attribute.name.as_str()),
+    }
+}
@@ -0,0 +1,231 @@
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::path::PathBuf;
+
+use zip::CompressionMethod;
+use zip::write::SimpleFileOptions;
+
+use crate::ast::*;
+use crate::chunked::{ChunkPlan, plan_document_chunks};
+use crate::datetime::{build_timestamp, format_utc_timestamp};
+use crate::trace;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpubOptions {
+    // Directory that relative image paths are read from
+    pub base_dir: PathBuf,
+    pub modified: Option<String>,
+}
+
+impl Default for EpubOptions {
+    fn default() -> Self {
+        EpubOptions {
+            base_dir: PathBuf::from("."),
+            modified: None,
+        }
+    }
+}
+
+impl Document {
+    pub fn to_epub(&self, options: &EpubOptions) -> io::Result<Vec<u8>> {
+        trace::span!(DEBUG, "render", backend = "epub");
+        let title = self.header.as_ref().map(|h| h.title.clone()).unwrap_or_else(|| "Untitled".to_string());
+        let language = self.attribute("lang").unwrap_or("en").to_string();
+        let chapters = plan_document_chunks(self, 1, "xhtml");
+        let images = collect_images(&chapters);
+        
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        // The mimetype entry must come first and be stored uncompressed
+        zip.start_file("mimetype", SimpleFileOptions::default().compression_method(CompressionMethod::Stored))?;
+        zip.write_all(b"application/epub+zip")?;
+        
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.start_file("META-INF/container.xml", deflated)?;
+        zip.write_all(CONTAINER_XML.as_bytes())?;
+        
+        zip.start_file("OEBPS/content.opf", deflated)?;
+        zip.write_all(self.package_document(&title, &language, &chapters, &images, options).as_bytes())?;
+        
+        zip.start_file("OEBPS/nav.xhtml", deflated)?;
+        zip.write_all(nav_document(&title, &language, &chapters).as_bytes())?;
+        
+        for (index, chapter) in chapters.iter().enumerate() {
+            let mut body = String::new();
+            if index == 0 {
+                body.push_str(&format!("<h1>{}</h1>\n", escape_html(&title)));
+            }
+            for block in &chapter.blocks {
+                body.push_str(&block.to_html());
+            }
+            zip.start_file(format!("OEBPS/{}", chapter.file_name), deflated)?;
+            zip.write_all(xhtml_page(&chapter.title, &language, &xhtml(&body)).as_bytes())?;
+        }
+        
+        for image in &images {
+            let data = fs::read(options.base_dir.join(image))
+                .map_err(|e| io::Error::new(e.kind(), format!("cannot read image '{}': {}", image, e)))?;
+            zip.start_file(format!("OEBPS/{}", image), deflated)?;
+            zip.write_all(&data)?;
+        }
+        
+        Ok(zip.finish()?.into_inner())
+    }
+
+    fn package_document(
+        &self,
+        title: &str,
+        language: &str,
+        chapters: &[ChunkPlan],
+        images: &[String],
+        options: &EpubOptions,
+    ) -> String {
+        let identifier = match self.attribute("uuid") {
+            Some(uuid) => format!("urn:uuid:{}", uuid),
+            None => format!("urn:adoc:{}", title.to_lowercase().replace(char::is_whitespace, "-")),
+        };
+        let modified = options.modified.clone().unwrap_or_else(|| format_utc_timestamp(build_timestamp()));
+        
+        let mut opf = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        opf.push_str("<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"bookid\">\n");
+        opf.push_str("<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n");
+        opf.push_str(&format!("<dc:identifier id=\"bookid\">{}</dc:identifier>\n", escape_html(&identifier)));
+        opf.push_str(&format!("<dc:title>{}</dc:title>\n", escape_html(title)));
+        opf.push_str(&format!("<dc:language>{}</dc:language>\n", escape_html(language)));
+        if let Some(author) = self.attribute("author") {
+            opf.push_str(&format!("<dc:creator>{}</dc:creator>\n", escape_html(author)));
+        }
+        opf.push_str(&format!("<meta property=\"dcterms:modified\">{}</meta>\n", escape_html(&modified)));
+        opf.push_str("</metadata>\n<manifest>\n");
+        opf.push_str("<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n");
+        for (index, chapter) in chapters.iter().enumerate() {
+            opf.push_str(&format!(
+                "<item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+                index,
+                escape_html(&chapter.file_name)
+            ));
+        }
+        for (index, image) in images.iter().enumerate() {
+            opf.push_str(&format!(
+                "<item id=\"image-{}\" href=\"{}\" media-type=\"{}\"/>\n",
+                index,
+                escape_html(image),
+                image_media_type(image)
+            ));
+        }
+        opf.push_str("</manifest>\n<spine>\n");
+        for index in 0..chapters.len() {
+            opf.push_str(&format!("<itemref idref=\"chapter-{}\"/>\n", index));
+        }
+        opf.push_str("</spine>\n</package>\n");
+        opf
+    }
+}
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+<rootfiles>
+<rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+</rootfiles>
+</container>
+"#;
+
+fn nav_document(title: &str, language: &str, chapters: &[ChunkPlan]) -> String {
+    let mut body = String::from("<nav epub:type=\"toc\" id=\"toc\">\n<h1>Contents</h1>\n<ol>\n");
+    // The index page is the title page; top-level chapters are listed beside it
+    body.push_str(&nav_item(&chapters[0], String::new()));
+    body.push_str(&nav_items(chapters, 0));
+    body.push_str("</ol>\n</nav>\n");
+    xhtml_page(title, language, &body)
+}
+
+fn nav_items(chapters: &[ChunkPlan], parent: usize) -> String {
+    chapters.iter()
+        .enumerate()
+        .filter(|(_, chapter)| chapter.parent == Some(parent))
+        .map(|(index, chapter)| {
+            let nested = nav_items(chapters, index);
+            let nested = if nested.is_empty() { nested } else { format!("\n<ol>\n{}</ol>\n", nested) };
+            nav_item(chapter, nested)
+        })
+        .collect()
+}
+
+fn nav_item(chapter: &ChunkPlan, nested: String) -> String {
+    format!("<li><a href=\"{}\">{}</a>{}</li>\n", escape_html(&chapter.file_name), escape_html(&chapter.title), nested)
+}
+
+fn xhtml_page(title: &str, language: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{lang}\" lang=\"{lang}\">\n<head>\n<meta charset=\"UTF-8\"/>\n<title>{title}</title>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        lang = escape_html(language),
+        title = escape_html(title),
+        body = body
+    )
+}
+
+// The HTML renderer emits void elements without a closing slash, which XHTML requires
+fn xhtml(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find("<br>").into_iter().chain(rest.find("<img ")).min() {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find('>').unwrap_or(rest.len() - 1);
+        let tag = &rest[..end];
+        output.push_str(tag.trim_end_matches('/'));
+        output.push_str("/>");
+        rest = &rest[end + 1..];
+    }
+    output.push_str(rest);
+    output
+}
+
+fn collect_images(chapters: &[ChunkPlan]) -> Vec<String> {
+    let mut images = Vec::new();
+    for chapter in chapters {
+        walk_block_inlines(&chapter.blocks, &mut |element| {
+            if let InlineElement::Macro { kind: MacroKind::Image { path, .. } } = element
+                && is_embeddable(path)
+                && !images.contains(path)
+            {
+                images.push(path.clone());
+            }
+        });
+        collect_block_images(&chapter.blocks, &mut images);
+    }
+    images
+}
+
+fn collect_block_images(blocks: &[Block], images: &mut Vec<String>) {
+    for block in blocks {
+        match block {
+            Block::Image { path, .. } if is_embeddable(path) && !images.contains(path) => images.push(path.clone()),
+            Block::Section { blocks, .. } | Block::Sidebar { blocks, .. } | Block::Admonition { blocks, .. } => {
+                collect_block_images(blocks, images);
+            }
+            Block::List { items, .. } => {
+                for item in items {
+                    collect_block_images(item.blocks(), images);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Only relative paths inside the base directory can be packaged
+fn is_embeddable(path: &str) -> bool {
+    !path.contains("://") && !path.starts_with('/') && !path.split('/').any(|segment| segment == "..")
+}
+
+fn image_media_type(path: &str) -> &'static str {
+    let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
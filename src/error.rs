@@ -0,0 +1,55 @@
+use core::fmt;
+
+use crate::prelude::*;
+
+// Errors are plain data, so they can be sent between threads and passed across the wasm
+// boundary as they are
+
+// Where the grammar rejected a document. The message is pest's, with the offending line
+// and a caret under the column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    // Set when the document is one of several, as in a workspace
+    pub document: Option<String>,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn in_document(self, name: &str) -> ParseError {
+        ParseError { document: Some(name.to_string()), ..self }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.document {
+            Some(document) => write!(f, "{}: {}", document, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+// An attribute or configuration file that is not valid in its format, or that gives a
+// setting a value of the wrong type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub message: String,
+}
+
+impl ConfigError {
+    pub fn new(message: impl fmt::Display) -> ConfigError {
+        ConfigError { message: message.to_string() }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl core::error::Error for ConfigError {}
@@ -0,0 +1,102 @@
+use crate::ast::*;
+use crate::parser::number_sections;
+use crate::prelude::*;
+
+impl Document {
+    // The section with `selector` as its id, or else at the path of titles `Guide/Install`,
+    // as a document of its own: the section's title becomes the document title and its
+    // subsections move up to match. The header takes the attributes in effect where the
+    // section starts, including those set in the body above it, so it renders the same.
+    // A chapter taken out of a book is an article.
+    pub fn extract(&self, selector: &str) -> Option<Document> {
+        let header_attributes: Vec<Attribute> = self.header.as_ref()
+            .map(|header| header.attributes.iter().filter(|attribute| attribute.name != "doctype").cloned().collect())
+            .unwrap_or_default();
+        let mut attributes = header_attributes.clone();
+        let mut found = find_section(&self.body, selector, &mut attributes);
+        if found.is_none() {
+            let path: Vec<&str> = selector.split('/').map(str::trim).collect();
+            attributes = header_attributes;
+            found = find_section_by_path(&self.body, &path, &mut attributes);
+        }
+        let (level, title, blocks) = found?;
+        
+        let mut body = blocks.to_vec();
+        rebase_levels(&mut body, level - 1);
+        number_sections(&mut body, &attributes);
+        Some(Document { header: Some(Header { title: title.to_string(), attributes }), body })
+    }
+}
+
+// Attribute entries hold from where they are written to the end of the document, so every
+// entry before the section, in sections of its own or not, is applied to `attributes` on
+// the way to it
+fn find_section<'a>(blocks: &'a [Block], id: &str, attributes: &mut Vec<Attribute>) -> Option<(usize, &'a str, &'a [Block])> {
+    for block in blocks {
+        match block {
+            Block::Section { level, title, id: section_id, blocks, .. } => {
+                if section_id.as_deref() == Some(id) {
+                    return Some((*level, title, blocks));
+                }
+                if let Some(found) = find_section(blocks, id, attributes) {
+                    return Some(found);
+                }
+            }
+            Block::BlockMetadata { kind } => apply_entry(kind, attributes),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn find_section_by_path<'a>(blocks: &'a [Block], path: &[&str], attributes: &mut Vec<Attribute>) -> Option<(usize, &'a str, &'a [Block])> {
+    let (first, rest) = path.split_first()?;
+    for block in blocks {
+        match block {
+            Block::Section { level, title, blocks, .. } if title == first => {
+                if rest.is_empty() {
+                    return Some((*level, title, blocks));
+                }
+                return find_section_by_path(blocks, rest, attributes);
+            }
+            Block::Section { blocks, .. } => apply_entries(blocks, attributes),
+            Block::BlockMetadata { kind } => apply_entry(kind, attributes),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn apply_entries(blocks: &[Block], attributes: &mut Vec<Attribute>) {
+    for block in blocks {
+        match block {
+            Block::Section { blocks, .. } => apply_entries(blocks, attributes),
+            Block::BlockMetadata { kind } => apply_entry(kind, attributes),
+            _ => {}
+        }
+    }
+}
+
+fn apply_entry(kind: &BlockMetadataKind, attributes: &mut Vec<Attribute>) {
+    match kind {
+        BlockMetadataKind::AttributeEntry(attribute) => {
+            attributes.retain(|existing| existing.name != attribute.name);
+            attributes.push(attribute.clone());
+        }
+        BlockMetadataKind::AttributeUnset(name) => attributes.retain(|existing| existing.name != *name),
+        _ => {}
+    }
+}
+
+fn rebase_levels(blocks: &mut [Block], offset: usize) {
+    for block in blocks {
+        match block {
+            Block::Section { level, blocks, .. } => {
+                *level -= offset;
+                rebase_levels(blocks, offset);
+            }
+            Block::Sidebar { blocks, .. } => rebase_levels(blocks, offset),
+            _ => {}
+        }
+    }
+}
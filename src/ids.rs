@@ -0,0 +1,183 @@
+use alloc::collections::BTreeMap;
+
+use crate::parser::generate_id;
+use crate::prelude::*;
+
+// Turns section titles into ids. Whatever it returns is what the TOC, the heading anchors and
+// `<<id>>` references all see, so a site can hold every document to its own slug rules.
+// A number is appended afterwards to tell apart sections that end up with the same id.
+pub trait IdGenerator: Send + Sync {
+    // `prefix` and `separator` are the document's `idprefix` and `idseparator`, `_` unless set
+    fn generate_id(&self, title: &str, prefix: &str, separator: &str) -> String;
+}
+
+// The rules Asciidoctor uses: lowercased, with runs of spaces, `-`, `.` and `_` turned into
+// the separator and other punctuation dropped
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultIdGenerator;
+
+impl IdGenerator for DefaultIdGenerator {
+    fn generate_id(&self, title: &str, prefix: &str, separator: &str) -> String {
+        generate_id(title, prefix, separator)
+    }
+}
+
+// What becomes of a letter with no ASCII spelling, such as one from a CJK script
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SlugFallback {
+    // Written as the percent-encoded bytes of its UTF-8, `中` as `%E4%B8%AD`
+    #[default]
+    PercentEncode,
+    Drop,
+}
+
+// Ids made only of ASCII, for sites whose URLs or tooling can't carry anything else. Accented
+// Latin, Cyrillic and Greek letters are spelled out (`Привет` gives `privet`), and other letters
+// are handled by the fallback. A title that leaves nothing behind gets `section`.
+#[derive(Debug, Clone, Default)]
+pub struct AsciiIdGenerator {
+    fallback: SlugFallback,
+    // Spellings that take the place of the built-in ones
+    custom: BTreeMap<char, String>,
+}
+
+impl AsciiIdGenerator {
+    pub fn new(fallback: SlugFallback) -> Self {
+        AsciiIdGenerator { fallback, custom: BTreeMap::new() }
+    }
+    
+    // Spells the lowercase letter `letter` as `ascii`, which can be empty to drop it
+    pub fn transliterate(mut self, letter: char, ascii: &str) -> Self {
+        self.custom.insert(letter, ascii.to_ascii_lowercase());
+        self
+    }
+}
+
+impl IdGenerator for AsciiIdGenerator {
+    fn generate_id(&self, title: &str, prefix: &str, separator: &str) -> String {
+        let mut id = String::from(prefix);
+        let mut pending_separator = false;
+        
+        for ch in title.to_lowercase().chars() {
+            let mut buffer = [0; 4];
+            let spelling = match self.custom.get(&ch) {
+                Some(spelling) => spelling.as_str(),
+                None if ch.is_ascii_alphanumeric() => &*ch.encode_utf8(&mut buffer),
+                None if ch == ' ' || ch == '-' || ch == '.' || ch == '_' => {
+                    pending_separator = true;
+                    continue;
+                }
+                None => match transliterate(ch) {
+                    Some(spelling) => spelling,
+                    None if ch.is_alphanumeric() && self.fallback == SlugFallback::PercentEncode => &*ch.encode_utf8(&mut buffer),
+                    None => continue,
+                },
+            };
+            if spelling.is_empty() {
+                continue;
+            }
+            if pending_separator && id.len() > prefix.len() {
+                id.push_str(separator);
+            }
+            pending_separator = false;
+            for byte in spelling.bytes() {
+                if byte.is_ascii() {
+                    id.push(char::from(byte));
+                } else {
+                    id.push_str(&format!("%{:02X}", byte));
+                }
+            }
+        }
+        
+        if id.len() == prefix.len() {
+            id.push_str("section");
+        }
+        id
+    }
+}
+
+// The ASCII spelling of a lowercase accented Latin, Cyrillic or Greek letter
+fn transliterate(letter: char) -> Option<&'static str> {
+    Some(match letter {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ð' | 'ď' | 'đ' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĳ' => "ij",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ß' => "ss",
+        'ś' | 'ŝ' | 'ş' | 'š' => "s",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        // Cyrillic, as Russian and Ukrainian are usually romanized
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' | 'ґ' => "g",
+        'д' => "d",
+        'е' | 'ё' | 'э' => "e",
+        'є' => "ye",
+        'ж' => "zh",
+        'з' => "z",
+        'и' | 'і' => "i",
+        'ї' => "yi",
+        'й' | 'ы' => "y",
+        'к' => "k",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ю' => "yu",
+        'я' => "ya",
+        // Greek
+        'α' | 'ά' => "a",
+        'β' => "v",
+        'γ' => "g",
+        'δ' => "d",
+        'ε' | 'έ' => "e",
+        'ζ' => "z",
+        'η' | 'ή' | 'ι' | 'ί' | 'ϊ' | 'ΐ' => "i",
+        'θ' => "th",
+        'κ' => "k",
+        'λ' => "l",
+        'μ' => "m",
+        'ν' => "n",
+        'ξ' => "x",
+        'ο' | 'ό' | 'ω' | 'ώ' => "o",
+        'π' => "p",
+        'ρ' => "r",
+        'σ' | 'ς' => "s",
+        'τ' => "t",
+        'υ' | 'ύ' | 'ϋ' | 'ΰ' => "y",
+        'φ' => "f",
+        'χ' => "ch",
+        'ψ' => "ps",
+        _ => return None,
+    })
+}
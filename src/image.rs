@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::ast::ImageSizer;
+use crate::include::is_uri;
+
+// Sizes local images by reading their headers, with paths taken relative to `base_dir`.
+// Remote images are left unsized.
+#[derive(Debug, Clone)]
+pub struct FileImageSizer {
+    base_dir: PathBuf,
+}
+
+impl FileImageSizer {
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        FileImageSizer { base_dir: base_dir.into() }
+    }
+}
+
+impl ImageSizer for FileImageSizer {
+    fn image_size(&self, path: &str) -> Option<(u32, u32)> {
+        if is_uri(path) {
+            return None;
+        }
+        probe_size(&fs::read(self.base_dir.join(path)).ok()?)
+    }
+}
+
+// Width and height from a PNG, JPEG or SVG header
+pub fn probe_size(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_size(data)
+    } else if data.starts_with(&[0xFF, 0xD8]) {
+        jpeg_size(data)
+    } else {
+        svg_size(std::str::from_utf8(data).ok()?)
+    }
+}
+
+// The IHDR chunk always comes first, right after the signature
+fn png_size(data: &[u8]) -> Option<(u32, u32)> {
+    if data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    Some((be_u32(data.get(16..20)?), be_u32(data.get(20..24)?)))
+}
+
+// Segments are walked up to the start-of-frame marker, which holds the height then the width
+fn jpeg_size(data: &[u8]) -> Option<(u32, u32)> {
+    let mut index = 2;
+    loop {
+        while *data.get(index)? != 0xFF {
+            index += 1;
+        }
+        while *data.get(index)? == 0xFF {
+            index += 1;
+        }
+        let marker = data[index];
+        index += 1;
+        match marker {
+            // Markers without a length
+            0x01 | 0xD0..=0xD8 => continue,
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let frame = data.get(index + 3..index + 7)?;
+                let height = u16::from_be_bytes([frame[0], frame[1]]);
+                let width = u16::from_be_bytes([frame[2], frame[3]]);
+                return Some((u32::from(width), u32::from(height)));
+            }
+            0xD9 | 0xDA => return None,
+            _ => {
+                let length = data.get(index..index + 2)?;
+                index += usize::from(u16::from_be_bytes([length[0], length[1]]));
+            }
+        }
+    }
+}
+
+// `width` and `height` in pixels on the root element, or else the size of its `viewBox`
+fn svg_size(text: &str) -> Option<(u32, u32)> {
+    let start = text.find("<svg")?;
+    let tag = &text[start..start + text[start..].find('>')?];
+    let pixels = |value: &str| value.strip_suffix("px").unwrap_or(value).trim().parse::<f64>().ok();
+    
+    if let (Some(width), Some(height)) = (attribute(tag, "width").and_then(pixels), attribute(tag, "height").and_then(pixels)) {
+        return Some((width.round() as u32, height.round() as u32));
+    }
+    let view_box: Vec<f64> = attribute(tag, "viewBox")?
+        .split([' ', ','])
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    match view_box[..] {
+        [_, _, width, height] => Some((width.round() as u32, height.round() as u32)),
+        _ => None,
+    }
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(index) = rest.find(name) {
+        let before = rest[..index].chars().next_back();
+        let after = rest[index + name.len()..].trim_start();
+        rest = &rest[index + name.len()..];
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=') else { continue };
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &value[1..];
+        return Some(&value[..value.find(quote)?]);
+    }
+    None
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
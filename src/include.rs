@@ -0,0 +1,322 @@
+use core::error::Error;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::path::{Component, Path, PathBuf};
+
+use crate::json::json_string;
+#[cfg(feature = "std")]
+use crate::parser::SafeMode;
+use crate::prelude::*;
+
+#[cfg(feature = "http-include")]
+use std::collections::HashMap;
+#[cfg(feature = "http-include")]
+use std::sync::Mutex;
+
+pub trait IncludeResolver: Send + Sync {
+    fn resolve(&self, target: &str) -> Result<String, Box<dyn Error + Send + Sync>>;
+}
+
+pub fn is_uri(target: &str) -> bool {
+    target.starts_with("http://") || target.starts_with("https://")
+}
+
+// A target written in an included file names a file next to that one, so includes nest the
+// way they would on disk. Resolvers are given the result, relative to the root document.
+// URIs and absolute paths are left as written.
+pub(crate) fn nested_target(parent: &str, target: &str) -> String {
+    let Some((dir, _)) = parent.rsplit_once('/') else {
+        return target.to_string();
+    };
+    if is_uri(target) || target.starts_with(['/', '\\']) || target.get(1..2) == Some(":") {
+        return target.to_string();
+    }
+    if is_uri(parent) {
+        return format!("{}/{}", dir, target);
+    }
+    
+    // A `..` that climbs above the root document's directory is kept for the resolver to judge
+    let mut segments: Vec<&str> = dir.split('/').filter(|segment| !matches!(*segment, "" | ".")).collect();
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." if segments.last().is_some_and(|last| *last != "..") => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+// Reads include targets as files relative to `base_dir`. Below `SafeMode::Unsafe`, the
+// default being `Secure`, only files under `base_dir` are read: absolute targets and those
+// climbing out with `..` are refused.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct FileIncludeResolver {
+    base_dir: PathBuf,
+    safe: SafeMode,
+}
+
+#[cfg(feature = "std")]
+impl FileIncludeResolver {
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        FileIncludeResolver { base_dir: base_dir.into(), safe: SafeMode::default() }
+    }
+
+    pub fn with_safe_mode(self, safe: SafeMode) -> Self {
+        FileIncludeResolver { safe, ..self }
+    }
+}
+
+#[cfg(feature = "std")]
+impl IncludeResolver for FileIncludeResolver {
+    fn resolve(&self, target: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        if is_uri(target) {
+            return Err(format!("'{}' is not a local file", target).into());
+        }
+        if self.safe > SafeMode::Unsafe && !stays_within(Path::new(target)) {
+            return Err(format!("'{}' is outside the base directory", target).into());
+        }
+        Ok(fs::read_to_string(self.base_dir.join(target))?)
+    }
+}
+
+// A relative path that never climbs above where it starts
+#[cfg(feature = "std")]
+fn stays_within(path: &Path) -> bool {
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+// Every include directive met while preprocessing, in document order. `parent` is the
+// root document's name or the target of the include the directive appeared in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IncludeGraph {
+    pub root: String,
+    pub edges: Vec<IncludeEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncludeEdge {
+    pub parent: String,
+    pub target: String,
+    pub line: usize,
+    pub depth: usize,
+    pub resolved: bool,
+}
+
+impl IncludeGraph {
+    // Each resolved target once, in the order it was first included
+    pub fn dependencies(&self) -> Vec<&str> {
+        let mut dependencies: Vec<&str> = Vec::new();
+        for edge in self.edges.iter().filter(|edge| edge.resolved) {
+            if !dependencies.contains(&edge.target.as_str()) {
+                dependencies.push(&edge.target);
+            }
+        }
+        dependencies
+    }
+
+    pub fn to_tree(&self) -> String {
+        let mut tree = format!("{}\n", self.root);
+        for edge in &self.edges {
+            let status = if edge.resolved { "" } else { " (unresolved)" };
+            tree.push_str(&format!("{}{}{}\n", "  ".repeat(edge.depth + 1), edge.target, status));
+        }
+        tree
+    }
+
+    pub fn to_json(&self) -> String {
+        let dependencies: Vec<String> = self.dependencies().into_iter().map(json_string).collect();
+        let edges: Vec<String> = self.edges.iter()
+            .map(|edge| format!(
+                "    {{\"from\": {}, \"to\": {}, \"line\": {}, \"resolved\": {}}}",
+                json_string(&edge.parent), json_string(&edge.target), edge.line, edge.resolved,
+            ))
+            .collect();
+        format!(
+            "{{\n  \"root\": {},\n  \"dependencies\": [{}],\n  \"edges\": [{}]\n}}\n",
+            json_string(&self.root),
+            dependencies.join(", "),
+            if edges.is_empty() { String::new() } else { format!("\n{}\n  ", edges.join(",\n")) },
+        )
+    }
+    
+    // Unresolved includes are drawn dashed
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!("digraph includes {{\n  {};\n", dot_string(&self.root));
+        for edge in &self.edges {
+            let style = if edge.resolved { "" } else { " [style=dashed]" };
+            dot.push_str(&format!("  {} -> {}{};\n", dot_string(&edge.parent), dot_string(&edge.target), style));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// Where each line of a document with its includes expanded was written, so diagnostics can
+// point at the included file rather than the combined text the parser read
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceMap {
+    // The root document's name first, then include targets as written
+    files: Vec<String>,
+    // For each line of the expanded text, an index into `files` and the 1-based line there
+    lines: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLine<'a> {
+    pub file: &'a str,
+    pub line: usize,
+}
+
+impl SourceMap {
+    pub(crate) fn new(root: &str) -> SourceMap {
+        SourceMap { files: vec![root.to_string()], lines: Vec::new() }
+    }
+    
+    // `line` is 1-based, in the expanded text
+    pub fn origin(&self, line: usize) -> Option<SourceLine<'_>> {
+        let &(file, line) = self.lines.get(line.checked_sub(1)?)?;
+        Some(SourceLine { file: &self.files[file], line })
+    }
+    
+    // Lines of the expanded text
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+    
+    // Whether a line came from the root document rather than an include
+    pub fn is_root(&self, line: usize) -> bool {
+        self.lines.get(line.wrapping_sub(1)).is_none_or(|&(file, _)| file == 0)
+    }
+    
+    // `target` is none for a line of the root document
+    pub(crate) fn push(&mut self, target: Option<&str>, line: usize) {
+        let index = match target {
+            None => 0,
+            Some(target) => match self.files[1..].iter().position(|name| name == target) {
+                Some(index) => index + 1,
+                None => {
+                    self.files.push(target.to_string());
+                    self.files.len() - 1
+                }
+            },
+        };
+        self.lines.push((index, line));
+    }
+}
+
+fn dot_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpIncludeOptions {
+    pub allow: Vec<String>,
+    pub timeout: Duration,
+}
+
+impl Default for HttpIncludeOptions {
+    fn default() -> Self {
+        HttpIncludeOptions {
+            allow: Vec::new(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl HttpIncludeOptions {
+    // Remote content is only fetched from explicitly allowed URL prefixes
+    pub fn permits(&self, url: &str) -> bool {
+        is_uri(url) && self.allow.iter().any(|prefix| url_within(url, prefix))
+    }
+}
+
+// Whether `url` has the scheme, host and port of `prefix` and a path at or below its path.
+// Comparing the parsed parts, with the path split at `/`, keeps `https://example.com` from
+// admitting `https://example.com.evil.org` and `https://host/org` from admitting
+// `https://host/org-evil`. A backslash, whitespace or dot segment could be read differently
+// by the client that fetches the URL, so a URL holding one never matches.
+pub(crate) fn url_within(url: &str, prefix: &str) -> bool {
+    let (Some((scheme, host, path)), Some((prefix_scheme, prefix_host, prefix_path))) = (split_url(url), split_url(prefix)) else {
+        return false;
+    };
+    if url.contains(|ch: char| ch == '\\' || ch.is_whitespace() || ch.is_control()) {
+        return false;
+    }
+    if path.split('/').any(|segment| matches!(segment.to_ascii_lowercase().replace("%2e", ".").as_str(), "." | "..")) {
+        return false;
+    }
+    let prefix_path = prefix_path.trim_end_matches('/');
+    scheme.eq_ignore_ascii_case(prefix_scheme)
+        && host.eq_ignore_ascii_case(prefix_host)
+        && (path == prefix_path || path.strip_prefix(prefix_path).is_some_and(|rest| rest.starts_with('/')))
+}
+
+// The scheme, host with any port, and path of a URL, leaving out user info, query and fragment
+fn split_url(url: &str) -> Option<(&str, &str, &str)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let end = rest.find(['/', '?', '#', '\\']).unwrap_or(rest.len());
+    let authority = &rest[..end];
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let rest = &rest[end..];
+    let path = &rest[..rest.find(['?', '#']).unwrap_or(rest.len())];
+    Some((scheme, host, path))
+}
+
+#[cfg(feature = "http-include")]
+pub struct HttpIncludeResolver {
+    options: HttpIncludeOptions,
+    client: reqwest::blocking::Client,
+    cache: Mutex<HashMap<String, String>>,
+}
+
+#[cfg(feature = "http-include")]
+impl HttpIncludeResolver {
+    pub fn new(options: HttpIncludeOptions) -> Result<Self, reqwest::Error> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(options.timeout)
+            .user_agent(concat!("adoc/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        
+        Ok(HttpIncludeResolver {
+            options,
+            client,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+}
+
+#[cfg(feature = "http-include")]
+impl IncludeResolver for HttpIncludeResolver {
+    fn resolve(&self, target: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        if !self.options.permits(target) {
+            return Err(format!("'{}' is not in the include allowlist", target).into());
+        }
+        
+        if let Some(content) = self.cache.lock().unwrap().get(target) {
+            return Ok(content.clone());
+        }
+        
+        let content = self.client.get(target).send()?.error_for_status()?.text()?;
+        self.cache.lock().unwrap().insert(target.to_string(), content.clone());
+        Ok(content)
+    }
+}
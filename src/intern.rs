@@ -0,0 +1,175 @@
+use alloc::collections::BTreeSet;
+use alloc::sync::Arc;
+use core::borrow::Borrow;
+use core::fmt;
+use core::ops::Deref;
+
+use crate::ast::*;
+use crate::prelude::*;
+
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    pub fn new(value: &str) -> Symbol {
+        Symbol(Arc::from(value))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+// Attribute names, roles and language ids repeat throughout a document and across the
+// documents of a build, so symbols passed through one pool share one allocation per
+// distinct value. A pool belongs to whoever is parsing; there is no process-wide one
+// to lock.
+#[derive(Debug, Default)]
+pub struct SymbolPool {
+    symbols: BTreeSet<Arc<str>>,
+}
+
+impl SymbolPool {
+    pub fn new() -> Self {
+        SymbolPool::default()
+    }
+    
+    // The pool's symbol for `value`, allocated the first time it is asked for
+    pub fn symbol(&mut self, value: &str) -> Symbol {
+        match self.symbols.get(value) {
+            Some(shared) => Symbol(Arc::clone(shared)),
+            None => {
+                let symbol = Symbol::new(value);
+                self.symbols.insert(Arc::clone(&symbol.0));
+                symbol
+            }
+        }
+    }
+
+    pub fn intern(&mut self, symbol: &mut Symbol) {
+        match self.symbols.get(symbol.as_str()) {
+            Some(shared) => symbol.0 = Arc::clone(shared),
+            None => {
+                self.symbols.insert(Arc::clone(&symbol.0));
+            }
+        }
+    }
+
+    pub fn intern_document(&mut self, document: &mut Document) {
+        if let Some(header) = &mut document.header {
+            for attribute in &mut header.attributes {
+                self.intern(&mut attribute.name);
+            }
+        }
+        self.intern_blocks(&mut document.body);
+        // Paragraphs, admonitions, table cells and list items hold formatted text with roles
+        walk_block_inlines_mut(&mut document.body, &mut |element| {
+            if let InlineElement::Formatted { attributes, .. } = element {
+                for role in &mut attributes.roles {
+                    self.intern(role);
+                }
+            }
+        });
+    }
+    
+    // Every kind of block is named so that a new one has to say what it holds
+    fn intern_blocks(&mut self, blocks: &mut [Block]) {
+        for block in blocks {
+            match block {
+                Block::Section { blocks, .. } | Block::Admonition { blocks, .. } => self.intern_blocks(blocks),
+                Block::Sidebar { roles, blocks, .. } => {
+                    for role in roles {
+                        self.intern(role);
+                    }
+                    self.intern_blocks(blocks);
+                }
+                Block::DelimitedBlock { language, .. } => {
+                    if let Some(language) = language {
+                        self.intern(language);
+                    }
+                }
+                Block::BlockMetadata { kind: BlockMetadataKind::Attribute(entries) } => {
+                    for entry in entries {
+                        self.intern(entry);
+                    }
+                }
+                Block::BlockMetadata { kind: BlockMetadataKind::AttributeEntry(attribute) } => self.intern(&mut attribute.name),
+                Block::BlockMetadata { kind: BlockMetadataKind::AttributeUnset(name) } => self.intern(name),
+                Block::List { items, .. } => {
+                    for item in items {
+                        self.intern_blocks(item.blocks_mut());
+                    }
+                }
+                Block::BlockMetadata { .. }
+                | Block::Paragraph { .. }
+                | Block::Table { .. }
+                | Block::Image { .. }
+                | Block::TableOfContents { .. }
+                | Block::PageBreak
+                | Block::Comment { .. } => {}
+            }
+        }
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(value: &str) -> Self {
+        Symbol::new(value)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(value: String) -> Self {
+        Symbol::new(&value)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
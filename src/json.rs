@@ -0,0 +1,195 @@
+use crate::ast::*;
+use crate::prelude::*;
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_optional(value: Option<&str>) -> String {
+    value.map(json_string).unwrap_or_else(|| "null".to_string())
+}
+
+fn json_array<T>(items: &[T], to_json: impl Fn(&T) -> String) -> String {
+    format!("[{}]", items.iter().map(to_json).collect::<Vec<_>>().join(","))
+}
+
+// Goes up with any change to the JSON that readers of the previous version would notice, and
+// is written into every document as `version`
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+// A JSON Schema for `Document::to_json` at `JSON_SCHEMA_VERSION`, to validate documents
+// against and generate typed bindings from
+pub const JSON_SCHEMA: &str = include_str!("schema.json");
+
+// The tree as compact JSON, each node tagged with a `type`, for consumers outside Rust such
+// as the JavaScript bindings
+impl Document {
+    pub fn to_json(&self) -> String {
+        let attributes: Vec<String> = self.header.iter()
+            .flat_map(|header| &header.attributes)
+            .map(|attribute| format!("{}:{}", json_string(&attribute.name), json_optional(attribute.value.as_deref())))
+            .collect();
+        format!(
+            "{{\"version\":{},\"title\":{},\"attributes\":{{{}}},\"blocks\":{}}}",
+            JSON_SCHEMA_VERSION,
+            json_optional(self.header.as_ref().map(|header| header.title.as_str())),
+            attributes.join(","),
+            json_array(&self.body, block_json),
+        )
+    }
+}
+
+fn block_json(block: &Block) -> String {
+    match block {
+        Block::Section { level, title, id, number, blocks } => format!(
+            "{{\"type\":\"section\",\"level\":{},\"title\":{},\"id\":{},\"number\":{},\"blocks\":{}}}",
+            level, json_string(title), json_optional(id.as_deref()), json_optional(number.as_deref()), json_array(blocks, block_json),
+        ),
+        Block::Paragraph { content } => format!("{{\"type\":\"paragraph\",\"content\":{}}}", json_array(content, inline_json)),
+        Block::Admonition { kind, content, blocks } => format!(
+            "{{\"type\":\"admonition\",\"kind\":\"{}\",\"content\":{},\"blocks\":{}}}",
+            kind.name(), json_array(content, inline_json), json_array(blocks, block_json),
+        ),
+        Block::DelimitedBlock { kind, content, language, title } => {
+            let kind = match kind {
+                DelimitedBlockKind::Listing => "\"listing\"".to_string(),
+                DelimitedBlockKind::Example => "\"example\"".to_string(),
+                DelimitedBlockKind::Literal => "\"literal\"".to_string(),
+                DelimitedBlockKind::Quote(Attribution { author, citation }) => format!(
+                    "\"quote\",\"author\":{},\"citation\":{}",
+                    json_optional(author.as_deref()), json_optional(citation.as_deref()),
+                ),
+            };
+            format!(
+                "{{\"type\":\"delimited\",\"kind\":{},\"content\":{},\"language\":{},\"title\":{}}}",
+                kind, json_string(content), json_optional(language.as_deref()), json_optional(title.as_deref()),
+            )
+        }
+        Block::List { kind, items } => {
+            let kind = match kind {
+                ListKind::Unordered => "\"unordered\"".to_string(),
+                ListKind::Ordered => "\"ordered\"".to_string(),
+                ListKind::Description(style) => {
+                    let style = match style {
+                        DescriptionListStyle::Stacked => "stacked",
+                        DescriptionListStyle::Horizontal => "horizontal",
+                        DescriptionListStyle::Qanda => "qanda",
+                        DescriptionListStyle::Glossary => "glossary",
+                    };
+                    format!("\"description\",\"style\":\"{}\"", style)
+                }
+            };
+            format!("{{\"type\":\"list\",\"kind\":{},\"items\":{}}}", kind, json_array(items, list_item_json))
+        }
+        Block::Sidebar { title, roles, blocks } => format!(
+            "{{\"type\":\"sidebar\",\"title\":{},\"roles\":{},\"blocks\":{}}}",
+            json_optional(title.as_deref()), json_array(roles, |role| json_string(role)), json_array(blocks, block_json),
+        ),
+        Block::Table { title, header, rows } => format!(
+            "{{\"type\":\"table\",\"title\":{},\"header\":{},\"rows\":{}}}",
+            json_optional(title.as_deref()), header, json_array(rows, |row| json_array(row, table_cell_json)),
+        ),
+        Block::BlockMetadata { kind } => {
+            let fields = match kind {
+                BlockMetadataKind::Title(title) => format!("\"kind\":\"title\",\"title\":{}", json_string(title)),
+                BlockMetadataKind::Attribute(values) => {
+                    format!("\"kind\":\"attribute\",\"values\":{}", json_array(values, |value| json_string(value)))
+                }
+                BlockMetadataKind::Anchor(id) => format!("\"kind\":\"anchor\",\"id\":{}", json_string(id)),
+                BlockMetadataKind::AttributeEntry(attribute) => format!(
+                    "\"kind\":\"attribute-entry\",\"name\":{},\"value\":{}",
+                    json_string(&attribute.name), json_optional(attribute.value.as_deref()),
+                ),
+                BlockMetadataKind::AttributeUnset(name) => format!("\"kind\":\"attribute-unset\",\"name\":{}", json_string(name)),
+            };
+            format!("{{\"type\":\"metadata\",{}}}", fields)
+        }
+        Block::TableOfContents { levels } => format!(
+            "{{\"type\":\"toc\",\"levels\":{}}}",
+            levels.map(|levels| levels.to_string()).unwrap_or_else(|| "null".to_string()),
+        ),
+        Block::Image { path, alt, width, height, title } => format!(
+            "{{\"type\":\"image\",\"path\":{},\"alt\":{},\"width\":{},\"height\":{},\"title\":{}}}",
+            json_string(path), json_optional(alt.as_deref()), json_optional(width.as_deref()), json_optional(height.as_deref()), json_optional(title.as_deref()),
+        ),
+        Block::PageBreak => "{\"type\":\"page-break\"}".to_string(),
+        Block::Comment { text, delimited } => format!("{{\"type\":\"comment\",\"text\":{},\"delimited\":{}}}", json_string(text), delimited),
+    }
+}
+
+fn table_cell_json(cell: &TableCell) -> String {
+    format!(
+        "{{\"content\":{},\"colspan\":{},\"rowspan\":{},\"align\":{}}}",
+        json_array(&cell.content, inline_json), cell.colspan, cell.rowspan, json_optional(cell.align.map(CellAlignment::name)),
+    )
+}
+
+fn list_item_json(item: &ListItem) -> String {
+    match item {
+        ListItem::Unordered { content, blocks } | ListItem::Ordered { content, blocks } => format!(
+            "{{\"content\":{},\"blocks\":{}}}",
+            json_array(content, inline_json), json_array(blocks, block_json),
+        ),
+        ListItem::Description { term, description, blocks } => format!(
+            "{{\"term\":{},\"description\":{},\"blocks\":{}}}",
+            json_string(term),
+            description.as_deref().map(|description| json_array(description, inline_json)).unwrap_or_else(|| "null".to_string()),
+            json_array(blocks, block_json),
+        ),
+    }
+}
+
+fn inline_json(element: &InlineElement) -> String {
+    match element {
+        InlineElement::Text(text) => format!("{{\"type\":\"text\",\"text\":{}}}", json_string(text)),
+        InlineElement::Formatted { kind, content, attributes } => {
+            let kind = match kind {
+                FormattedTextKind::Strong => "strong",
+                FormattedTextKind::Emphasis => "emphasis",
+                FormattedTextKind::Monospace => "monospace",
+                FormattedTextKind::LiteralMonospace => "literal-monospace",
+                FormattedTextKind::Superscript => "superscript",
+                FormattedTextKind::Subscript => "subscript",
+                FormattedTextKind::Mark => "mark",
+            };
+            format!(
+                "{{\"type\":\"formatted\",\"kind\":\"{}\",\"content\":{},\"id\":{},\"roles\":{}}}",
+                kind,
+                json_array(content, inline_json),
+                json_optional(attributes.id.as_deref()),
+                json_array(&attributes.roles, |role| json_string(role)),
+            )
+        }
+        InlineElement::Macro { kind } => match kind {
+            MacroKind::Link { url, text } => format!(
+                "{{\"type\":\"link\",\"url\":{},\"text\":{}}}",
+                json_string(url), json_optional(text.as_deref()),
+            ),
+            MacroKind::Image { path, attributes } => format!(
+                "{{\"type\":\"image\",\"path\":{},\"attributes\":{}}}",
+                json_string(path), json_optional(attributes.as_deref()),
+            ),
+            MacroKind::CrossReference { target, text } => format!(
+                "{{\"type\":\"xref\",\"target\":{},\"text\":{}}}",
+                json_string(target), json_optional(text.as_deref()),
+            ),
+            MacroKind::Term { term, target } => format!(
+                "{{\"type\":\"term\",\"term\":{},\"target\":{}}}",
+                json_string(term), json_optional(target.as_deref()),
+            ),
+        },
+        InlineElement::LineBreak => "{\"type\":\"linebreak\"}".to_string(),
+        InlineElement::Passthrough(content) => format!("{{\"type\":\"passthrough\",\"content\":{}}}", json_string(content)),
+    }
+}
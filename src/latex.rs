@@ -0,0 +1,325 @@
+use crate::ast::*;
+use crate::prelude::*;
+use crate::trace;
+use crate::parser::glossary_id;
+
+impl Document {
+    pub fn to_latex(&self) -> String {
+        trace::span!(DEBUG, "render", backend = "latex");
+        let book = self.attribute("doctype") == Some("book");
+        let mut latex = String::new();
+        
+        latex.push_str(if book { "\\documentclass{book}\n" } else { "\\documentclass{article}\n" });
+        latex.push_str("\\usepackage[utf8]{inputenc}\n");
+        latex.push_str("\\usepackage[T1]{fontenc}\n");
+        latex.push_str("\\usepackage{graphicx}\n");
+        latex.push_str("\\usepackage{hyperref}\n");
+        
+        if let Some(header) = &self.header {
+            latex.push_str(&format!("\\title{{{}}}\n", escape_latex(&header.title)));
+            if let Some(author) = header.attribute("author") {
+                latex.push_str(&format!("\\author{{{}}}\n", escape_latex(author)));
+            }
+            let date = header.attribute("revdate").or(header.attribute("date")).unwrap_or("");
+            latex.push_str(&format!("\\date{{{}}}\n", escape_latex(date)));
+        }
+        
+        latex.push_str("\\begin{document}\n");
+        if self.header.is_some() {
+            latex.push_str("\\maketitle\n");
+        }
+        for block in &self.body {
+            latex.push_str(&block_to_latex(block, book));
+        }
+        latex.push_str("\\end{document}\n");
+        
+        latex
+    }
+}
+
+fn block_to_latex(block: &Block, book: bool) -> String {
+    match block {
+        Block::Section { level, title, id, number, blocks } => {
+            let commands: &[&str] = if book {
+                &["chapter", "section", "subsection", "subsubsection", "paragraph"]
+            } else {
+                &["section", "subsection", "subsubsection", "paragraph", "subparagraph"]
+            };
+            let command = if book && *level == 1 {
+                "part"
+            } else {
+                commands[level.saturating_sub(2).min(commands.len() - 1)]
+            };
+            // Starred commands keep LaTeX from numbering what the document left unnumbered
+            let star = if number.is_some() { "" } else { "*" };
+            let mut latex = format!("\\{}{}{{{}}}\n", command, star, escape_latex(title));
+            if let Some(id) = id {
+                latex.push_str(&format!("\\label{{{}}}\n", label(id)));
+            }
+            latex.push('\n');
+            // LaTeX has a single table of contents, so mini-TOCs within sections are left out
+            for block in blocks.iter().filter(|block| !matches!(block, Block::TableOfContents { .. })) {
+                latex.push_str(&block_to_latex(block, book));
+            }
+            latex
+        }
+        Block::Paragraph { content } => format!("{}\n\n", inlines_to_latex(content)),
+        Block::Admonition { kind, content, blocks } => {
+            let mut latex = format!("\\begin{{quote}}\n\\textbf{{{}:}}", kind.caption());
+            if !content.is_empty() {
+                latex.push_str(&format!(" {}", inlines_to_latex(content)));
+            }
+            latex.push('\n');
+            for block in blocks {
+                latex.push_str(&block_to_latex(block, book));
+            }
+            latex.push_str("\\end{quote}\n\n");
+            latex
+        }
+        Block::DelimitedBlock { kind, content, title, .. } => match kind {
+            DelimitedBlockKind::Listing | DelimitedBlockKind::Literal => {
+                let title = title.as_deref().map(|title| format!("\\noindent\\texttt{{{}}}\n", escape_latex(title))).unwrap_or_default();
+                format!("{}{}\n", title, verbatim(content))
+            }
+            DelimitedBlockKind::Quote(attribution) => {
+                let caption = [attribution.author.as_deref().map(escape_latex), attribution.citation.as_deref().map(|citation| format!("\\emph{{{}}}", escape_latex(citation)))]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let caption = if caption.is_empty() { String::new() } else { format!("\n\\par\\hfill--- {}", caption) };
+                format!("\\begin{{quote}}\n{}{}\n\\end{{quote}}\n\n", escape_latex(content.trim_end()), caption)
+            }
+            DelimitedBlockKind::Example => {
+                format!("\\begin{{center}}\\fbox{{\\parbox{{0.9\\linewidth}}{{{}}}}}\\end{{center}}\n\n", escape_latex(content.trim_end()))
+            }
+        },
+        Block::List { kind, items } => list_to_latex(kind, items, book),
+        // Set off by rules above and below, since a verbatim block inside can't go in a box
+        Block::Sidebar { title, blocks, .. } => {
+            let mut latex = String::from("\\noindent\\rule{\\linewidth}{0.4pt}\n\n");
+            if let Some(title) = title {
+                latex.push_str(&format!("\\noindent\\textbf{{{}}}\n\n", escape_latex(title)));
+            }
+            for block in blocks {
+                latex.push_str(&block_to_latex(block, book));
+            }
+            latex.push_str("\\noindent\\rule{\\linewidth}{0.4pt}\n\n");
+            latex
+        }
+        Block::Table { title, header, rows } => table_to_latex(title.as_deref(), *header, rows),
+        Block::BlockMetadata { .. } => String::new(),
+        Block::TableOfContents { .. } => "\\tableofcontents\n\n".to_string(),
+        Block::Image { path, title, .. } => {
+            let mut latex = format!("\\begin{{figure}}[h]\n\\centering\n{}\n", include_graphics(path));
+            if let Some(title) = title {
+                latex.push_str(&format!("\\caption{{{}}}\n", escape_latex(title)));
+            }
+            latex.push_str("\\end{figure}\n\n");
+            latex
+        }
+        Block::PageBreak => "\\clearpage\n\n".to_string(),
+        Block::Comment { text, delimited: false } => format!("%{}\n\n", text),
+        Block::Comment { text, delimited: true } => {
+            let lines: String = text.lines().map(|line| if line.is_empty() { "%\n".to_string() } else { format!("% {}\n", line) }).collect();
+            format!("{}\n", lines)
+        }
+    }
+}
+
+// Ruled all round, with the header row in bold and a rule under it. A cell spanning or
+// aligning columns is set with `\multicolumn`; one spanning rows fills its first row only,
+// leaving its columns empty in the rows below, since `\multirow` needs a package of its own.
+fn table_to_latex(title: Option<&str>, header: bool, rows: &[Vec<TableCell>]) -> String {
+    let columns = rows.iter().map(|row| row.iter().map(|cell| cell.colspan).sum()).max().unwrap_or(0).max(1);
+    let mut latex = String::new();
+    if let Some(title) = title {
+        latex.push_str(&format!("\\noindent\\textbf{{{}}}\n\n", escape_latex(title)));
+    }
+    latex.push_str(&format!("\\begin{{center}}\n\\begin{{tabular}}{{|{}}}\n\\hline\n", "l|".repeat(columns)));
+    // Rows below still covered by a cell above, by column
+    let mut covered = vec![0; columns];
+    for (index, row) in rows.iter().enumerate() {
+        let mut cells: Vec<String> = Vec::new();
+        let mut row = row.iter();
+        let mut column = 0;
+        while column < columns {
+            if covered[column] > 0 {
+                covered[column] -= 1;
+                cells.push(String::new());
+                column += 1;
+                continue;
+            }
+            let Some(cell) = row.next() else { break };
+            let mut text = inlines_to_latex(&cell.content);
+            if header && index == 0 {
+                text = format!("\\textbf{{{}}}", text);
+            }
+            if cell.colspan > 1 || cell.align.is_some() {
+                let align = match cell.align {
+                    Some(CellAlignment::Center) => "c",
+                    Some(CellAlignment::Right) => "r",
+                    _ => "l",
+                };
+                // The first column draws the rule on its left as well
+                let left = if column == 0 { "|" } else { "" };
+                text = format!("\\multicolumn{{{}}}{{{}{}|}}{{{}}}", cell.colspan, left, align, text);
+            }
+            for covered in covered.iter_mut().skip(column).take(cell.colspan) {
+                *covered = cell.rowspan - 1;
+            }
+            column += cell.colspan;
+            cells.push(text);
+        }
+        latex.push_str(&format!("{} \\\\\n", cells.join(" & ")));
+        if header && index == 0 {
+            latex.push_str("\\hline\n");
+        }
+    }
+    latex.push_str("\\hline\n\\end{tabular}\n\\end{center}\n\n");
+    latex
+}
+
+fn list_to_latex(kind: &ListKind, items: &[ListItem], book: bool) -> String {
+    let environment = match kind {
+        ListKind::Unordered => "itemize",
+        ListKind::Ordered => "enumerate",
+        ListKind::Description(DescriptionListStyle::Qanda) => "enumerate",
+        ListKind::Description(_) => "description",
+    };
+    
+    let mut latex = format!("\\begin{{{}}}\n", environment);
+    for item in items {
+        match item {
+            ListItem::Unordered { content, .. } | ListItem::Ordered { content, .. } => {
+                latex.push_str(&format!("\\item {}\n", inlines_to_latex(content).trim_end()));
+            }
+            ListItem::Description { term, description, .. } => {
+                let description = description.as_ref().map(|d| inlines_to_latex(d)).unwrap_or_default();
+                if *kind == ListKind::Description(DescriptionListStyle::Qanda) {
+                    latex.push_str(&format!("\\item \\emph{{{}}}\n", escape_latex(term)));
+                    if !description.trim_end().is_empty() {
+                        latex.push_str(&format!("\n{}\n", description.trim_end()));
+                    }
+                } else if *kind == ListKind::Description(DescriptionListStyle::Glossary) {
+                    latex.push_str(&format!("\\item[{}] \\label{{{}}} {}\n", escape_latex(term), label(&glossary_id(term)), description.trim_end()));
+                } else {
+                    latex.push_str(&format!("\\item[{}] {}\n", escape_latex(term), description.trim_end()));
+                }
+            }
+        }
+        // Attached blocks and nested lists continue the item
+        for block in item.blocks() {
+            latex.push_str(&block_to_latex(block, book));
+        }
+    }
+    latex.push_str(&format!("\\end{{{}}}\n\n", environment));
+    latex
+}
+
+fn inlines_to_latex(elements: &[InlineElement]) -> String {
+    elements.iter().map(inline_to_latex).collect()
+}
+
+fn inline_to_latex(element: &InlineElement) -> String {
+    match element {
+        InlineElement::Text(text) => escape_latex(text),
+        InlineElement::Formatted { kind, content, .. } => {
+            let command = match kind {
+                FormattedTextKind::Strong => "textbf",
+                FormattedTextKind::Emphasis => "emph",
+                FormattedTextKind::Monospace | FormattedTextKind::LiteralMonospace => "texttt",
+                FormattedTextKind::Superscript => "textsuperscript",
+                FormattedTextKind::Subscript => "textsubscript",
+                // Roles have no meaning in LaTeX, so marked text is set as it is
+                FormattedTextKind::Mark => return inlines_to_latex(content),
+            };
+            format!("\\{}{{{}}}", command, inlines_to_latex(content))
+        }
+        InlineElement::Macro { kind } => match kind {
+            MacroKind::Link { url, text } => {
+                let text = escape_latex(text.as_deref().unwrap_or(url));
+                format!("\\href{{{}}}{{{}}}", escape_url(url), text)
+            }
+            MacroKind::Image { path, .. } => include_graphics(path),
+            MacroKind::CrossReference { target, text } => {
+                let text = escape_latex(text.as_deref().unwrap_or(target));
+                format!("\\hyperref[{}]{{{}}}", label(target), text)
+            }
+            MacroKind::Term { term, target: Some(target) } => format!("\\hyperref[{}]{{{}}}", label(target), escape_latex(term)),
+            MacroKind::Term { term, target: None } => escape_latex(term),
+        },
+        InlineElement::LineBreak => "\\\\\n".to_string(),
+        // Raw HTML means nothing to LaTeX, so only its text is kept
+        InlineElement::Passthrough(content) => escape_latex(&strip_tags(content)),
+    }
+}
+
+// Nothing inside `verbatim` is interpreted up to the first `\end{verbatim}`, so content holding
+// that would end the block early and run the rest as LaTeX. Such content is set line by line
+// in a typewriter font instead, escaped like any other text.
+fn verbatim(content: &str) -> String {
+    if !content.contains("\\end{verbatim}") {
+        return format!("\\begin{{verbatim}}\n{}\\end{{verbatim}}\n", ensure_newline(content));
+    }
+    let lines: Vec<String> = content
+        .lines()
+        .map(|line| if line.is_empty() { "\\mbox{}".to_string() } else { escape_latex(line).replace(' ', "\\ ") })
+        .collect();
+    format!("\\begin{{flushleft}}\\ttfamily\n{}\n\\end{{flushleft}}\n", lines.join("\\\\\n"))
+}
+
+fn ensure_newline(content: &str) -> String {
+    if content.ends_with('\n') { content.to_string() } else { format!("{}\n", content) }
+}
+
+// Ids go into `\label` and `\hyperref` unescaped, where a brace or bracket would end the argument,
+// so anything outside a safe alphabet is written as `:` and its bytes in hex. Both ends map an
+// id the same way, so references still find their labels.
+fn label(id: &str) -> String {
+    let mut label = String::with_capacity(id.len());
+    for byte in id.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.') {
+            label.push(byte as char);
+        } else {
+            label.push_str(&format!(":{:02x}", byte));
+        }
+    }
+    label
+}
+
+fn escape_url(url: &str) -> String {
+    url.replace('\\', "\\\\").replace('#', "\\#").replace('%', "\\%").replace('{', "\\{").replace('}', "\\}")
+}
+
+// A brace or backslash in a file name can't be escaped inside `\includegraphics` and could end
+// its argument and run commands, so such a path is shown as text instead
+fn include_graphics(path: &str) -> String {
+    if path.contains(['{', '}', '\\']) {
+        format!("\\texttt{{{}}}", escape_latex(path))
+    } else {
+        format!("\\includegraphics{{{}}}", escape_url(path))
+    }
+}
+
+pub(crate) fn escape_latex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            // The arrows replacements leave, which not every engine can typeset as text
+            '\u{2192}' => escaped.push_str("\\textrightarrow{}"),
+            '\u{2190}' => escaped.push_str("\\textleftarrow{}"),
+            '\u{21d2}' => escaped.push_str("$\\Rightarrow$"),
+            '\u{21d0}' => escaped.push_str("$\\Leftarrow$"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
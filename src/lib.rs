@@ -1,172 +1,2254 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "arena")]
+pub mod arena;
 pub mod ast;
+#[cfg(feature = "std")]
+pub mod attributes;
+#[cfg(feature = "std")]
+pub mod book;
+pub mod catalog;
+#[cfg(feature = "std")]
+pub mod chunked;
+#[cfg(feature = "std")]
+pub mod config;
+pub mod context;
+mod datetime;
+#[cfg(feature = "std")]
+pub mod diagnostics;
+mod dump;
+#[cfg(feature = "epub")]
+pub mod epub;
+pub mod error;
+mod extract;
+pub mod ids;
+#[cfg(feature = "image-size")]
+pub mod image;
+pub mod include;
+pub mod intern;
+mod json;
+pub mod latex;
+#[cfg(feature = "std")]
+pub mod linkcheck;
+#[cfg(feature = "markdown")]
+pub mod markdown;
 pub mod parser;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+pub mod position;
+mod prelude;
+pub mod printer;
+pub mod prose;
+pub mod resource;
+pub mod search;
+pub mod tags;
+#[cfg(feature = "std")]
+pub mod site;
+#[cfg(feature = "std")]
+pub mod stream;
+pub mod theme;
+mod trace;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "std")]
+pub mod workspace;
 
 pub use ast::*;
-pub use parser::AsciiDocParser;
+#[cfg(feature = "std")]
+pub use book::Book;
+pub use error::{ConfigError, ParseError};
+pub use intern::Symbol;
+pub use json::{JSON_SCHEMA, JSON_SCHEMA_VERSION};
+pub use parser::{AsciiDocParser, ParseOptions, SafeMode, Substitutions};
+#[cfg(feature = "std")]
+pub use stream::BlockIter;
+#[cfg(feature = "std")]
+pub use workspace::{ConflictPolicy, DocumentSet, Workspace};
+
+// Servers share parse options and parsed documents between threads
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Document>();
+    assert_send_sync::<Block>();
+    assert_send_sync::<InlineElement>();
+    assert_send_sync::<ParseOptions>();
+    assert_send_sync::<HtmlOptions>();
+    assert_send_sync::<ParseError>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<Workspace>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<DocumentSet>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<Book>();
+};
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_simple_document() {
-        let input = "= Test Document\n\nHello world!";
-        let result = AsciiDocParser::parse_document(input);
-        match &result {
-            Ok(_) => {},
-            Err(e) => println!("Parse error: {}", e),
+    fn test_extract_external_links() {
+        let input = r#"= Document
+
+See https://example.com/docs for details.
+
+----
+https://example.com/in-code
+----
+
+* Visit link:https://rust-lang.org[Rust]
+* Read <<intro>>"#;
+        
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let links = linkcheck::extract_external_links("doc.adoc", input, &doc);
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].url, "https://example.com/docs");
+        assert_eq!(links[0].line, Some(3));
+        assert_eq!(links[1].url, "https://rust-lang.org");
+        assert_eq!(links[1].line, Some(9));
+        assert_eq!(links[1].source, "doc.adoc");
+    }
+
+    #[test]
+    fn test_link_check_allow_deny() {
+        let options = linkcheck::LinkCheckOptions {
+            allow: vec!["example.com".to_string()],
+            deny: vec!["https://example.com/private".to_string()],
+            ..Default::default()
+        };
+        
+        assert!(options.permits("https://example.com/docs"));
+        assert!(options.permits("https://www.example.com/"));
+        assert!(!options.permits("https://example.com/private/page"));
+        assert!(!options.permits("https://rust-lang.org"));
+        // A denied prefix ends at a path segment, and hosts compare whole
+        assert!(options.permits("https://example.com/private-notes"));
+        assert!(!options.permits("https://example.com.evil.org/"));
+        assert!(!options.permits("https://example.com@evil.org/"));
+    }
+
+    struct StaticResolver;
+
+    impl include::IncludeResolver for StaticResolver {
+        fn resolve(&self, target: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+            match target {
+                "https://example.com/snippet.adoc" => Ok("Shared snippet text.".to_string()),
+                _ => Err("not found".into()),
+            }
         }
-        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_uri_include_resolution() {
+        let input = "= Document\n\ninclude::https://example.com/snippet.adoc[]\n";
+        let mut options = ParseOptions {
+            safe: SafeMode::Unsafe,
+            include_resolver: Some(std::sync::Arc::new(StaticResolver)),
+            ..Default::default()
+        };
         
-        let doc = result.unwrap();
-        assert!(doc.header.is_some());
-        assert_eq!(doc.header.unwrap().title, "Test Document");
-        assert_eq!(doc.body.len(), 1);
+        let doc = AsciiDocParser::parse_document_with_options(input, &options).unwrap();
+        assert_eq!(doc.body[0], Block::Paragraph {
+            content: vec![InlineElement::Text("Shared snippet text.".to_string())],
+        });
+        
+        // Remote includes are never fetched outside of unsafe mode
+        options.safe = SafeMode::Safe;
+        let doc = AsciiDocParser::parse_document_with_options(input, &options).unwrap();
+        assert!(doc.to_html().contains("Unresolved directive"));
+    }
+
+    #[test]
+    fn test_include_graph() {
+        let input = "= Document\n\ninclude::https://example.com/snippet.adoc[]\ninclude::missing.adoc[]\n";
+        let options = ParseOptions {
+            safe: SafeMode::Unsafe,
+            include_resolver: Some(std::sync::Arc::new(StaticResolver)),
+            ..Default::default()
+        };
+        
+        let graph = AsciiDocParser::include_graph("index.adoc", input, &options);
+        assert_eq!(graph.edges.len(), 2);
+        assert_eq!((graph.edges[0].parent.as_str(), graph.edges[0].line), ("index.adoc", 3));
+        assert!(graph.edges[0].resolved && !graph.edges[1].resolved);
+        assert_eq!(graph.dependencies(), ["https://example.com/snippet.adoc"]);
+        assert!(graph.to_dot().contains("\"index.adoc\" -> \"missing.adoc\" [style=dashed];"));
+        assert!(graph.to_json().contains("\"dependencies\": [\"https://example.com/snippet.adoc\"]"));
+    }
+
+    #[test]
+    fn test_include_source_map() {
+        struct PartResolver;
+        impl include::IncludeResolver for PartResolver {
+            fn resolve(&self, target: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+                match target {
+                    "intro.adoc" => Ok("== Intro\n\ninclude::note.adoc[]\n".to_string()),
+                    "note.adoc" => Ok("A note.".to_string()),
+                    "broken.adoc" => Ok("Text.\n\n----\nnever closed\n".to_string()),
+                    _ => Err("not found".into()),
+                }
+            }
+        }
+        let options = ParseOptions { include_resolver: Some(std::sync::Arc::new(PartResolver)), ..Default::default() };
+        let input = "= Guide\n\ninclude::intro.adoc[]\n\nifdef::backend-html5[Web only.]\ninclude::missing.adoc[]\nEnd.\n";
+        let (expanded, map) = AsciiDocParser::expand_includes("guide.adoc", input, &options);
+        assert_eq!(expanded.lines().count(), map.len());
+        let origins: Vec<(&str, usize)> = (1..=map.len()).map(|line| map.origin(line).unwrap()).map(|origin| (origin.file, origin.line)).collect();
+        assert_eq!(origins, [
+            ("guide.adoc", 1), ("guide.adoc", 2),
+            ("intro.adoc", 1), ("intro.adoc", 2), ("note.adoc", 1),
+            ("guide.adoc", 4), ("guide.adoc", 5), ("guide.adoc", 6), ("guide.adoc", 7),
+        ]);
+        assert!(map.is_root(1) && !map.is_root(5));
+        assert_eq!(map.origin(0), None);
+        assert_eq!(map.origin(10), None);
+        
+        let error = AsciiDocParser::parse_document_with_options("= Guide\n\nFirst.\n\ninclude::broken.adoc[]\n", &options).unwrap_err();
+        assert_eq!((error.document.as_deref(), error.line), (Some("broken.adoc"), 3));
+    }
+
+    #[test]
+    fn test_file_includes() {
+        let dir = std::env::temp_dir().join(format!("adoc-include-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("docs/sub")).unwrap();
+        std::fs::write(dir.join("secret.adoc"), "Secret.").unwrap();
+        std::fs::write(dir.join("docs/shared.adoc"), "Shared.").unwrap();
+        std::fs::write(dir.join("docs/sub/a.adoc"), "include::b.adoc[]\n\ninclude::../shared.adoc[]\n\ninclude::../../secret.adoc[]\n").unwrap();
+        std::fs::write(dir.join("docs/sub/b.adoc"), "Next to a.").unwrap();
+        
+        // Nested includes are read from the including file's directory, and nothing outside
+        // the base directory is read below unsafe mode
+        let input = format!("include::sub/a.adoc[]\n\ninclude::{}[]\n", dir.join("secret.adoc").display());
+        let resolver = include::FileIncludeResolver::new(dir.join("docs"));
+        let options = ParseOptions { include_resolver: Some(std::sync::Arc::new(resolver.clone())), ..Default::default() };
+        let html = AsciiDocParser::parse_document_with_options(&input, &options).unwrap().to_html();
+        assert!(html.starts_with("<p>Next to a.</p>\n<p>Shared.</p>\n"), "{}", html);
+        assert!(!html.contains("Secret.") && html.contains("Unresolved directive - include::../../secret.adoc[]"));
+        let graph = AsciiDocParser::include_graph("index.adoc", &input, &options);
+        assert_eq!(graph.edges.iter().map(|edge| edge.target.as_str()).collect::<Vec<_>>()[..3], ["sub/a.adoc", "sub/b.adoc", "shared.adoc"]);
+        
+        let resolver = resolver.with_safe_mode(SafeMode::Unsafe);
+        let options = ParseOptions { safe: SafeMode::Unsafe, include_resolver: Some(std::sync::Arc::new(resolver)), ..Default::default() };
+        let html = AsciiDocParser::parse_document_with_options(&input, &options).unwrap().to_html();
+        assert_eq!(html.matches("Secret.").count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn test_header_with_attributes() {
-        let input = r#"= My Document
-:author: John Doe
-:version: 1.0
+    fn test_tagged_regions() {
+        const SOURCE: &str = "fn main() {\n    // tag::setup[]\n    let client = Client::new();\n    // tag::config[]\n    client.configure();\n    // end::config[]\n    // end::setup[]\n    // tag::setup[]\n    client.start();\n    // end::setup[]\n}\n<!-- tag::open[] -->\nleft open\n";
+        
+        let regions = tags::tagged_regions(SOURCE);
+        let found: Vec<(&str, usize, usize)> = regions.iter().map(|region| (region.name.as_str(), region.start_line, region.end_line)).collect();
+        assert_eq!(found, [("setup", 2, 7), ("config", 4, 6), ("setup", 8, 10), ("open", 12, 13)]);
+        assert_eq!(regions[0].content, "    let client = Client::new();\n    client.configure();\n");
+        
+        assert_eq!(tags::tagged_region(SOURCE, "setup").unwrap(), "    let client = Client::new();\n    client.configure();\n    client.start();\n");
+        assert_eq!(tags::select_tags(SOURCE, &["setup", "!config"]).unwrap(), "    let client = Client::new();\n    client.start();\n");
+        assert_eq!(tags::select_tags(SOURCE, &["!setup", "!open"]).unwrap(), "fn main() {\n}\n");
+        assert_eq!(tags::tagged_region(SOURCE, "missing"), None);
+        // Markers must start a word and close with `[]`
+        assert!(tags::tagged_regions("backend::x[]\ntag::y[z]\n").is_empty());
+
+        struct SnippetResolver;
+        impl include::IncludeResolver for SnippetResolver {
+            fn resolve(&self, _target: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+                Ok("// tag::intro[]\nIntro text.\n// end::intro[]\n// tag::rest[]\nMore.\n// end::rest[]\n".to_string())
+            }
+        }
+        let options = ParseOptions { include_resolver: Some(std::sync::Arc::new(SnippetResolver)), ..Default::default() };
+        let doc = AsciiDocParser::parse_document_with_options("include::snippets.adoc[tag=intro]\n", &options).unwrap();
+        assert_eq!(doc.to_html(), "<p>Intro text.</p>\n");
+        let doc = AsciiDocParser::parse_document_with_options("include::snippets.adoc[tags=rest;intro]\n", &options).unwrap();
+        assert_eq!(doc.to_html(), "<p>Intro text. More.</p>\n");
+    }
+
+    #[test]
+    fn test_http_include_allowlist() {
+        let options = include::HttpIncludeOptions {
+            allow: vec!["https://raw.example.com/shared/".to_string()],
+            ..Default::default()
+        };
+        
+        assert!(options.permits("https://raw.example.com/shared/intro.adoc"));
+        assert!(!options.permits("https://raw.example.com/other/intro.adoc"));
+        assert!(!options.permits("shared/intro.adoc"));
+        
+        // Look-alike hosts and paths are refused, as is anything a client might resolve elsewhere
+        let options = include::HttpIncludeOptions {
+            allow: vec!["https://example.com".to_string(), "https://host/org".to_string()],
+            ..Default::default()
+        };
+        assert!(options.permits("https://example.com/a.adoc"));
+        assert!(options.permits("https://EXAMPLE.com"));
+        assert!(options.permits("https://host/org/a.adoc"));
+        assert!(!options.permits("https://example.com.evil.org/a.adoc"));
+        assert!(!options.permits("https://example.com@evil.org/a.adoc"));
+        assert!(!options.permits("https://evil.org\\@example.com/a.adoc"));
+        assert!(!options.permits("http://example.com/a.adoc"));
+        assert!(!options.permits("https://example.com:8443/a.adoc"));
+        assert!(!options.permits("https://host/org-evil/a.adoc"));
+        assert!(!options.permits("https://host/org/../evil/a.adoc"));
+        assert!(!options.permits("https://host/org/%2E%2E/evil/a.adoc"));
+    }
+
+    #[test]
+    fn test_workspace_includes_and_xrefs() {
+        let mut workspace = Workspace::new();
+        workspace.add_document("index.adoc", r#"= Index
+
+See <<guide/install.adoc#_requirements>> and <<missing.adoc#nowhere,Missing>>."#);
+        workspace.add_document("guide/install.adoc", r#"= Install
+
+include::partials/note.adoc[]
+
+== Requirements
+
+Back to <<../index.adoc#,the index>>."#);
+        workspace.add_document("guide/partials/note.adoc", "Shared note.\n\ninclude::tip.adoc[]");
+        workspace.add_document("guide/partials/tip.adoc", "Shared tip.");
+        
+        let set = workspace.parse().unwrap();
+        let install = set.get("guide/install.adoc").unwrap();
+        assert_eq!(install.body[0], Block::Paragraph {
+            content: vec![InlineElement::Text("Shared note.".to_string())],
+        });
+        // Includes in an included file are relative to that file
+        assert_eq!(install.body[1], Block::Paragraph {
+            content: vec![InlineElement::Text("Shared tip.".to_string())],
+        });
+        
+        let entry = set.catalog().get("guide/install.adoc", "_requirements").unwrap();
+        assert_eq!(entry.title.as_deref(), Some("Requirements"));
+        
+        let html = set.get("index.adoc").unwrap().to_html();
+        assert!(html.contains(r#"<a href="guide/install.html#_requirements">Requirements</a>"#));
+        
+        assert_eq!(set.unresolved_xrefs().len(), 1);
+        assert_eq!(set.unresolved_xrefs()[0].target, "missing.adoc#nowhere");
+    }
 
-Content here."#;
+    #[test]
+    fn test_attribute_merge() {
+        let mut workspace = Workspace::new();
+        workspace.add_document("a.adoc", "= A\n:sectnums:\n:icons: font\n:description: First\n\nText.");
+        workspace.add_document("b.adoc", "= B\n:icons: image\n:experimental:\n:description: Second\n\nText.");
+        let set = workspace.parse().unwrap();
+        let attribute = |set: &DocumentSet, document: &str, name: &str| {
+            set.get(document).unwrap().attribute(name).map(str::to_string)
+        };
         
-        let result = AsciiDocParser::parse_document(input);
-        assert!(result.is_ok());
+        let mut first = set.clone();
+        let conflicts = first.merge_attributes(ConflictPolicy::FirstWins).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, diagnostics::DiagnosticKind::AttributeConflict);
+        assert_eq!(conflicts[0].message, "attribute 'icons' is set to 'font' in a.adoc, 'image' in b.adoc");
+        assert_eq!(attribute(&first, "b.adoc", "icons").as_deref(), Some("font"));
+        assert_eq!(attribute(&first, "b.adoc", "sectnums").as_deref(), Some(""));
+        assert_eq!(attribute(&first, "a.adoc", "experimental").as_deref(), Some(""));
+        // Each document keeps its own description
+        assert_eq!(attribute(&first, "b.adoc", "description").as_deref(), Some("Second"));
         
-        let doc = result.unwrap();
-        let header = doc.header.unwrap();
-        assert_eq!(header.title, "My Document");
-        assert_eq!(header.attributes.len(), 2);
-        assert_eq!(header.attributes[0].name, "author");
-        assert_eq!(header.attributes[0].value, Some("John Doe".to_string()));
+        let mut last = set.clone();
+        last.merge_attributes(ConflictPolicy::LastWins).unwrap();
+        assert_eq!(attribute(&last, "a.adoc", "icons").as_deref(), Some("image"));
+        
+        let mut strict = set.clone();
+        assert_eq!(strict.merge_attributes(ConflictPolicy::Error).unwrap_err().len(), 1);
+        assert_eq!(strict, set);
+    }
+
+    struct SiteResolver;
+
+    impl resource::ResourceResolver for SiteResolver {
+        fn resolve_xref(&self, id: &resource::ResourceId) -> Option<resource::ResolvedXref> {
+            let component = id.component.as_deref().unwrap_or("docs");
+            let module = id.module.as_deref().unwrap_or("ROOT");
+            let page = id.path.strip_suffix(".adoc")?;
+            let fragment = id.fragment.as_ref().map(|f| format!("#{}", f)).unwrap_or_default();
+            Some(resource::ResolvedXref {
+                url: format!("/{}/{}/{}.html{}", component, module, page, fragment),
+                title: Some(format!("{} page", page)),
+            })
+        }
+
+        fn read_include(&self, id: &resource::ResourceId) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+            match (id.family.as_deref(), id.path.as_str()) {
+                (Some("partial"), "intro.adoc") => Ok("Partial content.".to_string()),
+                _ => Err("missing".into()),
+            }
+        }
     }
 
     #[test]
-    fn test_sections() {
+    fn test_resource_id_parsing() {
+        let id = resource::ResourceId::parse("2.0@server:admin:partial$setup/ports.adoc#tcp").unwrap();
+        assert_eq!(id.version.as_deref(), Some("2.0"));
+        assert_eq!(id.component.as_deref(), Some("server"));
+        assert_eq!(id.module.as_deref(), Some("admin"));
+        assert_eq!(id.family.as_deref(), Some("partial"));
+        assert_eq!(id.path, "setup/ports.adoc");
+        assert_eq!(id.fragment.as_deref(), Some("tcp"));
+        
+        let id = resource::ResourceId::parse("cli::index.adoc").unwrap();
+        assert_eq!(id.component.as_deref(), Some("cli"));
+        assert_eq!(id.module, None);
+        assert!(!resource::ResourceId::parse("intro").unwrap().is_qualified());
+    }
+
+    #[test]
+    fn test_resource_xrefs_and_includes() {
         let input = r#"= Document
 
-== Section 1
+include::ROOT:partial$intro.adoc[]
+
+See xref:admin:install.adoc#ports[] and xref:cli::index.adoc[the CLI], or <<local>>."#;
+        let options = ParseOptions {
+            resource_resolver: Some(std::sync::Arc::new(SiteResolver)),
+            ..Default::default()
+        };
+        
+        let doc = AsciiDocParser::parse_document_with_options(input, &options).unwrap();
+        let html = doc.to_html();
+        assert!(html.contains("<p>Partial content.</p>"));
+        assert!(html.contains(r#"<a href="/docs/admin/install.html#ports">install page</a>"#));
+        assert!(html.contains(r#"<a href="/cli/ROOT/index.html">the CLI</a>"#));
+        assert!(html.contains(r##"<a href="#local">local</a>"##));
+    }
+
+    #[test]
+    fn test_custom_id_generator() {
+        struct AsciiSlugs;
+        impl ids::IdGenerator for AsciiSlugs {
+            fn generate_id(&self, title: &str, _prefix: &str, _separator: &str) -> String {
+                let words: Vec<String> = title.split_whitespace()
+                    .map(|word| word.chars().filter(char::is_ascii_alphanumeric).collect::<String>().to_ascii_lowercase())
+                    .filter(|word| !word.is_empty())
+                    .collect();
+                words.join("-")
+            }
+        }
+        
+        let input = "= Guide\n:idseparator: -\n\ntoc::[]\n\n== Setup 日本語\n\nSee <<setup>>.\n\n== Setup\n";
+        let options = ParseOptions { id_generator: Some(std::sync::Arc::new(AsciiSlugs)), ..Default::default() };
+        let doc = AsciiDocParser::parse_document_with_options(input, &options).unwrap();
+        let html = doc.to_html();
+        assert!(html.contains("<li><a href=\"#setup\">Setup 日本語</a></li>"));
+        assert!(html.contains("<h2 id=\"setup\">Setup 日本語</h2>"));
+        assert!(html.contains("<h2 id=\"setup-2\">Setup</h2>"));
+        assert!(html.contains("<a href=\"#setup\">"));
+        
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        assert!(doc.to_html().contains("<h2 id=\"_setup-日本語\">Setup 日本語</h2>"));
+    }
+
+    #[test]
+    fn test_ascii_id_transliteration() {
+        use ids::{AsciiIdGenerator, IdGenerator, SlugFallback};
+        
+        let ascii = AsciiIdGenerator::default();
+        assert_eq!(ascii.generate_id("Café Straße", "_", "_"), "_cafe_strasse");
+        assert_eq!(ascii.generate_id("Привет, мир", "", "-"), "privet-mir");
+        assert_eq!(ascii.generate_id("Ελληνικά", "_", "_"), "_ellinika");
+        assert_eq!(ascii.generate_id("Setup 中文", "_", "_"), "_setup_%E4%B8%AD%E6%96%87");
+        assert_eq!(ascii.generate_id("中文", "_", "_"), ascii.generate_id("中文", "_", "_"));
+        
+        let dropping = AsciiIdGenerator::new(SlugFallback::Drop).transliterate('中', "zhong").transliterate('&', "and");
+        assert_eq!(dropping.generate_id("中文 & more", "_", "_"), "_zhong_and_more");
+        assert_eq!(dropping.generate_id("文", "_", "_"), "_section");
+        
+        let options = ParseOptions { id_generator: Some(std::sync::Arc::new(AsciiIdGenerator::new(SlugFallback::Drop))), ..Default::default() };
+        let doc = AsciiDocParser::parse_document_with_options("== 日本\n\n== 中国\n", &options).unwrap();
+        let html = doc.to_html();
+        assert!(html.contains("<h2 id=\"_section\">日本</h2>"));
+        assert!(html.contains("<h2 id=\"_section_2\">中国</h2>"));
+    }
+
+    #[test]
+    fn test_concurrent_parsing_with_shared_options() {
+        let options = std::sync::Arc::new(ParseOptions {
+            safe: SafeMode::Unsafe,
+            include_resolver: Some(std::sync::Arc::new(StaticResolver)),
+            ..Default::default()
+        });
+        
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let options = std::sync::Arc::clone(&options);
+                std::thread::spawn(move || {
+                    let input = format!("= Doc {}\n\ninclude::https://example.com/snippet.adoc[]\n", i);
+                    AsciiDocParser::parse_document_with_options(&input, &options).unwrap()
+                })
+            })
+            .collect();
+        
+        for (i, handle) in handles.into_iter().enumerate() {
+            let doc = handle.join().unwrap();
+            assert_eq!(doc.header.unwrap().title, format!("Doc {}", i));
+            assert!(doc.body[0].to_html().contains("Shared snippet text."));
+        }
+    }
+
+    #[cfg(feature = "arena")]
+    #[test]
+    fn test_arena_document() {
+        let input = "= Arena\n:author: Jo Writer\n\n== Usage\n\nSome *bold* text, see <<_usage>> and link:https://example.com[site].\n\n* one\n* two\n\nTerm:: Definition\n\n[source,rust]\n----\nfn main() {}\n----";
+        
+        let owned = AsciiDocParser::parse_document(input).unwrap();
+        let arena = bumpalo::Bump::new();
+        let doc = arena::Document::alloc_in(&arena, &owned);
+        assert_eq!(doc.header.unwrap().title, "Arena");
+        assert_eq!(doc.attribute("author"), Some("Jo Writer"));
+        let arena::Block::Section { id, blocks, .. } = &doc.body[0] else { panic!("Expected section block") };
+        assert_eq!(*id, Some("_usage"));
+        assert!(matches!(blocks[0], arena::Block::Paragraph { .. }));
+        assert_eq!(doc.to_html(), owned.to_html());
+        
+        // Heading links come from the header as they do for `ast`
+        let owned = AsciiDocParser::parse_document("= T
+:sectanchors:
+:sectlinks:
 
-=== Subsection
+== S
+").unwrap();
+        let doc = arena::Document::alloc_in(&arena, &owned);
+        assert!(doc.to_html().contains("<h2 id=\"_s\"><a class=\"anchor\" href=\"#_s\"></a><a class=\"link\" href=\"#_s\">S</a></h2>"), "{}", doc.to_html());
+        assert_eq!(doc.to_html(), owned.to_html());
+    }
 
-Content"#;
+    #[test]
+    fn test_interned_attribute_names() {
+        // A document shares one allocation per distinct name
+        let doc = AsciiDocParser::parse_document("= One\n:toc-placement: left\n\n:toc-placement: right\n").unwrap();
+        let Block::BlockMetadata { kind: BlockMetadataKind::AttributeEntry(entry) } = &doc.body[0] else { panic!("Expected attribute entry") };
+        assert_eq!(doc.header.as_ref().unwrap().attributes[0].name.as_ptr(), entry.name.as_ptr());
+        
+        // So do roles, on sidebars and on text in admonitions and table cells
+        let doc = AsciiDocParser::parse_document("[.tip]\n****\nAside.\n****\n\nNOTE: A [.tip]#hint#.\n\n|===\n|[.tip]#cell#\n|===\n").unwrap();
+        let Block::Sidebar { roles, .. } = &doc.body[0] else { panic!("Expected sidebar") };
+        let mut roles = roles.clone();
+        doc.walk_inlines(&mut |element| {
+            if let InlineElement::Formatted { attributes, .. } = element {
+                roles.extend(attributes.roles.iter().cloned());
+            }
+        });
+        assert_eq!(roles, ["tip", "tip", "tip"]);
+        assert!(roles.iter().all(|role| role.as_ptr() == roles[0].as_ptr()));
+        
+        // The documents of a workspace share them with each other
+        let mut workspace = Workspace::new();
+        workspace.add_document("one.adoc", "= One\n:toc-placement: left\n\n[,rust]\n----\nfn one() {}\n----");
+        workspace.add_document("two.adoc", "= Two\n:toc-placement: right\n\n[,rust]\n----\nfn two() {}\n----");
+        let set = workspace.parse().unwrap();
+        let (first, second) = (set.get("one.adoc").unwrap(), set.get("two.adoc").unwrap());
+        
+        let name = |doc: &Document| doc.header.as_ref().unwrap().attributes[0].name.clone();
+        assert_eq!(name(first), "toc-placement");
+        assert_eq!(name(first).as_ptr(), name(second).as_ptr());
+        
+        let language = |doc: &Document| match &doc.body[0] {
+            Block::DelimitedBlock { language: Some(language), .. } => language.clone(),
+            _ => panic!("Expected delimited block with a language"),
+        };
+        assert_eq!(language(first), "rust");
+        assert_eq!(language(first).as_ptr(), language(second).as_ptr());
+        
+        // As do documents parsed with one pool, which hands out the symbols as they are read
+        let mut symbols = intern::SymbolPool::new();
+        let options = ParseOptions::default();
+        let mut sidebar_roles = |input| match AsciiDocParser::parse_document_with_symbols(input, &options, &mut symbols).unwrap().body.remove(0) {
+            Block::Sidebar { roles, .. } => roles,
+            _ => panic!("Expected sidebar"),
+        };
+        let (first, second) = (sidebar_roles("[.tip]\n****\nOne.\n****\n"), sidebar_roles("[.tip]\n****\nTwo.\n****\n"));
+        assert_eq!(first[0].as_ptr(), second[0].as_ptr());
+        assert_eq!(symbols.symbol("tip").as_ptr(), first[0].as_ptr());
+    }
+
+    #[test]
+    fn test_streamed_html_matches_rendered_html() {
+        let input = "= Streaming\n\nPreamble.\n\n== First\n\nText with *bold*.\n\n=== Nested\n\n* item\n\n== Second\n\n----\ncode\n----";
+        
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let mut out = Vec::new();
+        doc.write_html(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), doc.to_html());
+    }
+
+    #[test]
+    fn test_deterministic_output() {
+        let input = "= Repeat\n:author: Jo Writer\n:lang: en\n\n== Notes\n\nSee <<_notes_2>>.\n\n== Notes\n\n=== Notes\n\n* one\n** two\n\nTerm:: Definition\n\n[,rust]\n----\nfn main() {}\n----";
         
-        let result = AsciiDocParser::parse_document(input);
-        match &result {
-            Ok(_) => {},
-            Err(e) => println!("Parse error: {}", e),
+        let render = || {
+            let doc = AsciiDocParser::parse_document(input).unwrap();
+            let mut streamed = Vec::new();
+            doc.write_html(&mut streamed).unwrap();
+            let chunks: Vec<String> = doc.to_chunked_html(2).into_iter().map(|chunk| chunk.file_name + &chunk.html).collect();
+            (doc.to_html(), streamed, doc.to_latex(), chunks)
+        };
+        let first = render();
+        assert_eq!(first, render());
+        assert!(first.0.contains(r#"<h2 id="_notes_2">Notes</h2>"#));
+        assert!(first.0.contains(r#"<h3 id="_notes_3">Notes</h3>"#));
+        
+        let mut workspace = Workspace::new();
+        for name in ["zeta.adoc", "alpha.adoc", "mid/beta.adoc"] {
+            workspace.add_document(name, input);
         }
-        assert!(result.is_ok());
+        let set = workspace.parse().unwrap();
+        let names: Vec<&str> = set.documents().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["alpha.adoc", "mid/beta.adoc", "zeta.adoc"]);
+    }
+
+    #[test]
+    fn test_parse_tree_dump() {
+        let tree = AsciiDocParser::parse_tree("= Title\n\n* item\n", &ParseOptions::default()).unwrap();
+        let lines: Vec<&str> = tree.lines().collect();
+        assert_eq!(lines[0], "document 0..16 @1:1");
+        assert!(lines.contains(&"      title_text 2..7 @1:3 \"Title\""));
+        assert!(lines.contains(&"          unordered_item 9..16 @3:1"));
+        assert!(lines.contains(&"            list_content 11..15 @3:3 \"item\""));
+        assert_eq!(lines.last(), Some(&"  EOI 16..16 @4:1 \"\""));
         
-        let doc = result.unwrap();
-        println!("Body has {} blocks: {:#?}", doc.body.len(), doc.body);
+        assert!(AsciiDocParser::parse_tree("", &ParseOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_author_line() {
+        let input = "= Notes\nKismet R. Lee <kismet@example.org>; B. Lee\n:icons: font\n\nBody text.";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let attribute = |name: &str| doc.attribute(name);
+        let authors: Vec<(String, Option<String>)> = doc.render_context().authors.into_iter().map(|author| (author.name, author.email)).collect();
+        assert_eq!(authors, [("Kismet R. Lee".to_string(), Some("kismet@example.org".to_string())), ("B. Lee".to_string(), None)]);
+        assert_eq!((attribute("firstname"), attribute("middlename"), attribute("lastname")), (Some("Kismet"), Some("R."), Some("Lee")));
+        assert_eq!((attribute("authorinitials"), attribute("email")), (Some("KRL"), Some("kismet@example.org")));
+        assert_eq!((attribute("author_2"), attribute("email_2")), (Some("B. Lee"), None));
+        assert_eq!(attribute("icons"), Some("font"));
         assert_eq!(doc.body.len(), 1);
         
-        if let Block::Section { level, title, .. } = &doc.body[0] {
-            assert_eq!(*level, 2);
-            assert_eq!(title, "Section 1");
-        } else {
-            panic!("Expected section block");
+        let html = doc.to_html();
+        assert!(html.contains("<span id=\"author\" class=\"author\">Kismet R. Lee</span><br>\n"));
+        assert!(html.contains("<span id=\"author_2\" class=\"author\">B. Lee</span><br>\n"));
+        
+        // Four words can't be a name, so the line stays in the body
+        let doc = AsciiDocParser::parse_document("= Notes\nThis is not a name.").unwrap();
+        assert_eq!((doc.attribute("author"), doc.body.len()), (None, 1));
+    }
+
+    #[test]
+    fn test_header_and_footer_toggles() {
+        let input = "= Notes\n:author: Jo Writer\n:revnumber: 1.2\n:noheader:\n:nofooter:\n\nBody text.";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        assert_eq!(doc.to_html(), "<p>Body text.</p>\n");
+        
+        let mut doc = AsciiDocParser::parse_document("= Notes\n\nBody text.").unwrap();
+        assert!(!doc.to_html().contains("id=\"footer\""));
+        doc.set_last_updated(std::time::UNIX_EPOCH + std::time::Duration::from_secs(86400 + 3661));
+        assert!(doc.attribute("docdatetime").is_some());
+        assert!(doc.to_html().contains("<div id=\"footer-text\">\nLast updated "));
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let doc = AsciiDocParser::parse_document("= Notes\n\nFirst line\nsecond line.").unwrap();
+        let reflowed = AsciiDocParser::parse_document("= Notes\n\n\nFirst line second line.\n").unwrap();
+        let edited = AsciiDocParser::parse_document("= Notes\n\nFirst line, second line.").unwrap();
+        
+        assert_eq!(doc.content_hash(), reflowed.content_hash());
+        assert_ne!(doc.content_hash(), edited.content_hash());
+        // Pinned so that caches keyed on the hash survive upgrades
+        assert_eq!(doc.content_hash(), 0x347a8f8691c87697);
+    }
+
+    #[test]
+    fn test_document_summary() {
+        let input = "= Post\n\n----\nskipped code\n----\n\nA *short*   intro,\nwith a https://example.com[link].\n\n== More\n\nSecond paragraph.";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        assert_eq!(doc.summary(100), "A short intro, with a link. Second paragraph.");
+        assert_eq!(doc.summary(20), "A short intro, with…");
+        assert_eq!(doc.summary(16), "A short intro…");
+        assert_eq!(doc.summary(0), "");
+        
+        let doc = AsciiDocParser::parse_document("Cafe\u{301}s 👩\u{200D}💻 🇫🇷 and more").unwrap();
+        assert_eq!(doc.summary(8), "Cafe\u{301}s 👩\u{200D}💻…");
+        let doc = AsciiDocParser::parse_document("日本語のテキストです").unwrap();
+        assert_eq!(doc.summary(4), "日本語…");
+    }
+
+    #[test]
+    fn test_search_index() {
+        let input = "= Guide\n\nIntro text.\n\n== Install\n\nRun *cargo install*.\n\n=== Linux\n\n* Use the package manager\n\n== Usage\n\n----\nadoc book.adoc\n----";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let mut index = search::SearchIndex::new();
+        index.add_document("guide.adoc", &doc);
+        
+        let entries = index.entries();
+        let ids: Vec<&str> = entries.iter().map(|entry| entry.id.as_str()).collect();
+        assert_eq!(ids, ["", "_install", "_linux", "_usage"]);
+        assert_eq!(entries[0].text, "Intro text.");
+        assert_eq!(entries[1].text, "Run cargo install.");
+        assert_eq!(entries[2].breadcrumb, ["Guide", "Install"]);
+        assert_eq!(entries[3].text, "adoc book.adoc");
+        assert!(index.to_json().contains("{\"document\": \"guide.adoc\", \"id\": \"_linux\", \"title\": \"Linux\", \"breadcrumb\": [\"Guide\", \"Install\"], \"text\": \"Use the package manager\"}"));
+    }
+
+    #[test]
+    fn test_untrusted_html_profile() {
+        let input = "Click link:javascript:alert(1)[here] or link:JaVa\tScRiPt:alert(1)[there], \
+see image:data:image/svg+xml;base64,PHN2Zz4=[x\" onerror=\"alert(1)] and <<javascript:alert(1).adoc,notes>>.\n\n\
+<script>alert(1)</script> with link:https://example.com[a site], link:guide.html#setup:linux[a page] and link:mailto:me@example.com[mail].";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        
+        let html = doc.to_html_with(&HtmlOptions::untrusted());
+        assert!(!html.to_lowercase().contains("script:"), "{}", html);
+        assert!(!html.contains("data:") && !html.contains("<img"));
+        assert!(!html.contains("<script>") && !html.contains("\" onerror"));
+        assert!(html.contains("Click here or there"));
+        assert!(html.contains("<a href=\"https://example.com\">a site</a>"));
+        assert!(html.contains("<a href=\"guide.html#setup:linux\">a page</a>"));
+        assert!(html.contains("<a href=\"mailto:me@example.com\">mail</a>"));
+        
+        // The default profile trusts the author
+        assert!(doc.to_html().contains("<a href=\"javascript:alert(1)\">here</a>"));
+    }
+
+    #[test]
+    fn test_url_rewriting() {
+        let input = "See link:https://example.com[home], link:https://spam.example[spam], <<guide.adoc#_setup,setup>> \
+and link:ftp://files.example/a.zip[files].";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let rewriter = |mut target: UrlTarget| {
+            if target.url.contains("spam.example") {
+                return None;
+            }
+            match target.context {
+                UrlContext::CrossReference => target.url = format!("/docs/{}", target.url.replace(".html", "/")),
+                _ => target.attributes.push(("rel".to_string(), "nofollow".to_string())),
+            }
+            target.attributes.push(("onclick".to_string(), "track()".to_string()));
+            Some(target)
+        };
+        let mut options = HtmlOptions { url_rewriter: Some(std::sync::Arc::new(rewriter)), ..Default::default() };
+        
+        let html = doc.to_html_with(&options);
+        assert!(html.contains("<a href=\"https://example.com\" rel=\"nofollow\" onclick=\"track()\">home</a>"));
+        assert!(html.contains(", spam, "));
+        assert!(html.contains("<a href=\"/docs/guide/#_setup\" onclick=\"track()\">setup</a>"));
+        
+        // Sanitizing still vets what the rewriter returns
+        options.sanitize = true;
+        let html = doc.to_html_with(&options);
+        assert!(!html.contains("onclick") && html.contains("and files."));
+        options.allowed_schemes.push("ftp".to_string());
+        assert!(doc.to_html_with(&options).contains("<a href=\"ftp://files.example/a.zip\" rel=\"nofollow\">files</a>"));
+    }
+
+    #[test]
+    fn test_external_link_annotation() {
+        let doc = AsciiDocParser::parse_document("See link:https://example.com[home], link:guide.html[the guide], <<setup>> \
+and image:https://example.com/logo.png[Logo].\n\n[[setup]]\n== Setup").unwrap();
+        let mut options = HtmlOptions { external_links: true, ..Default::default() };
+        let html = doc.to_html_with(&options);
+        assert!(html.contains("<a href=\"https://example.com\" rel=\"noopener noreferrer\" target=\"_blank\">home</a>"));
+        assert!(html.contains("<a href=\"guide.html\">the guide</a>"));
+        assert!(html.contains("<a href=\"#setup\">"));
+        assert!(!html.contains("logo.png\" rel="));
+        
+        // Added to what a rewriter sets rather than replacing it
+        options.external_link_class = Some("external".to_string());
+        options.url_rewriter = Some(std::sync::Arc::new(|mut target: UrlTarget| {
+            target.attributes.push(("rel".to_string(), "nofollow".to_string()));
+            Some(target)
+        }));
+        let html = doc.to_html_with(&options);
+        assert!(html.contains("<a href=\"https://example.com\" rel=\"nofollow noopener noreferrer\" target=\"_blank\" class=\"external\">home</a>"));
+        assert!(html.contains("<a href=\"guide.html\" rel=\"nofollow\">the guide</a>"));
+    }
+
+    #[test]
+    fn test_listing_titles() {
+        let input = ".Setup\n[source,sh,filename=\"install.sh\"]\n----\nmake\n----\n\n[source,sh,linenums]\n----\nmake\n----\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        // A `.Title` wins over the file name, which then stays in the attribute list
+        assert_eq!(doc.body[1], Block::DelimitedBlock {
+            kind: DelimitedBlockKind::Listing,
+            content: "make\n".to_string(),
+            language: Some(Symbol::new("sh")),
+            title: Some("Setup".to_string()),
+        });
+        assert!(matches!(&doc.body[2], Block::BlockMetadata { kind: BlockMetadataKind::Attribute(_) }));
+        assert!(matches!(&doc.body[3], Block::DelimitedBlock { language: Some(_), title: None, .. }));
+        let reprinted = AsciiDocParser::parse_document(&doc.to_asciidoc()).unwrap();
+        assert_eq!(reprinted.to_html(), doc.to_html());
+        
+        let doc = AsciiDocParser::parse_document("[source,sh,filename=install.sh]\n----\nmake\n----\n").unwrap();
+        assert!(matches!(&doc.body[..], [Block::DelimitedBlock { title: Some(title), .. }] if title == "install.sh"));
+        let mut options = HtmlOptions { standalone: true, ..Default::default() };
+        assert!(doc.to_html_with(&options).contains("<span class=\"filename\">install.sh</span>\n<button type=\"button\" class=\"copy-button\""));
+        assert!(!doc.to_html_with(&options).contains("<script>"));
+        options.copy_button_script = true;
+        assert!(doc.to_html_with(&options).contains("navigator.clipboard.writeText(code.innerText);\n});\n</script>\n</body>"));
+    }
+
+    #[test]
+    fn test_stray_continuation_lines() {
+        // A `+` line only attaches a block inside a list; anywhere else it is text
+        let text = |doc: &Document, index: usize| match &doc.body[index] {
+            Block::Paragraph { content } => plain_text(content),
+            block => panic!("Expected paragraph, got {:?}", block),
+        };
+        let doc = AsciiDocParser::parse_document("+").unwrap();
+        assert_eq!(text(&doc, 0), "+");
+        let doc = AsciiDocParser::parse_document("Hello\n+\nWorld").unwrap();
+        assert_eq!(text(&doc, 0), "Hello + World");
+        
+        // With nothing below it, or after a blank line, it doesn't carry the list on
+        for input in ["* a\n+\n", "* a\n\n+\nb"] {
+            let doc = AsciiDocParser::parse_document(input).unwrap();
+            assert!(matches!(&doc.body[0], Block::List { items, .. } if items.len() == 1 && items[0].blocks().is_empty()), "{}", input);
+            assert!(text(&doc, 1).starts_with('+'), "{}", input);
         }
+        let doc = AsciiDocParser::parse_document("* a\n+\nAttached.\n+\nAlso attached.").unwrap();
+        assert!(matches!(&doc.body[0], Block::List { items, .. } if items[0].blocks().len() == 2));
     }
 
     #[test]
-    fn test_delimited_block() {
-        let input = r#"= Document
+    fn test_comment_blocks() {
+        let input = "Before.\n\n////\n== Not a section\n\n----\n////\n\nAfter.\n\n/////\nleft open\n////\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        assert_eq!(doc.to_html(), "<p>Before.</p>\n<p>After.</p>\n");
+        assert!(diagnostics::check(input, &ParseOptions::default()).is_empty());
+        
+        let options = ParseOptions { comments: true, ..Default::default() };
+        let kept = AsciiDocParser::parse_document_with_options(input, &options).unwrap();
+        assert_eq!(kept.body[1], Block::Comment { text: "== Not a section\n\n----\n".to_string(), delimited: true });
+        assert_eq!(kept.body[3], Block::Comment { text: "left open\n////\n".to_string(), delimited: true });
+        assert_eq!(kept.to_html(), doc.to_html());
+        assert!(kept.to_latex().contains("% == Not a section\n%\n% ----\n"));
+        let reprinted = AsciiDocParser::parse_document_with_options(&kept.to_asciidoc(), &options).unwrap();
+        assert_eq!(reprinted.body[1], kept.body[1]);
+        
+        // Whitespace may follow the slashes, and a line that only starts with them is text
+        let doc = AsciiDocParser::parse_document("//// \nhidden\n////\t\nShown.").unwrap();
+        assert_eq!(doc.to_html(), "<p>Shown.</p>\n");
+        let doc = AsciiDocParser::parse_document("////x\nmore").unwrap();
+        assert_eq!(doc.to_html(), "<p>////x more</p>\n");
+    }
+
+    #[test]
+    fn test_line_comments() {
+        let input = "// License: MIT\nFirst line\n// inside\nsecond line.\n\n* item\n\n//TODO: more\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        assert_eq!(doc.to_html(), "<p>First line second line.</p>\n<ul>\n<li>item</li>\n</ul>\n");
+        
+        let options = ParseOptions { comments: true, ..Default::default() };
+        let kept = AsciiDocParser::parse_document_with_options(input, &options).unwrap();
+        assert_eq!(kept.body[0], Block::Comment { text: " License: MIT".to_string(), delimited: false });
+        assert_eq!(kept.body[3], Block::Comment { text: "TODO: more".to_string(), delimited: false });
+        assert_eq!(kept.to_html(), doc.to_html());
+        assert_eq!(kept.dump(Some(input)).lines().last(), Some("Comment kind=line text=\"TODO: more\" @8:1-8:13"));
+        let reprinted = kept.to_asciidoc();
+        assert!(reprinted.starts_with("// License: MIT\n") && reprinted.ends_with("//TODO: more\n"));
+        assert_eq!(AsciiDocParser::parse_document_with_options(&reprinted, &options).unwrap().to_asciidoc(), reprinted);
+    }
+
+    #[test]
+    fn test_block_hooks() {
+        let doc = AsciiDocParser::parse_document("== Setup\n\nRun it.\n\n----\nmake\n----\n\n* item\n+\n----\nnested\n----").unwrap();
+        let hook = |block: &Block, html: String| match block {
+            Block::DelimitedBlock { kind: DelimitedBlockKind::Listing, .. } => {
+                format!("<div class=\"copyable\"><button>Copy</button>\n{}</div>\n", html)
+            }
+            Block::Section { id: Some(id), .. } => html.replace("</h2>", &format!(" <a class=\"permalink\" href=\"#{}\">#</a></h2>", id)),
+            _ => html,
+        };
+        let options = HtmlOptions { block_hook: Some(std::sync::Arc::new(hook)), ..Default::default() };
+        let html = doc.to_html_with(&options);
+        assert!(html.contains("<h2 id=\"_setup\">Setup <a class=\"permalink\" href=\"#_setup\">#</a></h2>\n<p>Run it.</p>\n"));
+        assert!(html.contains("<div class=\"copyable\"><button>Copy</button>\n<pre><code>make\n</code></pre>\n</div>\n"));
+        assert!(html.contains("<div class=\"copyable\"><button>Copy</button>\n<pre><code>nested\n</code></pre>\n</div>"));
+        
+        let mut streamed = Vec::new();
+        doc.write_html_with(&mut streamed, &options).unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), html);
+    }
+
+    #[test]
+    fn test_external_attributes() {
+        let input = "= Document\n:product: Draft\n\n[subs=attributes+]\n----\n{product} {version}\n----\n";
+        let options = ParseOptions {
+            attributes: vec![
+                Attribute { name: Symbol::from("product"), value: Some("Widget".to_string()) },
+                Attribute { name: Symbol::from("version"), value: Some("2.1".to_string()) },
+            ],
+            ..Default::default()
+        };
+        
+        let doc = AsciiDocParser::parse_document_with_options(input, &options).unwrap();
+        assert_eq!(doc.header.as_ref().unwrap().attribute("product"), Some("Widget"));
+        assert!(matches!(&doc.body[1], Block::DelimitedBlock { content, .. } if content == "Widget 2.1\n"));
+
+        #[cfg(feature = "attribute-files")]
+        {
+            let attributes = attributes::from_json(r#"{"product": "Widget", "version": 2, "draft": true, "beta": false}"#).unwrap();
+            assert_eq!(attributes.len(), 3);
+            assert_eq!(attributes, attributes::from_toml("product = \"Widget\"\nversion = 2\ndraft = true\nbeta = false\n").unwrap());
+            assert!(attributes::from_json("[]").is_err());
+        }
+    }
+
+    #[test]
+    fn test_render_context() {
+        let input = "= Guide\n:authors: Ada Lovelace; Charles Babbage\n:email_2: cb@example.com\n:revnumber: 1.2\n\nIntro.\n\n== Setup\n\n=== Install\n\n==== Details\n\n== Usage\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let context = doc.render_context();
+        
+        assert_eq!(context.title.as_deref(), Some("Guide"));
+        assert_eq!(context.authors[1].name, "Charles Babbage");
+        assert_eq!(context.authors[1].email.as_deref(), Some("cb@example.com"));
+        assert_eq!(context.revision.number.as_deref(), Some("1.2"));
+        assert_eq!(context.toc.len(), 2);
+        assert_eq!(context.toc[0].children[0].title, "Install");
+        assert!(context.toc[0].children[0].children.is_empty());
+        assert_eq!(context.sections.len(), 4);
+        assert!(context.sections[1].html.starts_with("<h3 id=\"_install\">Install</h3>"));
+        assert!(context.body_html.starts_with("<p>Intro.</p>") && !context.body_html.contains("id=\"header\""));
+        assert!(context.to_json().contains("\"revision\": {\"number\": \"1.2\", \"date\": null, \"remark\": null}"));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let input = "= Doc\n\nSee <<nowhere>>.\n\n== Intro\n\n==== Deep\n\n[[dup]]\n== One\n\n[[dup]]\n== Two\n";
+        let diagnostics = diagnostics::check(input, &ParseOptions::default());
+        let found: Vec<(&str, Option<usize>)> = diagnostics.iter().map(|diagnostic| (diagnostic.code(), diagnostic.line)).collect();
+        assert_eq!(found, [("ADOC006", Some(3)), ("ADOC004", Some(7)), ("ADOC011", Some(7)), ("ADOC011", Some(10)), ("ADOC005", Some(12)), ("ADOC011", Some(13))]);
+        assert_eq!(diagnostics[0].to_string(), "ADOC006: cross reference target not found: nowhere");
+        
+        let diagnostics = diagnostics::check("Text\n\n----\ncode\n", &ParseOptions::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].name(), diagnostics[0].severity()), ("unterminated-block", diagnostics::Severity::Error));
+        assert_eq!(diagnostics::lookup("ADOC001"), Some(diagnostics[0].kind.rule()));
+        assert_eq!(diagnostics::lookup("duplicate-id").map(|rule| rule.code), Some("ADOC005"));
+        
+        // Suppression comments silence the named rules on the following line only
+        let input = "= Doc\n\n// adoc-lint-disable-next-line unresolved-xref\nSee <<nowhere>>.\n\n// adoc-lint-disable-next-line ADOC005\nSee <<elsewhere>>.\n\n// adoc-lint-disable-next-line\n==== Deep\n";
+        let diagnostics = diagnostics::check(input, &ParseOptions::default());
+        let found: Vec<(&str, Option<usize>)> = diagnostics.iter().map(|diagnostic| (diagnostic.code(), diagnostic.line)).collect();
+        assert_eq!(found, [("ADOC006", Some(7))]);
+    }
+
+    #[test]
+    fn test_structure_diagnostics() {
+        use diagnostics::{DiagnosticKind, StructureRules};
+        
+        let input = "= Doc\n\n== Lists\n\n* one\n** two\n*** three\n**** four\n***** five\n. deeper\n\n== Empty\n\n== Parent\n\n=== Child\n\nText.\n";
+        let found: Vec<(DiagnosticKind, Option<usize>)> = diagnostics::check(input, &ParseOptions::default()).iter()
+            .map(|diagnostic| (diagnostic.kind, diagnostic.line))
+            .collect();
+        assert_eq!(found, [(DiagnosticKind::ListTooDeep, Some(9)), (DiagnosticKind::EmptySection, Some(12))]);
+        
+        let config = config::Config { structure: StructureRules { max_list_depth: 2 }, ..Default::default() };
+        let diagnostics = diagnostics::check_with_config(input, &ParseOptions::default(), &config);
+        assert_eq!((diagnostics[0].line, diagnostics[0].message.as_str()), (Some(7), "list nested 3 levels deep, more than 2"));
+        assert_eq!(diagnostics[0].name(), "list-too-deep");
+
+        #[cfg(feature = "config")]
+        {
+            assert_eq!(config::from_toml("[structure]\nmax-list-depth = 2\n").unwrap(), config);
+            assert!(config::from_toml("[structure]\nmax-list-depth = 0\n").is_err());
+        }
+    }
+
+    #[test]
+    fn test_document_json() {
+        let doc = AsciiDocParser::parse_document("= Doc\n:version: 1\n\n== Intro\n\nSome *bold* text.\n\n* item\n").unwrap();
+        assert_eq!(
+            doc.to_json(),
+            concat!(
+                r#"{"version":1,"title":"Doc","attributes":{"version":"1"},"blocks":[{"type":"section","level":2,"title":"Intro","id":"_intro","number":null,"blocks":["#,
+                r#"{"type":"paragraph","content":[{"type":"text","text":"Some "},{"type":"formatted","kind":"strong","content":[{"type":"text","text":"bold"}],"id":null,"roles":[]},{"type":"text","text":" text."}]},"#,
+                r#"{"type":"list","kind":"unordered","items":[{"content":[{"type":"text","text":"item"}],"blocks":[]}]}]}]}"#,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_json_schema() {
+        assert!(JSON_SCHEMA.contains(&format!("\"$id\": \"urn:adoc:document:{}\"", JSON_SCHEMA_VERSION)));
+        assert!(JSON_SCHEMA.contains(&format!("\"version\": {{ \"const\": {} }}", JSON_SCHEMA_VERSION)));
+
+        #[cfg(feature = "attribute-files")]
+        {
+            let schema: serde_json::Value = serde_json::from_str(JSON_SCHEMA).unwrap();
+            let types: Vec<&str> = ["block", "inline"].iter()
+                .flat_map(|name| schema["$defs"][name]["oneOf"].as_array().unwrap())
+                .filter_map(|variant| variant["properties"]["type"]["const"].as_str())
+                .collect();
+            let doc = AsciiDocParser::parse_document("= Doc\n\n== Intro\n\nNOTE: Some *bold* text.\n\n* item\n").unwrap();
+            let json: serde_json::Value = serde_json::from_str(&doc.to_json()).unwrap();
+            assert_eq!(json["version"], JSON_SCHEMA_VERSION);
+            let section = &json["blocks"][0];
+            for node in [section, &section["blocks"][0], &section["blocks"][0]["content"][1], &section["blocks"][1]] {
+                assert!(types.contains(&node["type"].as_str().unwrap()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_node_at_position() {
+        use position::{Node, Position};
+        
+        let input = "= Title\n\n== Setup\n\nRun *cargo build* first.\n\n* one\n* see <<setup>>\n\n[source,rust]\n----\nfn main() {}\n----\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        
+        let path = doc.node_at_position(input, 5, 7);
+        assert_eq!(path.len(), 3);
+        assert!(matches!(path[0].node, Node::Block(Block::Section { .. })));
+        assert_eq!(path[0].span.start, Position { line: 3, column: 1 });
+        assert_eq!(path[0].span.end, Position { line: 13, column: 5 });
+        assert!(matches!(path[1].node, Node::Block(Block::Paragraph { .. })));
+        assert!(matches!(path[2].node, Node::Inline(InlineElement::Formatted { kind: FormattedTextKind::Strong, .. })));
+        assert_eq!(path[2].span.start, Position { line: 5, column: 5 });
+        assert_eq!(path[2].span.end, Position { line: 5, column: 18 });
+        
+        let path = doc.node_at_position(input, 8, 10);
+        let kinds: Vec<&str> = path.iter().map(|located| match located.node {
+            Node::Block(Block::Section { .. }) => "section",
+            Node::Block(Block::List { .. }) => "list",
+            Node::ListItem(_) => "item",
+            Node::Inline(InlineElement::Macro { .. }) => "xref",
+            _ => "other",
+        }).collect();
+        assert_eq!(kinds, ["section", "list", "item", "xref"]);
+        
+        // The listing took the attribute list above it
+        let path = doc.node_at_position(input, 12, 3);
+        assert!(matches!(path.last().unwrap().node, Node::Block(Block::DelimitedBlock { language: Some(_), .. })));
+        assert_eq!(path.last().unwrap().span.start, Position { line: 10, column: 1 });
+        let path = doc.node_at_position(input, 10, 1);
+        assert!(matches!(path.last().unwrap().node, Node::Block(Block::DelimitedBlock { .. })));
+        assert!(doc.node_at_position(input, 1, 3).is_empty());
+    }
+
+    #[test]
+    fn test_document_dump() {
+        let input = "= Guide\n:product: Widget\n\n== Intro\n\nSome *bold* text.\n\n* one\n+\n----\nmake\n----\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        assert_eq!(doc.dump(Some(input)), concat!(
+            "Header \"Guide\"\n",
+            "  Attribute name=\"product\" value=\"Widget\"\n",
+            "Section level=2 title=\"Intro\" id=\"_intro\" @4:1-12:5\n",
+            "  Paragraph @6:1-6:18\n",
+            "    Text \"Some \"\n",
+            "    Strong @6:6-6:12\n",
+            "      Text \"bold\"\n",
+            "    Text \" text.\"\n",
+            "  List kind=unordered @8:1-12:5\n",
+            "    Item @8:1-12:5\n",
+            "      Text \"one\"\n",
+            "      Listing content=\"make\\n\" @10:1-12:5\n",
+        ));
+        assert!(!doc.dump(None).contains('@'));
+    }
+
+    #[test]
+    fn test_folding_ranges_and_symbols() {
+        use position::{FoldingKind, FoldingRange, Position};
+        
+        let input = "= Guide\n\n== Install\n\n* download\n* unpack\n\n=== From source\n\n----\nmake\nmake install\n----\n\n== Use\n\nRun it.\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        
+        assert_eq!(doc.folding_ranges(input), vec![
+            FoldingRange { start_line: 3, end_line: 13, kind: FoldingKind::Section },
+            FoldingRange { start_line: 5, end_line: 6, kind: FoldingKind::List },
+            FoldingRange { start_line: 8, end_line: 13, kind: FoldingKind::Section },
+            FoldingRange { start_line: 10, end_line: 13, kind: FoldingKind::DelimitedBlock },
+            FoldingRange { start_line: 15, end_line: 17, kind: FoldingKind::Section },
+        ]);
+        
+        let symbols = doc.symbols(input);
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0].title, "Install");
+        assert_eq!(symbols[0].id.as_deref(), Some("_install"));
+        assert_eq!(symbols[0].heading.end, Position { line: 3, column: 11 });
+        assert_eq!(symbols[0].children.len(), 1);
+        assert_eq!(symbols[0].children[0].title, "From source");
+        assert_eq!(symbols[0].children[0].span.start, Position { line: 8, column: 1 });
+        assert_eq!(symbols[1].span.end, Position { line: 17, column: 8 });
+    }
+
+    #[test]
+    fn test_prose_runs() {
+        let input = r#"= The `adoc` Guide
+:product: Adoc
+
+== Getting started
+
+Install {product} with `cargo install adoc` and read
+https://example.com/docs[the *manual*] or <<usage,the usage notes>>.
 
+.Example output
 ----
-Code block content
-line 2
-----"#;
+Listing teh code
+----
+
+* Use snake_case names
+CPU:: the processor
+"#;
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let runs = doc.prose_runs(input);
+        let texts: Vec<&str> = runs.iter().map(|run| run.text.as_str()).collect();
+        assert_eq!(texts, [
+            "Getting started",
+            "Install",
+            "with",
+            "and read",
+            "the",
+            "manual",
+            "or",
+            "the usage notes",
+            "Example output",
+            "Use snake_case names",
+            "CPU",
+            "the processor",
+        ]);
+        assert_eq!(runs[2].span.start, position::Position { line: 6, column: 19 });
+        assert_eq!(runs[2].span.end, position::Position { line: 6, column: 23 });
+        assert_eq!(runs[7].span.start, position::Position { line: 7, column: 51 });
+    }
+
+    #[test]
+    fn test_vocabulary_rules() {
+        use diagnostics::{DiagnosticKind, Vocabulary};
+        
+        let vocabulary = Vocabulary {
+            banned: vec!["simply".to_string()],
+            preferred: vec![("e-mail".to_string(), "email".to_string())],
+            case: vec!["GitHub".to_string()],
+        };
+        let input = "= Setup\n\nSimply push to github and wait for the E-mail.\n\n----\nsimply github\n----\n\n* Open GitHub, not `github`\n";
+        let found = diagnostics::check_with_vocabulary(input, &ParseOptions::default(), &vocabulary);
+        let found: Vec<(DiagnosticKind, Option<usize>, &str)> = found.iter()
+            .map(|diagnostic| (diagnostic.kind, diagnostic.line, diagnostic.message.as_str()))
+            .collect();
+        assert_eq!(found, [
+            (DiagnosticKind::BannedTerm, Some(3), "'Simply' is not allowed"),
+            (DiagnosticKind::PreferredTerm, Some(3), "use 'email' instead of 'E-mail'"),
+            (DiagnosticKind::TermCase, Some(3), "write 'github' as 'GitHub'"),
+        ]);
+        assert!(diagnostics::check(input, &ParseOptions::default()).is_empty());
+        assert_eq!(diagnostics::lookup("term-case").unwrap().code, "ADOC009");
+
+        #[cfg(feature = "config")]
+        {
+            let config = config::from_toml("[vocabulary]\nbanned = [\"simply\"]\ncase = [\"GitHub\"]\n\n[vocabulary.preferred]\ne-mail = \"email\"\n").unwrap();
+            assert_eq!(config.vocabulary, vocabulary);
+            assert!(config::from_toml("[vocabulary]\nbanned = \"simply\"\n").is_err());
+            assert_eq!(config::from_toml("").unwrap(), config::Config::default());
+        }
+    }
+
+    #[test]
+    fn test_revisions() {
+        use context::Revision;
         
-        let result = AsciiDocParser::parse_document(input);
-        assert!(result.is_ok());
+        let revision = |number: Option<&str>, date: Option<&str>, remark: Option<&str>| Revision {
+            number: number.map(str::to_string),
+            date: date.map(str::to_string),
+            remark: remark.map(str::to_string),
+        };
+        let doc = AsciiDocParser::parse_document("= Notes\n:revision: v1.0, 2024-01-15: First\n:revision: 2024-02-01: Typos\n:revision: V2.0\n\nText.\n").unwrap();
+        assert_eq!(doc.revisions(), vec![
+            revision(Some("1.0"), Some("2024-01-15"), Some("First")),
+            revision(None, Some("2024-02-01"), Some("Typos")),
+            revision(Some("2.0"), None, None),
+        ]);
+        assert!(!doc.to_html().contains("revhistory"));
         
-        let doc = result.unwrap();
-        assert_eq!(doc.body.len(), 1);
+        let doc = AsciiDocParser::parse_document("= Notes\n:revnumber: 3.1\n:revremark: Beta\n:revhistory: Changes\n\nText.\n").unwrap();
+        assert_eq!(doc.revisions(), vec![revision(Some("3.1"), None, Some("Beta"))]);
+        assert!(doc.to_html().contains("<h2 id=\"_revision_history\">Changes</h2>\n<table class=\"revhistory\">"));
+        assert!(AsciiDocParser::parse_document("= Notes\n\nText.\n").unwrap().revisions().is_empty());
         
-        if let Block::DelimitedBlock { kind, content } = &doc.body[0] {
-            assert!(matches!(kind, DelimitedBlockKind::Listing));
-            assert!(content.contains("Code block content"));
-        } else {
-            panic!("Expected delimited block");
+        // The revision line under the author line sets the same attributes
+        let doc = AsciiDocParser::parse_document("= Notes\nJo Writer\nv1.0, 2020-01-01: First draft\n:icons: font\n\nText.\n").unwrap();
+        assert_eq!(doc.revisions(), vec![revision(Some("1.0"), Some("2020-01-01"), Some("First draft"))]);
+        assert_eq!((doc.attribute("revnumber"), doc.attribute("revdate"), doc.attribute("icons")), (Some("1.0"), Some("2020-01-01"), Some("font")));
+        assert!(doc.to_html().contains("<span id=\"revnumber\">version 1.0,</span>\n"));
+        let doc = AsciiDocParser::parse_document("= Notes\nJo Writer\n2020-01-01\n\nText.\n").unwrap();
+        assert_eq!(doc.revisions(), vec![revision(None, Some("2020-01-01"), None)]);
+    }
+
+    #[test]
+    fn test_glossary_terms() {
+        let input = "== Usage\n\nCall the term:[api] or a term:[Widget].\n\n[glossary]\n== Glossary\n\nAPI:: The public interface.\n\n=== More\n\nCLI:: The command line.\n\n== After\n\nPlain:: Not a glossary.\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let html = doc.to_html();
+        assert!(html.contains("Call the <a class=\"term\" href=\"#_glossary_api\">api</a> or a <span class=\"term\">Widget</span>."));
+        assert!(html.contains("<dl class=\"glossary\">\n<dt id=\"_glossary_api\">API</dt>\n<dd>The public interface.</dd>\n</dl>"));
+        assert!(html.contains("<dt id=\"_glossary_cli\">CLI</dt>"));
+        assert!(html.contains("<dl>\n<dt>Plain</dt>"));
+        assert!(doc.to_asciidoc().contains("Call the term:[api] or a term:[Widget]."));
+        
+        let mut catalog = catalog::Catalog::new();
+        catalog.add_document("guide.adoc", &doc);
+        let terms: Vec<&str> = catalog.glossary().iter().map(|entry| entry.term.as_str()).collect();
+        assert_eq!(terms, ["API", "CLI"]);
+        let entry = catalog.glossary_term("cli").unwrap();
+        assert_eq!((entry.id.as_str(), entry.definition.as_deref()), ("_glossary_cli", Some("The command line.")));
+        assert_eq!(catalog.get("guide.adoc", "_glossary_api").unwrap().title.as_deref(), Some("API"));
+    }
+
+    #[test]
+    fn test_image_blocks() {
+        let input = ":imagesdir: images\n\n.Architecture\nimage::{imagesdir}/diagram.png[\"Services, queues\", 640, 480]\n\nimage::logo.svg[width=100,title=Logo]\n\nimage::plain.png[]\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        assert_eq!(doc.body[1], Block::Image {
+            path: "images/diagram.png".to_string(),
+            alt: Some("Services, queues".to_string()),
+            width: Some("640".to_string()),
+            height: Some("480".to_string()),
+            title: Some("Architecture".to_string()),
+        });
+        assert_eq!(doc.body[2], Block::Image { path: "logo.svg".to_string(), alt: None, width: Some("100".to_string()), height: None, title: Some("Logo".to_string()) });
+        assert_eq!(doc.body[3].to_html(), "<div class=\"imageblock\">\n<div class=\"content\">\n<img src=\"plain.png\" alt=\"plain\">\n</div>\n</div>\n");
+        assert_eq!(doc.body[1].to_html(), "<div class=\"imageblock\">\n<div class=\"content\">\n<img src=\"images/diagram.png\" alt=\"Services, queues\" width=\"640\" height=\"480\">\n</div>\n<div class=\"title\">Architecture</div>\n</div>\n");
+        
+        // Sizes in the macro win over what the sizer measures
+        let sizer = |_: &str| Some((1, 1));
+        let options = HtmlOptions { image_sizer: Some(std::sync::Arc::new(sizer)), ..Default::default() };
+        assert!(doc.body[2].to_html_with(&options).contains("<img src=\"logo.svg\" alt=\"logo\" width=\"100\">"));
+        
+        let printed = doc.to_asciidoc();
+        assert!(printed.contains(".Architecture\nimage::images/diagram.png[\"Services, queues\",640,480]\n"));
+        assert_eq!(AsciiDocParser::parse_document(&printed).unwrap().body, doc.body);
+        assert!(doc.to_json().contains("{\"type\":\"image\",\"path\":\"plain.png\",\"alt\":null,\"width\":null,\"height\":null,\"title\":null}"));
+        
+        // A path can't break out of `\includegraphics` to run commands
+        let doc = AsciiDocParser::parse_document("image::x}\\input{/etc/passwd[]\n\nSee image:y}\\input{z[].\n").unwrap();
+        let latex = doc.to_latex();
+        assert!(!latex.contains("\\input{"), "{}", latex);
+        assert!(latex.contains("\\texttt{x\\}\\textbackslash{}input\\{/etc/passwd}"), "{}", latex);
+        assert!(AsciiDocParser::parse_document("image::a%b.png[]").unwrap().to_latex().contains("\\includegraphics{a\\%b.png}"));
+    }
+
+    #[test]
+    fn test_latex_escapes_listings_and_labels() {
+        // A listing can't close `verbatim` early and run what follows
+        let doc = AsciiDocParser::parse_document("----\n\\end{verbatim}\n\\input{/etc/hostname}\n\\begin{verbatim}\n----\n").unwrap();
+        let latex = doc.to_latex();
+        assert!(!latex.contains("\\input{"), "{}", latex);
+        assert!(latex.contains("\\begin{flushleft}\\ttfamily\n\\textbackslash{}end\\{verbatim\\}\\\\\n\\textbackslash{}input\\{/etc/hostname\\}\\\\\n"), "{}", latex);
+        let doc = AsciiDocParser::parse_document("----\nlet x = 1;\n----\n").unwrap();
+        assert!(doc.to_latex().contains("\\begin{verbatim}\nlet x = 1;\n\\end{verbatim}\n"));
+        
+        // Ids can't end the argument of `\label` or `\hyperref`, and both ends agree
+        let doc = AsciiDocParser::parse_document("[[a}b]]\n== Section\n\nSee <<a}b,there>> and <<x]z,go>>.\n").unwrap();
+        let latex = doc.to_latex();
+        assert!(latex.contains("\\label{a:7db}\n"), "{}", latex);
+        assert!(latex.contains("\\hyperref[a:7db]{there}"), "{}", latex);
+        assert!(latex.contains("\\hyperref[x:5dz]{go}"), "{}", latex);
+    }
+
+    #[test]
+    fn test_image_size_and_loading() {
+        let doc = AsciiDocParser::parse_document("A image:logo.png[Logo] and image:https://example.com/x.png[X].").unwrap();
+        let sizer = |path: &str| (path == "logo.png").then_some((120, 40));
+        let options = HtmlOptions { lazy_loading: true, async_decoding: true, image_sizer: Some(std::sync::Arc::new(sizer)), ..Default::default() };
+        assert_eq!(doc.to_html_with(&options), "<p>A <img src=\"logo.png\" alt=\"Logo\" width=\"120\" height=\"40\" loading=\"lazy\" decoding=\"async\"> and <img src=\"https://example.com/x.png\" alt=\"X\" loading=\"lazy\" decoding=\"async\">.</p>\n");
+        assert!(doc.to_html().contains("<img src=\"logo.png\" alt=\"Logo\">"));
+
+        #[cfg(feature = "image-size")]
+        {
+            use image::probe_size;
+            
+            let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+            png.extend_from_slice(&[0, 0, 2, 0, 0, 0, 1, 0x2C]);
+            assert_eq!(probe_size(&png), Some((512, 300)));
+            
+            // An APP0 segment before the baseline frame
+            let jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0, 4, 0, 0, 0xFF, 0xC0, 0, 11, 8, 0, 50, 0, 80, 3];
+            assert_eq!(probe_size(&jpeg), Some((80, 50)));
+            
+            assert_eq!(probe_size(b"<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"64px\" height='32'>"), Some((64, 32)));
+            assert_eq!(probe_size(b"<?xml version=\"1.0\"?>\n<svg viewBox=\"0 0 100.5 20\">"), Some((101, 20)));
+            assert_eq!(probe_size(b"<svg width=\"50%\">"), None);
         }
     }
 
     #[test]
-    fn test_unordered_list() {
-        let input = r#"= Document
+    fn test_standalone_social_metadata() {
+        let doc = AsciiDocParser::parse_document("= Launch\n:description: What is new.\n\nText.\n").unwrap();
+        let options = HtmlOptions { standalone: true, ..Default::default() };
+        let html = doc.to_html_with(&options);
+        assert!(html.starts_with("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Launch</title>\n<meta name=\"description\" content=\"What is new.\">\n<meta property=\"og:type\" content=\"article\">\n<meta property=\"og:title\" content=\"Launch\">\n"));
+        assert!(html.contains("<meta name=\"twitter:card\" content=\"summary\">\n"));
+        assert!(html.contains("<p>Text.</p>\n<div id=\"footer\">") && html.ends_with("</div>\n</body>\n</html>\n"));
+        
+        let mut streamed = Vec::new();
+        doc.write_html_with(&mut streamed, &options).unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), html);
+        
+        // Without a description or image there is nothing to unfurl
+        let doc = AsciiDocParser::parse_document("= Plain\n\nText.\n").unwrap();
+        assert!(!doc.to_html_with(&options).contains("og:"));
+        assert!(!doc.to_html().contains("<head>"));
+        
+        // Sanitizing vets the image's scheme like any other URL
+        let doc = AsciiDocParser::parse_document("= Launch\n:og-image: javascript:alert(1)\n\nText.\n").unwrap();
+        let sanitized = HtmlOptions { standalone: true, ..HtmlOptions::untrusted() };
+        assert!(!doc.to_html_with(&sanitized).contains("javascript:"));
+        assert!(doc.to_html_with(&options).contains("<meta property=\"og:image\" content=\"javascript:alert(1)\">\n"));
+        let doc = AsciiDocParser::parse_document("= Launch\n:og-image: https://example.com/card.png\n\nText.\n").unwrap();
+        assert!(doc.to_html_with(&sanitized).contains("<meta name=\"twitter:image\" content=\"https://example.com/card.png\">\n"));
+    }
 
-* Item 1
-* Item 2
-** Nested item"#;
+    #[test]
+    fn test_site_index_and_sitemap() {
+        let mut workspace = Workspace::new();
+        workspace.add_document("guide/setup.adoc", "= Setup & Install\n\nText.");
+        workspace.add_document("notes.adoc", "No header.");
+        let pages = workspace.parse().unwrap().pages();
+        assert_eq!(pages.iter().map(|page| (page.path.as_str(), page.title.as_str())).collect::<Vec<_>>(), [
+            ("guide/setup.html", "Setup & Install"),
+            ("notes.html", "notes.adoc"),
+        ]);
+        
+        let index = site::index_html("Docs", &pages);
+        assert!(index.contains("<ul class=\"site-index\">\n<li>guide\n<ul>\n<li><a href=\"guide/setup.html\">Setup &amp; Install</a></li>\n</ul>\n</li>\n<li><a href=\"notes.html\">notes.adoc</a></li>\n</ul>\n"));
+        assert_eq!(site::sitemap_xml("https://example.com/docs/", &pages), "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n<url><loc>https://example.com/docs/guide/setup.html</loc></url>\n<url><loc>https://example.com/docs/notes.html</loc></url>\n</urlset>\n");
+        assert_eq!(site::output_path("README.md"), "README.html");
+    }
+
+    #[test]
+    fn test_navigation_from_manifest() {
+        let manifest = site::Manifest::parse("# Reading order\nindex.adoc\nGuides\n  guide/setup.adoc\n    guide/usage.adoc\nmissing.adoc\n");
+        assert_eq!(manifest.documents(), ["index.adoc", "guide/setup.adoc", "guide/usage.adoc", "missing.adoc"]);
+        assert_eq!(manifest.entries[1].title.as_deref(), Some("Guides"));
+        
+        let mut workspace = Workspace::new();
+        workspace.add_document("index.adoc", "= Home\n\nText.");
+        workspace.add_document("guide/setup.adoc", "= Setup\n\nText.");
+        workspace.add_document("guide/usage.adoc", "= Usage\n\nText.");
+        let set = workspace.parse().unwrap();
+        let navigation = set.navigation(Some(&manifest));
         
-        let result = AsciiDocParser::parse_document(input);
-        assert!(result.is_ok());
+        let usage = &navigation["guide/usage.adoc"];
+        let link = |title: &str, href: Option<&str>| context::NavLink { title: title.to_string(), href: href.map(str::to_string) };
+        assert_eq!(usage.previous, Some(link("Setup", Some("setup.html"))));
+        assert_eq!(usage.next, None);
+        assert_eq!(usage.breadcrumbs, [link("Guides", None), link("Setup", Some("setup.html"))]);
+        assert_eq!(navigation["index.adoc"].next, Some(link("Setup", Some("guide/setup.html"))));
+        assert_eq!(navigation["guide/setup.adoc"].previous, Some(link("Home", Some("../index.html"))));
+        
+        let options = HtmlOptions { navigation: usage.clone(), ..Default::default() };
+        let html = set.get("guide/usage.adoc").unwrap().to_html_with(&options);
+        assert!(html.contains("<nav class=\"breadcrumbs\">\n<ol>\n<li>Guides</li>\n<li><a href=\"setup.html\">Setup</a></li>\n</ol>\n</nav>\n"));
+        assert!(html.contains("<nav class=\"page-nav\">\n<a rel=\"prev\" href=\"setup.html\">Setup</a>\n</nav>\n"));
+        assert!(!set.get("guide/usage.adoc").unwrap().to_html().contains("<nav"));
+        
+        let chunks = set.get("index.adoc").unwrap().to_chunked_html_with(1, &HtmlOptions { navigation: navigation["index.adoc"].clone(), ..Default::default() });
+        assert!(chunks.last().unwrap().html.contains("<a rel=\"next\" href=\"guide/setup.html\">Setup</a>"));
+        assert_eq!(site::relative_path("a/b/c.html", "a/d.html"), "../d.html");
+    }
+
+    #[test]
+    fn test_nav_document_sidebar() {
+        let manifest = site::Manifest::parse_nav(".Guides\n* xref:index.adoc[]\n** xref:guide/setup.adoc#install[Setting up]\n* Reference\n** xref:missing.adoc[]\n").unwrap();
+        assert_eq!(manifest.documents(), ["index.adoc", "guide/setup.adoc", "missing.adoc"]);
+        assert_eq!(manifest.entries.len(), 1);
+        assert_eq!(manifest.entries[0].title.as_deref(), Some("Guides"));
+        assert_eq!(manifest.entries[0].children[1].title.as_deref(), Some("Reference"));
+        
+        let mut workspace = Workspace::new();
+        workspace.add_document("index.adoc", "= Home\n\nText.");
+        workspace.add_document("guide/setup.adoc", "= Setup\n\nText.");
+        let set = workspace.parse().unwrap();
+        let navigation = set.navigation(Some(&manifest));
+        let setup = &navigation["guide/setup.adoc"];
+        assert_eq!(setup.breadcrumbs.iter().map(|link| link.title.as_str()).collect::<Vec<_>>(), ["Guides", "Home"]);
+        
+        let options = HtmlOptions { navigation: setup.clone(), ..Default::default() };
+        let html = set.get("guide/setup.adoc").unwrap().to_html_with(&options);
+        assert!(html.starts_with("<nav class=\"sidebar\">\n<ul>\n<li><span>Guides</span>\n<ul>\n<li><a href=\"../index.html\">Home</a>\n<ul>\n<li class=\"current\"><a href=\"setup.html\" aria-current=\"page\">Setting up</a></li>\n</ul>\n</li>\n<li><span>Reference</span>\n<ul>\n<li><span>missing.adoc</span></li>\n</ul>\n</li>\n</ul>\n</li>\n</ul>\n</nav>\n"));
+        
+        let json = set.get("guide/setup.adoc").unwrap().render_context_with(&options).to_json();
+        assert!(json.contains("{\"title\": \"Setting up\", \"href\": \"setup.html\", \"current\": true, \"children\": []}"));
+    }
+
+    #[test]
+    fn test_quote_attribution() {
+        let doc = AsciiDocParser::parse_document(".Motto\n[quote, Anon, Proverbs]\nMany hands.\n\n\"Quoted.\"\n-- Jo\n\n> Plain.").unwrap();
+        let attribution = |block: &Block| match block {
+            Block::DelimitedBlock { kind: DelimitedBlockKind::Quote(attribution), content, .. } => (attribution.author.clone(), attribution.citation.clone(), content.clone()),
+            _ => panic!("Expected quote block, got {:?}", block),
+        };
+        assert_eq!(doc.body.len(), 4);
+        assert_eq!(attribution(&doc.body[1]), (Some("Anon".to_string()), Some("Proverbs".to_string()), "Many hands.\n".to_string()));
+        assert_eq!(attribution(&doc.body[2]), (Some("Jo".to_string()), None, "Quoted.\n".to_string()));
+        assert_eq!(attribution(&doc.body[3]), (None, None, "Plain.\n".to_string()));
+        assert!(doc.to_json().contains("\"kind\":\"quote\",\"author\":\"Jo\",\"citation\":null"));
+    }
+
+    #[test]
+    fn test_literal_blocks() {
+        let doc = AsciiDocParser::parse_document("Text.\n\n  indented *line*\n    deeper\n\n......\n  {kept}\n....\n......\n\n====\n....\n====\n....\n====").unwrap();
+        let literal = |content: &str| Block::DelimitedBlock { kind: DelimitedBlockKind::Literal, content: content.to_string(), language: None, title: None };
+        assert_eq!(doc.body[1..], [
+            literal("indented *line*\n  deeper\n"),
+            literal("  {kept}\n....\n"),
+            Block::DelimitedBlock { kind: DelimitedBlockKind::Example, content: "....\n====\n....\n".to_string(), language: None, title: None },
+        ]);
+        assert_eq!(AsciiDocParser::parse_document(&doc.to_asciidoc()).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_sidebar_blocks() {
+        let doc = AsciiDocParser::parse_document(".Tips\n[.tip.wide]\n****\nFirst *point*.\n\n* one\n* two\n****").unwrap();
+        let Block::Sidebar { title, roles, blocks } = &doc.body[0] else { panic!("Expected sidebar") };
+        assert_eq!(doc.body.len(), 1);
+        assert_eq!(title.as_deref(), Some("Tips"));
+        assert_eq!(roles, &["tip", "wide"]);
+        assert!(matches!(blocks[..], [Block::Paragraph { .. }, Block::List { kind: ListKind::Unordered, .. }]));
         
-        let doc = result.unwrap();
+        let html = doc.to_html();
+        assert!(html.contains("<aside class=\"tip wide\">\n<div class=\"title\">Tips</div>\n<p>First <strong>point</strong>.</p>\n<ul>"));
+        assert_eq!(AsciiDocParser::parse_document(&doc.to_asciidoc()).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_tables() {
+        let doc = AsciiDocParser::parse_document(".Prices\n|===\n| Name | Price\n\n| *tea* | 2\n| a \\| b\n| 3\n|===").unwrap();
         assert_eq!(doc.body.len(), 1);
+        let Block::Table { title, header, rows } = &doc.body[0] else { panic!("Expected table") };
+        assert_eq!(title.as_deref(), Some("Prices"));
+        assert!(*header);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2][0].content, vec![InlineElement::Text("a | b".to_string())]);
+        
+        let html = doc.to_html();
+        assert!(html.contains("<table>\n<caption>Prices</caption>\n<thead>\n<tr>\n<th>Name</th>\n<th>Price</th>\n</tr>\n</thead>\n<tbody>\n<tr>\n<td><strong>tea</strong></td>"));
+        assert_eq!(AsciiDocParser::parse_document(&doc.to_asciidoc()).unwrap(), doc);
+        
+        // Columns from the attribute list, with cells written one to a line
+        let doc = AsciiDocParser::parse_document("[cols=\"2*\"]\n|===\n| a\n| b\n\n| c\n| d\n|===").unwrap();
+        let Block::Table { header, rows, .. } = &doc.body[1] else { panic!("Expected table") };
+        assert!(!*header);
+        assert_eq!(rows.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2]);
+        let doc = AsciiDocParser::parse_document("[%noheader]\n|===\n| a | b\n\n| c | d\n|===").unwrap();
+        assert!(matches!(doc.body[1], Block::Table { header: false, .. }));
+        
+        // A table left open runs to the end, and only a whole `|===` line delimits one
+        let doc = AsciiDocParser::parse_document("|===\n|a\n").unwrap();
+        assert!(matches!(&doc.body[..], [Block::Table { rows, .. }] if rows.len() == 1));
+        let doc = AsciiDocParser::parse_document("|===x\ntext").unwrap();
+        assert_eq!(doc.body, [Block::Paragraph { content: vec![InlineElement::Text("|===x".to_string()), InlineElement::Text(" ".to_string()), InlineElement::Text("text".to_string())] }]);
+    }
+
+    #[test]
+    fn test_table_cell_specs() {
+        let doc = AsciiDocParser::parse_document("|===\n| a | b | c\n2+^| wide >| right\n.2+| tall | d | e\n| f | g\n|===").unwrap();
+        let Block::Table { rows, .. } = &doc.body[0] else { panic!("Expected table") };
+        let spans: Vec<Vec<(usize, usize)>> = rows.iter().map(|row| row.iter().map(|cell| (cell.colspan, cell.rowspan)).collect()).collect();
+        assert_eq!(spans, [vec![(1, 1); 3], vec![(2, 1), (1, 1)], vec![(1, 2), (1, 1), (1, 1)], vec![(1, 1); 2]]);
+        assert_eq!(rows[1][0].align, Some(CellAlignment::Center));
+        assert_eq!(rows[1][1].align, Some(CellAlignment::Right));
         
-        if let Block::List { kind, items } = &doc.body[0] {
-            assert!(matches!(kind, ListKind::Unordered));
-            assert_eq!(items.len(), 3);
-        } else {
-            panic!("Expected list block");
+        let html = doc.to_html();
+        assert!(html.contains("<td colspan=\"2\" style=\"text-align: center\">wide</td>\n<td style=\"text-align: right\">right</td>"));
+        assert!(html.contains("<td rowspan=\"2\">tall</td>"));
+        assert_eq!(AsciiDocParser::parse_document(&doc.to_asciidoc()).unwrap(), doc);
+        
+        // Text ending in something like a spec is only one when it touches the `|`
+        let doc = AsciiDocParser::parse_document("|===\n| 2+ | a^|\n|===").unwrap();
+        let Block::Table { rows, .. } = &doc.body[0] else { panic!("Expected table") };
+        assert_eq!(plain_text(&rows[0][0].content), "2+");
+        assert_eq!(plain_text(&rows[0][1].content), "a^");
+        
+        // The space before a spec can be more than one byte long
+        let doc = AsciiDocParser::parse_document("|===\n| a\u{a0}2+| b\u{3000}>| c\n|===").unwrap();
+        let Block::Table { rows, .. } = &doc.body[0] else { panic!("Expected table") };
+        assert_eq!(rows[0].iter().map(|cell| cell.colspan).collect::<Vec<_>>(), [1, 2, 1]);
+        assert_eq!(rows[0][2].align, Some(CellAlignment::Right));
+    }
+
+    #[test]
+    fn test_block_iter() {
+        fn flatten(blocks: &[Block], flat: &mut Vec<Block>) {
+            for block in blocks {
+                match block {
+                    Block::Section { blocks, .. } => {
+                        let mut heading = block.clone();
+                        if let Block::Section { blocks, .. } = &mut heading {
+                            blocks.clear();
+                        }
+                        flat.push(heading);
+                        flatten(blocks, flat);
+                    }
+                    block => flat.push(block.clone()),
+                }
+            }
         }
+        
+        let input = "= Report\n:sectnums:\n:host: db1\n\nPreamble on {host}.\n\n== Runs\n\n:host: db2\n\n.Output\n[source,subs=attributes+]\n----\nstart {host}\n\nend\n----\n\n* one\n\n* two\n\nifdef::host[]\nHost set.\n\nStill set.\nendif::[]\n\n=== Run {counter:run}\n\n[[later]]\n== Runs\n\nRun {counter:run}.\n";
+        let options = ParseOptions::default();
+        let blocks: Vec<Block> = BlockIter::new(input.as_bytes(), options.clone()).collect::<std::io::Result<_>>().unwrap();
+        let doc = AsciiDocParser::parse_document_with_options(input, &options).unwrap();
+        let mut expected = Vec::new();
+        flatten(&doc.body, &mut expected);
+        assert_eq!(blocks, expected);
+        assert!(matches!(&blocks[1], Block::Section { number: Some(number), blocks, .. } if number == "1." && blocks.is_empty()));
+        
+        let mut iter = BlockIter::new("= Log\n:level: info\n\nFirst.\n".as_bytes(), options);
+        let header = iter.header().unwrap().cloned().unwrap();
+        assert_eq!((header.title.as_str(), header.attribute("level")), ("Log", Some("info")));
+        assert_eq!(iter.count(), 1);
     }
 
     #[test]
-    fn test_formatted_text() {
-        let input = r#"= Document
+    fn test_parse_header_only() {
+        let inputs = [
+            "[.landing]\n= Guide\n:author: Ada Lovelace\n:revnumber: 1.2\nifdef::backend-html5[:web:]\n\n== Intro\n\nText.\n",
+            "= Title only",
+            "= Guide\nAda Lovelace <ada@example.org>; Charles Babbage\nv1.2, 1843-09-01: Notes\n:toc:\n\nText.\n",
+            "= Guide\n:toc:\nBody right under it.\n",
+            "No header.\n\n= Not a title\n",
+        ];
+        for input in inputs {
+            assert_eq!(AsciiDocParser::parse_header_only(input), AsciiDocParser::parse_document(input).unwrap().header, "{}", input);
+        }
+        
+        let header = AsciiDocParser::parse_header_only("= Guide\nAda Lovelace <ada@example.org>\nv1.2, 1843-09-01\n").unwrap();
+        let attribute = |name| header.attribute(name);
+        assert_eq!((attribute("author"), attribute("email")), (Some("Ada Lovelace"), Some("ada@example.org")));
+        assert_eq!((attribute("revnumber"), attribute("revdate")), (Some("1.2"), Some("1843-09-01")));
+        
+        // Nothing past the header is read, not even a block left open
+        let header = AsciiDocParser::parse_header_only("= Notes\n:icons: font\n\n----\nnever closed\n").unwrap();
+        assert_eq!((header.title.as_str(), header.attribute("icons")), ("Notes", Some("font")));
+        
+        let options = ParseOptions { attributes: vec![Attribute { name: Symbol::new("icons"), value: Some("image".to_string()) }], ..Default::default() };
+        let header = AsciiDocParser::parse_header_only_with_options("= Notes\n:icons: font\n", &options).unwrap();
+        assert_eq!(header.attribute("icons"), Some("image"));
+    }
 
-This is *bold* and _italic_ text."#;
+    #[test]
+    fn test_document_roles() {
+        let doc = AsciiDocParser::parse_document("[.landing.wide]\n= Home\n:docrole: dark landing\n\nText.").unwrap();
+        assert_eq!(doc.roles(), ["landing", "wide", "dark"]);
+        assert_eq!(doc.render_context().body_class, "article landing wide dark");
+        let options = HtmlOptions { standalone: true, ..HtmlOptions::default() };
+        assert!(doc.to_html_with(&options).contains("<body class=\"article landing wide dark\">\n"));
+        assert_eq!(AsciiDocParser::parse_document(&doc.to_asciidoc()).unwrap(), doc);
         
-        let result = AsciiDocParser::parse_document(input);
-        assert!(result.is_ok());
+        let doc = AsciiDocParser::parse_document("[.lead]\nNo title.").unwrap();
+        assert!(doc.header.is_none() && doc.roles().is_empty());
+        let book = AsciiDocParser::parse_document("= Book\n:doctype: book\n").unwrap();
+        assert!(book.to_html_with(&options).contains("<body class=\"book\">"));
+    }
+
+    #[test]
+    fn test_attribute_table() {
+        let input = "= Guide\n:product: Widget\n:version: 2\n\n:title: {product} {version}\n\nSee {title}.\n\n:product!:\n\nNo {product}.";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let table = doc.attribute_table(input);
+        let rows: Vec<(&str, Option<&str>, bool, usize)> = table.iter()
+            .map(|definition| (definition.name.as_str(), definition.computed.as_deref(), definition.unset, definition.span.start.line))
+            .collect();
+        assert_eq!(rows, [
+            ("product", Some("Widget"), false, 2),
+            ("version", Some("2"), false, 3),
+            ("title", Some("Widget 2"), false, 5),
+            ("product", None, true, 9),
+        ]);
+        assert_eq!(table[2].value.as_deref(), Some("{product} {version}"));
+        assert_eq!(table[2].span.end, position::Position { line: 5, column: 28 });
         
-        let doc = result.unwrap();
-        assert_eq!(doc.body.len(), 1);
+        let definition = doc.attribute_definition_at(input, 7, 7).unwrap();
+        assert_eq!((definition.name.as_str(), definition.span.start.line), ("title", 5));
+        assert_eq!(doc.attribute_definition_at(input, 11, 6), None);
+        assert_eq!(doc.attribute_definition_at(input, 7, 1), None);
+    }
+
+    #[test]
+    fn test_conditional_blocks() {
+        let input = "= Doc\n\nifdef::backend-html5[]\nHTML.\nendif::[]\n\nifdef::backend-latex,backend-pdf[]\nPrint.\nendif::[]\n\nifndef::profile-draft[Final.]\n\nifdef::doctype-book[Book.]\n:doctype: book\n\nifdef::doctype-book[Book now.]\n";
+        let text = |options: &ParseOptions| {
+            let doc = AsciiDocParser::parse_document_with_options(input, options).unwrap();
+            doc.body.iter()
+                .filter_map(|block| match block {
+                    Block::Paragraph { content } => Some(plain_text(content)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(text(&ParseOptions::default()), ["HTML.", "Final.", "Book now."]);
+        let latex = ParseOptions { backend: Some("latex".to_string()), ..Default::default() };
+        assert_eq!(text(&latex), ["Print.", "Final.", "Book now."]);
+        let draft = ParseOptions { attributes: vec![Attribute { name: Symbol::from("profile-draft"), value: None }], ..Default::default() };
+        assert_eq!(text(&draft), ["HTML.", "Book now."]);
+    }
+
+    #[test]
+    fn test_substitution_toggles() {
+        let line = "Don't -- wait... (C) -> \"`quoted *twice*`\" with `a -- b`, \\(C) and --verbose at <<intro>> or https://example.com\n";
+        let doc = AsciiDocParser::parse_document(line).unwrap();
+        assert_eq!(doc.to_html(), "<p>Don\u{2019}t \u{2014} wait\u{2026} \u{a9} \u{2192} \u{201c}quoted <strong>twice</strong>\u{201d} with <code>a -- b</code>, (C) and --verbose at <a href=\"#intro\">intro</a> or <a href=\"https://example.com\">https://example.com</a></p>\n");
+        
+        let off = AsciiDocParser::parse_document(&format!(":no-replacements:\n:no-smart-quotes:\n:no-macros:\n\n{}\n:no-macros!:\n\n<<intro>>", line)).unwrap();
+        let html = off.to_html();
+        assert!(html.starts_with("<p>Don&#39;t -- wait... (C) -&gt; &quot;<code>quoted *twice*</code>&quot; with <code>a -- b</code>, \\(C) and --verbose at &lt;&lt;intro&gt;&gt; or https://example.com</p>\n"));
+        assert!(html.ends_with("<p><a href=\"#intro\">intro</a></p>\n"));
+        
+        let options = ParseOptions { substitutions: Substitutions { smart_quotes: false, ..Default::default() }, ..Default::default() };
+        let plain = AsciiDocParser::parse_document_with_options("It's \"`here`\".", &options).unwrap();
+        assert_eq!(plain.to_html(), "<p>It\u{2019}s &quot;<code>here</code>&quot;.</p>\n");
+    }
+
+    #[test]
+    fn test_attribute_cycles() {
+        let input = "= Cycles\n:full: {base}/guide\n:base: /docs\n:a: {b}\n:b: {a}\n:n: 1\n:n: {n}2\n:esc: \\{base} {lbrace}base}\n\n{full} {a} {n} {esc}\n\n:self: {self}\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        assert!(doc.to_html().ends_with("<p>/docs/guide {b} 12 {base} {base}</p>\n"));
+        
+        let table = doc.attribute_table(input);
+        assert_eq!(table[2].cycle, None);
+        assert_eq!(table[3].cycle, Some(vec!["b".to_string(), "a".to_string(), "b".to_string()]));
+        let found: Vec<(usize, String)> = diagnostics::check(input, &ParseOptions::default()).into_iter()
+            .filter(|diagnostic| diagnostic.kind == diagnostics::DiagnosticKind::AttributeCycle)
+            .map(|diagnostic| (diagnostic.line.unwrap(), diagnostic.message))
+            .collect();
+        assert_eq!(found, [
+            (5, "attribute reference cycle: b -> a -> b".to_string()),
+            (12, "attribute reference cycle: self -> self".to_string()),
+        ]);
+        
+        let chain: String = (0..12).map(|n| format!(":a{}: {{a{}}}\n", n, n + 1)).collect();
+        let deep = format!("{}:a12: end\n\n{{a0}}", chain);
+        assert_eq!(AsciiDocParser::parse_document(&deep).unwrap().to_html(), "<p>{a9}</p>\n");
+        let diagnostics = diagnostics::check(&deep, &ParseOptions::default());
+        assert_eq!((diagnostics[0].line, diagnostics[0].message.as_str()), (Some(10), "attribute references nested too deeply: a0 -> a1 -> a2 -> a3 -> a4 -> a5 -> a6 -> a7 -> a8 -> a9"));
+    }
+
+    #[test]
+    fn test_attribute_references() {
+        let input = "= Guide\n:product: Widget\n:version: 2\n:repo: https://example.com/widget\n:release: {product} {version}\n\n== Installing {product}\n\n.{release}.txt\n----\nnotes\n----\n\nSee {repo}[the repository] for {release}, not \\{product} or {missing}.\n\n:product!:\n\nNo {product}.";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let html = doc.to_html();
+        assert!(html.contains("<h2 id=\"_installing_widget\">Installing Widget</h2>"));
+        assert!(html.contains("<span class=\"filename\">Widget 2.txt</span>"));
+        assert!(html.contains("See <a href=\"https://example.com/widget\">the repository</a> for Widget 2, not {product} or {missing}."));
+        assert!(html.contains("<p>No {product}.</p>"));
+        
+        let drop = AsciiDocParser::parse_document("= Guide\n:attribute-missing: drop\n\nKept {missing} here.").unwrap();
+        assert!(drop.to_html().contains("<p>Kept  here.</p>"));
+        let drop_line = AsciiDocParser::parse_document("= Guide\n:attribute-missing: drop-line\n\nKept\n{missing} here\nas well.").unwrap();
+        assert!(drop_line.to_html().contains("<p>Kept as well.</p>"));
+    }
+
+    #[test]
+    fn test_counters() {
+        let input = "= Guide\n:step: 4\n\n.Step {counter:example}\n----\nmake\n----\n\nStep {counter:step}, then {counter:step}{counter2:step}; now {step}.\n\n[[part-{counter:part:A}]]\n== Part {part}\n\n* Step {counter:example}, not \\{counter:example}\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let html = doc.to_html();
+        assert!(html.contains("<span class=\"filename\">Step 1</span>"));
+        assert!(html.contains("<p>Step 5, then 6; now 7.</p>"));
+        assert!(html.contains("<h2 id=\"part-A\">Part A</h2>"));
+        assert!(html.contains("<li>Step 2, not {counter:example}</li>"));
+        
+        let reset = AsciiDocParser::parse_document("{counter:n} {counter:n}\n\n:n: 10\n\n{counter:n}").unwrap();
+        assert!(reset.to_html().ends_with("<p>1 2</p>\n<p>11</p>\n"));
+    }
+
+    #[test]
+    fn test_intrinsic_attributes() {
+        let options = ParseOptions {
+            docfile: Some("guide/setup.adoc".to_string()),
+            backend: Some("latex".to_string()),
+            now: Some(1_700_000_000),
+            ..Default::default()
+        };
+        let input = "= Setup\n\n{docname} in {docdir}, written to {docname}{outfilesuffix} on {localdate} at {localtime}.\n\n* \\{docfile} {docfile}\n\nifdef::docname[Named.]\n";
+        let doc = AsciiDocParser::parse_document_with_options(input, &options).unwrap();
+        let html = doc.to_html();
+        assert!(html.contains("<p>setup in guide, written to setup.tex on 2023-11-14 at 22:13:20 +0000.</p>"));
+        assert!(html.contains("<li>{docfile} guide/setup.adoc</li>"));
+        assert!(html.contains("<p>Named.</p>"));
         
-        if let Block::Paragraph { content } = &doc.body[0] {
-            assert!(!content.is_empty());
-        } else {
-            panic!("Expected paragraph block");
+        let header = AsciiDocParser::parse_document_with_options("= Setup\n:docname: intro\n\n{docname}", &options).unwrap();
+        assert!(header.to_html().contains("<p>intro</p>"));
+        assert!(AsciiDocParser::parse_document("{docname}").unwrap().to_html().contains("{docname}"));
+    }
+
+    #[test]
+    fn test_reproducible_output() {
+        let input = "= Notes\n:revnumber: 1.0\n:docdatetime: 2024-01-15 10:20:30 +0000\n:reproducible:\n\nOn {localdate}.";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let html = doc.to_html();
+        assert!(html.contains("Version 1.0") && !html.contains("Last updated"));
+        if std::env::var_os("SOURCE_DATE_EPOCH").is_none() {
+            assert!(html.contains("<p>On {localdate}.</p>"));
         }
+        
+        let pinned = ParseOptions { now: Some(0), ..Default::default() };
+        let doc = AsciiDocParser::parse_document_with_options(input, &pinned).unwrap();
+        assert!(doc.to_html().contains("<p>On 1970-01-01.</p>"));
+        
+        let mut doc = AsciiDocParser::parse_document("= Notes\n:reproducible:\n").unwrap();
+        doc.set_last_updated(std::time::SystemTime::now());
+        assert_eq!(doc.attribute("docdatetime"), None);
     }
 
     #[test]
-    fn test_link_macro() {
-        let input = r#"= Document
+    fn test_footer_customization() {
+        let input = "= Notes\n:revnumber: 2.1\n:version-label: Release\n:docdatetime: 2024-01-15 10:20:30 +0000\n:footer-text: Licensed under CC BY 4.0\n\nBody.";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        assert!(doc.to_html().contains("<div id=\"footer-text\">\nRelease 2.1<br>\nLast updated 2024-01-15 10:20:30 +0000<br>\nLicensed under CC BY 4.0\n</div>"));
+        
+        let standalone = HtmlOptions { standalone: true, ..HtmlOptions::default() };
+        let html = doc.to_html_with(&standalone);
+        assert!(html.contains(&format!("Licensed under CC BY 4.0<br>\nGenerated by adoc {}\n</div>", env!("CARGO_PKG_VERSION"))));
+        let bare = AsciiDocParser::parse_document("= Notes\n\nBody.").unwrap();
+        assert!(bare.to_html_with(&standalone).contains("<div id=\"footer-text\">\nGenerated by adoc "));
+        assert!(!bare.to_html().contains("footer"));
+        
+        let templated = HtmlOptions { footer_template: Some("<span>{revnumber}</span> via {generator} {unknown}".to_string()), ..HtmlOptions::default() };
+        assert!(doc.to_html_with(&templated).contains(&format!("<div id=\"footer-text\">\n<span>2.1</span> via adoc {} {{unknown}}\n</div>", env!("CARGO_PKG_VERSION"))));
+    }
 
-Visit link:https://example.com[Example Site] for more info."#;
+    #[test]
+    fn test_stylesheet_themes() {
+        use crate::theme::Theme;
         
-        let result = AsciiDocParser::parse_document(input);
-        assert!(result.is_ok());
+        let standalone = HtmlOptions { standalone: true, ..HtmlOptions::default() };
+        let html = AsciiDocParser::parse_document("= Guide\n\nText.").unwrap().to_html_with(&standalone);
+        assert!(html.contains(&format!("<style>\n{}</style>\n</head>", Theme::Default.css())));
         
-        let doc = result.unwrap();
-        assert_eq!(doc.body.len(), 1);
+        let dark = AsciiDocParser::parse_document("= Guide\n:stylesheet: dark\n\nText.").unwrap();
+        assert!(dark.to_html_with(&standalone).contains(Theme::Dark.css()));
+        let print = HtmlOptions { stylesheet: Some("print".to_string()), ..standalone.clone() };
+        assert!(dark.to_html_with(&print).contains(Theme::Print.css()));
+        assert_eq!(dark.theme(&print), Some((Theme::Print, "adoc-print.css".to_string())));
+        
+        let linked = AsciiDocParser::parse_document("= Guide\n:stylesheet: dark\n:linkcss:\n:stylesdir: css/\n\nText.").unwrap();
+        let html = linked.to_html_with(&standalone);
+        assert!(html.contains("<link rel=\"stylesheet\" href=\"css/adoc-dark.css\">\n</head>") && !html.contains("<style>"));
+        assert_eq!(linked.theme(&standalone), Some((Theme::Dark, "css/adoc-dark.css".to_string())));
+        
+        let custom = AsciiDocParser::parse_document("= Guide\n:stylesheet: site.css\n\nText.").unwrap();
+        assert!(custom.to_html_with(&standalone).contains("<link rel=\"stylesheet\" href=\"site.css\">\n"));
+        assert_eq!(custom.theme(&standalone), None);
+        let unstyled = AsciiDocParser::parse_document("= Guide\n:stylesheet: none\n\nText.").unwrap();
+        assert!(!unstyled.to_html_with(&standalone).contains("stylesheet") && !unstyled.to_html_with(&standalone).contains("<style>"));
+        
+        // Sanitizing vets a linked stylesheet's scheme like any other URL
+        let hostile = AsciiDocParser::parse_document("= Guide\n:stylesheet: javascript:alert(1)\n\nText.").unwrap();
+        let sanitized = HtmlOptions { standalone: true, ..HtmlOptions::untrusted() };
+        assert!(!hostile.to_html_with(&sanitized).contains("javascript:"));
+        assert!(custom.to_html_with(&sanitized).contains("<link rel=\"stylesheet\" href=\"site.css\">\n"));
+        
+        // Chunked pages share one copy of the theme
+        let chunks = dark.to_chunked_html(1);
+        assert!(chunks[0].html.contains("<link rel=\"stylesheet\" href=\"adoc-dark.css\">\n"));
+        assert!(!dark.to_html().contains("<style>"));
     }
 
     #[test]
-    fn test_description_list() {
-        let input = r#"= Document
+    fn test_print_output() {
+        use crate::context::{NavLink, PageNavigation};
+        use crate::theme::Theme;
+        
+        let input = "= Handout\n\nSee https://example.com/guide[the guide], https://example.com/ and <<_next>>.\n\n<<<\n\n== Next\n\nText.";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        assert!(matches!(doc.body[1], Block::PageBreak));
+        
+        let screen = doc.to_html();
+        assert!(screen.contains("<div style=\"break-after: page\"></div>\n<h2 id=\"_next\">"));
+        assert!(!screen.contains("(https://example.com/guide)"));
+        
+        let navigation = PageNavigation { next: Some(NavLink { title: "After".to_string(), href: Some("after.html".to_string()) }), ..Default::default() };
+        let print = HtmlOptions { print: true, standalone: true, navigation, ..HtmlOptions::default() };
+        let html = doc.to_html_with(&print);
+        assert!(html.contains("<a href=\"https://example.com/guide\">the guide</a> <span class=\"url\">(https://example.com/guide)</span>"));
+        assert!(html.contains("<a href=\"https://example.com/\">https://example.com/</a> and "));
+        assert!(html.contains("<a href=\"#_next\">") && !html.contains("(#_next)"));
+        assert!(html.contains(Theme::Print.css()) && !html.contains("after.html"));
+    }
 
-Term 1:: Definition 1
-Term 2:: Definition 2"#;
+    #[test]
+    fn test_inline_passthrough_policy() {
+        let doc = AsciiDocParser::parse_document("Stop+++<br>+++here and pass:[<b onclick=\"x()\">bold</b>] text.").unwrap();
+        let Block::Paragraph { content } = &doc.body[0] else { panic!("Expected paragraph") };
+        assert_eq!(content[1], InlineElement::Passthrough("<br>".to_string()));
         
-        let result = AsciiDocParser::parse_document(input);
-        assert!(result.is_ok());
+        assert_eq!(doc.to_html(), "<p>Stop<br>here and <b onclick=\"x()\">bold</b> text.</p>\n");
+        let escaped = HtmlOptions { passthrough: PassthroughPolicy::Escape, ..HtmlOptions::default() };
+        assert_eq!(doc.to_html_with(&escaped), "<p>Stop&lt;br&gt;here and &lt;b onclick=&quot;x()&quot;&gt;bold&lt;/b&gt; text.</p>\n");
+        let stripped = HtmlOptions { passthrough: PassthroughPolicy::Strip, ..HtmlOptions::default() };
+        assert_eq!(doc.to_html_with(&stripped), "<p>Stophere and bold text.</p>\n");
         
-        let doc = result.unwrap();
-        assert_eq!(doc.body.len(), 1);
+        // Sanitizing never writes raw HTML, whatever the policy says
+        assert_eq!(doc.to_html_with(&HtmlOptions::untrusted()), doc.to_html_with(&escaped));
+        let verbatim = HtmlOptions { passthrough: PassthroughPolicy::Verbatim, ..HtmlOptions::untrusted() };
+        assert_eq!(doc.to_html_with(&verbatim), doc.to_html_with(&escaped));
+        assert_eq!(plain_text(content), "Stophere and bold text.");
+    }
+
+    #[test]
+    fn test_section_extraction() {
+        let input = "= Manual\n:doctype: book\n:sectnums:\n\n== Intro\n\n:edition: Pro\n\nHello.\n\n== Guide\n\n=== Install\n\nRun it.\n\n==== Linux\n\nUse apt.\n\n:edition!:\n\n=== Usage\n\nUse it.\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        
+        let install = doc.extract("Guide/Install").unwrap();
+        let header = install.header.as_ref().unwrap();
+        assert_eq!(header.title, "Install");
+        assert_eq!((header.attribute("doctype"), header.attribute("edition")), (None, Some("Pro")));
+        let Block::Section { level, title, number, .. } = &install.body[1] else { panic!("Expected section") };
+        assert_eq!((*level, title.as_str(), number.as_deref()), (2, "Linux", Some("1.")));
+        assert_eq!(install.to_html(), doc.extract("_install").unwrap().to_html());
+        
+        // A chapter comes along whole, and an attribute unset before a section is left out
+        let guide = doc.extract("_guide").unwrap();
+        assert_eq!(guide.to_asciidoc().matches("\n== ").count(), 2);
+        assert!(guide.to_html().contains("<h2 id=\"_usage\">2. Usage</h2>"));
+        assert!(doc.extract("_usage").unwrap().attribute("edition").is_none());
+        assert!(doc.extract("Guide/Missing").is_none() && doc.extract("_nowhere").is_none());
+    }
+
+    #[test]
+    fn test_book_assembly() {
+        let one = AsciiDocParser::parse_document("= Getting Started\n:source-language: rust\n\n== Overview\n\nSee xref:two.adoc#_overview[] and xref:two.adoc[].").unwrap();
+        let two = AsciiDocParser::parse_document("= Reference\n:source-language: c\n\n== Overview\n\nBack to <<_overview>>.").unwrap();
+        
+        let mut book = Book::new("Manual");
+        book.set_attribute("author", Some("Jo Writer".to_string()));
+        book.add_chapter("one.adoc", one);
+        book.add_chapter("two.adoc", two);
+        let doc = book.assemble();
+        
+        let header = doc.header.as_ref().unwrap();
+        assert_eq!(header.title, "Manual");
+        assert_eq!(doc.attribute("doctype"), Some("book"));
+        assert_eq!(doc.attribute("author"), Some("Jo Writer"));
+        assert_eq!(doc.attribute("source-language"), Some("rust"));
+        
+        assert_eq!(doc.body.len(), 2);
+        let Block::Section { level, id, blocks, .. } = &doc.body[1] else { panic!("Expected chapter section") };
+        assert_eq!((*level, id.as_deref()), (2, Some("_reference")));
+        let Block::Section { level, id, .. } = &blocks[0] else { panic!("Expected nested section") };
+        assert_eq!((*level, id.as_deref()), (3, Some("_overview_2")));
+        
+        let html = doc.to_html();
+        assert!(html.contains(r##"See <a href="#_overview_2">_overview_2</a> and <a href="#_reference">_reference</a>."##));
+        assert!(html.contains(r##"Back to <a href="#_overview_2">_overview_2</a>."##));
+        
+        book.set_attribute("sectnums", None);
+        let html = book.assemble().to_html();
+        assert!(html.contains(r#"<h2 id="_reference">2. Reference</h2>"#));
+        assert!(html.contains(r#"<h3 id="_overview_2">2.1. Overview</h3>"#));
+        
+        // Anchors inside sidebars, admonitions and list items are renamed along with their xrefs
+        let chapter = |title: &str| {
+            let input = format!("= {}\n\n****\n[[tip]]\nAside.\n****\n\n[NOTE]\n====\n[[warn]]\nCareful.\n====\n\n* item\n+\n[[step]]\nMore.\n\nSee <<tip>>, <<warn>> and <<step>>.\n", title);
+            AsciiDocParser::parse_document(&input).unwrap()
+        };
+        let mut book = Book::new("Manual");
+        book.add_chapter("one.adoc", chapter("One"));
+        book.add_chapter("two.adoc", chapter("Two"));
+        let doc = book.assemble();
+        let Block::Section { blocks, .. } = &doc.body[1] else { panic!("Expected chapter section") };
+        let anchor = |blocks: &[Block]| match &blocks[0] {
+            Block::BlockMetadata { kind: BlockMetadataKind::Anchor(id) } => id.clone(),
+            block => panic!("Expected anchor, got {:?}", block),
+        };
+        let (Block::Sidebar { blocks: sidebar, .. }, Block::Admonition { blocks: note, .. }, Block::List { items, .. }) = (&blocks[0], &blocks[1], &blocks[2]) else {
+            panic!("Expected sidebar, admonition and list")
+        };
+        assert_eq!([anchor(sidebar), anchor(note), anchor(items[0].blocks())], ["tip_2", "warn_2", "step_2"]);
+        assert!(doc.to_html().contains(r##"See <a href="#tip_2">tip_2</a>, <a href="#warn_2">warn_2</a> and <a href="#step_2">step_2</a>."##));
+    }
+
+    #[test]
+    fn test_chunked_html() {
+        let input = r#"= Book
+
+Preamble text.
+
+== Chapter One
+
+See <<_chapter_two>>.
+
+=== Details
+
+More.
+
+== Chapter Two
+
+Done."#;
+        
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let chunks = doc.to_chunked_html(1);
+        let names: Vec<&str> = chunks.iter().map(|c| c.file_name.as_str()).collect();
+        assert_eq!(names, vec!["index.html", "_chapter_one.html", "_chapter_two.html"]);
+        
+        assert!(chunks[0].html.contains("<p>Preamble text.</p>"));
+        assert!(chunks[0].html.contains(r#"<li><a href="_chapter_one.html">Chapter One</a></li>"#));
+        assert!(chunks[1].html.contains(r#"<h3 id="_details">Details</h3>"#));
+        assert!(chunks[1].html.contains(r##"<a href="_chapter_two.html#_chapter_two">Chapter Two</a>"##));
+        assert!(chunks[1].html.contains(r#"<a rel="prev" href="index.html">Book</a>"#));
+        assert!(chunks[1].html.contains(r#"<a rel="next" href="_chapter_two.html">Chapter Two</a>"#));
+        assert!(!chunks[2].html.contains(r#"rel="next""#));
+        
+        let chunks = doc.to_chunked_html(2);
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[2].file_name, "_details.html");
+        assert!(chunks[2].html.contains(r#"<a rel="up" href="_chapter_one.html">Chapter One</a>"#));
+        
+        // Ids are written by the document, so they can't name a file outside the output directory
+        let doc = AsciiDocParser::parse_document("= Book\n\n[[../escaped]]\n== One\n\n[[..\\..]]\n== Two\n\n[[.index]]\n== Three\n").unwrap();
+        let names: Vec<String> = doc.to_chunked_html(1).into_iter().map(|chunk| chunk.file_name).collect();
+        assert_eq!(names, ["index.html", "_escaped.html", "_.html", "index-2.html"]);
+        let dir = std::path::Path::new("/tmp/chunk/out");
+        assert!(names.iter().all(|name| dir.join(name).parent() == Some(dir)));
+    }
+
+    #[test]
+    fn test_admonition_paragraphs() {
+        let doc = AsciiDocParser::parse_document("TIP: Use *both* hands.\nAnd a second line.\n\nNOTE:no space\n\nWARNING: ").unwrap();
+        assert_eq!(doc.body[0], Block::Admonition {
+            kind: AdmonitionKind::Tip,
+            content: vec![
+                InlineElement::Text("Use ".to_string()),
+                InlineElement::Formatted { kind: FormattedTextKind::Strong, content: vec![InlineElement::Text("both".to_string())], attributes: InlineAttributes::default() },
+                InlineElement::Text(" hands.".to_string()),
+                InlineElement::Text(" ".to_string()),
+                InlineElement::Text("And a second line.".to_string()),
+            ],
+            blocks: Vec::new(),
+        });
+        assert!(matches!(doc.body[1], Block::Paragraph { .. }));
+        assert!(matches!(doc.body[2], Block::Paragraph { .. }));
+        let html = doc.to_html();
+        assert!(html.contains("<div class=\"admonitionblock tip\">\n<table>\n<tr>\n<td class=\"icon\">\n<div class=\"title\">Tip</div>\n</td>\n<td class=\"content\">\nUse <strong>both</strong> hands."));
+        assert_eq!(AsciiDocParser::parse_document(&doc.to_asciidoc()).unwrap().to_html(), html);
+    }
+
+    #[test]
+    fn test_admonition_blocks() {
+        let input = "[IMPORTANT]\n====\nFirst.\n\n----\ncode\n----\n====\n\n[NOTE]\nStyled.\n\n[NOTE]\n****\nStill a sidebar.\n****\n";
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        assert_eq!(doc.body[0], Block::Admonition {
+            kind: AdmonitionKind::Important,
+            content: Vec::new(),
+            blocks: vec![
+                Block::Paragraph { content: vec![InlineElement::Text("First.".to_string())] },
+                Block::DelimitedBlock { kind: DelimitedBlockKind::Listing, content: "code\n".to_string(), language: None, title: None },
+            ],
+        });
+        assert_eq!(doc.body[1], Block::Admonition {
+            kind: AdmonitionKind::Note,
+            content: vec![InlineElement::Text("Styled.".to_string())],
+            blocks: Vec::new(),
+        });
+        assert!(matches!(doc.body[3], Block::Sidebar { .. }));
+        let html = doc.to_html();
+        assert!(html.contains("<td class=\"content\">\n<p>First.</p>\n<pre><code>code\n</code></pre>\n</td>"));
+        assert_eq!(AsciiDocParser::parse_document(&doc.to_asciidoc()).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_inline_code_languages() {
+        let doc = AsciiDocParser::parse_document("Call [source,js]`fetch()`, [,sh]`+ls *+`, [.language-rust]`Vec::new()` or [source]`x` and [.term]*bold*.").unwrap();
+        let html = doc.to_html();
+        assert!(html.contains("Call <code class=\"language-js\">fetch()</code>, <code class=\"language-sh\">ls *</code>, <code class=\"language-rust\">Vec::new()</code>"));
+        assert!(html.contains("or [source]<code>x</code> and <strong class=\"term\">bold</strong>."));
+        assert_eq!(AsciiDocParser::parse_document(&doc.to_asciidoc()).unwrap(), doc);
+        assert_eq!(AsciiDocParser::parse_document("A [source,js]*bold*.").unwrap().to_html(), "<p>A [source,js]<strong>bold</strong>.</p>\n");
+    }
+
+    #[test]
+    fn test_change_markup() {
+        let doc = AsciiDocParser::parse_document("Up to [.deleted]#10#[.added]#20# and [del]#old# [ins.note]#new#.").unwrap();
+        let html = doc.to_html();
+        assert!(html.contains("<del class=\"deleted\">10</del><ins class=\"added\">20</ins>"));
+        assert!(html.contains("<del class=\"del\">old</del> <ins class=\"ins note\">new</ins>"));
+        assert_eq!(AsciiDocParser::parse_document(&doc.to_asciidoc()).unwrap(), doc);
+
+        #[cfg(feature = "markdown")]
+        {
+            let doc = markdown::MarkdownParser::parse_document("Was ~~old~~.").unwrap();
+            assert_eq!(doc.to_html(), "<p>Was <del class=\"deleted\">old</del>.</p>\n");
+        }
+    }
+
+    #[cfg(feature = "markdown")]
+    #[test]
+    fn test_markdown_ingestion() {
+        let input = "# Guide\n\nSome **bold** and `code`, see [setup](#setup).\n\n## Setup {#setup}\n\n1. First\n   - nested\n2. Second\n\n```rust\nfn main() {}\n```\n";
+        
+        let doc = markdown::MarkdownParser::parse_document(input).unwrap();
+        assert_eq!(doc.header.as_ref().unwrap().title, "Guide");
+        assert_eq!(doc.body[0], Block::Paragraph {
+            content: vec![
+                InlineElement::Text("Some ".to_string()),
+                InlineElement::Formatted { kind: FormattedTextKind::Strong, content: vec![InlineElement::Text("bold".to_string())], attributes: InlineAttributes::default() },
+                InlineElement::Text(" and ".to_string()),
+                InlineElement::Formatted { kind: FormattedTextKind::Monospace, content: vec![InlineElement::Text("code".to_string())], attributes: InlineAttributes::default() },
+                InlineElement::Text(", see ".to_string()),
+                InlineElement::Macro { kind: MacroKind::CrossReference { target: "setup".to_string(), text: Some("setup".to_string()) } },
+                InlineElement::Text(".".to_string()),
+            ],
+        });
+        
+        let Block::Section { level, id, blocks, .. } = &doc.body[1] else { panic!("Expected section block") };
+        assert_eq!((*level, id.as_deref()), (2, Some("setup")));
+        let Block::List { kind: ListKind::Ordered, items } = &blocks[0] else { panic!("Expected ordered list") };
+        assert_eq!(items[0].blocks(), [Block::List {
+            kind: ListKind::Unordered,
+            items: vec![ListItem::Unordered { content: vec![InlineElement::Text("nested".to_string())], blocks: Vec::new() }],
+        }]);
+        assert_eq!(blocks[1], Block::DelimitedBlock {
+            kind: DelimitedBlockKind::Listing,
+            content: "fn main() {}\n".to_string(),
+            language: Some(Symbol::new("rust")),
+            title: None,
+        });
+        
+        let mut workspace = Workspace::new();
+        workspace.add_document("index.adoc", "= Index\n\nRead xref:guide.md#_usage[].");
+        workspace.add_document("guide.md", "# Guide\n\n## Usage\n\nBack to [the index](index.adoc).");
+        let set = workspace.parse().unwrap();
+        assert!(set.unresolved_xrefs().is_empty());
+        assert!(set.get("index.adoc").unwrap().to_html().contains(r#"<a href="guide.html#_usage">Usage</a>"#));
+        assert!(set.get("guide.md").unwrap().to_html().contains(r#"<a href="index.html">the index</a>"#));
+    }
+
+    #[cfg(feature = "epub")]
+    #[test]
+    fn test_epub_package() {
+        use std::io::Read;
+        
+        let dir = std::env::temp_dir().join("adoc-epub-test");
+        std::fs::create_dir_all(dir.join("images")).unwrap();
+        std::fs::write(dir.join("images/cover.png"), b"\x89PNG").unwrap();
+        
+        let input = r#"= My Book
+:author: Jane Doe
+:doctype: book
+
+image:images/cover.png[Cover]
+
+== First Chapter
+
+Line one. +
+See <<_second_chapter>>.
+
+== Second Chapter
+
+The end."#;
+        let doc = AsciiDocParser::parse_document(input).unwrap();
+        let options = epub::EpubOptions {
+            base_dir: dir,
+            modified: Some("2024-01-01T00:00:00Z".to_string()),
+        };
+        let data = doc.to_epub(&options).unwrap();
+        assert_eq!(data, doc.to_epub(&options).unwrap());
+        
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data)).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+        assert!(archive.by_name("OEBPS/images/cover.png").is_ok());
+        
+        let mut opf = String::new();
+        archive.by_name("OEBPS/content.opf").unwrap().read_to_string(&mut opf).unwrap();
+        assert!(opf.contains("<dc:creator>Jane Doe</dc:creator>"));
+        assert!(opf.contains(r#"<item id="chapter-1" href="_first_chapter.xhtml" media-type="application/xhtml+xml"/>"#));
+        assert!(opf.contains(r#"<itemref idref="chapter-2"/>"#));
+        
+        let mut chapter = String::new();
+        archive.by_name("OEBPS/_first_chapter.xhtml").unwrap().read_to_string(&mut chapter).unwrap();
+        assert!(chapter.contains(r##"<a href="_second_chapter.xhtml#_second_chapter">Second Chapter</a>"##));
+        
+        let mut index = String::new();
+        archive.by_name("OEBPS/index.xhtml").unwrap().read_to_string(&mut index).unwrap();
+        assert!(index.contains(r#"<img src="images/cover.png" alt="Cover"/>"#));
+    }
+    
+    // Generators for documents in the canonical shape the parser produces, so printing
+    // and reparsing must give back the identical tree
+    mod roundtrip {
+        use super::*;
+        use proptest::prelude::*;
+
+        fn words() -> impl Strategy<Value = String> {
+            prop::collection::vec("[a-z]{1,8}", 1..4).prop_map(|words| words.join(" "))
+        }
+
+        fn inline_element() -> impl Strategy<Value = InlineElement> {
+            let kind = prop_oneof![
+                Just(FormattedTextKind::Strong),
+                Just(FormattedTextKind::Emphasis),
+                Just(FormattedTextKind::Monospace),
+                Just(FormattedTextKind::LiteralMonospace),
+                Just(FormattedTextKind::Superscript),
+                Just(FormattedTextKind::Subscript),
+                Just(FormattedTextKind::Mark),
+            ];
+            let attributes = (prop::option::of("[a-z]{1,8}"), prop::collection::vec("[a-z]{1,8}", 0..3))
+                .prop_map(|(id, roles)| InlineAttributes { id, roles: roles.into_iter().map(Symbol::from).collect() });
+            prop_oneof![
+                3 => words().prop_map(InlineElement::Text),
+                1 => (kind, words(), attributes).prop_map(|(kind, mut text, mut attributes)| {
+                    // Super- and subscripts can't contain spaces
+                    if matches!(kind, FormattedTextKind::Superscript | FormattedTextKind::Subscript) {
+                        text.retain(|c| c != ' ');
+                    }
+                    // Literal monospace keeps markup and references as they are
+                    if kind == FormattedTextKind::LiteralMonospace {
+                        text = text.replacen(' ', " *{nbsp}* ", 1);
+                    }
+                    // Marked text is only read with an attribute list in front of it
+                    if kind == FormattedTextKind::Mark && attributes.is_empty() {
+                        attributes.roles.push(Symbol::new("role"));
+                    }
+                    InlineElement::Formatted { kind, content: vec![InlineElement::Text(text)], attributes }
+                }),
+                1 => ("[a-z]{1,8}", prop::option::of(words())).prop_map(|(path, text)| InlineElement::Macro {
+                    kind: MacroKind::Link { url: format!("https://example.com/{}", path), text },
+                }),
+                1 => ("[a-z]{1,8}", prop::option::of(words())).prop_map(|(name, alt)| InlineElement::Macro {
+                    kind: MacroKind::Image { path: format!("images/{}.png", name), attributes: alt },
+                }),
+                1 => ("[a-z]{1,8}", prop::option::of(words())).prop_map(|(id, text)| InlineElement::Macro {
+                    kind: MacroKind::CrossReference { target: format!("_{}", id), text },
+                }),
+            ]
+        }
+
+        fn inline_content() -> impl Strategy<Value = Vec<InlineElement>> {
+            prop::collection::vec(inline_element(), 1..6).prop_map(|elements| {
+                // Adjacent text runs are indistinguishable once printed
+                let mut merged: Vec<InlineElement> = Vec::new();
+                for element in elements {
+                    if let (Some(InlineElement::Text(last)), InlineElement::Text(text)) = (merged.last_mut(), &element) {
+                        last.push_str(text);
+                    } else {
+                        merged.push(element);
+                    }
+                }
+                merged
+            })
+        }
+
+        fn delimited_block() -> impl Strategy<Value = Block> {
+            let kind = prop_oneof![
+                Just(DelimitedBlockKind::Listing),
+                Just(DelimitedBlockKind::Example),
+                Just(DelimitedBlockKind::Literal),
+                Just(DelimitedBlockKind::Quote(Attribution::default())),
+            ];
+            let lines = prop::collection::vec(words().prop_map(|line| line + "\n"), 0..3);
+            (kind, lines, prop::option::of("[a-z]{1,8}")).prop_map(|(kind, lines, language)| {
+                let language = if kind == DelimitedBlockKind::Listing { language.map(Symbol::from) } else { None };
+                Block::DelimitedBlock { kind, content: lines.concat(), language, title: None }
+            })
+        }
         
-        if let Block::List { kind, items } = &doc.body[0] {
-            assert!(matches!(kind, ListKind::Description));
-            assert_eq!(items.len(), 2);
-        } else {
-            panic!("Expected description list");
+        // Attached blocks come first and a nested list last, the only order the parser reads
+        fn attached_blocks(depth: u32) -> BoxedStrategy<Vec<Block>> {
+            let attached = prop::collection::vec(prop_oneof![
+                inline_content().prop_map(|content| Block::Paragraph { content }),
+                delimited_block(),
+            ], 0..2);
+            let nested = if depth > 0 { prop::option::weighted(0.3, list(depth - 1)).boxed() } else { Just(None).boxed() };
+            (attached, nested).prop_map(|(mut blocks, nested)| {
+                blocks.extend(nested);
+                blocks
+            }).boxed()
+        }
+
+        fn list(depth: u32) -> BoxedStrategy<Block> {
+            let items = prop::collection::vec((inline_content(), attached_blocks(depth)), 1..4);
+            prop_oneof![
+                (any::<bool>(), items).prop_map(|(ordered, items)| {
+                    let items = items.into_iter().map(|(content, blocks)| {
+                        if ordered { ListItem::Ordered { content, blocks } } else { ListItem::Unordered { content, blocks } }
+                    }).collect();
+                    let kind = if ordered { ListKind::Ordered } else { ListKind::Unordered };
+                    Block::List { kind, items }
+                }),
+                prop::collection::vec((words(), prop::option::of(words()), attached_blocks(depth)), 1..4).prop_map(|items| Block::List {
+                    kind: ListKind::Description(DescriptionListStyle::Stacked),
+                    items: items.into_iter().map(|(term, description, blocks)| ListItem::Description {
+                        term,
+                        description: description.map(|text| vec![InlineElement::Text(text)]),
+                        blocks,
+                    }).collect(),
+                }),
+            ].boxed()
+        }
+
+        fn leaf_blocks() -> impl Strategy<Value = Vec<Block>> {
+            let block = prop_oneof![
+                inline_content().prop_map(|content| Block::Paragraph { content }),
+                delimited_block(),
+                list(2),
+                prop::option::of(1..5usize).prop_map(|levels| Block::TableOfContents { levels }),
+            ];
+            prop::collection::vec(block, 0..4)
+        }
+
+        fn section(level: usize) -> BoxedStrategy<Block> {
+            let subsections = if level < 4 {
+                prop::collection::vec(section(level + 1), 0..3).boxed()
+            } else {
+                Just(Vec::new()).boxed()
+            };
+            (words(), leaf_blocks(), subsections).prop_map(move |(title, mut blocks, subsections)| {
+                blocks.extend(subsections);
+                Block::Section { level, title, id: None, number: None, blocks }
+            }).boxed()
+        }
+
+        fn number_sections(blocks: &mut [Block], next: &mut usize) {
+            for block in blocks {
+                if let Block::Section { id, blocks, .. } = block {
+                    *next += 1;
+                    *id = Some(format!("_s{}", next));
+                    number_sections(blocks, next);
+                }
+            }
+        }
+
+        fn document() -> impl Strategy<Value = Document> {
+            let attribute = ("[a-z]{1,8}", prop::option::of(words()))
+                .prop_map(|(name, value)| Attribute { name: Symbol::from(name), value });
+            let header = (words(), prop::collection::vec(attribute, 0..3))
+                .prop_map(|(title, attributes)| Header { title, attributes });
+            let sections = prop::collection::vec(section(2), 0..3);
+            (prop::option::of(header), leaf_blocks(), sections).prop_map(|(header, mut body, sections)| {
+                body.extend(sections);
+                number_sections(&mut body, &mut 0);
+                Document { header, body }
+            })
+        }
+        
+        proptest! {
+            #[test]
+            fn test_print_parse_round_trip(document in document()) {
+                let source = document.to_asciidoc();
+                let reparsed = AsciiDocParser::parse_document(&source).unwrap();
+                prop_assert_eq!(&reparsed, &document, "source:\n{}", source);
+            }
+
+            #[test]
+            fn test_print_and_render_idempotent(document in document()) {
+                let source = document.to_asciidoc();
+                let reparsed = AsciiDocParser::parse_document(&source).unwrap();
+                prop_assert_eq!(reparsed.to_asciidoc(), source);
+                prop_assert_eq!(reparsed.to_html(), document.to_html());
+            }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,200 @@
+use crate::ast::*;
+use crate::include::url_within;
+
+#[cfg(feature = "linkcheck")]
+use std::collections::HashMap;
+#[cfg(feature = "linkcheck")]
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalLink {
+    pub url: String,
+    pub source: String,
+    pub line: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkCheckOptions {
+    pub concurrency: usize,
+    pub timeout: Duration,
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl Default for LinkCheckOptions {
+    fn default() -> Self {
+        LinkCheckOptions {
+            concurrency: 8,
+            timeout: Duration::from_secs(10),
+            allow: Vec::new(),
+            deny: Vec::new(),
+        }
+    }
+}
+
+impl LinkCheckOptions {
+    // An empty allow list permits every URL that is not denied
+    pub fn permits(&self, url: &str) -> bool {
+        if self.deny.iter().any(|pattern| url_matches(url, pattern)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|pattern| url_matches(url, pattern))
+    }
+}
+
+// A pattern with a scheme is a URL prefix; one without names a host and its subdomains
+fn url_matches(url: &str, pattern: &str) -> bool {
+    if pattern.contains("://") {
+        return url_within(url, pattern);
+    }
+    let host = url_host(url).to_ascii_lowercase();
+    let pattern = pattern.to_ascii_lowercase();
+    host == pattern || host.ends_with(&format!(".{}", pattern))
+}
+
+fn url_host(url: &str) -> &str {
+    let rest = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let end = rest.find(['/', '?', '#', '\\']).unwrap_or(rest.len());
+    let authority = &rest[..end];
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    // A bracketed IPv6 address holds colons of its own
+    match host.rfind(':') {
+        Some(index) if !host[index..].contains(']') => &host[..index],
+        _ => host,
+    }
+}
+
+fn is_external_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+pub fn extract_external_links(source: &str, input: &str, document: &Document) -> Vec<ExternalLink> {
+    let mut urls = Vec::new();
+    document.walk_inlines(&mut |element| {
+        if let InlineElement::Macro { kind: MacroKind::Link { url, .. } } = element
+            && is_external_url(url)
+        {
+            urls.push(url.clone());
+        }
+    });
+    
+    // Links are collected in document order, so each one is searched for after the previous match
+    let mut search_from = 0;
+    urls.into_iter()
+        .map(|url| {
+            let position = input[search_from..].find(&url)
+                .map(|pos| search_from + pos)
+                .or_else(|| input.find(&url));
+            let line = position.map(|pos| {
+                search_from = pos + url.len();
+                input[..pos].matches('\n').count() + 1
+            });
+            ExternalLink { url, source: source.to_string(), line }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkStatus {
+    Alive(u16),
+    Dead(u16),
+    Error(String),
+    Skipped,
+}
+
+impl LinkStatus {
+    pub fn is_dead(&self) -> bool {
+        matches!(self, LinkStatus::Dead(_) | LinkStatus::Error(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkReport {
+    pub link: ExternalLink,
+    pub status: LinkStatus,
+}
+
+#[cfg(feature = "linkcheck")]
+pub struct LinkChecker {
+    options: LinkCheckOptions,
+    client: reqwest::blocking::Client,
+    cache: Mutex<HashMap<String, LinkStatus>>,
+}
+
+#[cfg(feature = "linkcheck")]
+impl LinkChecker {
+    pub fn new(options: LinkCheckOptions) -> Result<Self, reqwest::Error> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(options.timeout)
+            .user_agent(concat!("adoc-linkcheck/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        
+        Ok(LinkChecker {
+            options,
+            client,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn check(&self, links: &[ExternalLink]) -> Vec<LinkReport> {
+        let mut pending: Vec<&str> = Vec::new();
+        {
+            let cache = self.cache.lock().unwrap();
+            for link in links {
+                let url = link.url.as_str();
+                if self.options.permits(url) && !cache.contains_key(url) && !pending.contains(&url) {
+                    pending.push(url);
+                }
+            }
+        }
+        
+        let workers = self.options.concurrency.max(1).min(pending.len());
+        let queue = Mutex::new(pending.into_iter());
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let next = queue.lock().unwrap().next();
+                    let Some(url) = next else { break };
+                    let status = self.check_url(url);
+                    self.cache.lock().unwrap().insert(url.to_string(), status);
+                });
+            }
+        });
+        
+        let cache = self.cache.lock().unwrap();
+        links.iter()
+            .map(|link| LinkReport {
+                link: link.clone(),
+                status: cache.get(&link.url).cloned().unwrap_or(LinkStatus::Skipped),
+            })
+            .collect()
+    }
+
+    pub fn dead_links(&self, links: &[ExternalLink]) -> Vec<LinkReport> {
+        self.check(links).into_iter().filter(|report| report.status.is_dead()).collect()
+    }
+
+    fn check_url(&self, url: &str) -> LinkStatus {
+        match self.client.head(url).send() {
+            // Some servers refuse HEAD requests, so retry those with GET
+            Ok(response) if matches!(response.status().as_u16(), 405 | 501) => {
+                match self.client.get(url).send() {
+                    Ok(response) => status_from_code(response.status().as_u16()),
+                    Err(e) => LinkStatus::Error(e.to_string()),
+                }
+            }
+            Ok(response) => status_from_code(response.status().as_u16()),
+            Err(e) => LinkStatus::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "linkcheck")]
+fn status_from_code(code: u16) -> LinkStatus {
+    if code >= 400 {
+        LinkStatus::Dead(code)
+    } else {
+        LinkStatus::Alive(code)
+    }
+}
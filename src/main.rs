@@ -1,37 +1,1044 @@
-mod ast;
-mod parser;
-
-use parser::AsciiDocParser;
+use adoc::AsciiDocParser;
+use adoc::ast::Attribute;
+use adoc::include::FileIncludeResolver;
 use std::env;
 use std::fs;
+use std::io::{self, BufRead, BufWriter, IsTerminal, Write};
+use std::path::Path;
 use std::process;
+use std::sync::Arc;
+
+struct CliOptions {
+    inputs: Vec<String>,
+    attribute_sources: Vec<String>,
+    config_path: Option<String>,
+    check_links: bool,
+    link_check: adoc::linkcheck::LinkCheckOptions,
+    digest: bool,
+    lint: bool,
+    search_index: bool,
+    debug_parse: bool,
+    ast: bool,
+    schema: bool,
+    repl: bool,
+    include_graph: Option<String>,
+    base_dir: Option<String>,
+    chunked_dir: Option<String>,
+    chunk_depth: usize,
+    epub_path: Option<String>,
+    latex_path: Option<String>,
+    pdf_path: Option<String>,
+    pdf_engine: Option<String>,
+    output: Option<String>,
+    sanitize: bool,
+    standalone: bool,
+    site_index: bool,
+    sitemap_base_url: Option<String>,
+    manifest: Option<String>,
+    attribute_conflicts: Option<adoc::ConflictPolicy>,
+    footer_template: Option<String>,
+    theme: Option<String>,
+    print: bool,
+    external_links: bool,
+    copy_buttons: bool,
+    extract: Option<String>,
+    verbose: bool,
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    let options = parse_args(&args);
+    if options.verbose {
+        init_tracing();
+    }
+    let attributes = load_attributes(&options.attribute_sources);
     
-    if args.len() != 2 {
-        eprintln!("Usage: {} <input.adoc>", args[0]);
-        process::exit(1);
+    if options.repl {
+        repl(&attributes, options.base_dir.as_deref());
+        return;
     }
     
-    let input_path = &args[1];
+    if options.schema {
+        print!("{}", adoc::JSON_SCHEMA);
+        return;
+    }
+    
+    if options.check_links {
+        check_links(&options.inputs, &attributes, options.base_dir.as_deref(), &options.link_check);
+        return;
+    }
     
-    let content = match fs::read_to_string(input_path) {
-        Ok(content) => content,
+    if options.digest {
+        print_digests(&options.inputs, &attributes, options.base_dir.as_deref());
+        return;
+    }
+    
+    if options.search_index {
+        print_search_index(&options.inputs, &attributes, options.base_dir.as_deref());
+        return;
+    }
+    
+    if options.lint {
+        lint(&options.inputs, &attributes, options.base_dir.as_deref(), &load_config(options.config_path.as_deref()));
+        return;
+    }
+    
+    if options.inputs.len() != 1 {
+        usage(&args[0]);
+    }
+    
+    let input_path = &options.inputs[0];
+    if Path::new(input_path).is_dir() {
+        build_site(input_path, &options, &attributes);
+        return;
+    }
+    let content = read_input(input_path);
+    
+    if options.debug_parse {
+        debug_parse(input_path, content.as_str());
+        return;
+    }
+    
+    if options.ast {
+        print_ast(input_path, content.as_str(), &attributes);
+        return;
+    }
+    
+    if let Some(format) = &options.include_graph {
+        print_include_graph(input_path, content.as_str(), options.base_dir.as_deref(), format);
+        return;
+    }
+    
+    match parse_input_for(input_path, content.as_str(), &attributes, backend(&options), options.base_dir.as_deref()) {
+        Ok(mut document) => {
+            if let Some(selector) = &options.extract {
+                document = document.extract(selector).unwrap_or_else(|| {
+                    eprintln!("No section '{}' in '{}'", selector, input_path);
+                    process::exit(1);
+                });
+            }
+            // AsciiDoc written back out carries no timestamp of its own
+            let source_path = options.output.as_deref().filter(|path| path.ends_with(".adoc"));
+            if let Ok(modified) = fs::metadata(input_path).and_then(|metadata| metadata.modified())
+                && source_path.is_none()
+            {
+                document.set_last_updated(modified);
+            }
+            if let Some(dir) = &options.chunked_dir {
+                write_chunks(&document, dir, options.chunk_depth, &html_options(&options));
+            } else if let Some(epub_path) = &options.epub_path {
+                write_epub(&document, input_path, epub_path);
+            } else if let Some(latex_path) = &options.latex_path {
+                write_latex(&document, latex_path);
+            } else if let Some(pdf_path) = &options.pdf_path {
+                write_pdf(&document, input_path, pdf_path, options.pdf_engine.as_deref());
+            } else if let Some(source_path) = source_path {
+                write_asciidoc(&document, source_path);
+            } else {
+                write_html(&document, options.output.as_deref(), &html_options(&options));
+            }
+        }
+        Err(e) => {
+            eprintln!("Parse error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> CliOptions {
+    let mut options = CliOptions {
+        inputs: Vec::new(),
+        attribute_sources: Vec::new(),
+        config_path: None,
+        check_links: false,
+        link_check: adoc::linkcheck::LinkCheckOptions::default(),
+        digest: false,
+        lint: false,
+        search_index: false,
+        debug_parse: false,
+        ast: false,
+        schema: false,
+        repl: false,
+        include_graph: None,
+        base_dir: None,
+        chunked_dir: None,
+        chunk_depth: 1,
+        epub_path: None,
+        latex_path: None,
+        pdf_path: None,
+        pdf_engine: None,
+        output: None,
+        sanitize: false,
+        standalone: false,
+        site_index: false,
+        sitemap_base_url: None,
+        manifest: None,
+        attribute_conflicts: None,
+        footer_template: None,
+        theme: None,
+        print: false,
+        external_links: false,
+        copy_buttons: false,
+        extract: None,
+        verbose: false,
+    };
+    
+    let mut iter = args.iter().skip(1);
+    match args.get(1).map(String::as_str) {
+        Some("ast") => options.ast = true,
+        Some("schema") => options.schema = true,
+        Some("repl") => options.repl = true,
+        _ => {}
+    }
+    if options.ast || options.schema || options.repl {
+        iter.next();
+    }
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--check-links" => options.check_links = true,
+            "--allow-url" => match iter.next() {
+                Some(pattern) => options.link_check.allow.push(pattern.clone()),
+                None => usage(&args[0]),
+            },
+            "--deny-url" => match iter.next() {
+                Some(pattern) => options.link_check.deny.push(pattern.clone()),
+                None => usage(&args[0]),
+            },
+            "--concurrency" => match iter.next().and_then(|count| count.parse().ok()).filter(|count| *count > 0) {
+                Some(count) => options.link_check.concurrency = count,
+                None => usage(&args[0]),
+            },
+            "--digest" => options.digest = true,
+            "--lint" => options.lint = true,
+            "--search-index" => options.search_index = true,
+            "--debug-parse" => options.debug_parse = true,
+            "--trace-includes" => options.include_graph = Some("tree".to_string()),
+            "--include-graph" => match iter.next() {
+                Some(format) if format == "json" || format == "dot" => options.include_graph = Some(format.clone()),
+                _ => usage(&args[0]),
+            },
+            "--base-dir" => match iter.next() {
+                Some(dir) => options.base_dir = Some(dir.clone()),
+                None => usage(&args[0]),
+            },
+            "--attributes-from" => match iter.next() {
+                Some(source) => options.attribute_sources.push(source.clone()),
+                None => usage(&args[0]),
+            },
+            "--config" => match iter.next() {
+                Some(path) => options.config_path = Some(path.clone()),
+                None => usage(&args[0]),
+            },
+            "--sanitize" => options.sanitize = true,
+            "-s" | "--standalone" => options.standalone = true,
+            "--print" => options.print = true,
+            "--external-links" => options.external_links = true,
+            "--copy-buttons" => options.copy_buttons = true,
+            "--index" => options.site_index = true,
+            "--manifest" => match iter.next() {
+                Some(path) => options.manifest = Some(path.clone()),
+                None => usage(&args[0]),
+            },
+            "--attribute-conflicts" => match iter.next().map(String::as_str) {
+                Some("first-wins") => options.attribute_conflicts = Some(adoc::ConflictPolicy::FirstWins),
+                Some("last-wins") => options.attribute_conflicts = Some(adoc::ConflictPolicy::LastWins),
+                Some("error") => options.attribute_conflicts = Some(adoc::ConflictPolicy::Error),
+                _ => usage(&args[0]),
+            },
+            "--footer-template" => match iter.next() {
+                Some(path) => options.footer_template = Some(path.clone()),
+                None => usage(&args[0]),
+            },
+            "--theme" => match iter.next() {
+                Some(theme) => options.theme = Some(theme.clone()),
+                None => usage(&args[0]),
+            },
+            "--extract" => match iter.next() {
+                Some(selector) => options.extract = Some(selector.clone()),
+                None => usage(&args[0]),
+            },
+            "--sitemap" => match iter.next() {
+                Some(base_url) => options.sitemap_base_url = Some(base_url.clone()),
+                None => usage(&args[0]),
+            },
+            "-v" | "--verbose" => options.verbose = true,
+            "--chunked" => match iter.next() {
+                Some(dir) => options.chunked_dir = Some(dir.clone()),
+                None => usage(&args[0]),
+            },
+            "--epub" => match iter.next() {
+                Some(path) => options.epub_path = Some(path.clone()),
+                None => usage(&args[0]),
+            },
+            "--latex" => match iter.next() {
+                Some(path) => options.latex_path = Some(path.clone()),
+                None => usage(&args[0]),
+            },
+            "--pdf" => match iter.next() {
+                Some(path) => options.pdf_path = Some(path.clone()),
+                None => usage(&args[0]),
+            },
+            "--pdf-engine" => match iter.next() {
+                Some(engine) => options.pdf_engine = Some(engine.clone()),
+                None => usage(&args[0]),
+            },
+            "-o" | "--output" => match iter.next() {
+                Some(path) => options.output = Some(path.clone()),
+                None => usage(&args[0]),
+            },
+            "--chunk-depth" => match iter.next().and_then(|depth| depth.parse().ok()) {
+                Some(depth) => options.chunk_depth = depth,
+                None => usage(&args[0]),
+            },
+            _ if arg.starts_with("--") => usage(&args[0]),
+            _ => options.inputs.push(arg.clone()),
+        }
+    }
+    
+    if options.inputs.is_empty() != (options.repl || options.schema) {
+        usage(&args[0]);
+    }
+    
+    options
+}
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {} [--verbose] [--sanitize] [--standalone] [--print] [--external-links] [--copy-buttons] [--theme <default|dark|print|none|file.css>] [--footer-template <file.html>] [--attributes-from <file.json|file.toml|env:PREFIX>]... [--base-dir <dir>] [-o <output.html>] <input.adoc|input.md>", program);
+    eprintln!("       {} [--standalone] [--index] [--sitemap <base-url>] [--manifest <file|nav.adoc>] [--attribute-conflicts <first-wins|last-wins|error>] -o <output-dir> <input-dir>", program);
+    eprintln!("       {} --extract <section-id|Title/Subtitle> [-o <output.adoc|output.html>] <input.adoc>", program);
+    eprintln!("       {} --chunked <output-dir> [--chunk-depth <n>] [--theme <name|file.css>] <input.adoc>", program);
+    eprintln!("       {} --epub <output.epub> <input.adoc>", program);
+    eprintln!("       {} --latex <output.tex> <input.adoc>", program);
+    eprintln!("       {} --pdf <output.pdf> [--pdf-engine <pdflatex|xelatex|lualatex|tectonic>] <input.adoc>", program);
+    eprintln!("       {} --debug-parse <input.adoc>", program);
+    eprintln!("       {} ast <input.adoc|input.md>", program);
+    eprintln!("       {} schema", program);
+    eprintln!("       {} repl [--base-dir <dir>]", program);
+    eprintln!("       {} --trace-includes | --include-graph <json|dot> [--base-dir <dir>] <input.adoc>", program);
+    eprintln!("       {} --digest [--base-dir <dir>] <input.adoc>...", program);
+    eprintln!("       {} --search-index [--base-dir <dir>] <input.adoc>...", program);
+    eprintln!("       {} --lint [--config <adoc.toml>] [--base-dir <dir>] <input.adoc>...", program);
+    eprintln!("       {} --check-links [--allow-url <url|host>]... [--deny-url <url|host>]... [--concurrency <n>] [--base-dir <dir>] <input.adoc>...", program);
+    process::exit(1);
+}
+
+// Logs parse phases, resolved includes and render timings to stderr
+#[cfg(feature = "tracing")]
+fn init_tracing() {
+    use tracing_subscriber::fmt::format::FmtSpan;
+    
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(io::stderr)
+        .init();
+}
+
+#[cfg(not(feature = "tracing"))]
+fn init_tracing() {
+    eprintln!("Verbose output requires building adoc with the `tracing` feature");
+}
+
+enum Source {
+    Owned(String),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Source {
+    fn as_str(&self) -> &str {
+        match self {
+            Source::Owned(content) => content,
+            // Checked once in `load_input`, which refuses a file that isn't UTF-8
+            #[cfg(feature = "mmap")]
+            Source::Mapped(map) => unsafe { std::str::from_utf8_unchecked(map) },
+        }
+    }
+}
+
+fn html_options(options: &CliOptions) -> adoc::HtmlOptions {
+    let mut html_options = if options.sanitize { adoc::HtmlOptions::untrusted() } else { adoc::HtmlOptions::default() };
+    html_options.standalone = options.standalone;
+    html_options.stylesheet = options.theme.clone();
+    html_options.print = options.print;
+    html_options.external_links = options.external_links;
+    html_options.copy_button_script = options.copy_buttons;
+    html_options.footer_template = options.footer_template.as_ref().map(|path| match fs::read_to_string(path) {
+        Ok(template) => template,
+        Err(e) => {
+            eprintln!("Error reading footer template '{}': {}", path, e);
+            process::exit(1);
+        }
+    });
+    html_options
+}
+
+fn read_input(input_path: &str) -> Source {
+    match load_input(input_path) {
+        Ok(source) => source,
         Err(e) => {
             eprintln!("Error reading file '{}': {}", input_path, e);
             process::exit(1);
         }
+    }
+}
+
+#[cfg(feature = "mmap")]
+fn load_input(input_path: &str) -> io::Result<Source> {
+    let file = fs::File::open(input_path)?;
+    if !file.metadata()?.is_file() || file.metadata()?.len() == 0 {
+        return fs::read_to_string(input_path).map(Source::Owned);
+    }
+    
+    // The mapping assumes the input is not truncated or rewritten while it is converted
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    std::str::from_utf8(&map).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Source::Mapped(map))
+}
+
+#[cfg(not(feature = "mmap"))]
+fn load_input(input_path: &str) -> io::Result<Source> {
+    fs::read_to_string(input_path).map(Source::Owned)
+}
+
+// `env:PREFIX` reads the environment variables starting with PREFIX and anything else is a
+// JSON or TOML file. A later source wins over an earlier one for the same attribute.
+fn load_attributes(sources: &[String]) -> Vec<Attribute> {
+    let mut attributes: Vec<Attribute> = Vec::new();
+    for source in sources {
+        let loaded = match source.strip_prefix("env:") {
+            Some(prefix) => adoc::attributes::from_env(prefix),
+            None => load_attribute_file(source),
+        };
+        for attribute in loaded {
+            attributes.retain(|existing| existing.name != attribute.name);
+            attributes.push(attribute);
+        }
+    }
+    attributes
+}
+
+#[cfg(feature = "attribute-files")]
+fn load_attribute_file(path: &str) -> Vec<Attribute> {
+    let result = fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|source| {
+        let attributes = if path.ends_with(".toml") {
+            adoc::attributes::from_toml(&source)
+        } else {
+            adoc::attributes::from_json(&source)
+        };
+        attributes.map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(attributes) => attributes,
+        Err(e) => {
+            eprintln!("Error reading attributes '{}': {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "attribute-files"))]
+fn load_attribute_file(_path: &str) -> Vec<Attribute> {
+    eprintln!("Reading attributes from a file requires building adoc with the `attribute-files` feature");
+    process::exit(1);
+}
+
+// `adoc.toml` in the working directory is read unless another file is named
+#[cfg(feature = "config")]
+fn load_config(path: Option<&str>) -> adoc::config::Config {
+    let path = match path {
+        Some(path) => path,
+        None if Path::new(adoc::config::CONFIG_FILE).exists() => adoc::config::CONFIG_FILE,
+        None => return adoc::config::Config::default(),
+    };
+    let result = fs::read_to_string(path).map_err(|e| e.to_string())
+        .and_then(|source| adoc::config::from_toml(&source).map_err(|e| e.to_string()));
+    match result {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error reading config '{}': {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "config"))]
+fn load_config(path: Option<&str>) -> adoc::config::Config {
+    if path.is_some() {
+        eprintln!("Reading a config file requires building adoc with the `config` feature");
+        process::exit(1);
+    }
+    adoc::config::Config::default()
+}
+
+// What `ifdef::backend-*[]` sees for the output being written, checked in the order `main`
+// picks the output
+fn backend(options: &CliOptions) -> &'static str {
+    if options.chunked_dir.is_some() {
+        adoc::parser::DEFAULT_BACKEND
+    } else if options.epub_path.is_some() {
+        "epub3"
+    } else if options.latex_path.is_some() {
+        "latex"
+    } else if options.pdf_path.is_some() {
+        "pdf"
+    } else {
+        adoc::parser::DEFAULT_BACKEND
+    }
+}
+
+fn parse_input(input_path: &str, content: &str, attributes: &[Attribute], base_dir: Option<&str>) -> Result<adoc::Document, adoc::ParseError> {
+    parse_input_for(input_path, content, attributes, adoc::parser::DEFAULT_BACKEND, base_dir)
+}
+
+fn parse_input_for(
+    input_path: &str,
+    content: &str,
+    attributes: &[Attribute],
+    backend: &str,
+    base_dir: Option<&str>,
+) -> Result<adoc::Document, adoc::ParseError> {
+    #[cfg(feature = "markdown")]
+    if adoc::markdown::is_markdown(input_path) {
+        return adoc::markdown::MarkdownParser::parse_document(content);
+    }
+    
+    let options = adoc::ParseOptions {
+        include_resolver: Some(include_resolver(input_path, base_dir)),
+        attributes: attributes.to_vec(),
+        backend: Some(backend.to_string()),
+        // Snippets typed into the REPL come from no file
+        docfile: Some(input_path.to_string()).filter(|path| path != "<stdin>"),
+        ..Default::default()
     };
+    AsciiDocParser::parse_document_with_options(content, &options)
+}
+
+// Includes are read relative to `base_dir`, or else to the directory of the input file
+fn include_resolver(input_path: &str, base_dir: Option<&str>) -> Arc<FileIncludeResolver> {
+    let base_dir = base_dir.map(Path::new).or_else(|| Path::new(input_path).parent()).map(Path::to_path_buf).unwrap_or_default();
+    Arc::new(FileIncludeResolver::new(base_dir))
+}
+
+// Prints the pest pair tree followed by the document tree built from it
+fn debug_parse(input_path: &str, content: &str) {
+    #[cfg(feature = "markdown")]
+    if adoc::markdown::is_markdown(input_path) {
+        eprintln!("--debug-parse only applies to AsciiDoc input");
+        process::exit(1);
+    }
+    
+    let result = AsciiDocParser::parse_tree(content, &adoc::ParseOptions::default())
+        .and_then(|tree| Ok((tree, AsciiDocParser::parse_document(content)?)));
+    match result {
+        Ok((tree, document)) => {
+            println!("{}", tree);
+            println!("{:#?}", document);
+        }
+        Err(e) => {
+            eprintln!("Parse error in '{}': {}", input_path, e);
+            process::exit(1);
+        }
+    }
+}
+
+// Prints the document tree with where each node is in the file. Includes are left as written
+// so the positions are those of the file itself.
+fn print_ast(input_path: &str, content: &str, attributes: &[Attribute]) {
+    #[cfg(feature = "markdown")]
+    if adoc::markdown::is_markdown(input_path) {
+        match adoc::markdown::MarkdownParser::parse_document(content) {
+            Ok(document) => print!("{}", document.dump(None)),
+            Err(e) => {
+                eprintln!("Parse error in '{}': {}", input_path, e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
     
-    match AsciiDocParser::parse_document(&content) {
+    let options = adoc::ParseOptions {
+        attributes: attributes.to_vec(),
+        docfile: Some(input_path.to_string()),
+        ..Default::default()
+    };
+    match AsciiDocParser::parse_document_with_options(content, &options) {
+        Ok(document) => print!("{}", document.dump(Some(content))),
+        Err(e) => {
+            eprintln!("Parse error in '{}': {}", input_path, e);
+            process::exit(1);
+        }
+    }
+}
+
+// Reads snippets from stdin and prints the document tree and HTML of each. A blank line ends
+// a snippet, except inside a delimited block, and the end of input ends the last one.
+fn repl(attributes: &[Attribute], base_dir: Option<&str>) {
+    let stdin = io::stdin();
+    let mut snippet = String::new();
+    let mut open_delimiter: Option<String> = None;
+    prompt("adoc> ");
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Error reading stdin: {}", e);
+                process::exit(1);
+            }
+        };
+        if line.trim().is_empty() && open_delimiter.is_none() {
+            if !snippet.is_empty() {
+                evaluate_snippet(&snippet, attributes, base_dir);
+                snippet.clear();
+            }
+            prompt("adoc> ");
+            continue;
+        }
+        
+        if let Some(delimiter) = block_delimiter(&line) {
+            match &open_delimiter {
+                Some(open) if open == delimiter => open_delimiter = None,
+                Some(_) => {}
+                None => open_delimiter = Some(delimiter.to_string()),
+            }
+        }
+        snippet.push_str(&line);
+        snippet.push('\n');
+        prompt("  ... ");
+    }
+    if !snippet.is_empty() {
+        evaluate_snippet(&snippet, attributes, base_dir);
+    }
+}
+
+// Prompts are left out when stdin is piped in
+fn prompt(text: &str) {
+    if !io::stdin().is_terminal() {
+        return;
+    }
+    eprint!("{}", text);
+    let _ = io::stderr().flush();
+}
+
+// `----`, `====`, `****`, `____`, `....`, `++++` and `////` lines open and close blocks
+fn block_delimiter(line: &str) -> Option<&str> {
+    let line = line.trim_end();
+    let first = line.chars().next()?;
+    (line.len() >= 4 && "-=*_.+/".contains(first) && line.chars().all(|c| c == first)).then_some(line)
+}
+
+fn evaluate_snippet(snippet: &str, attributes: &[Attribute], base_dir: Option<&str>) {
+    match parse_input("<stdin>", snippet, attributes, base_dir) {
         Ok(document) => {
-            let html = document.to_html();
-            println!("{}", html);
+            println!("{:#?}", document);
+            println!("{}", document.to_html());
+        }
+        Err(e) => eprintln!("Parse error: {}", e),
+    }
+    let _ = io::stdout().flush();
+}
+
+// One `hash  path` line per input, in the style of sha256sum
+fn print_digests(input_paths: &[String], attributes: &[Attribute], base_dir: Option<&str>) {
+    for input_path in input_paths {
+        let content = read_input(input_path);
+        match parse_input(input_path, content.as_str(), attributes, base_dir) {
+            Ok(document) => println!("{:016x}  {}", document.content_hash(), input_path),
+            Err(e) => {
+                eprintln!("Parse error in '{}': {}", input_path, e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+// Reports findings in the `path:line: severity[code]: message` form editors pick up, and
+// fails when there are any
+fn lint(input_paths: &[String], attributes: &[Attribute], base_dir: Option<&str>, config: &adoc::config::Config) {
+    let mut count = 0;
+    for input_path in input_paths {
+        let content = read_input(input_path);
+        let options = adoc::ParseOptions {
+            include_resolver: Some(include_resolver(input_path, base_dir)),
+            attributes: attributes.to_vec(),
+            docfile: Some(input_path.clone()),
+            ..Default::default()
+        };
+        for diagnostic in adoc::diagnostics::check_with_config(content.as_str(), &options, config) {
+            let location = match diagnostic.line {
+                Some(line) => format!("{}:{}", input_path, line),
+                None => input_path.clone(),
+            };
+            let severity = match diagnostic.severity() {
+                adoc::diagnostics::Severity::Warning => "warning",
+                adoc::diagnostics::Severity::Error => "error",
+            };
+            println!("{}: {}[{}]: {}", location, severity, diagnostic.code(), diagnostic.message);
+            println!("  help: {}", diagnostic.help());
+            count += 1;
+        }
+    }
+    
+    if count > 0 {
+        eprintln!("{} problem{} found", count, if count == 1 { "" } else { "s" });
+        process::exit(1);
+    }
+}
+
+fn print_search_index(input_paths: &[String], attributes: &[Attribute], base_dir: Option<&str>) {
+    let mut index = adoc::search::SearchIndex::new();
+    for input_path in input_paths {
+        let content = read_input(input_path);
+        match parse_input(input_path, content.as_str(), attributes, base_dir) {
+            Ok(document) => index.add_document(input_path, &document),
+            Err(e) => {
+                eprintln!("Parse error in '{}': {}", input_path, e);
+                process::exit(1);
+            }
+        }
+    }
+    print!("{}", index.to_json());
+}
+
+// Follows includes the way a conversion would and prints the files the input depends on
+fn print_include_graph(input_path: &str, content: &str, base_dir: Option<&str>, format: &str) {
+    let options = adoc::ParseOptions {
+        include_resolver: Some(include_resolver(input_path, base_dir)),
+        ..Default::default()
+    };
+    
+    let graph = AsciiDocParser::include_graph(input_path, content, &options);
+    match format {
+        "json" => print!("{}", graph.to_json()),
+        "dot" => print!("{}", graph.to_dot()),
+        _ => print!("{}", graph.to_tree()),
+    }
+}
+
+fn write_html(document: &adoc::Document, output_path: Option<&str>, options: &adoc::HtmlOptions) {
+    // With `:linkcss:` a built-in theme is written beside the page that links it
+    if options.standalone && document.attribute("linkcss").is_some()
+        && let (Some(path), Some((theme, theme_path))) = (output_path, document.theme(options))
+    {
+        write_theme(theme, &Path::new(path).parent().unwrap_or(Path::new("")).join(theme_path));
+    }
+    let result = match output_path {
+        Some(path) => write_atomically(Path::new(path), |out| document.write_html_with(out, options)),
+        None => {
+            let mut out = BufWriter::new(io::stdout().lock());
+            document.write_html_with(&mut out, options).and_then(|_| out.flush())
+        }
+    };
+    
+    if let Err(e) = result {
+        eprintln!("Error writing output '{}': {}", output_path.unwrap_or("-"), e);
+        process::exit(1);
+    }
+}
+
+fn write_asciidoc(document: &adoc::Document, path: &str) {
+    if let Err(e) = write_atomically(Path::new(path), |out| out.write_all(document.to_asciidoc().as_bytes())) {
+        eprintln!("Error writing output '{}': {}", path, e);
+        process::exit(1);
+    }
+}
+
+fn write_theme(theme: adoc::theme::Theme, path: &Path) {
+    let result = path.parent().map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| write_atomically(path, |out| out.write_all(theme.css().as_bytes())));
+    if let Err(e) = result {
+        eprintln!("Error writing stylesheet '{}': {}", path.display(), e);
+        process::exit(1);
+    }
+}
+
+// Converts every document under `dir` into the same layout under the output directory, with
+// cross references between them pointing at each other's pages
+fn build_site(dir: &str, options: &CliOptions, attributes: &[Attribute]) {
+    let Some(output_dir) = &options.output else {
+        eprintln!("Converting a directory needs an output directory given with -o");
+        process::exit(1);
+    };
+    // Each page includes relative to itself within the directory, so there is no one base
+    if options.base_dir.is_some() {
+        eprintln!("--base-dir can't be used when converting a directory");
+        process::exit(1);
+    }
+    let mut sources = Vec::new();
+    if let Err(e) = collect_sources(Path::new(dir), Path::new(dir), &mut sources) {
+        eprintln!("Error reading '{}': {}", dir, e);
+        process::exit(1);
+    }
+    // Without a manifest given, a `nav.adoc` at the top describes the site instead of being
+    // converted as a page of it
+    let nav = match &options.manifest {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(source) => Some((path.clone(), source)),
+            Err(e) => {
+                eprintln!("Error reading '{}': {}", path, e);
+                process::exit(1);
+            }
+        },
+        None => sources.iter()
+            .position(|(name, _)| name == "nav.adoc")
+            .map(|index| sources.remove(index)),
+    };
+    let manifest = nav.map(|(path, source)| {
+        if !path.ends_with(".adoc") {
+            return adoc::site::Manifest::parse(&source);
         }
+        match adoc::site::Manifest::parse_nav(&source) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                eprintln!("Parse error: {}", e.in_document(&path));
+                process::exit(1);
+            }
+        }
+    });
+    
+    let mut workspace = adoc::Workspace::with_options(adoc::ParseOptions {
+        include_resolver: Some(Arc::new(FileIncludeResolver::new(dir))),
+        attributes: attributes.to_vec(),
+        ..Default::default()
+    });
+    for (name, source) in sources {
+        workspace.add_document(name, source);
+    }
+    let mut set = match workspace.parse() {
+        Ok(set) => set,
         Err(e) => {
             eprintln!("Parse error: {}", e);
             process::exit(1);
         }
+    };
+    // Without a policy each page keeps the header attributes it sets
+    if let Some(policy) = options.attribute_conflicts {
+        match set.merge_attributes(policy) {
+            Ok(conflicts) => {
+                for conflict in conflicts {
+                    eprintln!("{}: warning[{}]: {}", dir, conflict.code(), conflict.message);
+                }
+            }
+            Err(conflicts) => {
+                for conflict in conflicts {
+                    eprintln!("{}: error[{}]: {}", dir, conflict.code(), conflict.message);
+                }
+                process::exit(1);
+            }
+        }
+    }
+    
+    let mut navigation = set.navigation(manifest.as_ref());
+    
+    let html_options = html_options(options);
+    let output_dir = Path::new(output_dir);
+    let write = |path: &str, write: &dyn Fn(&mut dyn Write) -> io::Result<()>| {
+        let path = output_dir.join(path);
+        let result = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| write_atomically(&path, write));
+        if let Err(e) = result {
+            eprintln!("Error writing '{}': {}", path.display(), e);
+            process::exit(1);
+        }
+    };
+    for (name, document) in set.documents() {
+        let mut document = document.clone();
+        if let Ok(modified) = fs::metadata(Path::new(dir).join(name)).and_then(|metadata| metadata.modified()) {
+            document.set_last_updated(modified);
+        }
+        let html_options = adoc::HtmlOptions { navigation: navigation.remove(name).unwrap_or_default(), ..html_options.clone() };
+        write(&adoc::site::output_path(name), &|out| document.write_html_with(out, &html_options));
+    }
+    
+    let mut pages = set.pages();
+    if options.site_index {
+        if pages.iter().any(|page| page.path == "index.html") {
+            eprintln!("Not writing index.html, since a document is converted to it");
+        } else {
+            let title = Path::new(dir).canonicalize().ok()
+                .and_then(|dir| dir.file_name().map(|name| name.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| "Documents".to_string());
+            let index = adoc::site::index_html(&title, &pages);
+            write("index.html", &|out| out.write_all(index.as_bytes()));
+            pages.insert(0, adoc::site::SitePage { source: String::new(), path: "index.html".to_string(), title });
+        }
+    }
+    if let Some(base_url) = &options.sitemap_base_url {
+        let sitemap = adoc::site::sitemap_xml(base_url, &pages);
+        write("sitemap.xml", &|out| out.write_all(sitemap.as_bytes()));
+    }
+}
+
+// Documents by their path under `root`. Hidden entries are skipped, and so are files starting
+// with `_`, which by convention are only ever included into other documents.
+fn collect_sources(root: &Path, dir: &Path, sources: &mut Vec<(String, String)>) -> io::Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            collect_sources(root, &path, sources)?;
+        } else if !file_name.starts_with('_') && is_document(&file_name) {
+            let name = path.strip_prefix(root).unwrap_or(&path).components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+            sources.push((name, fs::read_to_string(&path)?));
+        }
+    }
+    Ok(())
+}
+
+fn is_document(file_name: &str) -> bool {
+    #[cfg(feature = "markdown")]
+    if adoc::markdown::is_markdown(file_name) {
+        return true;
+    }
+    file_name.ends_with(".adoc") || file_name.ends_with(".asciidoc")
+}
+
+// Output goes to a hidden temporary file beside `path` that is renamed over it once complete,
+// so a failed or interrupted build never leaves a truncated file where the output belongs.
+// The temporary file is removed again when writing fails.
+fn write_atomically(path: &Path, write: impl FnOnce(&mut dyn Write) -> io::Result<()>) -> io::Result<()> {
+    let Some(file_name) = path.file_name() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "output path has no file name"));
+    };
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name.to_string_lossy(), process::id()));
+    
+    let result = fs::File::create(&temp_path)
+        .and_then(|file| {
+            let mut out = BufWriter::new(file);
+            write(&mut out)?;
+            out.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn write_chunks(document: &adoc::Document, dir: &str, depth: usize, options: &adoc::HtmlOptions) {
+    if let Err(e) = fs::create_dir_all(dir) {
+        eprintln!("Error creating directory '{}': {}", dir, e);
+        process::exit(1);
+    }
+    
+    // The pages link a built-in theme rather than each embedding it
+    if let Some((theme, path)) = document.theme(options) {
+        write_theme(theme, &Path::new(dir).join(path));
+    }
+    for chunk in document.to_chunked_html_with(depth, options) {
+        let path = Path::new(dir).join(&chunk.file_name);
+        if let Err(e) = write_atomically(&path, |out| out.write_all(chunk.html.as_bytes())) {
+            eprintln!("Error writing file '{}': {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(feature = "epub")]
+fn write_epub(document: &adoc::Document, input_path: &str, epub_path: &str) {
+    let options = adoc::epub::EpubOptions {
+        base_dir: Path::new(input_path).parent().map(Path::to_path_buf).unwrap_or_default(),
+        ..Default::default()
+    };
+    
+    let result = document.to_epub(&options)
+        .and_then(|data| write_atomically(Path::new(epub_path), |out| out.write_all(&data)));
+    if let Err(e) = result {
+        eprintln!("Error writing EPUB '{}': {}", epub_path, e);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "epub"))]
+fn write_epub(_document: &adoc::Document, _input_path: &str, _epub_path: &str) {
+    eprintln!("EPUB output requires building adoc with the `epub` feature");
+    process::exit(1);
+}
+
+fn write_latex(document: &adoc::Document, latex_path: &str) {
+    if let Err(e) = write_atomically(Path::new(latex_path), |out| out.write_all(document.to_latex().as_bytes())) {
+        eprintln!("Error writing file '{}': {}", latex_path, e);
+        process::exit(1);
+    }
+}
+
+#[cfg(feature = "pdf")]
+fn write_pdf(document: &adoc::Document, input_path: &str, pdf_path: &str, engine: Option<&str>) {
+    let mut options = adoc::pdf::PdfOptions {
+        base_dir: Path::new(input_path).parent().map(Path::to_path_buf).unwrap_or_default(),
+        ..Default::default()
+    };
+    if let Some(engine) = engine {
+        options.engine = engine.to_string();
+    }
+    
+    let result = document.to_pdf(&options)
+        .and_then(|data| write_atomically(Path::new(pdf_path), |out| out.write_all(&data)));
+    if let Err(e) = result {
+        eprintln!("Error writing PDF '{}': {}", pdf_path, e);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "pdf"))]
+fn write_pdf(_document: &adoc::Document, _input_path: &str, _pdf_path: &str, _engine: Option<&str>) {
+    eprintln!("PDF output requires building adoc with the `pdf` feature");
+    process::exit(1);
+}
+
+#[cfg(feature = "linkcheck")]
+fn check_links(input_paths: &[String], attributes: &[Attribute], base_dir: Option<&str>, options: &adoc::linkcheck::LinkCheckOptions) {
+    use adoc::linkcheck::{LinkChecker, LinkStatus, extract_external_links};
+    
+    let mut links = Vec::new();
+    for input_path in input_paths {
+        let content = read_input(input_path);
+        match parse_input(input_path, content.as_str(), attributes, base_dir) {
+            Ok(document) => links.extend(extract_external_links(input_path, content.as_str(), &document)),
+            Err(e) => {
+                eprintln!("Parse error in '{}': {}", input_path, e);
+                process::exit(1);
+            }
+        }
+    }
+    
+    let checker = match LinkChecker::new(options.clone()) {
+        Ok(checker) => checker,
+        Err(e) => {
+            eprintln!("Error creating link checker: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let dead = checker.dead_links(&links);
+    for report in &dead {
+        let location = match report.link.line {
+            Some(line) => format!("{}:{}", report.link.source, line),
+            None => report.link.source.clone(),
+        };
+        let reason = match &report.status {
+            LinkStatus::Dead(code) => format!("HTTP {}", code),
+            LinkStatus::Error(message) => message.clone(),
+            _ => String::new(),
+        };
+        println!("{}: {} ({})", location, report.link.url, reason);
+    }
+    
+    eprintln!("Checked {} links, {} dead", links.len(), dead.len());
+    if !dead.is_empty() {
+        process::exit(1);
     }
 }
+
+#[cfg(not(feature = "linkcheck"))]
+fn check_links(_input_paths: &[String], _attributes: &[Attribute], _base_dir: Option<&str>, _options: &adoc::linkcheck::LinkCheckOptions) {
+    eprintln!("Link checking requires building adoc with the `linkcheck` feature");
+    process::exit(1);
+}
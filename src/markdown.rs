@@ -0,0 +1,345 @@
+use std::collections::BTreeSet;
+
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+use crate::ast::*;
+use crate::error::ParseError;
+use crate::intern::Symbol;
+use crate::ids::DefaultIdGenerator;
+use crate::parser::{assign_section_ids, nest_sections};
+
+pub struct MarkdownParser;
+
+impl MarkdownParser {
+    // Markdown has no syntax errors; the result matches the AsciiDoc parser's
+    pub fn parse_document(input: &str) -> Result<Document, ParseError> {
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS
+            | Options::ENABLE_HEADING_ATTRIBUTES
+            | Options::ENABLE_GFM;
+        
+        let mut builder = DocumentBuilder::default();
+        for event in Parser::new_ext(input, options) {
+            builder.push(event);
+        }
+        Ok(builder.finish())
+    }
+}
+
+pub fn is_markdown(name: &str) -> bool {
+    name.ends_with(".md") || name.ends_with(".markdown")
+}
+
+#[derive(Default)]
+struct DocumentBuilder {
+    header: Option<Header>,
+    blocks: Vec<Block>,
+    // Open inline containers; the first one belongs to the current leaf block
+    inlines: Vec<Vec<InlineElement>>,
+    destinations: Vec<String>,
+    heading: Option<(usize, Option<String>)>,
+    code: Option<(String, DelimitedBlockKind, Option<Symbol>)>,
+    // Open lists, outermost first, with the items read so far
+    lists: Vec<(ListKind, Vec<ListItem>)>,
+    quote_depth: usize,
+    quote_text: String,
+    // How each column's cells are aligned
+    table_alignments: Vec<Option<CellAlignment>>,
+    table_rows: Vec<Vec<TableCell>>,
+    table_row: Vec<TableCell>,
+}
+
+impl DocumentBuilder {
+    fn push(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => match &mut self.code {
+                Some((content, _, _)) => content.push_str(&text),
+                None => self.push_text(&text),
+            },
+            Event::Code(code) => self.push_inline(InlineElement::Formatted {
+                kind: FormattedTextKind::Monospace,
+                content: vec![InlineElement::Text(code.to_string())],
+                attributes: InlineAttributes::default(),
+            }),
+            Event::InlineHtml(html) if html.trim().trim_end_matches('/').trim() == "<br" => {
+                self.push_inline(InlineElement::LineBreak)
+            }
+            Event::SoftBreak => self.push_text("\n"),
+            Event::HardBreak => self.push_inline(InlineElement::LineBreak),
+            Event::InlineHtml(html) => self.push_inline(InlineElement::Passthrough(html.to_string())),
+            Event::TaskListMarker(checked) => self.push_text(if checked { "[x] " } else { "[ ] " }),
+            // Block-level raw HTML has no counterpart in the document model
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading { level, id, .. } => {
+                self.heading = Some((heading_level(level), id.map(|id| id.to_string())));
+                self.begin_inlines();
+            }
+            Tag::Paragraph => {
+                // Paragraphs of a loose list item continue the item's content
+                if self.inlines.is_empty() {
+                    self.begin_inlines();
+                } else if !self.inlines[0].is_empty() {
+                    self.push_text("\n");
+                }
+            }
+            Tag::CodeBlock(kind) => {
+                self.code = Some(match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        let language = info.split_whitespace().next().map(Symbol::new);
+                        (String::new(), DelimitedBlockKind::Listing, language)
+                    }
+                    CodeBlockKind::Indented => (String::new(), DelimitedBlockKind::Literal, None),
+                });
+            }
+            Tag::BlockQuote(_) => self.quote_depth += 1,
+            Tag::List(start) => {
+                // A nested list follows the text of the item it belongs to
+                if !self.lists.is_empty() {
+                    self.flush_item();
+                }
+                let kind = if start.is_some() { ListKind::Ordered } else { ListKind::Unordered };
+                self.lists.push((kind, Vec::new()));
+            }
+            Tag::Item | Tag::TableCell => self.begin_inlines(),
+            Tag::Table(alignments) => {
+                self.table_alignments = alignments.iter()
+                    .map(|alignment| match alignment {
+                        Alignment::None => None,
+                        Alignment::Left => Some(CellAlignment::Left),
+                        Alignment::Center => Some(CellAlignment::Center),
+                        Alignment::Right => Some(CellAlignment::Right),
+                    })
+                    .collect();
+            }
+            Tag::TableHead | Tag::TableRow => self.table_row.clear(),
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough => self.inlines.push(Vec::new()),
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.destinations.push(dest_url.to_string());
+                self.inlines.push(Vec::new());
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Heading(_) => {
+                let title = plain_text(&self.take_inlines());
+                let Some((level, id)) = self.heading.take() else { return };
+                if level == 1 && self.header.is_none() && self.blocks.is_empty() {
+                    self.header = Some(Header { title, attributes: Vec::new() });
+                } else {
+                    self.blocks.push(Block::Section { level, title, id, number: None, blocks: Vec::new() });
+                }
+            }
+            TagEnd::Paragraph if self.lists.is_empty() => {
+                let content = trim_inlines(self.take_inlines());
+                self.emit(Block::Paragraph { content });
+            }
+            TagEnd::CodeBlock => {
+                if let Some((content, kind, language)) = self.code.take() {
+                    self.emit(Block::DelimitedBlock { kind, content, language, title: None });
+                }
+            }
+            TagEnd::BlockQuote(_) => {
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+                if self.quote_depth == 0 {
+                    let content = std::mem::take(&mut self.quote_text).trim_end().to_string();
+                    self.blocks.push(Block::DelimitedBlock { kind: DelimitedBlockKind::Quote(Attribution::default()), content, language: None, title: None });
+                }
+            }
+            TagEnd::List(_) => {
+                let Some((kind, items)) = self.lists.pop() else { return };
+                let list = Block::List { kind, items };
+                match self.lists.last_mut() {
+                    Some((parent_kind, parent_items)) => {
+                        if parent_items.is_empty() {
+                            parent_items.push(list_item(parent_kind, Vec::new()));
+                        }
+                        if let Some(item) = parent_items.last_mut() {
+                            item.blocks_mut().push(list);
+                        }
+                    }
+                    None => self.emit(list),
+                }
+            }
+            TagEnd::Item => self.flush_item(),
+            TagEnd::TableCell => {
+                let content = trim_inlines(self.take_inlines());
+                let align = self.table_alignments.get(self.table_row.len()).copied().flatten();
+                self.table_row.push(TableCell { align, ..TableCell::new(content) });
+            }
+            TagEnd::TableHead | TagEnd::TableRow => {
+                let row = std::mem::take(&mut self.table_row);
+                self.table_rows.push(row);
+            }
+            // Every Markdown table has a header row
+            TagEnd::Table => {
+                let rows = std::mem::take(&mut self.table_rows);
+                self.emit(Block::Table { title: None, header: true, rows });
+            }
+            TagEnd::Emphasis | TagEnd::Strong => {
+                let content = self.inlines.pop().unwrap_or_default();
+                let kind = if tag == TagEnd::Strong { FormattedTextKind::Strong } else { FormattedTextKind::Emphasis };
+                self.push_inline(InlineElement::Formatted { kind, content, attributes: InlineAttributes::default() });
+            }
+            // Struck-out text reads as deleted
+            TagEnd::Strikethrough => {
+                let content = self.inlines.pop().unwrap_or_default();
+                let attributes = InlineAttributes { id: None, roles: vec![Symbol::new("deleted")] };
+                self.push_inline(InlineElement::Formatted { kind: FormattedTextKind::Mark, content, attributes });
+            }
+            TagEnd::Link => {
+                let text = plain_text(&self.inlines.pop().unwrap_or_default());
+                let url = self.destinations.pop().unwrap_or_default();
+                let text = if text.is_empty() { None } else { Some(text) };
+                self.push_inline(link_element(url, text));
+            }
+            TagEnd::Image => {
+                let alt = plain_text(&self.inlines.pop().unwrap_or_default());
+                let path = self.destinations.pop().unwrap_or_default();
+                self.push_inline(InlineElement::Macro {
+                    kind: MacroKind::Image { path, attributes: if alt.is_empty() { None } else { Some(alt) } },
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn begin_inlines(&mut self) {
+        self.inlines = vec![Vec::new()];
+    }
+
+    fn take_inlines(&mut self) -> Vec<InlineElement> {
+        // Close any containers the events left open
+        while self.inlines.len() > 1 {
+            let content = self.inlines.pop().unwrap();
+            self.inlines.last_mut().unwrap().extend(content);
+        }
+        self.inlines.pop().unwrap_or_default()
+    }
+
+    fn push_inline(&mut self, element: InlineElement) {
+        if self.inlines.is_empty() {
+            self.begin_inlines();
+        }
+        self.inlines.last_mut().unwrap().push(element);
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if let Some(InlineElement::Text(last)) = self.inlines.last_mut().and_then(|inlines| inlines.last_mut()) {
+            last.push_str(text);
+        } else {
+            self.push_inline(InlineElement::Text(text.to_string()));
+        }
+    }
+
+    fn flush_item(&mut self) {
+        if self.inlines.is_empty() {
+            return;
+        }
+        let content = trim_inlines(self.take_inlines());
+        if let Some((kind, items)) = self.lists.last_mut() {
+            items.push(list_item(kind, content));
+        }
+    }
+
+    fn emit(&mut self, block: Block) {
+        // Quote blocks hold plain text, so nested blocks are flattened into it
+        if self.quote_depth > 0 {
+            if !self.quote_text.is_empty() {
+                self.quote_text.push('\n');
+            }
+            self.quote_text.push_str(&block_text(&block));
+            self.quote_text.push('\n');
+        } else {
+            self.blocks.push(block);
+        }
+    }
+
+    fn finish(self) -> Document {
+        let mut body = nest_sections(self.blocks);
+        assign_section_ids(&mut body, &DefaultIdGenerator, "_", "_", &mut BTreeSet::new());
+        Document { header: self.header, body }
+    }
+}
+
+fn list_item(kind: &ListKind, content: Vec<InlineElement>) -> ListItem {
+    match kind {
+        ListKind::Ordered => ListItem::Ordered { content, blocks: Vec::new() },
+        _ => ListItem::Unordered { content, blocks: Vec::new() },
+    }
+}
+
+fn heading_level(level: HeadingLevel) -> usize {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+// Relative links to other documents become cross references so workspaces can resolve them
+fn link_element(url: String, text: Option<String>) -> InlineElement {
+    let path = url.split('#').next().unwrap_or("");
+    let kind = if let Some(id) = url.strip_prefix('#') {
+        MacroKind::CrossReference { target: id.to_string(), text }
+    } else if !url.contains(':') && (is_markdown(path) || path.ends_with(".adoc")) {
+        MacroKind::CrossReference { target: url, text }
+    } else {
+        MacroKind::Link { url, text }
+    };
+    InlineElement::Macro { kind }
+}
+
+fn trim_inlines(mut content: Vec<InlineElement>) -> Vec<InlineElement> {
+    if let Some(InlineElement::Text(text)) = content.last_mut() {
+        let trimmed = text.trim_end().len();
+        text.truncate(trimmed);
+        if text.is_empty() {
+            content.pop();
+        }
+    }
+    content
+}
+
+fn block_text(block: &Block) -> String {
+    match block {
+        Block::Paragraph { content } => plain_text(content),
+        Block::Admonition { kind, content, blocks } => blocks.iter()
+            .fold(format!("{}: {}", kind.label(), plain_text(content)), |text, block| format!("{}\n{}", text.trim_end(), block_text(block))),
+        Block::DelimitedBlock { content, .. } => content.trim_end().to_string(),
+        Block::List { items, .. } => items.iter()
+            .map(|item| {
+                let text = match item {
+                    ListItem::Unordered { content, .. } => format!("* {}", plain_text(content)),
+                    ListItem::Ordered { content, .. } => format!(". {}", plain_text(content)),
+                    ListItem::Description { term, description, .. } => {
+                        format!("{}:: {}", term, description.as_deref().map(plain_text).unwrap_or_default())
+                    }
+                };
+                item.blocks().iter().fold(text, |text, block| format!("{}\n{}", text, block_text(block)))
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Section { title, .. } => title.clone(),
+        Block::Sidebar { blocks, .. } => blocks.iter().map(block_text).collect::<Vec<_>>().join("\n"),
+        Block::Table { rows, .. } => rows.iter()
+            .map(|row| row.iter().map(|cell| plain_text(&cell.content)).collect::<Vec<_>>().join(" | "))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Image { path, alt, .. } => image_alt(path, alt.as_deref()).to_string(),
+        Block::BlockMetadata { .. } | Block::TableOfContents { .. } | Block::PageBreak | Block::Comment { .. } => String::new(),
+    }
+}
@@ -1,47 +1,1260 @@
 use pest::Parser;
 use pest_derive::Parser;
+use alloc::borrow::Cow;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::sync::Arc;
+use core::cell::RefCell;
 use crate::ast::*;
+use crate::error::ParseError;
+use crate::context::parse_revision_line;
+use crate::include::{IncludeEdge, IncludeGraph, IncludeResolver, SourceMap, is_uri, nested_target};
+use crate::tags::{include_tags, select_tags};
+use crate::intern::{Symbol, SymbolPool};
+use crate::prelude::*;
+use crate::ids::{DefaultIdGenerator, IdGenerator};
+use crate::resource::{ResourceId, ResourceResolver};
+use crate::datetime;
+use crate::trace;
+
+const MAX_INCLUDE_DEPTH: usize = 64;
 
 #[derive(Parser)]
 #[grammar = "asciidoc.pest"]
 pub struct AsciiDocParser;
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SafeMode {
+    Unsafe,
+    Safe,
+    Server,
+    #[default]
+    Secure,
+}
+
+#[derive(Clone, Default)]
+pub struct ParseOptions {
+    pub safe: SafeMode,
+    pub include_resolver: Option<Arc<dyn IncludeResolver>>,
+    pub resource_resolver: Option<Arc<dyn ResourceResolver>>,
+    // Section ids come from `DefaultIdGenerator` unless set
+    pub id_generator: Option<Arc<dyn IdGenerator>>,
+    // Set from outside the document, replacing header entries of the same name
+    pub attributes: Vec<Attribute>,
+    // The converter the document is parsed for, `html5` unless set, which conditionals such
+    // as `ifdef::backend-pdf[]` test
+    pub backend: Option<String>,
+    // The path the document was read from, which `docname`, `docdir` and the like come from
+    pub docfile: Option<String>,
+    // Seconds since the Unix epoch that `localdate` and `localtime` show, in UTC. When unset
+    // they come from SOURCE_DATE_EPOCH or the clock, and are left out without `std` or when
+    // `:reproducible:` is set with nothing to pin them to.
+    pub now: Option<u64>,
+    // Keeps `//` lines and `////` blocks between blocks as `Block::Comment` for tooling.
+    // Lines inside a paragraph are dropped either way.
+    pub comments: bool,
+    // The inline substitutions applied to the whole document, which its `:no-replacements:`,
+    // `:no-smart-quotes:` and `:no-macros:` entries can turn off further
+    pub substitutions: Substitutions,
+}
+
+// Substitutions on inline text that can be turned off for a whole document, all on unless
+// set otherwise. Setting `no-replacements`, `no-smart-quotes` or `no-macros` turns one off
+// from that entry on, and unsetting it turns it back on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Substitutions {
+    // `(C)`, `(R)`, `(TM)`, `--`, `...`, the arrows and the apostrophe in `don't` as the
+    // characters they stand for
+    pub replacements: bool,
+    // ``"`text`"`` and ``'`text`'`` in curved quotation marks
+    pub smart_quotes: bool,
+    // Links, images, cross references and terms, bare URLs included
+    pub macros: bool,
+}
+
+impl Default for Substitutions {
+    fn default() -> Self {
+        Substitutions { replacements: true, smart_quotes: true, macros: true }
+    }
+}
+
+impl Substitutions {
+    // An entry setting or unsetting one of the `no-` attributes
+    fn apply(&mut self, name: &str, set: bool) {
+        let enabled = !set;
+        match name {
+            "no-replacements" => self.replacements = enabled,
+            "no-smart-quotes" => self.smart_quotes = enabled,
+            "no-macros" => self.macros = enabled,
+            _ => {}
+        }
+    }
+}
+
+pub const DEFAULT_BACKEND: &str = "html5";
+
+impl ParseOptions {
+    // Attributes a document can reference without setting them: where it was read from, what
+    // it is converted to and when. Entries in the document take their place.
+    pub fn intrinsic_attributes(&self) -> Vec<Attribute> {
+        self.intrinsics(self.attributes.iter().any(|attribute| attribute.name == "reproducible"))
+    }
+
+    fn intrinsics(&self, reproducible: bool) -> Vec<Attribute> {
+        let mut attributes = Vec::new();
+        let mut set = |name: &str, value: &str| attributes.push(Attribute { name: Symbol::new(name), value: Some(value.to_string()) });
+        
+        let backend = self.backend.as_deref().unwrap_or(DEFAULT_BACKEND);
+        set("backend", backend);
+        set("basebackend", base_backend(backend));
+        set("outfilesuffix", match backend {
+            "latex" => ".tex",
+            "pdf" => ".pdf",
+            "epub3" => ".epub",
+            _ => ".html",
+        });
+        if let Some(docfile) = &self.docfile {
+            let (docdir, file_name) = docfile.rsplit_once(['/', '\\']).unwrap_or(("", docfile));
+            let (docname, suffix) = match file_name.rfind('.') {
+                Some(dot) if dot > 0 => (&file_name[..dot], &file_name[dot..]),
+                _ => (file_name, ""),
+            };
+            set("docfile", docfile);
+            set("docdir", docdir);
+            set("docname", docname);
+            set("docfilesuffix", suffix);
+        }
+        if let Some(now) = self.now.or_else(|| clock(reproducible)) {
+            let (date, time) = datetime::format_date_time(now);
+            set("localyear", &date[..4]);
+            set("localdatetime", &format!("{} {}", date, time));
+            set("localdate", &date);
+            set("localtime", &time);
+        }
+        attributes
+    }
+}
+
+// A reproducible build only reads the time SOURCE_DATE_EPOCH pins
+#[cfg(feature = "std")]
+fn clock(reproducible: bool) -> Option<u64> {
+    match datetime::source_date_epoch() {
+        Some(epoch) => Some(epoch),
+        None if reproducible => None,
+        None => Some(datetime::build_timestamp()),
+    }
+}
+
+#[cfg(not(feature = "std"))]
+fn clock(_reproducible: bool) -> Option<u64> {
+    None
+}
+
+// HTML for EPUB, and otherwise the backend without its version
+fn base_backend(backend: &str) -> &str {
+    match backend {
+        "epub3" => "html",
+        backend => backend.trim_end_matches(|c: char| c.is_ascii_digit()),
+    }
+}
+
 impl AsciiDocParser {
-    pub fn parse_document(input: &str) -> Result<Document, Box<dyn std::error::Error>> {
-        let pairs = AsciiDocParser::parse(Rule::document, input)?;
+    pub fn parse_document(input: &str) -> Result<Document, ParseError> {
+        Self::parse_document_with_options(input, &ParseOptions::default())
+    }
+
+    pub fn parse_document_with_options(input: &str, options: &ParseOptions) -> Result<Document, ParseError> {
+        Self::parse_document_with_symbols(input, options, &mut SymbolPool::new())
+    }
+    
+    // Attribute names, roles and languages are taken from `symbols` as they are read, so the
+    // documents of a build parsed with one pool share a single allocation for each
+    pub fn parse_document_with_symbols(input: &str, options: &ParseOptions, symbols: &mut SymbolPool) -> Result<Document, ParseError> {
+        trace::span!(DEBUG, "parse", bytes = input.len());
+        let mut map = SourceMap::new("");
+        let input = {
+            trace::span!(DEBUG, "preprocess");
+            preprocess(input, options, 0, "", &mut Vec::new(), &mut map)
+        };
+        let mut pairs = {
+            trace::span!(DEBUG, "pest");
+            AsciiDocParser::parse(Rule::document, &input).map_err(|error| included_error(syntax_error(error), &map))?
+        };
+        
+        match pairs.next() {
+            Some(pair) if pair.as_rule() == Rule::document => {
+                let mut document = {
+                    trace::span!(DEBUG, "build");
+                    let shared = RefCell::new(core::mem::take(symbols));
+                    let document = parse_document_pair(pair, options, &shared);
+                    *symbols = shared.into_inner();
+                    document
+                };
+                if let Some(resolver) = &options.resource_resolver {
+                    trace::span!(DEBUG, "resolve_xrefs");
+                    resolve_resource_xrefs(&mut document.body, resolver.as_ref());
+                }
+                Ok(document)
+            }
+            _ => unreachable!(),
+        }
+    }
+    
+    // The header alone, the same as a full parse would give, for tools that index many files
+    // by title and attributes. Only the lines up to the first blank one are read.
+    pub fn parse_header_only(input: &str) -> Option<Header> {
+        Self::parse_header_only_with_options(input, &ParseOptions::default())
+    }
+
+    pub fn parse_header_only_with_options(input: &str, options: &ParseOptions) -> Option<Header> {
+        let end = input.split_inclusive('\n')
+            .take_while(|line| !line.trim().is_empty())
+            .map(str::len)
+            .sum();
+        let input = preprocess(&input[..end], options, 0, "", &mut Vec::new(), &mut SourceMap::new(""));
+        let pair = AsciiDocParser::parse(Rule::header, &input).ok()?.next()?;
+        let mut header = parse_header(pair, &mut SymbolPool::new());
+        override_attributes(&mut header.attributes, &options.attributes);
+        Some(header)
+    }
+    
+    // Pretty-prints the raw pest pairs with rule names, byte spans and line:column positions,
+    // for finding out why some syntax produced an unexpected tree
+    pub fn parse_tree(input: &str, options: &ParseOptions) -> Result<String, ParseError> {
+        let input = preprocess(input, options, 0, "", &mut Vec::new(), &mut SourceMap::new(""));
+        let pairs = AsciiDocParser::parse(Rule::document, &input).map_err(syntax_error)?;
         
+        let mut tree = String::new();
         for pair in pairs {
-            match pair.as_rule() {
-                Rule::document => return Ok(parse_document_pair(pair)),
-                _ => unreachable!(),
+            write_pair_tree(pair, 0, &mut tree);
+        }
+        Ok(tree)
+    }
+    
+    // Follows include directives the way parsing would, without parsing, so build tools can
+    // learn which files a document depends on
+    pub fn include_graph(name: &str, input: &str, options: &ParseOptions) -> IncludeGraph {
+        let mut edges = Vec::new();
+        preprocess(input, options, 0, name, &mut edges, &mut SourceMap::new(name));
+        IncludeGraph { root: name.to_string(), edges }
+    }
+    
+    // The text the grammar reads, with includes and conditionals applied, and where each of
+    // its lines was written
+    pub fn expand_includes(name: &str, input: &str, options: &ParseOptions) -> (String, SourceMap) {
+        let mut map = SourceMap::new(name);
+        let expanded = preprocess(input, options, 0, name, &mut Vec::new(), &mut map);
+        (expanded.into_owned(), map)
+    }
+}
+
+fn syntax_error(error: pest::error::Error<Rule>) -> ParseError {
+    let (line, column) = match error.line_col {
+        pest::error::LineColLocation::Pos(position) | pest::error::LineColLocation::Span(position, _) => position,
+    };
+    ParseError { document: None, line, column, message: error.to_string() }
+}
+
+// An error in an included file names the file and the line there
+fn included_error(error: ParseError, map: &SourceMap) -> ParseError {
+    match map.origin(error.line) {
+        Some(origin) if !map.is_root(error.line) => ParseError { document: Some(origin.file.to_string()), line: origin.line, ..error },
+        _ => error,
+    }
+}
+
+fn write_pair_tree(pair: pest::iterators::Pair<Rule>, depth: usize, tree: &mut String) {
+    const MAX_PREVIEW: usize = 60;
+    
+    let span = pair.as_span();
+    let (line, column) = pair.line_col();
+    tree.push_str(&format!("{}{:?} {}..{} @{}:{}", "  ".repeat(depth), pair.as_rule(), span.start(), span.end(), line, column));
+    
+    let mut inner = pair.clone().into_inner().peekable();
+    if inner.peek().is_none() {
+        let text = pair.as_str();
+        match text.char_indices().nth(MAX_PREVIEW) {
+            Some((end, _)) => tree.push_str(&format!(" {:?}...", &text[..end])),
+            None => tree.push_str(&format!(" {:?}", text)),
+        }
+    }
+    tree.push('\n');
+    
+    for child in inner {
+        write_pair_tree(child, depth + 1, tree);
+    }
+}
+
+fn preprocess<'a>(input: &'a str, options: &ParseOptions, depth: usize, parent: &str, includes: &mut Vec<IncludeEdge>, map: &mut SourceMap) -> Cow<'a, str> {
+    preprocess_lines(input, options, depth, parent, includes, map, &mut Defined::new(options))
+}
+
+// The attributes `ifdef` and `ifndef` test: those set from outside, the intrinsic `backend-*`
+// and `doctype-*` ones, and every entry read so far, including those in included files
+struct Defined {
+    names: BTreeSet<String>,
+    // Set from outside, so entries in the document do not change them
+    locked: BTreeSet<String>,
+}
+
+impl Defined {
+    fn new(options: &ParseOptions) -> Defined {
+        let backend = options.backend.as_deref().unwrap_or(DEFAULT_BACKEND);
+        let locked: BTreeSet<String> = options.attributes.iter().map(|attribute| attribute.name.to_string()).collect();
+        let mut names = locked.clone();
+        names.extend(options.intrinsic_attributes().into_iter().map(|attribute| attribute.name.to_string()));
+        names.extend(["doctype", "doctype-article"].map(String::from));
+        names.insert(format!("backend-{}", backend));
+        names.insert(format!("basebackend-{}", base_backend(backend)));
+        let mut defined = Defined { names, locked };
+        if let Some(doctype) = options.attributes.iter().find(|attribute| attribute.name == "doctype") {
+            defined.set_doctype(doctype.value.as_deref().unwrap_or_default());
+        }
+        defined
+    }
+    
+    // An attribute entry line, `:name: value`, `:name!:` or `:!name:`
+    fn read_entry(&mut self, line: &str) {
+        let Some((name, value)) = line.strip_prefix(':').and_then(|rest| rest.split_once(':')) else { return };
+        let unset = name.starts_with('!') || name.ends_with('!');
+        let name = name.trim_matches('!');
+        if !is_attribute_name(name) || self.locked.contains(name) {
+            return;
+        }
+        if name == "doctype" && !unset {
+            self.set_doctype(value.trim());
+        }
+        if unset {
+            self.names.remove(name);
+        } else {
+            self.names.insert(name.to_string());
+        }
+    }
+
+    fn set_doctype(&mut self, doctype: &str) {
+        self.names.retain(|name| !name.starts_with("doctype-"));
+        self.names.insert(format!("doctype-{}", doctype));
+    }
+    
+    // `a,b` holds when either is set and `a+b` when both are
+    fn test(&self, names: &str) -> bool {
+        if names.contains('+') {
+            names.split('+').all(|name| self.names.contains(name.trim()))
+        } else {
+            names.split(',').any(|name| self.names.contains(name.trim()))
+        }
+    }
+}
+
+enum Conditional<'a> {
+    // `ifdef::name[]` or `ifndef::name[]`, holding whether the lines up to `endif` are kept
+    Start(bool),
+    // The one-line form, `ifdef::name[text]`
+    Line(bool, &'a str),
+    End,
+}
+
+fn parse_conditional<'a>(line: &'a str, defined: &Defined) -> Option<Conditional<'a>> {
+    if line.starts_with("endif::") && line.ends_with(']') {
+        return Some(Conditional::End);
+    }
+    let (negated, rest) = match line.strip_prefix("ifdef::") {
+        Some(rest) => (false, rest),
+        None => (true, line.strip_prefix("ifndef::")?),
+    };
+    let (names, text) = rest.strip_suffix(']')?.split_once('[')?;
+    if names.is_empty() {
+        return None;
+    }
+    let holds = defined.test(names) != negated;
+    Some(if text.is_empty() { Conditional::Start(holds) } else { Conditional::Line(holds, text) })
+}
+
+// The input is borrowed as long as every line so far is kept as written, so a document
+// without includes or conditionals is parsed where it lies
+fn preprocess_lines<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+    depth: usize,
+    parent: &str,
+    includes: &mut Vec<IncludeEdge>,
+    map: &mut SourceMap,
+    defined: &mut Defined,
+) -> Cow<'a, str> {
+    let resolving = options.include_resolver.is_some() || options.resource_resolver.is_some();
+    let file = Some(parent).filter(|_| depth > 0);
+    // Whether each open conditional keeps its lines, innermost last. An `endif` without one
+    // open is dropped.
+    let mut conditions: Vec<bool> = Vec::new();
+    
+    let mut output = Cow::Borrowed("");
+    let mut offset = 0;
+    for (index, line) in input.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        let directive = line.trim_end_matches(['\n', '\r']);
+        let active = conditions.iter().all(|kept| *kept);
+        match parse_conditional(directive, defined) {
+            Some(Conditional::Start(holds)) => {
+                conditions.push(holds);
+                continue;
+            }
+            Some(Conditional::End) => {
+                conditions.pop();
+                continue;
+            }
+            Some(Conditional::Line(holds, text)) => {
+                if active && holds {
+                    let output = output.to_mut();
+                    output.push_str(text);
+                    output.push('\n');
+                    map.push(file, index + 1);
+                }
+                continue;
+            }
+            None if !active => continue,
+            None => {}
+        }
+        let Some((written, attributes)) = parse_include(directive) else {
+            if directive.starts_with(':') {
+                defined.read_entry(directive);
+            }
+            keep_line(&mut output, input, start, offset);
+            map.push(file, index + 1);
+            continue;
+        };
+        // An included file's own includes are read from its directory
+        let target = if depth > 0 { &nested_target(parent, written) } else { written };
+        
+        // URI includes fetch remote content, so they are only honored in unsafe mode
+        let content = if !resolving || depth >= MAX_INCLUDE_DEPTH || (is_uri(target) && options.safe >= SafeMode::Safe) {
+            None
+        } else {
+            resolve_include(target, options).map(|content| match include_tags(attributes) {
+                Some(tags) if !tags.is_empty() => select_tags(&content, &tags).unwrap_or_default(),
+                _ => content,
+            })
+        };
+        includes.push(IncludeEdge {
+            parent: parent.to_string(),
+            target: target.to_string(),
+            line: index + 1,
+            depth,
+            resolved: content.is_some(),
+        });
+        
+        match content {
+            Some(content) => {
+                trace::event!(DEBUG, target, depth, bytes = content.len(), "include resolved");
+                let output = output.to_mut();
+                output.push_str(&preprocess_lines(&content, options, depth + 1, target, includes, map, defined));
+                if !output.ends_with('\n') {
+                    output.push('\n');
+                }
+            }
+            // Without a resolver the directive is left for the parser as written
+            None if !resolving => {
+                keep_line(&mut output, input, start, offset);
+                map.push(file, index + 1);
             }
+            None => {
+                trace::event!(WARN, target, depth, "include unresolved");
+                output.to_mut().push_str(&format!("Unresolved directive - {}\n", directive));
+                map.push(file, index + 1);
+            }
+        }
+    }
+    
+    output
+}
+
+// Adds `input[start..end]` as written, still borrowing when nothing before it was changed
+fn keep_line<'a>(output: &mut Cow<'a, str>, input: &'a str, start: usize, end: usize) {
+    match output {
+        Cow::Borrowed(kept) if kept.len() == start => *output = Cow::Borrowed(&input[..end]),
+        _ => output.to_mut().push_str(&input[start..end]),
+    }
+}
+
+fn resolve_include(target: &str, options: &ParseOptions) -> Option<String> {
+    if let Some(resolver) = &options.resource_resolver
+        && !is_uri(target)
+        && let Some(id) = ResourceId::parse(target)
+        && id.is_qualified()
+    {
+        return resolver.read_include(&id).ok();
+    }
+    
+    options.include_resolver.as_ref()?.resolve(target).ok()
+}
+
+fn resolve_resource_xrefs(blocks: &mut [Block], resolver: &dyn ResourceResolver) {
+    walk_block_inlines_mut(blocks, &mut |element| {
+        let InlineElement::Macro { kind: MacroKind::CrossReference { target, text } } = element else {
+            return;
+        };
+        let Some(id) = ResourceId::parse(target) else {
+            return;
+        };
+        // Plain `<<id>>` references stay within the current document
+        if !id.is_qualified() && !id.path.ends_with(".adoc") {
+            return;
         }
         
-        unreachable!()
+        if let Some(resolved) = resolver.resolve_xref(&id) {
+            let text = text.clone().or(resolved.title);
+            *element = InlineElement::Macro {
+                kind: MacroKind::Link { url: resolved.url, text },
+            };
+        }
+    });
+}
+
+// The target and attribute list of an include directive
+fn parse_include(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix("include::")?;
+    let bracket = rest.find('[')?;
+    if !rest.ends_with(']') || bracket == 0 {
+        return None;
     }
+    Some((&rest[..bracket], &rest[bracket + 1..rest.len() - 1]))
 }
 
-fn parse_document_pair(pair: pest::iterators::Pair<Rule>) -> Document {
+// Without a header, external attributes still apply while parsing but are not kept
+fn parse_document_pair(pair: pest::iterators::Pair<Rule>, options: &ParseOptions, symbols: &Symbols) -> Document {
+    let external = options.attributes.as_slice();
     let mut header = None;
     let mut body = Vec::new();
+    let mut intrinsics = BTreeMap::new();
     
     for inner_pair in pair.into_inner() {
         match inner_pair.as_rule() {
             Rule::header => {
-                header = Some(parse_header(inner_pair));
+                let mut parsed = parse_header(inner_pair, &mut symbols.borrow_mut());
+                override_attributes(&mut parsed.attributes, external);
+                header = Some(parsed);
             }
             Rule::body => {
-                body = parse_body(inner_pair);
+                let start = body_start(document_attributes(&header, external), options);
+                intrinsics = start.intrinsics;
+                let counters = RefCell::new(BTreeMap::new());
+                body = parse_body(inner_pair, BodyContext {
+                    hardbreaks: start.hardbreaks,
+                    comments: options.comments,
+                    subs: start.subs,
+                    attributes: &start.values,
+                    counters: &counters,
+                    symbols,
+                    table: &[],
+                });
             }
             Rule::EOI => break,
             _ => {}
         }
     }
     
+    let attributes = document_attributes(&header, external);
+    let id_prefix = find_attribute(attributes, "idprefix").unwrap_or("_");
+    let id_separator = find_attribute(attributes, "idseparator").unwrap_or("_");
+    let mut used_ids = BTreeSet::new();
+    let id_generator = options.id_generator.as_deref().unwrap_or(&DefaultIdGenerator);
+    assign_section_ids(&mut body, id_generator, id_prefix, id_separator, &mut used_ids);
+    number_sections(&mut body, attributes);
+    substitute_verbatim_attributes(&mut body, &intrinsics, attributes);
+    link_glossary_terms(&mut body);
+    
     Document { header, body }
 }
 
-fn parse_header(pair: pest::iterators::Pair<Rule>) -> Header {
+// What references, hard breaks and substitutions in the body start from: the header's
+// entries, or those set from outside, over the intrinsic attributes
+struct BodyStart {
+    hardbreaks: bool,
+    subs: Substitutions,
+    intrinsics: BTreeMap<Symbol, String>,
+    values: BTreeMap<Symbol, String>,
+}
+
+fn body_start(attributes: &[Attribute], options: &ParseOptions) -> BodyStart {
+    let hardbreaks = attributes.iter().any(|attribute| is_hardbreaks_attribute(&attribute.name));
+    let mut subs = options.substitutions;
+    for attribute in attributes {
+        subs.apply(&attribute.name, true);
+    }
+    let reproducible = find_attribute(attributes, "reproducible").is_some();
+    let mut intrinsics: BTreeMap<Symbol, String> = options.intrinsics(reproducible).into_iter()
+        .map(|attribute| (attribute.name, attribute.value.unwrap_or_default()))
+        .collect();
+    for attribute in attributes {
+        if let Some(value) = intrinsics.get_mut(&attribute.name) {
+            *value = attribute.value.clone().unwrap_or_default();
+        }
+    }
+    let mut values = intrinsics.clone();
+    for attribute in attributes {
+        define_attribute(&mut values, attribute);
+    }
+    BodyStart { hardbreaks, subs, intrinsics, values }
+}
+
+// A document read a piece at a time, each piece ending between two blocks at the top level,
+// with what one piece leaves set carried into the next: attribute entries, counters, section
+// ids and numbers. Glossary terms are left unlinked, since their entries may come later.
+#[cfg(feature = "std")]
+pub(crate) struct BodyReader {
+    options: ParseOptions,
+    defined: Defined,
+    // Read with the first piece, which is parsed as the start of a document
+    header: Option<Header>,
+    started: bool,
+    hardbreaks: bool,
+    subs: Substitutions,
+    values: BTreeMap<Symbol, String>,
+    counters: Counters,
+    symbols: Symbols,
+    used_ids: BTreeSet<String>,
+    numbering: SectionNumbering,
+    // What `{name}` gives in listing and literal blocks marked `subs=attributes+`, and
+    // whether the block coming up is marked so
+    verbatim: BTreeMap<Symbol, String>,
+    subs_attributes: bool,
+}
+
+#[cfg(feature = "std")]
+impl BodyReader {
+    pub(crate) fn new(options: ParseOptions) -> BodyReader {
+        BodyReader {
+            defined: Defined::new(&options),
+            options,
+            header: None,
+            started: false,
+            hardbreaks: false,
+            subs: Substitutions::default(),
+            values: BTreeMap::new(),
+            counters: RefCell::new(BTreeMap::new()),
+            symbols: RefCell::new(SymbolPool::new()),
+            used_ids: BTreeSet::new(),
+            numbering: SectionNumbering::new(&[]),
+            verbatim: BTreeMap::new(),
+            subs_attributes: false,
+        }
+    }
+
+    pub(crate) fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    pub(crate) fn started(&self) -> bool {
+        self.started
+    }
+    
+    // The piece's blocks with sections flattened: each comes out as its heading alone,
+    // followed by what it holds. `line` is where the piece starts in the document.
+    pub(crate) fn read(&mut self, piece: &str, line: usize) -> Result<Vec<Block>, ParseError> {
+        let mut map = SourceMap::new("");
+        let input = preprocess_lines(piece, &self.options, 0, "", &mut Vec::new(), &mut map, &mut self.defined);
+        let rule = if self.started { Rule::nested_body } else { Rule::document };
+        let pairs = AsciiDocParser::parse(rule, &input).map_err(|error| match included_error(syntax_error(error), &map) {
+            error if error.document.is_none() => ParseError { line: error.line + line - 1, ..error },
+            error => error,
+        })?;
+        
+        let mut blocks = Vec::new();
+        for inner_pair in pairs.flat_map(|pair| pair.into_inner()) {
+            match inner_pair.as_rule() {
+                Rule::header => {
+                    let mut header = parse_header(inner_pair, &mut self.symbols.borrow_mut());
+                    override_attributes(&mut header.attributes, &self.options.attributes);
+                    self.header = Some(header);
+                }
+                Rule::body => {
+                    self.start();
+                    blocks = self.read_body(inner_pair);
+                }
+                _ => {}
+            }
+        }
+        Ok(blocks)
+    }
+
+    fn start(&mut self) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        let attributes = document_attributes(&self.header, &self.options.attributes);
+        let start = body_start(attributes, &self.options);
+        self.hardbreaks = start.hardbreaks;
+        self.subs = start.subs;
+        self.values = start.values;
+        self.numbering = SectionNumbering::new(attributes);
+        self.verbatim = start.intrinsics;
+        self.verbatim.extend(attributes.iter().map(|attribute| (attribute.name.clone(), attribute.value.clone().unwrap_or_default())));
+    }
+
+    fn read_body(&mut self, pair: pest::iterators::Pair<Rule>) -> Vec<Block> {
+        let context = BodyContext {
+            hardbreaks: self.hardbreaks,
+            comments: self.options.comments,
+            subs: self.subs,
+            attributes: &self.values,
+            counters: &self.counters,
+            symbols: &self.symbols,
+            table: &[],
+        };
+        let mut blocks = parse_body(pair, context);
+        
+        let attributes = document_attributes(&self.header, &self.options.attributes);
+        let id_prefix = find_attribute(attributes, "idprefix").unwrap_or("_");
+        let id_separator = find_attribute(attributes, "idseparator").unwrap_or("_");
+        let id_generator = self.options.id_generator.as_deref().unwrap_or(&DefaultIdGenerator);
+        assign_section_ids(&mut blocks, id_generator, id_prefix, id_separator, &mut self.used_ids);
+        self.numbering.number(&mut blocks);
+        substitute_verbatim_blocks(&mut blocks, &mut self.verbatim, &mut self.subs_attributes);
+        if let Some(resolver) = &self.options.resource_resolver {
+            resolve_resource_xrefs(&mut blocks, resolver.as_ref());
+        }
+        
+        let mut flat = Vec::new();
+        flatten_sections(blocks, &mut flat);
+        for block in &flat {
+            apply_entry(block, &mut self.hardbreaks, &mut self.subs, &mut self.values);
+        }
+        flat
+    }
+}
+
+// Glossary entries are anchored by their term, so `term:[API]` links to the entry for
+// "API". Terms are matched without regard to case.
+fn link_glossary_terms(body: &mut [Block]) {
+    let entries = glossary_entries(body);
+    if entries.is_empty() {
+        return;
+    }
+    walk_block_inlines_mut(body, &mut |element| {
+        if let InlineElement::Macro { kind: MacroKind::Term { term, target } } = element {
+            *target = entries.iter()
+                .find(|(entry, _)| entry.eq_ignore_ascii_case(term.trim()))
+                .map(|(_, id)| id.clone());
+        }
+    });
+}
+
+// Each term of a glossary list with the id its entry is anchored at, in document order
+pub(crate) fn glossary_entries(blocks: &[Block]) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    for block in blocks {
+        match block {
+            Block::Section { blocks, .. } | Block::Sidebar { blocks, .. } => entries.extend(glossary_entries(blocks)),
+            Block::List { kind: ListKind::Description(DescriptionListStyle::Glossary), items } => {
+                for item in items {
+                    if let ListItem::Description { term, .. } = item {
+                        entries.push((term.clone(), glossary_id(term)));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+pub(crate) fn glossary_id(term: &str) -> String {
+    generate_id(term, "_glossary_", "_")
+}
+
+fn document_attributes<'a>(header: &'a Option<Header>, external: &'a [Attribute]) -> &'a [Attribute] {
+    header.as_ref().map_or(external, |header| header.attributes.as_slice())
+}
+
+fn find_attribute<'a>(attributes: &'a [Attribute], name: &str) -> Option<&'a str> {
+    attributes.iter()
+        .find(|attribute| attribute.name == name)
+        .map(|attribute| attribute.value.as_deref().unwrap_or(""))
+}
+
+fn override_attributes(attributes: &mut Vec<Attribute>, external: &[Attribute]) {
+    for attribute in external {
+        match attributes.iter_mut().find(|existing| existing.name == attribute.name) {
+            Some(existing) => existing.value = attribute.value.clone(),
+            None => attributes.push(attribute.clone()),
+        }
+    }
+}
+
+pub(crate) fn assign_section_ids(
+    blocks: &mut [Block],
+    generator: &dyn IdGenerator,
+    prefix: &str,
+    separator: &str,
+    used_ids: &mut BTreeSet<String>,
+) {
+    for block in blocks {
+        if let Block::Section { title, id, blocks: children, .. } = block {
+            match id {
+                Some(id) => {
+                    used_ids.insert(id.clone());
+                }
+                None => {
+                    *id = Some(unique_id(generator.generate_id(title, prefix, separator), separator, used_ids));
+                }
+            }
+            assign_section_ids(children, generator, prefix, separator, used_ids);
+        }
+    }
+}
+
+// Section numbers follow `:sectnums:` (or the older `:numbered:`) down to `sectnumlevels`.
+// Numbering can be switched off and on again between sections, and a section marked
+// `%unnumbered` is skipped along with its subsections without consuming a number.
+// Books also number their parts with `:partnums:` and can prefix part and chapter
+// numbers with a signifier such as "Part" or "Chapter".
+pub(crate) fn number_sections(blocks: &mut [Block], attributes: &[Attribute]) {
+    SectionNumbering::new(attributes).number(blocks);
+}
+
+const DEFAULT_SECTNUMLEVELS: usize = 3;
+
+struct SectionNumbering {
+    enabled: bool,
+    max_depth: usize,
+    counters: Vec<usize>,
+    unnumbered_next: bool,
+    // The level of each section the next one may sit in, outermost first, and whether it is
+    // unnumbered. A document read in pieces carries this over from one piece to the next.
+    open: Vec<(usize, bool)>,
+    book: bool,
+    part_numbers: bool,
+    parts: usize,
+    part_signifier: Option<String>,
+    chapter_signifier: Option<String>,
+}
+
+impl SectionNumbering {
+    fn new(attributes: &[Attribute]) -> SectionNumbering {
+        let mut numbering = SectionNumbering {
+            enabled: false,
+            max_depth: DEFAULT_SECTNUMLEVELS,
+            counters: Vec::new(),
+            unnumbered_next: false,
+            open: Vec::new(),
+            book: false,
+            part_numbers: false,
+            parts: 0,
+            part_signifier: None,
+            chapter_signifier: None,
+        };
+        for attribute in attributes {
+            numbering.set(&attribute.name, Some(attribute.value.as_deref().unwrap_or("")));
+        }
+        numbering
+    }
+
+    fn number(&mut self, blocks: &mut [Block]) {
+        for block in blocks {
+            match block {
+                Block::Section { level, number, blocks, .. } => {
+                    while self.open.last().is_some_and(|(open, _)| open >= level) {
+                        self.open.pop();
+                    }
+                    let suppressed = self.open.last().is_some_and(|(_, unnumbered)| *unnumbered);
+                    let depth = level.saturating_sub(1);
+                    let unnumbered = suppressed || core::mem::take(&mut self.unnumbered_next);
+                    self.open.push((*level, unnumbered));
+                    *number = if unnumbered {
+                        None
+                    } else if depth == 0 {
+                        self.part_number()
+                    } else {
+                        self.section_number(depth)
+                    };
+                    self.number(blocks);
+                }
+                Block::BlockMetadata { kind } => match kind {
+                    BlockMetadataKind::AttributeEntry(attribute) => {
+                        self.set(&attribute.name, Some(attribute.value.as_deref().unwrap_or("")));
+                    }
+                    BlockMetadataKind::AttributeUnset(name) => self.set(name, None),
+                    BlockMetadataKind::Attribute(entries) if has_option(entries, "unnumbered") => self.unnumbered_next = true,
+                    _ => {}
+                },
+                _ => self.unnumbered_next = false,
+            }
+        }
+    }
+    
+    // Parts only exist in books and use roman numerals that run independently of chapters
+    fn part_number(&mut self) -> Option<String> {
+        if !self.book || !self.part_numbers {
+            return None;
+        }
+        self.parts += 1;
+        Some(with_signifier(&self.part_signifier, &format!("{}.", roman_numeral(self.parts))))
+    }
+
+    fn section_number(&mut self, depth: usize) -> Option<String> {
+        if !self.enabled || depth > self.max_depth {
+            return None;
+        }
+        self.counters.resize(depth, 0);
+        self.counters[depth - 1] += 1;
+        let number: String = self.counters.iter().map(|n| format!("{}.", n)).collect();
+        if self.book && depth == 1 {
+            Some(with_signifier(&self.chapter_signifier, &number))
+        } else {
+            Some(number)
+        }
+    }
+    
+    // `value` is None when the attribute is unset
+    fn set(&mut self, name: &str, value: Option<&str>) {
+        let text = value.filter(|value| !value.is_empty()).map(str::to_string);
+        match name {
+            "sectnums" | "numbered" => self.enabled = value.is_some(),
+            "sectnumlevels" => {
+                self.max_depth = value.and_then(|value| value.parse().ok()).unwrap_or(DEFAULT_SECTNUMLEVELS).min(5);
+            }
+            "doctype" => self.book = value == Some("book"),
+            "partnums" => self.part_numbers = value.is_some(),
+            "part-signifier" => self.part_signifier = text,
+            "chapter-signifier" => self.chapter_signifier = text,
+            _ => {}
+        }
+    }
+}
+
+fn with_signifier(signifier: &Option<String>, number: &str) -> String {
+    match signifier {
+        Some(signifier) => format!("{} {}", signifier, number),
+        None => number.to_string(),
+    }
+}
+
+fn roman_numeral(mut n: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut numeral = String::new();
+    for (value, symbol) in NUMERALS {
+        while n >= value {
+            numeral.push_str(symbol);
+            n -= value;
+        }
+    }
+    numeral
+}
+
+// Matches both the `[%name]` shorthand and `[options=name]`
+fn has_option(entries: &[Symbol], option: &str) -> bool {
+    entries.iter().any(|entry| {
+        if let Some(options) = entry.strip_prefix("options=").or_else(|| entry.strip_prefix("opts=")) {
+            return options.trim_matches('"').split(',').any(|name| name.trim() == option);
+        }
+        entry.split('%').skip(1).any(|name| name == option)
+    })
+}
+
+// Listing and literal blocks are verbatim, but `subs=attributes+` (or `+attributes`) in their
+// attribute list lets `{name}` references through, using the header attributes and any
+// entries set above the block. The other substitutions stay off.
+fn substitute_verbatim_attributes(blocks: &mut [Block], intrinsics: &BTreeMap<Symbol, String>, attributes: &[Attribute]) {
+    let mut values = intrinsics.clone();
+    values.extend(attributes.iter().map(|attribute| (attribute.name.clone(), attribute.value.clone().unwrap_or_default())));
+    let mut subs_attributes = false;
+    substitute_verbatim_blocks(blocks, &mut values, &mut subs_attributes);
+}
+
+fn substitute_verbatim_blocks(blocks: &mut [Block], values: &mut BTreeMap<Symbol, String>, subs_attributes: &mut bool) {
+    for block in blocks {
+        match block {
+            Block::BlockMetadata { kind: BlockMetadataKind::AttributeEntry(attribute) } => {
+                define_attribute(values, attribute);
+            }
+            Block::BlockMetadata { kind: BlockMetadataKind::AttributeUnset(name) } => {
+                values.remove(name);
+            }
+            Block::BlockMetadata { kind: BlockMetadataKind::Attribute(entries) } => {
+                *subs_attributes = has_attributes_sub(entries);
+            }
+            Block::BlockMetadata { .. } => {}
+            Block::DelimitedBlock { kind: DelimitedBlockKind::Listing | DelimitedBlockKind::Literal, content, .. } => {
+                if *subs_attributes {
+                    *content = substitute_attributes(content, values);
+                }
+                *subs_attributes = false;
+            }
+            Block::Section { blocks: children, .. } | Block::Sidebar { blocks: children, .. } => {
+                *subs_attributes = false;
+                substitute_verbatim_blocks(children, values, subs_attributes);
+            }
+            _ => *subs_attributes = false,
+        }
+    }
+}
+
+fn has_attributes_sub(entries: &[Symbol]) -> bool {
+    entries.iter()
+        .filter_map(|entry| entry.strip_prefix("subs="))
+        .flat_map(|subs| subs.trim_matches('"').split(','))
+        .any(|sub| sub.trim().trim_matches('+') == "attributes")
+}
+
+// Attributes every document defines, so prose can spell out characters that would otherwise
+// be read as markup
+const INTRINSIC_ATTRIBUTES: &[(&str, &str)] = &[
+    ("empty", ""),
+    ("sp", " "),
+    ("nbsp", "\u{a0}"),
+    ("zwsp", "\u{200b}"),
+    ("lbrace", "{"),
+    ("rbrace", "}"),
+];
+
+// References to attributes that are not defined are left as written unless `attribute-missing`
+// is `drop` (the reference is removed) or `drop-line` (the whole line is), and `warn` traces
+// them. A backslash in front of a reference (`\{name}`) keeps the braces without substituting.
+pub(crate) fn substitute_attributes(text: &str, values: &BTreeMap<Symbol, String>) -> String {
+    substitute_references(text, values, None)
+}
+
+// An attribute's value as text gets it, and the chain of references that came back round or
+// went too deep on the way
+pub(crate) fn resolve_attribute(name: &str, values: &BTreeMap<Symbol, String>) -> (String, Option<Vec<Symbol>>) {
+    let mut references = References::new(values, None, None);
+    let value = references.substitute(&format!("{{{}}}", name));
+    (value, references.cycle)
+}
+
+// Counters are only stepped with somewhere to keep them, and are left as written otherwise
+fn substitute_references(text: &str, values: &BTreeMap<Symbol, String>, counters: Option<&Counters>) -> String {
+    References::new(values, counters, None).substitute(text)
+}
+
+// How deep references in attribute values are followed before giving up, which only values
+// referring forward to attributes set after them ever need
+const ATTRIBUTE_DEPTH_LIMIT: usize = 8;
+
+// References in a value that were left unresolved when it was set, because they name an
+// attribute set later, are followed when the value is used. While an entry is being defined,
+// escaped references and the character attributes (`{lbrace}`, `{sp}`, ...) are kept as
+// written, so that only the text the value ends up in loses them.
+struct References<'a> {
+    values: &'a BTreeMap<Symbol, String>,
+    counters: Option<&'a Counters>,
+    mode: &'a str,
+    defining: bool,
+    // The attributes whose values are being substituted, outermost first
+    expanding: Vec<Symbol>,
+    // The first chain of references that led back to an attribute being substituted, or
+    // deeper than the limit
+    cycle: Option<Vec<Symbol>>,
+}
+
+impl<'a> References<'a> {
+    fn new(values: &'a BTreeMap<Symbol, String>, counters: Option<&'a Counters>, defining: Option<&Symbol>) -> Self {
+        References {
+            values,
+            counters,
+            mode: values.get("attribute-missing").map_or("skip", String::as_str),
+            defining: defining.is_some(),
+            expanding: defining.into_iter().cloned().collect(),
+            cycle: None,
+        }
+    }
+
+    fn substitute(&mut self, text: &str) -> String {
+        if self.mode == "drop-line" {
+            return text.split_inclusive('\n')
+                .map(|line| self.replace(line))
+                .filter_map(|(line, missing)| (!missing).then_some(line))
+                .collect();
+        }
+        self.replace(text).0
+    }
+    
+    // The text with its references replaced, and whether any of them was missing
+    fn replace(&mut self, text: &str) -> (String, bool) {
+        let mut result = String::with_capacity(text.len());
+        let mut missing = false;
+        let mut rest = text;
+        
+        while let Some(start) = rest.find('{') {
+            let after = &rest[start + 1..];
+            let Some(name) = after.find('}').map(|end| &after[..end]).filter(|name| {
+                is_attribute_name(name) || self.counters.is_some() && counter_reference(name).is_some()
+            }) else {
+                result.push_str(&rest[..=start]);
+                rest = after;
+                continue;
+            };
+            
+            let reference = &rest[start..start + name.len() + 2];
+            if rest[..start].ends_with('\\') {
+                result.push_str(&rest[..start - usize::from(!self.defining)]);
+                result.push_str(reference);
+            } else if let Some(counters) = self.counters
+                && let Some((shown, counter, initial)) = counter_reference(name)
+            {
+                result.push_str(&rest[..start]);
+                let value = step_counter(counters, self.values, counter, initial);
+                if shown {
+                    result.push_str(&value);
+                }
+            } else {
+                result.push_str(&rest[..start]);
+                let counted = self.counters.and_then(|counters| counters.borrow().get(name).cloned());
+                if let Some(value) = counted {
+                    result.push_str(&value);
+                } else if let Some(value) = self.attribute(name, reference) {
+                    missing |= value.1;
+                    result.push_str(&value.0);
+                } else if let Some(value) = intrinsic_attribute(name) {
+                    result.push_str(if self.defining { reference } else { value });
+                } else {
+                    if self.mode == "warn" {
+                        trace::event!(WARN, name, "attribute missing");
+                    }
+                    missing = true;
+                    if self.mode != "drop" {
+                        result.push_str(reference);
+                    }
+                }
+            }
+            rest = &after[name.len() + 1..];
+        }
+        
+        result.push_str(rest);
+        (result, missing)
+    }
+    
+    // An attribute's value with the references in it substituted in turn. An entry referring to
+    // the attribute it sets gets the value from before; anything else that comes back round to
+    // an attribute being substituted is a cycle, and the reference closing it is left as written.
+    fn attribute(&mut self, name: &str, reference: &str) -> Option<(String, bool)> {
+        let expanding = self.expanding.iter().any(|expanding| expanding == name);
+        let previous = self.defining && self.expanding.len() == 1;
+        let value = self.values.get(name);
+        if expanding && !(previous && value.is_some()) {
+            return Some(self.cycle(name, reference));
+        }
+        let value = value?;
+        if !value.contains('{') {
+            return Some((value.clone(), false));
+        }
+        if self.expanding.len() > ATTRIBUTE_DEPTH_LIMIT {
+            return Some(self.cycle(name, reference));
+        }
+        self.expanding.push(Symbol::from(name));
+        let substituted = self.replace(value);
+        self.expanding.pop();
+        Some(substituted)
+    }
+
+    fn cycle(&mut self, name: &str, reference: &str) -> (String, bool) {
+        trace::event!(WARN, name, "attribute reference cycle");
+        if self.cycle.is_none() {
+            self.cycle = Some(self.expanding.iter().cloned().chain([Symbol::from(name)]).collect());
+        }
+        (reference.to_string(), false)
+    }
+}
+
+// `counter:name` and `counter2:name`, which counts without showing the value, each with an
+// optional initial value after another colon
+fn counter_reference(reference: &str) -> Option<(bool, &str, Option<&str>)> {
+    let (shown, rest) = match reference.split_once(':')? {
+        ("counter", rest) => (true, rest),
+        ("counter2", rest) => (false, rest),
+        _ => return None,
+    };
+    let (name, initial) = match rest.split_once(':') {
+        Some((name, initial)) => (name, Some(initial).filter(|initial| !initial.is_empty())),
+        None => (rest, None),
+    };
+    is_attribute_name(name).then_some((shown, name, initial))
+}
+
+// A counter starts at its initial value, 1 unless given, and then counts up from a number or
+// on through the alphabet from a letter. An attribute of the same name gives the value it
+// counts on from.
+fn step_counter(counters: &Counters, values: &BTreeMap<Symbol, String>, name: &str, initial: Option<&str>) -> String {
+    let mut counters = counters.borrow_mut();
+    let current = counters.get(name).or_else(|| values.get(name));
+    let next = match current.map(String::as_str) {
+        Some(current) => match current.parse::<i64>() {
+            Ok(number) => (number + 1).to_string(),
+            Err(_) => {
+                let mut chars = current.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(letter), None) if letter.is_ascii_alphabetic() && !matches!(letter, 'z' | 'Z') => {
+                        char::from(letter as u8 + 1).to_string()
+                    }
+                    _ => initial.unwrap_or("1").to_string(),
+                }
+            }
+        },
+        None => initial.unwrap_or("1").to_string(),
+    };
+    counters.insert(Symbol::from(name), next.clone());
+    next
+}
+
+fn intrinsic_attribute(name: &str) -> Option<&'static str> {
+    INTRINSIC_ATTRIBUTES.iter().find(|(intrinsic, _)| *intrinsic == name).map(|(_, value)| *value)
+}
+
+pub(crate) fn is_attribute_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn attach_section_anchors(blocks: &mut Vec<Block>) {
+    let mut i = 0;
+    while i < blocks.len() {
+        // An anchor directly above a section becomes the section's id
+        if let Block::BlockMetadata { kind: BlockMetadataKind::Anchor(anchor) } = &blocks[i]
+            && matches!(blocks.get(i + 1), Some(Block::Section { id: None, .. }))
+        {
+            let anchor = anchor.clone();
+            blocks.remove(i);
+            if let Block::Section { id, .. } = &mut blocks[i] {
+                *id = Some(anchor);
+            }
+        }
+        i += 1;
+    }
+}
+
+pub(crate) fn generate_id(title: &str, prefix: &str, separator: &str) -> String {
+    let mut id = String::from(prefix);
+    let mut pending_separator = false;
+    
+    for ch in title.to_lowercase().chars() {
+        if ch.is_alphanumeric() {
+            if pending_separator && id.len() > prefix.len() {
+                id.push_str(separator);
+            }
+            pending_separator = false;
+            id.push(ch);
+        } else if ch == ' ' || ch == '-' || ch == '.' || ch == '_' {
+            pending_separator = true;
+        }
+    }
+    
+    id
+}
+
+pub(crate) fn unique_id(id: String, separator: &str, used_ids: &mut BTreeSet<String>) -> String {
+    let mut candidate = id.clone();
+    let mut counter = 2;
+    while used_ids.contains(&candidate) {
+        candidate = format!("{}{}{}", id, separator, counter);
+        counter += 1;
+    }
+    used_ids.insert(candidate.clone());
+    candidate
+}
+
+fn parse_header(pair: pest::iterators::Pair<Rule>, symbols: &mut SymbolPool) -> Header {
     let mut title = String::new();
     let mut attributes = Vec::new();
     
@@ -50,8 +1263,23 @@ fn parse_header(pair: pest::iterators::Pair<Rule>) -> Header {
             Rule::title => {
                 title = parse_title(inner_pair);
             }
+            Rule::document_roles => {
+                let roles: Vec<&str> = inner_pair.into_inner().map(|role| role.as_str()).collect();
+                attributes.push(Attribute { name: symbols.symbol("role"), value: Some(roles.join(" ")) });
+            }
+            Rule::author_line => {
+                attributes.extend(parse_author_line(inner_pair, symbols));
+            }
+            Rule::revision_line => {
+                let revision = parse_revision_line(inner_pair.into_inner().as_str());
+                for (name, value) in [("revnumber", revision.number), ("revdate", revision.date), ("revremark", revision.remark)] {
+                    if let Some(value) = value {
+                        attributes.push(Attribute { name: symbols.symbol(name), value: Some(value) });
+                    }
+                }
+            }
             Rule::header_attribute => {
-                attributes.push(parse_header_attribute(inner_pair));
+                attributes.push(parse_header_attribute(inner_pair, symbols));
             }
             _ => {}
         }
@@ -60,6 +1288,46 @@ fn parse_header(pair: pest::iterators::Pair<Rule>) -> Header {
     Header { title, attributes }
 }
 
+// The attributes Asciidoctor sets from the author line: `author`, `email`, the parts of the
+// name and the initials, with `_2`, `_3` and so on after the names of later authors, and
+// `authors` listing them all
+fn parse_author_line(pair: pest::iterators::Pair<Rule>, symbols: &mut SymbolPool) -> Vec<Attribute> {
+    let mut attributes = Vec::new();
+    let mut names = Vec::new();
+    let mut set = |name: String, value: &str| attributes.push(Attribute { name: symbols.symbol(&name), value: Some(value.to_string()) });
+    
+    for (index, author) in pair.into_inner().enumerate() {
+        let suffix = if index == 0 { String::new() } else { format!("_{}", index + 1) };
+        for part in author.into_inner() {
+            match part.as_rule() {
+                Rule::author_name => {
+                    let words: Vec<&str> = part.as_str().split_whitespace().collect();
+                    let name = words.join(" ");
+                    set(format!("author{}", suffix), &name);
+                    set(format!("firstname{}", suffix), words[0]);
+                    if let [_, middle, _] = words[..] {
+                        set(format!("middlename{}", suffix), middle);
+                    }
+                    if let [_, .., last] = words[..] {
+                        set(format!("lastname{}", suffix), last);
+                    }
+                    let initials: String = words.iter().filter_map(|word| word.chars().next()).collect();
+                    set(format!("authorinitials{}", suffix), &initials);
+                    names.push(name);
+                }
+                Rule::author_email => {
+                    set(format!("email{}", suffix), part.into_inner().as_str());
+                }
+                _ => {}
+            }
+        }
+    }
+    
+    trace::event!(DEBUG, authors = names.len(), "author line read");
+    set("authors".to_string(), &names.join("; "));
+    attributes
+}
+
 fn parse_title(pair: pest::iterators::Pair<Rule>) -> String {
     for inner_pair in pair.into_inner() {
         if inner_pair.as_rule() == Rule::title_text {
@@ -69,14 +1337,14 @@ fn parse_title(pair: pest::iterators::Pair<Rule>) -> String {
     String::new()
 }
 
-fn parse_header_attribute(pair: pest::iterators::Pair<Rule>) -> Attribute {
-    let mut name = String::new();
+fn parse_header_attribute(pair: pest::iterators::Pair<Rule>, symbols: &mut SymbolPool) -> Attribute {
+    let mut name = symbols.symbol("");
     let mut value = None;
     
     for inner_pair in pair.into_inner() {
         match inner_pair.as_rule() {
             Rule::attribute_name => {
-                name = inner_pair.as_str().to_string();
+                name = symbols.symbol(inner_pair.as_str());
             }
             Rule::attribute_value => {
                 let val = inner_pair.as_str().trim();
@@ -88,117 +1356,447 @@ fn parse_header_attribute(pair: pest::iterators::Pair<Rule>) -> Attribute {
         }
     }
     
+    trace::event!(DEBUG, name = name.as_str(), value = value.as_deref(), "attribute set");
     Attribute { name, value }
 }
 
-fn parse_body(pair: pest::iterators::Pair<Rule>) -> Vec<Block> {
+// What the text of a body is read with
+type Counters = RefCell<BTreeMap<Symbol, String>>;
+type Symbols = RefCell<SymbolPool>;
+
+#[derive(Clone, Copy)]
+struct BodyContext<'a> {
+    // Lines break where they do in the source while `:hardbreaks-option:` is set
+    hardbreaks: bool,
+    // Whether `//` lines are kept as blocks
+    comments: bool,
+    // The inline substitutions the entries above have left on
+    subs: Substitutions,
+    // Values for `{name}` references besides the built-in characters: the document's
+    // attributes and the entries above the block
+    attributes: &'a BTreeMap<Symbol, String>,
+    // Values of `{counter:name}`, which run on through the whole document
+    counters: &'a Counters,
+    // Where attribute names, roles and languages are taken from
+    symbols: &'a Symbols,
+    // The attribute list above the block being read, which gives a table its columns
+    table: &'a [Symbol],
+}
+
+fn parse_body(pair: pest::iterators::Pair<Rule>, mut context: BodyContext) -> Vec<Block> {
     let mut blocks = Vec::new();
+    let mut values = context.attributes.clone();
     
     for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::block {
-            if let Some(block) = parse_block(inner_pair) {
-                blocks.push(block);
+        if inner_pair.as_rule() == Rule::literal_paragraph {
+            blocks.push(parse_literal_paragraph(inner_pair));
+            continue;
+        }
+        if inner_pair.as_rule() == Rule::line_comment {
+            if context.comments {
+                let text = inner_pair.as_str().trim_end_matches(['\n', '\r']);
+                blocks.push(Block::Comment { text: text["//".len()..].to_string(), delimited: false });
+            }
+            continue;
+        }
+        if inner_pair.as_rule() == Rule::comment_block {
+            if context.comments {
+                let text = extract_delimited_content(inner_pair, Rule::comment_content);
+                blocks.push(Block::Comment { text, delimited: true });
             }
+            continue;
+        }
+        let table = block_attributes(&blocks);
+        let block_context = BodyContext { attributes: &values, table: &table, ..context };
+        let styled = block_style(&blocks).and_then(|(index, style)| {
+            let block = parse_styled_block(inner_pair.clone(), &style, block_context)?;
+            // The printer writes the style back from the block it made
+            blocks.remove(index);
+            Some(block)
+        });
+        if inner_pair.as_rule() == Rule::block
+            && let Some(mut block) = styled.or_else(|| parse_block(inner_pair, block_context))
+        {
+            match &mut block {
+                Block::Sidebar { title, roles, .. } => take_sidebar_metadata(&mut blocks, title, roles, &mut context.symbols.borrow_mut()),
+                Block::Table { title, .. } => take_block_title(&mut blocks, title),
+                Block::Image { title, .. } if title.is_none() => take_block_title(&mut blocks, title),
+                Block::DelimitedBlock { kind: DelimitedBlockKind::Listing, language, title, .. } => {
+                    take_listing_metadata(&mut blocks, language, title);
+                }
+                _ => {}
+            }
+            if let Some(name) = apply_entry(&block, &mut context.hardbreaks, &mut context.subs, &mut values) {
+                context.counters.borrow_mut().remove(name);
+            }
+            blocks.push(block);
         }
     }
     
     // Post-process to handle block attributes
-    process_block_attributes(&mut blocks);
+    process_block_attributes(&mut blocks, &mut context.symbols.borrow_mut());
+    apply_description_list_styles(&mut blocks);
+    attach_section_anchors(&mut blocks);
     
-    blocks
+    nest_sections(blocks)
+}
+
+// Attribute entries in the body apply to references, hard breaks and substitutions from
+// there on. The name set or unset is returned.
+fn apply_entry<'a>(block: &'a Block, hardbreaks: &mut bool, subs: &mut Substitutions, values: &mut BTreeMap<Symbol, String>) -> Option<&'a Symbol> {
+    match block {
+        Block::BlockMetadata { kind: BlockMetadataKind::AttributeEntry(attribute) } => {
+            *hardbreaks |= is_hardbreaks_attribute(&attribute.name);
+            subs.apply(&attribute.name, true);
+            define_attribute(values, attribute);
+            Some(&attribute.name)
+        }
+        Block::BlockMetadata { kind: BlockMetadataKind::AttributeUnset(name) } => {
+            *hardbreaks &= !is_hardbreaks_attribute(name);
+            subs.apply(name, false);
+            values.remove(name);
+            Some(name)
+        }
+        _ => None,
+    }
 }
 
-fn process_block_attributes(blocks: &mut Vec<Block>) {
+// References in an entry's value to attributes already set are resolved when it is defined,
+// and any chain of references that comes back round to it is returned
+pub(crate) fn define_attribute(values: &mut BTreeMap<Symbol, String>, attribute: &Attribute) -> Option<Vec<Symbol>> {
+    let mut references = References::new(values, None, Some(&attribute.name));
+    let value = references.substitute(attribute.value.as_deref().unwrap_or_default());
+    let cycle = references.cycle;
+    values.insert(attribute.name.clone(), value);
+    cycle
+}
+
+pub(crate) fn nest_sections(blocks: Vec<Block>) -> Vec<Block> {
+    let mut result = Vec::new();
+    // Stack of sections that are still collecting child blocks
+    let mut open: Vec<Block> = Vec::new();
+    
+    for block in blocks {
+        if let Block::Section { level, .. } = &block {
+            let level = *level;
+            close_sections(&mut open, &mut result, level);
+            open.push(block);
+        } else if let Some(Block::Section { blocks, .. }) = open.last_mut() {
+            blocks.push(block);
+        } else {
+            result.push(block);
+        }
+    }
+    
+    close_sections(&mut open, &mut result, 0);
+    result
+}
+
+// The other way round: each section as its heading alone, followed by what it held
+#[cfg(feature = "std")]
+fn flatten_sections(blocks: Vec<Block>, flat: &mut Vec<Block>) {
+    for block in blocks {
+        match block {
+            Block::Section { level, title, id, number, blocks } => {
+                flat.push(Block::Section { level, title, id, number, blocks: Vec::new() });
+                flatten_sections(blocks, flat);
+            }
+            block => flat.push(block),
+        }
+    }
+}
+
+fn close_sections(open: &mut Vec<Block>, result: &mut Vec<Block>, level: usize) {
+    while let Some(Block::Section { level: open_level, .. }) = open.last() {
+        if *open_level < level {
+            break;
+        }
+        let section = open.pop().unwrap();
+        if let Some(Block::Section { blocks, .. }) = open.last_mut() {
+            blocks.push(section);
+        } else {
+            result.push(section);
+        }
+    }
+}
+
+fn process_block_attributes(blocks: &mut Vec<Block>, symbols: &mut SymbolPool) {
     let mut i = 0;
     while i < blocks.len() {
         // Check if current block is a paragraph that looks like a block attribute
-        if let Block::Paragraph { content } = &blocks[i] {
-            if content.len() == 1 {
-                if let InlineElement::Text(text) = &content[0] {
-                    // Check if it matches block attribute pattern [,language] or [options]
-                    if text.starts_with('[') && text.ends_with(']') {
-                        let attr_content = &text[1..text.len()-1];
-                        let attributes: Vec<String> = attr_content.split(',').map(|s| s.trim().to_string()).collect();
-                        
-                        // Check if next block is a delimited block
-                        if i + 1 < blocks.len() {
-                            if let Block::DelimitedBlock { kind, content, language: _ } = &blocks[i + 1] {
-                                // Extract language from attributes
-                                let new_language = extract_language_from_attributes(&Some(attributes));
-                                
-                                // Replace the next block with updated language
-                                blocks[i + 1] = Block::DelimitedBlock {
-                                    kind: kind.clone(),
-                                    content: content.clone(),
-                                    language: new_language,
-                                };
-                                
-                                // Remove the attribute paragraph
-                                blocks.remove(i);
-                                continue;
-                            }
-                        }
-                    }
-                }
+        if let Block::Paragraph { content } = &blocks[i]
+            && content.len() == 1
+            && let InlineElement::Text(text) = &content[0]
+            // Check if it matches block attribute pattern [,language] or [options]
+            && text.starts_with('[') && text.ends_with(']')
+        {
+            let attr_content = &text[1..text.len()-1];
+            let attributes: Vec<String> = attr_content.split(',').map(|s| s.trim().to_string()).collect();
+            
+            // Check if next block is a delimited block
+            if i + 1 < blocks.len()
+                && let Block::DelimitedBlock { kind, content, language: _, title } = &blocks[i + 1]
+            {
+                // Extract language from attributes
+                let new_language = extract_language_from_attributes(&Some(attributes), symbols);
+                
+                // Replace the next block with updated language
+                blocks[i + 1] = Block::DelimitedBlock {
+                    kind: kind.clone(),
+                    content: content.clone(),
+                    language: new_language,
+                    title: title.clone(),
+                };
+                
+                // Remove the attribute paragraph
+                blocks.remove(i);
+                continue;
             }
         }
         i += 1;
     }
 }
 
-fn parse_block(pair: pest::iterators::Pair<Rule>) -> Option<Block> {
+// `.Title` and `[.role]` or `[sidebar.role]` lines right above a sidebar, which the printer
+// writes back from the block
+fn take_sidebar_metadata(blocks: &mut Vec<Block>, title: &mut Option<String>, roles: &mut Vec<Symbol>, symbols: &mut SymbolPool) {
+    let mut index = blocks.len();
+    while index > 0 {
+        index -= 1;
+        match &blocks[index] {
+            Block::BlockMetadata { kind: BlockMetadataKind::Title(text) } if title.is_none() => {
+                *title = Some(text.trim_end().to_string());
+            }
+            Block::BlockMetadata { kind: BlockMetadataKind::Attribute(entries) } if roles.is_empty() => {
+                let entries: Vec<&str> = entries.iter().map(|entry| entry.as_str()).collect();
+                let text = entries.join(",");
+                let text = text.strip_prefix("sidebar").unwrap_or(&text);
+                match InlineAttributes::parse_in(text, symbols) {
+                    Some(attributes) if attributes.id.is_none() => *roles = attributes.roles,
+                    _ if text.is_empty() => {}
+                    _ => break,
+                }
+            }
+            Block::BlockMetadata { kind: BlockMetadataKind::Anchor(_) } => continue,
+            _ => break,
+        }
+        blocks.remove(index);
+    }
+}
+
+// The `.Title` line above a table or listing, past its attribute list and any anchor
+fn take_block_title(blocks: &mut Vec<Block>, title: &mut Option<String>) {
+    let index = blocks.iter().rposition(|block| !matches!(block, Block::BlockMetadata { kind: BlockMetadataKind::Attribute(_) | BlockMetadataKind::Anchor(_) }));
+    if let Some(index) = index
+        && let Block::BlockMetadata { kind: BlockMetadataKind::Title(text) } = &blocks[index]
+    {
+        *title = Some(text.trim_end().to_string());
+        blocks.remove(index);
+    }
+}
+
+// `[source,rust]` or `[,rust]` above a listing gives its language, and `filename=` in the
+// list or a `.Title` line the title shown above the code. The printer writes back what was
+// taken, so a list holding anything else is left in the body.
+fn take_listing_metadata(blocks: &mut Vec<Block>, language: &mut Option<Symbol>, title: &mut Option<String>) {
+    take_block_title(blocks, title);
+    let index = blocks.iter().rposition(|block| !matches!(block, Block::BlockMetadata { kind: BlockMetadataKind::Anchor(_) }));
+    let Some(Block::BlockMetadata { kind: BlockMetadataKind::Attribute(entries) }) = index.map(|index| &blocks[index]) else {
+        return;
+    };
+    if !matches!(entries.first().map(|entry| entry.as_str()), Some("" | "source")) {
+        return;
+    }
+    let mut understood = true;
+    for (position, entry) in entries.iter().enumerate().skip(1) {
+        match entry.split_once('=') {
+            Some(("filename", file_name)) if title.is_none() => *title = Some(file_name.trim().trim_matches('"').to_string()),
+            None if position == 1 && !entry.is_empty() && language.is_none() => *language = Some(entry.clone()),
+            _ => understood = false,
+        }
+    }
+    if understood && let Some(index) = index {
+        blocks.remove(index);
+    }
+}
+
+// The entries of the attribute list above the next block, past any title or anchor
+fn block_attributes(blocks: &[Block]) -> Vec<Symbol> {
+    let index = blocks.iter().rposition(|block| !matches!(block, Block::BlockMetadata { kind: BlockMetadataKind::Title(_) | BlockMetadataKind::Anchor(_) }));
+    match index.map(|index| &blocks[index]) {
+        Some(Block::BlockMetadata { kind: BlockMetadataKind::Attribute(entries) }) => entries.clone(),
+        _ => Vec::new(),
+    }
+}
+
+// `[quote, author, citation]` or `[literal]` above the block, past any title or anchor
+fn block_style(blocks: &[Block]) -> Option<(usize, Vec<Symbol>)> {
+    let index = blocks.iter().rposition(|block| !matches!(block, Block::BlockMetadata { kind: BlockMetadataKind::Title(_) | BlockMetadataKind::Anchor(_) }))?;
+    let Block::BlockMetadata { kind: BlockMetadataKind::Attribute(entries) } = &blocks[index] else { return None };
+    let style = entries.first().map(|entry| entry.as_str())?;
+    (matches!(style, "quote" | "literal") || AdmonitionKind::from_label(style).is_some()).then(|| (index, entries.clone()))
+}
+
+// A quote block takes the attribution, and a paragraph becomes a block of the style holding
+// its lines. An admonition style makes an admonition of a paragraph or an example block.
+fn parse_styled_block(pair: pest::iterators::Pair<Rule>, style: &[Symbol], context: BodyContext) -> Option<Block> {
+    let inner_pair = pair.into_inner().next()?;
+    if let Some(kind) = AdmonitionKind::from_label(style[0].as_str()) {
+        return parse_admonition_block(inner_pair, kind, context);
+    }
+    let kind = match style[0].as_str() {
+        "quote" => {
+            let part = |index: usize| style.get(index).map(|entry| entry.to_string()).filter(|entry| !entry.is_empty());
+            DelimitedBlockKind::Quote(Attribution { author: part(1), citation: part(2) })
+        }
+        _ => DelimitedBlockKind::Literal,
+    };
+    match inner_pair.as_rule() {
+        Rule::paragraph => Some(Block::DelimitedBlock {
+            kind,
+            content: paragraph_lines(inner_pair).join("\n") + "\n",
+            language: None,
+            title: None,
+        }),
+        Rule::delimited_block => match parse_delimited_block(inner_pair, context) {
+            Block::DelimitedBlock { kind: DelimitedBlockKind::Quote(_), content, language, title } if matches!(kind, DelimitedBlockKind::Quote(_)) => {
+                Some(Block::DelimitedBlock { kind, content, language, title })
+            }
+            block @ Block::DelimitedBlock { kind: DelimitedBlockKind::Literal, .. } if kind == DelimitedBlockKind::Literal => Some(block),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn parse_admonition_block(pair: pest::iterators::Pair<Rule>, kind: AdmonitionKind, context: BodyContext) -> Option<Block> {
+    match pair.as_rule() {
+        Rule::paragraph => match parse_paragraph(pair, context) {
+            Block::Paragraph { content } | Block::Admonition { content, .. } => Some(Block::Admonition { kind, content, blocks: Vec::new() }),
+            _ => None,
+        },
+        Rule::delimited_block => {
+            let example = pair.into_inner().next().filter(|inner| inner.as_rule() == Rule::example_block)?;
+            let content = extract_delimited_content(example, Rule::example_content);
+            Some(Block::Admonition { kind, content: Vec::new(), blocks: parse_nested_blocks(&content, context) })
+        }
+        _ => None,
+    }
+}
+
+// The attribute list stays in the body, so the style is printed back as it was written.
+// `[glossary]` above a section makes every description list in it a glossary, up to the
+// next section at the same level or higher.
+fn apply_description_list_styles(blocks: &mut [Block]) {
+    let mut style = None;
+    let mut glossary_level = None;
+    for block in blocks {
+        match block {
+            Block::BlockMetadata { kind: BlockMetadataKind::Attribute(entries) } => {
+                style = match entries.first().map(|entry| entry.as_str()) {
+                    Some("horizontal") => Some(DescriptionListStyle::Horizontal),
+                    Some("qanda") => Some(DescriptionListStyle::Qanda),
+                    Some("glossary") => Some(DescriptionListStyle::Glossary),
+                    _ => None,
+                };
+            }
+            Block::BlockMetadata { kind: BlockMetadataKind::Title(_) | BlockMetadataKind::Anchor(_) } => {}
+            Block::List { kind: ListKind::Description(list_style), .. } => {
+                if let Some(style) = style.take().or(glossary_level.map(|_| DescriptionListStyle::Glossary)) {
+                    *list_style = style;
+                }
+            }
+            Block::Section { level, .. } => {
+                if style.take() == Some(DescriptionListStyle::Glossary) {
+                    glossary_level = Some(*level);
+                } else if glossary_level.is_some_and(|glossary_level| *level <= glossary_level) {
+                    glossary_level = None;
+                }
+            }
+            _ => style = None,
+        }
+    }
+}
+
+fn parse_block(pair: pest::iterators::Pair<Rule>, context: BodyContext) -> Option<Block> {
     for inner_pair in pair.into_inner() {
         match inner_pair.as_rule() {
-            Rule::section => return Some(parse_section(inner_pair)),
-            // Rule::attributed_block => return Some(parse_attributed_block(inner_pair)),
-            Rule::delimited_block => return Some(parse_delimited_block(inner_pair)),
-            Rule::list => return Some(parse_list(inner_pair)),
-            Rule::paragraph => return Some(parse_paragraph(inner_pair)),
-            Rule::block_metadata => return Some(parse_block_metadata(inner_pair)),
+            Rule::section => return Some(parse_section(inner_pair, context)),
+            Rule::delimited_block => return Some(parse_delimited_block(inner_pair, context)),
+            Rule::list => return Some(parse_list(inner_pair, context)),
+            Rule::paragraph => return Some(parse_paragraph(inner_pair, context)),
+            Rule::toc_macro => return Some(parse_toc_macro(inner_pair)),
+            Rule::image_block => return Some(parse_image_block(inner_pair, context)),
+            Rule::page_break => return Some(Block::PageBreak),
+            Rule::block_metadata => return Some(parse_block_metadata(inner_pair, context)),
             _ => {}
         }
     }
     None
 }
 
-fn parse_attributed_block(pair: pest::iterators::Pair<Rule>) -> Block {
-    let mut attributes: Option<Vec<String>> = None;
-    
+// `toc::[levels=3]` overrides how deep this table of contents goes
+fn parse_toc_macro(pair: pest::iterators::Pair<Rule>) -> Block {
+    let levels = pair.into_inner()
+        .filter(|inner_pair| inner_pair.as_rule() == Rule::macro_attributes)
+        .flat_map(|inner_pair| inner_pair.as_str().split(','))
+        .find_map(|attribute| attribute.trim().strip_prefix("levels=")?.trim_matches('"').parse().ok());
+    Block::TableOfContents { levels }
+}
+
+// The alt text, width and height are given in that order or by name, as are `title=` and
+// `alt=`. Quotes round a value let it hold commas.
+fn parse_image_block(pair: pest::iterators::Pair<Rule>, context: BodyContext) -> Block {
+    let mut path = String::new();
+    let mut values: [Option<String>; 4] = Default::default();
     for inner_pair in pair.into_inner() {
         match inner_pair.as_rule() {
-            Rule::block_attribute => {
-                attributes = Some(parse_block_attribute(inner_pair));
-            },
-            Rule::delimited_block => {
-                return parse_delimited_block_with_attributes(inner_pair, attributes);
-            },
+            Rule::image_block_path => path = substitute_attributes(inner_pair.as_str(), context.attributes),
+            Rule::macro_attributes => {
+                let mut position = 0;
+                for part in split_quoted(&substitute_attributes(inner_pair.as_str(), context.attributes)) {
+                    let (slot, value) = match part.split_once('=') {
+                        Some((name, value)) => match name.trim() {
+                            "alt" => (0, value),
+                            "width" => (1, value),
+                            "height" => (2, value),
+                            "title" => (3, value),
+                            _ => continue,
+                        },
+                        None if position < 3 => {
+                            position += 1;
+                            (position - 1, part)
+                        }
+                        None => continue,
+                    };
+                    let value = value.trim().trim_matches('"');
+                    if !value.is_empty() {
+                        values[slot] = Some(value.to_string());
+                    }
+                }
+            }
             _ => {}
         }
     }
-    
-    // Fallback if no delimited block found
-    Block::DelimitedBlock {
-        kind: DelimitedBlockKind::Literal,
-        content: String::new(),
-        language: None,
-    }
+    let [alt, width, height, title] = values;
+    Block::Image { path, alt, width, height, title }
 }
 
-fn parse_section(pair: pest::iterators::Pair<Rule>) -> Block {
+fn parse_section(pair: pest::iterators::Pair<Rule>, context: BodyContext) -> Block {
     let content = pair.as_str();
     let level = content.chars().take_while(|&c| c == '=').count();
-    let title = content.trim_start_matches('=').trim().to_string();
+    let title = substitute_outside_literals(content.trim_start_matches('=').trim(), context);
     
-    Block::Section { level, title, blocks: Vec::new() }
+    Block::Section { level, title, id: None, number: None, blocks: Vec::new() }
 }
 
 
-fn parse_delimited_block(pair: pest::iterators::Pair<Rule>) -> Block {
-    parse_delimited_block_with_attributes(pair, None)
+fn parse_delimited_block(pair: pest::iterators::Pair<Rule>, context: BodyContext) -> Block {
+    parse_delimited_block_with_attributes(pair, None, context)
 }
 
-fn parse_delimited_block_with_attributes(pair: pest::iterators::Pair<Rule>, attributes: Option<Vec<String>>) -> Block {
-    let language = extract_language_from_attributes(&attributes);
+fn parse_delimited_block_with_attributes(pair: pest::iterators::Pair<Rule>, attributes: Option<Vec<String>>, context: BodyContext) -> Block {
+    let language = extract_language_from_attributes(&attributes, &mut context.symbols.borrow_mut());
     
     for inner_pair in pair.into_inner() {
         match inner_pair.as_rule() {
@@ -207,6 +1805,7 @@ fn parse_delimited_block_with_attributes(pair: pest::iterators::Pair<Rule>, attr
                     kind: DelimitedBlockKind::Listing,
                     content: extract_delimited_content(inner_pair, Rule::listing_content),
                     language: language.clone(),
+                    title: None,
                 };
             }
             Rule::example_block => {
@@ -214,6 +1813,7 @@ fn parse_delimited_block_with_attributes(pair: pest::iterators::Pair<Rule>, attr
                     kind: DelimitedBlockKind::Example,
                     content: extract_delimited_content(inner_pair, Rule::example_content),
                     language: language.clone(),
+                    title: None,
                 };
             }
             Rule::literal_block => {
@@ -221,30 +1821,225 @@ fn parse_delimited_block_with_attributes(pair: pest::iterators::Pair<Rule>, attr
                     kind: DelimitedBlockKind::Literal,
                     content: extract_delimited_content(inner_pair, Rule::literal_content),
                     language: language.clone(),
+                    title: None,
                 };
             }
             Rule::sidebar_block => {
-                return Block::DelimitedBlock {
-                    kind: DelimitedBlockKind::Sidebar,
-                    content: extract_delimited_content(inner_pair, Rule::sidebar_content),
-                    language: language.clone(),
-                };
+                let content = extract_delimited_content(inner_pair, Rule::sidebar_content);
+                return Block::Sidebar { title: None, roles: Vec::new(), blocks: parse_nested_blocks(&content, context) };
             }
             Rule::quote_block => {
                 return Block::DelimitedBlock {
-                    kind: DelimitedBlockKind::Quote,
+                    kind: DelimitedBlockKind::Quote(Attribution::default()),
                     content: extract_delimited_content(inner_pair, Rule::quote_content),
                     language: language.clone(),
+                    title: None,
                 };
             }
+            Rule::table_block => return parse_table(&extract_delimited_content(inner_pair, Rule::table_content), context),
+            _ => {}
+        }
+    }
+    
+    Block::DelimitedBlock {
+        kind: DelimitedBlockKind::Literal,
+        content: String::new(),
+        language,
+        title: None,
+    }
+}
+
+// A cell starts at each `|` not escaped as `\|` and runs to the next, across lines. A spec
+// written right before the `|`, like `2+` or `.2+^`, spans and aligns the cell. Rows hold as
+// many columns as the `cols` attribute lists, or else as the cells on the first line cover,
+// and a cell spanning rows takes its columns in the rows below. The first row is a header
+// when the options say so, or when it is the whole first line and a blank line follows.
+fn parse_table(content: &str, context: BodyContext) -> Block {
+    let lines: Vec<&str> = content.lines().collect();
+    // Each cell with its text and the line it starts on
+    let mut cells: Vec<(TableCell, String, usize)> = Vec::new();
+    // Text before the first cell, which can only be the spec of that cell
+    let mut before = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            let text = cells.last_mut().map_or(&mut before, |(_, text, _)| text);
+            if c == '\\' && chars.peek() == Some(&'|') {
+                chars.next();
+                text.push('|');
+            } else if c == '|' {
+                let cell = take_cell_spec(text);
+                cells.push((cell, String::new(), index));
+            } else {
+                text.push(c);
+            }
+        }
+        if let Some((_, text, _)) = cells.last_mut() {
+            text.push('\n');
+        }
+    }
+    
+    let (columns, header) = table_options(context.table);
+    let first_line: usize = cells.iter().take_while(|(_, _, line)| *line == 0).map(|(cell, _, _)| cell.colspan).sum();
+    let columns = columns.unwrap_or(first_line).max(1);
+    let implicit_header = first_line == columns && lines.get(1).is_some_and(|line| line.trim().is_empty());
+    
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    // Columns of the current row taken so far, and of the rows below by cells above them
+    let mut taken = 0;
+    let mut reserved: Vec<usize> = Vec::new();
+    for (mut cell, text, _) in cells {
+        cell.content = table_cell_content(&text, context);
+        if reserved.len() + 1 < cell.rowspan {
+            reserved.resize(cell.rowspan - 1, 0);
+        }
+        for columns in &mut reserved[..cell.rowspan - 1] {
+            *columns += cell.colspan;
+        }
+        taken += cell.colspan;
+        row.push(cell);
+        while taken >= columns {
+            rows.push(core::mem::take(&mut row));
+            taken = if reserved.is_empty() { 0 } else { reserved.remove(0) };
+        }
+    }
+    if !row.is_empty() {
+        rows.push(row);
+    }
+    Block::Table { title: None, header: header.unwrap_or(implicit_header), rows }
+}
+
+// The lines of a cell are joined like those of a paragraph
+fn table_cell_content(text: &str, context: BodyContext) -> Vec<InlineElement> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    parse_paragraph_content(&substitute_outside_literals(&text, context), context.subs, &mut context.symbols.borrow_mut())
+}
+
+// Removes the spec from the end of the text before a `|`, returning a cell set up by it
+fn take_cell_spec(text: &mut String) -> TableCell {
+    let start = text.char_indices().rfind(|(_, ch)| ch.is_whitespace()).map_or(0, |(index, ch)| index + ch.len_utf8());
+    match cell_spec(&text[start..]) {
+        Some(cell) => {
+            text.truncate(start);
+            cell
+        }
+        None => TableCell::new(Vec::new()),
+    }
+}
+
+// `colspan.rowspan+` with either number left out, then `<`, `^` or `>` to align the text.
+// A vertical alignment such as `.^` and a style letter may follow and are accepted as part
+// of the spec, though neither changes the output.
+fn cell_spec(spec: &str) -> Option<TableCell> {
+    let mut cell = TableCell::new(Vec::new());
+    let mut rest = spec;
+    if let Some((span, after)) = spec.split_once('+') {
+        let (columns, rows) = match span.split_once('.') {
+            Some((columns, rows)) => (columns, Some(rows)),
+            None => (span, None),
+        };
+        if columns.is_empty() && rows.is_none() {
+            return None;
+        }
+        let count = |text: &str| text.parse::<usize>().ok().filter(|count| *count > 0);
+        if !columns.is_empty() {
+            cell.colspan = count(columns)?;
+        }
+        if let Some(rows) = rows {
+            cell.rowspan = count(rows)?;
+        }
+        rest = after;
+    }
+    let alignment = |c: char| match c {
+        '<' => Some(CellAlignment::Left),
+        '^' => Some(CellAlignment::Center),
+        '>' => Some(CellAlignment::Right),
+        _ => None,
+    };
+    if let Some(align) = rest.chars().next().and_then(alignment) {
+        cell.align = Some(align);
+        rest = &rest[1..];
+    }
+    if let Some(vertical) = rest.strip_prefix('.')
+        && vertical.chars().next().and_then(alignment).is_some()
+    {
+        rest = &vertical[1..];
+    }
+    if rest.len() == 1 && "adehlmsv".contains(rest) {
+        rest = "";
+    }
+    (!spec.is_empty() && rest.is_empty()).then_some(cell)
+}
+
+// The column count from `cols=3`, `cols="1,2"` or `cols="3*"`, and whether the first row is
+// a header from `%header`, `%noheader` or `options="header"`
+fn table_options(entries: &[Symbol]) -> (Option<usize>, Option<bool>) {
+    let text = entries.iter().map(|entry| entry.as_str()).collect::<Vec<_>>().join(",");
+    let mut columns = None;
+    let mut header = None;
+    let mut set_option = |option: &str| match option.trim() {
+        "header" => header = Some(true),
+        "noheader" => header = Some(false),
+        _ => {}
+    };
+    for (index, entry) in split_quoted(&text).into_iter().enumerate() {
+        let entry = entry.trim();
+        if let Some((name, value)) = entry.split_once('=') {
+            let value = value.trim().trim_matches('"');
+            match name.trim() {
+                "cols" => columns = column_count(value),
+                "options" | "opts" => value.split(',').for_each(&mut set_option),
+                _ => {}
+            }
+        } else if index == 0 {
+            entry.split('%').skip(1).for_each(&mut set_option);
+        }
+    }
+    (columns, header)
+}
+
+// A lone number is a count of columns; otherwise each spec is one column, or `n*` of them
+fn column_count(cols: &str) -> Option<usize> {
+    if let Ok(count) = cols.trim().parse() {
+        return Some(count);
+    }
+    cols.split([',', ';'])
+        .map(|spec| match spec.split_once('*') {
+            Some((count, _)) => count.trim().parse().ok(),
+            None => Some(1),
+        })
+        .sum()
+}
+
+// Splits at commas outside double quotes
+fn split_quoted(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                parts.push(&text[start..index]);
+                start = index + 1;
+            }
             _ => {}
         }
     }
-    
-    Block::DelimitedBlock {
-        kind: DelimitedBlockKind::Literal,
-        content: String::new(),
-        language,
+    parts.push(&text[start..]);
+    parts
+}
+
+// The content of a compound block, parsed like a document body of its own
+fn parse_nested_blocks(content: &str, context: BodyContext) -> Vec<Block> {
+    match AsciiDocParser::parse(Rule::nested_body, content) {
+        Ok(mut pairs) => pairs.next()
+            .and_then(|pair| pair.into_inner().find(|inner_pair| inner_pair.as_rule() == Rule::body))
+            .map(|body| parse_body(body, context))
+            .unwrap_or_default(),
+        // A nested block left open, say; the text is kept rather than lost
+        Err(_) => vec![Block::Paragraph { content: vec![InlineElement::Text(content.trim_end().to_string())] }],
     }
 }
 
@@ -257,179 +2052,323 @@ fn extract_delimited_content(pair: pest::iterators::Pair<Rule>, content_rule: Ru
     String::new()
 }
 
-fn extract_language_from_attributes(attributes: &Option<Vec<String>>) -> Option<String> {
+fn extract_language_from_attributes(attributes: &Option<Vec<String>>, symbols: &mut SymbolPool) -> Option<Symbol> {
     if let Some(attrs) = attributes {
         for attr in attrs {
             let trimmed = attr.trim();
             // Handle [,language] syntax - second attribute is language
-            if trimmed.starts_with(',') {
-                return Some(trimmed[1..].to_string());
+            if let Some(language) = trimmed.strip_prefix(',') {
+                return Some(symbols.symbol(language));
             }
             // Handle [language] syntax - if it's a known language or starts with a letter
             if !trimmed.is_empty() && !trimmed.contains('=') && !trimmed.contains(':') {
-                return Some(trimmed.to_string());
+                return Some(symbols.symbol(trimmed));
             }
         }
     }
     None
 }
 
-fn parse_list(pair: pest::iterators::Pair<Rule>) -> Block {
+// Lists still open while a list is read, from the outermost in, with the marker of each
+struct OpenList {
+    marker: String,
+    kind: ListKind,
+    items: Vec<ListItem>,
+}
+
+// Nesting comes from the order markers are first seen in, not their length: a marker already
+// in use returns to that list, and any other marker starts a list nested under the item
+// before it, so `*` followed by `-`, `***` or `.` is a second level
+fn parse_list(pair: pest::iterators::Pair<Rule>, context: BodyContext) -> Block {
+    // An attribute list above the list is not for a table attached to one of its items
+    let context = BodyContext { table: &[], ..context };
+    let mut open: Vec<OpenList> = Vec::new();
+    
     for inner_pair in pair.into_inner() {
         match inner_pair.as_rule() {
-            Rule::unordered_list => {
-                return Block::List {
-                    kind: ListKind::Unordered,
-                    items: parse_unordered_list(inner_pair),
-                };
+            Rule::list_item => {
+                let Some(item_pair) = inner_pair.into_inner().next() else { continue };
+                let (marker, kind, item) = parse_list_item(item_pair, context);
+                match open.iter().position(|list| list.marker == marker) {
+                    Some(index) => close_lists(&mut open, index + 1),
+                    None => open.push(OpenList { marker, kind, items: Vec::new() }),
+                }
+                if let Some(list) = open.last_mut() {
+                    list.items.push(item);
+                }
             }
-            Rule::ordered_list => {
-                return Block::List {
-                    kind: ListKind::Ordered,
-                    items: parse_ordered_list(inner_pair),
-                };
+            Rule::list_continuation => {
+                let blocks = parse_list_continuation(inner_pair, context);
+                if let Some(item) = open.last_mut().and_then(|list| list.items.last_mut()) {
+                    item.blocks_mut().extend(blocks);
+                }
             }
-            Rule::description_list => {
-                return Block::List {
-                    kind: ListKind::Description,
-                    items: parse_description_list(inner_pair),
-                };
+            Rule::list_literal => {
+                let block = parse_literal_paragraph(inner_pair);
+                if let Some(item) = open.last_mut().and_then(|list| list.items.last_mut()) {
+                    item.blocks_mut().push(block);
+                }
             }
             _ => {}
         }
     }
     
-    Block::List {
-        kind: ListKind::Unordered,
-        items: Vec::new(),
+    close_lists(&mut open, 1);
+    match open.pop() {
+        Some(list) => Block::List { kind: list.kind, items: list.items },
+        None => Block::List { kind: ListKind::Unordered, items: Vec::new() },
     }
 }
 
-fn parse_unordered_list(pair: pest::iterators::Pair<Rule>) -> Vec<ListItem> {
-    let mut items = Vec::new();
-    
-    for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::unordered_item {
-            let (level, content) = parse_unordered_item(inner_pair);
-            items.push(ListItem::Unordered { level, content });
+// Closes the lists nested deeper than `depth`, attaching each to the last item of its parent
+fn close_lists(open: &mut Vec<OpenList>, depth: usize) {
+    while open.len() > depth {
+        let Some(list) = open.pop() else { break };
+        if let Some(item) = open.last_mut().and_then(|parent| parent.items.last_mut()) {
+            item.blocks_mut().push(Block::List { kind: list.kind, items: list.items });
         }
     }
-    
-    items
 }
 
-fn parse_unordered_item(pair: pest::iterators::Pair<Rule>) -> (usize, Vec<InlineElement>) {
-    let text = pair.as_str();
-    let level = text.chars().take_while(|&c| c == '*').count();
-    let content_start = text.find(' ').unwrap_or(level) + 1;
-    let content = if content_start < text.len() {
-        parse_paragraph_content(&text[content_start..])
-    } else {
-        Vec::new()
-    };
+fn parse_list_item(pair: pest::iterators::Pair<Rule>, context: BodyContext) -> (String, ListKind, ListItem) {
+    let rule = pair.as_rule();
+    let mut marker = String::new();
+    let mut term = String::new();
+    let mut lines = Vec::new();
+    
+    for inner_pair in pair.into_inner() {
+        match inner_pair.as_rule() {
+            Rule::unordered_marker | Rule::ordered_marker | Rule::description_marker => {
+                marker = inner_pair.as_str().to_string();
+            }
+            Rule::description_term => term = inner_pair.as_str().to_string(),
+            Rule::list_content | Rule::description_text => {
+                let line = inner_pair.as_str().trim();
+                if !line.is_empty() {
+                    lines.push(line);
+                }
+            }
+            _ => {}
+        }
+    }
     
-    (level, content)
+    let text = lines.join(" ");
+    match rule {
+        Rule::ordered_item => {
+            let item = ListItem::Ordered { content: parse_paragraph_content(&substitute_outside_literals(&text, context), context.subs, &mut context.symbols.borrow_mut()), blocks: Vec::new() };
+            (marker, ListKind::Ordered, item)
+        }
+        Rule::description_item => {
+            let description = (!text.is_empty()).then(|| vec![InlineElement::Text(text)]);
+            let item = ListItem::Description { term, description, blocks: Vec::new() };
+            (marker, ListKind::Description(DescriptionListStyle::default()), item)
+        }
+        _ => {
+            let item = ListItem::Unordered { content: parse_paragraph_content(&substitute_outside_literals(&text, context), context.subs, &mut context.symbols.borrow_mut()), blocks: Vec::new() };
+            (marker, ListKind::Unordered, item)
+        }
+    }
 }
 
-fn parse_ordered_list(pair: pest::iterators::Pair<Rule>) -> Vec<ListItem> {
-    let mut items = Vec::new();
+fn parse_list_continuation(pair: pest::iterators::Pair<Rule>, context: BodyContext) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut language = None;
     
     for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::ordered_item {
-            let (level, content) = parse_ordered_item(inner_pair);
-            items.push(ListItem::Ordered { level, content });
+        match inner_pair.as_rule() {
+            Rule::block_metadata => blocks.push(parse_block_metadata(inner_pair, context)),
+            Rule::language_attribute => {
+                let attribute = inner_pair.as_str().trim_end().trim_start_matches('[').trim_end_matches(']');
+                language = Some(vec![attribute.to_string()]);
+            }
+            Rule::delimited_block => blocks.push(parse_delimited_block_with_attributes(inner_pair, language.take(), context)),
+            Rule::list_paragraph => blocks.push(parse_paragraph(inner_pair, context)),
+            _ => {}
         }
     }
     
-    items
+    blocks
 }
 
-fn parse_ordered_item(pair: pest::iterators::Pair<Rule>) -> (usize, Vec<InlineElement>) {
-    let text = pair.as_str();
-    let level = text.chars().take_while(|&c| c == '.').count();
-    let content_start = text.find(' ').unwrap_or(level) + 1;
-    let content = if content_start < text.len() {
-        parse_paragraph_content(&text[content_start..])
-    } else {
-        Vec::new()
-    };
+// An indented paragraph, on its own or under a list item, is kept as it was written, less
+// the indent of its least indented line
+fn parse_literal_paragraph(pair: pest::iterators::Pair<Rule>) -> Block {
+    let lines: Vec<&str> = pair.into_inner()
+        .filter(|inner_pair| inner_pair.as_rule() == Rule::literal_line)
+        .map(|inner_pair| inner_pair.as_str().trim_end_matches(['\n', '\r']))
+        .collect();
+    let indent = lines.iter()
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let content: String = lines.iter().map(|line| format!("{}\n", &line[indent..])).collect();
     
-    (level, content)
+    Block::DelimitedBlock { kind: DelimitedBlockKind::Literal, content, language: None, title: None }
+}
+
+fn paragraph_lines(pair: pest::iterators::Pair<'_, Rule>) -> Vec<&str> {
+    pair.into_inner()
+        .filter(|inner_pair| inner_pair.as_rule() == Rule::paragraph_line)
+        .flat_map(|inner_pair| inner_pair.into_inner())
+        .filter(|line_inner| line_inner.as_rule() == Rule::paragraph_text)
+        .map(|line_inner| line_inner.as_str())
+        .collect()
 }
 
-fn parse_description_list(pair: pest::iterators::Pair<Rule>) -> Vec<ListItem> {
-    let mut items = Vec::new();
+// Lines are joined with a space, or with a line break after a line ending in ` +` or
+// everywhere while `:hardbreaks-option:` is set
+fn parse_paragraph(pair: pest::iterators::Pair<Rule>, context: BodyContext) -> Block {
+    trace::span!(TRACE, "substitute", line = pair.line_col().0);
+    let mut lines = paragraph_lines(pair);
+    if let Some(quote) = quote_shorthand(&lines) {
+        return quote;
+    }
+    let admonition = lines.first().and_then(|line| admonition_label(line));
+    if let Some((_, text)) = admonition {
+        lines[0] = text;
+    }
+    let mut content = Vec::new();
+    let mut break_after = false;
     
-    for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::description_item {
-            items.push(parse_description_item(inner_pair));
+    for (index, line) in lines.iter().enumerate() {
+        let marked = line.strip_suffix(" +").filter(|_| index + 1 < lines.len());
+        let text = substitute_outside_literals(marked.unwrap_or(line), context);
+        // Under `attribute-missing: drop-line` nothing is left of it
+        if text.is_empty() {
+            continue;
+        }
+        // Add a separator between lines (except before the first line)
+        if !content.is_empty() {
+            content.push(if context.hardbreaks || break_after {
+                InlineElement::LineBreak
+            } else {
+                InlineElement::Text(" ".to_string())
+            });
         }
+        break_after = marked.is_some();
+        content.extend(parse_paragraph_content(&text, context.subs, &mut context.symbols.borrow_mut()));
     }
     
-    items
+    match admonition {
+        Some((kind, _)) => Block::Admonition { kind, content, blocks: Vec::new() },
+        None => Block::Paragraph { content },
+    }
 }
 
-fn parse_description_item(pair: pest::iterators::Pair<Rule>) -> ListItem {
-    let mut term = String::new();
-    let mut description = None;
-    
-    for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::description_term => {
-                term = inner_pair.as_str().to_string();
-            }
-            Rule::description_text => {
-                let text = inner_pair.as_str().trim();
-                if !text.is_empty() {
-                    description = Some(vec![InlineElement::Text(text.to_string())]);
-                }
-            }
-            _ => {}
+// `NOTE: ` or another label at the start of a paragraph, and the text after it
+fn admonition_label(line: &str) -> Option<(AdmonitionKind, &str)> {
+    let (label, text) = line.split_once(": ")?;
+    let text = text.trim_start();
+    AdmonitionKind::from_label(label).filter(|_| !text.is_empty()).map(|kind| (kind, text))
+}
+
+// Two ways to write a short quote without delimiters: the text in double quotes followed by
+// an attribution line, and Markdown's `>` on every line, optionally ending with one
+//
+//     "A little rebellion now and then is a good thing."
+//     -- Thomas Jefferson, Papers of Thomas Jefferson: Volume 11
+fn quote_shorthand(lines: &[&str]) -> Option<Block> {
+    let markdown: Option<Vec<&str>> = lines.iter()
+        .map(|line| line.strip_prefix("> ").or_else(|| line.strip_prefix('>')))
+        .collect();
+    let (text, attribution) = match markdown {
+        Some(lines) => match lines.split_last() {
+            Some((last, text)) if !text.is_empty() && last.starts_with("-- ") => (text.join("\n"), Some(*last)),
+            _ => (lines.join("\n"), None),
+        },
+        None => {
+            let (last, text) = lines.split_last().filter(|(last, text)| !text.is_empty() && last.starts_with("-- "))?;
+            let text = text.join("\n");
+            let text = text.strip_prefix('"')?.strip_suffix('"')?.to_string();
+            (text, Some(*last))
         }
+    };
+    let attribution = attribution.map_or_else(Attribution::default, |line| {
+        let line = line.trim_start_matches("-- ").trim();
+        let (author, citation) = match line.split_once(", ") {
+            Some((author, citation)) => (author, Some(citation.trim().to_string())),
+            None => (line, None),
+        };
+        Attribution { author: Some(author.to_string()).filter(|author| !author.is_empty()), citation }
+    });
+    Some(Block::DelimitedBlock { kind: DelimitedBlockKind::Quote(attribution), content: text + "\n", language: None, title: None })
+}
+
+// The text of `` `+text+` ``, `+++text+++` and `pass:[text]` is kept exactly as written, so
+// references in it are left alone
+fn substitute_outside_literals(line: &str, context: BodyContext) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some((start, end)) = next_literal(rest) {
+        result.push_str(&substitute_references(&rest[..start], context.attributes, Some(context.counters)));
+        result.push_str(&rest[start..end]);
+        rest = &rest[end..];
     }
-    
-    ListItem::Description { term, description }
+    result.push_str(&substitute_references(rest, context.attributes, Some(context.counters)));
+    result
 }
 
-fn parse_paragraph(pair: pest::iterators::Pair<Rule>) -> Block {
-    let mut content = Vec::new();
-    let mut first_line = true;
-    
-    for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::paragraph_line {
-            for line_inner in inner_pair.into_inner() {
-                if line_inner.as_rule() == Rule::paragraph_text {
-                    // Add space between lines (except for the first line)
-                    if !first_line && !content.is_empty() {
-                        content.push(InlineElement::Text(" ".to_string()));
-                    }
-                    content.extend(parse_paragraph_content(line_inner.as_str()));
-                    first_line = false;
-                }
-            }
+// Where the first closed literal span in `text` starts and ends
+fn next_literal(text: &str) -> Option<(usize, usize)> {
+    let mut from = 0;
+    loop {
+        let (start, opening) = ["`+", "+++", "pass:["].into_iter()
+            .filter_map(|opening| text[from..].find(opening).map(|start| (from + start, opening)))
+            .min_by_key(|(start, _)| *start)?;
+        let length = match opening {
+            "`+" => literal_monospace_end(&text[start..]).map(|end| end + 2),
+            _ => passthrough(&text[start..]).map(|(_, length)| length),
+        };
+        match length {
+            Some(length) => return Some((start, start + length)),
+            None => from = start + opening.len(),
         }
     }
-    
-    Block::Paragraph { content }
 }
 
-fn parse_block_metadata(pair: pest::iterators::Pair<Rule>) -> Block {
+// Position of the closing `+` for text starting with `` `+ ``
+fn literal_monospace_end(text: &str) -> Option<usize> {
+    text.strip_prefix("`+")?.find("+`").map(|end| end + 2)
+}
+
+// The content of the `+++text+++` or `pass:[text]` that `text` starts with, and the length
+// of the whole passthrough
+fn passthrough(text: &str) -> Option<(&str, usize)> {
+    if let Some(rest) = text.strip_prefix("+++") {
+        let end = rest.find("+++")?;
+        return Some((&rest[..end], end + 6));
+    }
+    let rest = text.strip_prefix("pass:[")?;
+    let end = rest.find(']')?;
+    Some((&rest[..end], end + 7))
+}
+
+fn is_hardbreaks_attribute(name: &str) -> bool {
+    name == "hardbreaks-option" || name == "hardbreaks"
+}
+
+fn parse_block_metadata(pair: pest::iterators::Pair<Rule>, context: BodyContext) -> Block {
     for inner_pair in pair.into_inner() {
         match inner_pair.as_rule() {
+            Rule::document_attribute => {
+                return Block::BlockMetadata {
+                    kind: parse_document_attribute(inner_pair, &mut context.symbols.borrow_mut()),
+                };
+            }
             Rule::block_title => {
-                let title = inner_pair.as_str().trim_start_matches('.').to_string();
+                let title = substitute_outside_literals(inner_pair.as_str().trim_start_matches('.'), context);
                 return Block::BlockMetadata {
                     kind: BlockMetadataKind::Title(title),
                 };
             }
             Rule::block_attribute => {
-                let attributes = parse_block_attribute(inner_pair);
+                let attributes = parse_block_attribute(inner_pair, &mut context.symbols.borrow_mut());
                 return Block::BlockMetadata {
                     kind: BlockMetadataKind::Attribute(attributes),
                 };
             }
             Rule::block_anchor => {
-                let anchor = parse_block_anchor(inner_pair);
+                let anchor = substitute_outside_literals(&parse_block_anchor(inner_pair), context);
                 return Block::BlockMetadata {
                     kind: BlockMetadataKind::Anchor(anchor),
                 };
@@ -443,113 +2382,63 @@ fn parse_block_metadata(pair: pest::iterators::Pair<Rule>) -> Block {
     }
 }
 
-fn parse_block_attribute(pair: pest::iterators::Pair<Rule>) -> Vec<String> {
-    let mut attributes = Vec::new();
+fn parse_document_attribute(pair: pest::iterators::Pair<Rule>, symbols: &mut SymbolPool) -> BlockMetadataKind {
+    let mut name = symbols.symbol("");
+    let mut value = None;
+    let mut unset = false;
     
     for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::attribute_list {
-            for attr_pair in inner_pair.into_inner() {
-                if attr_pair.as_rule() == Rule::attribute_entry {
-                    attributes.push(attr_pair.as_str().trim().to_string());
+        match inner_pair.as_rule() {
+            Rule::attribute_unset => unset = true,
+            Rule::attribute_name => name = symbols.symbol(inner_pair.as_str()),
+            Rule::attribute_value => {
+                let val = inner_pair.as_str().trim();
+                if !val.is_empty() {
+                    value = Some(val.to_string());
                 }
             }
+            _ => {}
         }
     }
     
-    attributes
-}
-
-fn parse_block_anchor(pair: pest::iterators::Pair<Rule>) -> String {
-    for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::anchor_id {
-            return inner_pair.as_str().to_string();
-        }
+    if unset {
+        BlockMetadataKind::AttributeUnset(name)
+    } else {
+        BlockMetadataKind::AttributeEntry(Attribute { name, value })
     }
-    String::new()
 }
 
-fn parse_inline_elements(pair: pest::iterators::Pair<Rule>) -> Vec<InlineElement> {
-    let mut elements = Vec::new();
+fn parse_block_attribute(pair: pest::iterators::Pair<Rule>, symbols: &mut SymbolPool) -> Vec<Symbol> {
+    let mut attributes = Vec::new();
     
     for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::inline_element {
-            if let Some(element) = parse_inline_element(inner_pair) {
-                elements.push(element);
+        if inner_pair.as_rule() == Rule::attribute_list {
+            for attr_pair in inner_pair.into_inner() {
+                if matches!(attr_pair.as_rule(), Rule::attribute_entry | Rule::empty_attribute_entry) {
+                    attributes.push(symbols.symbol(attr_pair.as_str().trim()));
+                }
             }
         }
     }
     
-    elements
+    attributes
 }
 
-fn parse_inline_element(pair: pest::iterators::Pair<Rule>) -> Option<InlineElement> {
+fn parse_block_anchor(pair: pest::iterators::Pair<Rule>) -> String {
     for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::formatted_text => return Some(parse_formatted_text(inner_pair)),
-            Rule::inline_macro => return Some(parse_inline_macro(inner_pair)),
-            Rule::line_break => return Some(InlineElement::LineBreak),
-            Rule::whitespace => {
-                return Some(InlineElement::Text(inner_pair.as_str().to_string()));
-            }
-            Rule::regular_text => {
-                return Some(InlineElement::Text(inner_pair.as_str().to_string()));
-            }
-            _ => {}
+        if inner_pair.as_rule() == Rule::anchor_id {
+            return inner_pair.as_str().to_string();
         }
     }
-    None
+    String::new()
 }
 
-fn parse_formatted_text(pair: pest::iterators::Pair<Rule>) -> InlineElement {
-    for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::strong_text => {
-                return InlineElement::Formatted {
-                    kind: FormattedTextKind::Strong,
-                    content: parse_formatted_content(inner_pair, Rule::strong_content),
-                };
-            }
-            Rule::emphasis_text => {
-                return InlineElement::Formatted {
-                    kind: FormattedTextKind::Emphasis,
-                    content: parse_formatted_content(inner_pair, Rule::emphasis_content),
-                };
-            }
-            Rule::monospace_text => {
-                return InlineElement::Formatted {
-                    kind: FormattedTextKind::Monospace,
-                    content: parse_formatted_content(inner_pair, Rule::monospace_content),
-                };
-            }
-            Rule::superscript_text => {
-                return InlineElement::Formatted {
-                    kind: FormattedTextKind::Superscript,
-                    content: parse_formatted_content(inner_pair, Rule::superscript_content),
-                };
-            }
-            Rule::subscript_text => {
-                return InlineElement::Formatted {
-                    kind: FormattedTextKind::Subscript,
-                    content: parse_formatted_content(inner_pair, Rule::subscript_content),
-                };
-            }
-            _ => {}
-        }
-    }
-    
-    InlineElement::Text(String::new())
-}
+const INLINE_MARKERS: [&str; 14] = ["+++", "pass:[", "*", "_", "`", "^", "~", "link:", "xref:", "term:", "image:", "https://", "http://", "<<"];
 
-fn parse_formatted_content(pair: pest::iterators::Pair<Rule>, content_rule: Rule) -> Vec<InlineElement> {
-    for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == content_rule {
-            return vec![InlineElement::Text(inner_pair.as_str().to_string())];
-        }
-    }
-    Vec::new()
-}
+// The markers left as plain text when macros are off
+const MACRO_MARKERS: [&str; 7] = ["link:", "xref:", "term:", "image:", "https://", "http://", "<<"];
 
-fn parse_paragraph_content(text: &str) -> Vec<InlineElement> {
+fn parse_paragraph_content(text: &str, subs: Substitutions, symbols: &mut SymbolPool) -> Vec<InlineElement> {
     let mut elements = Vec::new();
     let mut current_pos = 0;
     
@@ -560,58 +2449,12 @@ fn parse_paragraph_content(text: &str) -> Vec<InlineElement> {
         let mut marker_type = None;
         
         // Check for all formatting types
-        if let Some(pos) = remaining.find('*') {
-            if pos < earliest_pos {
-                earliest_pos = pos;
-                marker_type = Some("*");
-            }
-        }
-        if let Some(pos) = remaining.find('_') {
-            if pos < earliest_pos {
-                earliest_pos = pos;
-                marker_type = Some("_");
-            }
-        }
-        if let Some(pos) = remaining.find('`') {
-            if pos < earliest_pos {
-                earliest_pos = pos;
-                marker_type = Some("`");
-            }
-        }
-        if let Some(pos) = remaining.find('^') {
-            if pos < earliest_pos {
-                earliest_pos = pos;
-                marker_type = Some("^");
-            }
-        }
-        if let Some(pos) = remaining.find('~') {
-            if pos < earliest_pos {
-                earliest_pos = pos;
-                marker_type = Some("~");
-            }
-        }
-        if let Some(pos) = remaining.find("link:") {
-            if pos < earliest_pos {
-                earliest_pos = pos;
-                marker_type = Some("link:");
-            }
-        }
-        if let Some(pos) = remaining.find("https://") {
-            if pos < earliest_pos {
-                earliest_pos = pos;
-                marker_type = Some("https://");
-            }
-        }
-        if let Some(pos) = remaining.find("http://") {
-            if pos < earliest_pos {
-                earliest_pos = pos;
-                marker_type = Some("http://");
-            }
-        }
-        if let Some(pos) = remaining.find("<<") {
-            if pos < earliest_pos {
+        for marker in INLINE_MARKERS.into_iter().filter(|marker| subs.macros || !MACRO_MARKERS.contains(marker)) {
+            if let Some(pos) = remaining.find(marker)
+                && pos < earliest_pos
+            {
                 earliest_pos = pos;
-                marker_type = Some("<<");
+                marker_type = Some(marker);
             }
         }
         
@@ -624,6 +2467,16 @@ fn parse_paragraph_content(text: &str) -> Vec<InlineElement> {
             }
             
             match marker {
+                "+++" | "pass:[" => match passthrough(&text[actual_start..]) {
+                    Some((content, length)) => {
+                        elements.push(InlineElement::Passthrough(content.to_string()));
+                        current_pos = actual_start + length;
+                    }
+                    None => {
+                        elements.push(InlineElement::Text(marker.to_string()));
+                        current_pos = actual_start + marker.len();
+                    }
+                },
                 "*" => {
                     if let Some(end) = text[actual_start + 1..].find('*') {
                         let actual_end = actual_start + 1 + end;
@@ -631,6 +2484,7 @@ fn parse_paragraph_content(text: &str) -> Vec<InlineElement> {
                         elements.push(InlineElement::Formatted {
                             kind: FormattedTextKind::Strong,
                             content: vec![InlineElement::Text(content.to_string())],
+                            attributes: InlineAttributes::default(),
                         });
                         current_pos = actual_end + 1;
                     } else {
@@ -645,6 +2499,7 @@ fn parse_paragraph_content(text: &str) -> Vec<InlineElement> {
                         elements.push(InlineElement::Formatted {
                             kind: FormattedTextKind::Emphasis,
                             content: vec![InlineElement::Text(content.to_string())],
+                            attributes: InlineAttributes::default(),
                         });
                         current_pos = actual_end + 1;
                     } else {
@@ -653,12 +2508,21 @@ fn parse_paragraph_content(text: &str) -> Vec<InlineElement> {
                     }
                 }
                 "`" => {
-                    if let Some(end) = text[actual_start + 1..].find('`') {
+                    if let Some(end) = literal_monospace_end(&text[actual_start..]) {
+                        let content = &text[actual_start + 2..actual_start + end];
+                        elements.push(InlineElement::Formatted {
+                            kind: FormattedTextKind::LiteralMonospace,
+                            content: vec![InlineElement::Text(content.to_string())],
+                            attributes: InlineAttributes::default(),
+                        });
+                        current_pos = actual_start + end + 2;
+                    } else if let Some(end) = text[actual_start + 1..].find('`') {
                         let actual_end = actual_start + 1 + end;
                         let content = &text[actual_start + 1..actual_end];
                         elements.push(InlineElement::Formatted {
                             kind: FormattedTextKind::Monospace,
                             content: vec![InlineElement::Text(content.to_string())],
+                            attributes: InlineAttributes::default(),
                         });
                         current_pos = actual_end + 1;
                     } else {
@@ -667,12 +2531,13 @@ fn parse_paragraph_content(text: &str) -> Vec<InlineElement> {
                     }
                 }
                 "^" => {
-                    if let Some(end) = text[actual_start + 1..].find('^') {
+                    if let Some(end) = script_end(&text[actual_start + 1..], '^') {
                         let actual_end = actual_start + 1 + end;
                         let content = &text[actual_start + 1..actual_end];
                         elements.push(InlineElement::Formatted {
                             kind: FormattedTextKind::Superscript,
                             content: vec![InlineElement::Text(content.to_string())],
+                            attributes: InlineAttributes::default(),
                         });
                         current_pos = actual_end + 1;
                     } else {
@@ -681,12 +2546,13 @@ fn parse_paragraph_content(text: &str) -> Vec<InlineElement> {
                     }
                 }
                 "~" => {
-                    if let Some(end) = text[actual_start + 1..].find('~') {
+                    if let Some(end) = script_end(&text[actual_start + 1..], '~') {
                         let actual_end = actual_start + 1 + end;
                         let content = &text[actual_start + 1..actual_end];
                         elements.push(InlineElement::Formatted {
                             kind: FormattedTextKind::Subscript,
                             content: vec![InlineElement::Text(content.to_string())],
+                            attributes: InlineAttributes::default(),
                         });
                         current_pos = actual_end + 1;
                     } else {
@@ -721,6 +2587,80 @@ fn parse_paragraph_content(text: &str) -> Vec<InlineElement> {
                         current_pos = actual_start + 5;
                     }
                 }
+                "xref:" => {
+                    let target_start = actual_start + 5;
+                    let target_end = text[target_start..]
+                        .find(|c: char| c == '[' || c.is_whitespace())
+                        .map(|pos| target_start + pos);
+                    
+                    match target_end {
+                        Some(bracket_start) if bracket_start > target_start
+                            && text[bracket_start..].starts_with('[')
+                            && let Some(bracket_end) = text[bracket_start..].find(']') =>
+                        {
+                            let text_end = bracket_start + bracket_end;
+                            let xref_text = text[bracket_start + 1..text_end].trim().to_string();
+                            elements.push(InlineElement::Macro {
+                                kind: MacroKind::CrossReference {
+                                    target: text[target_start..bracket_start].to_string(),
+                                    text: if xref_text.is_empty() { None } else { Some(xref_text) },
+                                },
+                            });
+                            current_pos = text_end + 1;
+                        }
+                        _ => {
+                            elements.push(InlineElement::Text(text[actual_start..target_start].to_string()));
+                            current_pos = target_start;
+                        }
+                    }
+                }
+                // `term:[API]` names its term in the brackets; the glossary entry is found later
+                "term:" => {
+                    let bracket_start = actual_start + 5;
+                    match text[bracket_start..].strip_prefix('[').and_then(|rest| rest.find(']')) {
+                        Some(length) if !text[bracket_start + 1..bracket_start + 1 + length].trim().is_empty() => {
+                            let text_end = bracket_start + 1 + length;
+                            elements.push(InlineElement::Macro {
+                                kind: MacroKind::Term {
+                                    term: text[bracket_start + 1..text_end].to_string(),
+                                    target: None,
+                                },
+                            });
+                            current_pos = text_end + 1;
+                        }
+                        _ => {
+                            elements.push(InlineElement::Text(text[actual_start..bracket_start].to_string()));
+                            current_pos = bracket_start;
+                        }
+                    }
+                }
+                "image:" => {
+                    let path_start = actual_start + 6;
+                    let path_end = text[path_start..]
+                        .find(|c: char| c == '[' || c.is_whitespace())
+                        .map(|pos| path_start + pos);
+                    
+                    match path_end {
+                        Some(bracket_start) if bracket_start > path_start
+                            && text[bracket_start..].starts_with('[')
+                            && let Some(bracket_end) = text[bracket_start..].find(']') =>
+                        {
+                            let text_end = bracket_start + bracket_end;
+                            let attributes = text[bracket_start + 1..text_end].trim().to_string();
+                            elements.push(InlineElement::Macro {
+                                kind: MacroKind::Image {
+                                    path: text[path_start..bracket_start].to_string(),
+                                    attributes: if attributes.is_empty() { None } else { Some(attributes) },
+                                },
+                            });
+                            current_pos = text_end + 1;
+                        }
+                        _ => {
+                            elements.push(InlineElement::Text(text[actual_start..path_start].to_string()));
+                            current_pos = path_start;
+                        }
+                    }
+                }
                 "https://" | "http://" => {
                     // Find the end of the URL (space, newline, or common delimiters)
                     let url_start = actual_start;
@@ -734,7 +2674,7 @@ fn parse_paragraph_content(text: &str) -> Vec<InlineElement> {
                             break;
                         }
                         // Stop at period if it's followed by space (end of sentence)
-                        if ch == '.' && chars.get(i + 1).map_or(true, |&c| c.is_whitespace()) {
+                        if ch == '.' && chars.get(i + 1).is_none_or(|c| c.is_whitespace()) {
                             url_end = actual_start + remaining_text.char_indices().nth(i).unwrap().0;
                             break;
                         }
@@ -823,93 +2763,210 @@ fn parse_paragraph_content(text: &str) -> Vec<InlineElement> {
         }
     }
     
+    let mut elements = attach_inline_attributes(elements, symbols);
+    if subs.smart_quotes {
+        elements = smart_quotes(elements, subs, symbols);
+    }
+    if subs.replacements {
+        replace_characters(&mut elements);
+    }
     elements
 }
 
-fn parse_inline_macro(pair: pest::iterators::Pair<Rule>) -> InlineElement {
-    for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::link_macro => {
-                return InlineElement::Macro {
-                    kind: parse_link_macro(inner_pair),
-                };
-            }
-            Rule::image_macro => {
-                return InlineElement::Macro {
-                    kind: parse_image_macro(inner_pair),
-                };
+const SMART_QUOTES: [(char, char, char); 2] = [('"', '\u{201c}', '\u{201d}'), ('\'', '\u{2018}', '\u{2019}')];
+
+// ``"`text`"`` first reads as monospace text between straight quotes, which become curved
+// ones round the text, with the text read again for any formatting inside
+fn smart_quotes(elements: Vec<InlineElement>, subs: Substitutions, symbols: &mut SymbolPool) -> Vec<InlineElement> {
+    let mut result: Vec<InlineElement> = Vec::new();
+    let mut elements = elements.into_iter().peekable();
+    while let Some(element) = elements.next() {
+        let quoted = match &element {
+            InlineElement::Formatted { kind: FormattedTextKind::Monospace, content, attributes } if attributes.is_empty() => {
+                match content.as_slice() {
+                    [InlineElement::Text(text)] => Some(text.clone()),
+                    _ => None,
+                }
             }
-            Rule::xref_macro => {
-                return InlineElement::Macro {
-                    kind: parse_xref_macro(inner_pair),
-                };
+            _ => None,
+        };
+        let quotes = match (result.last(), elements.peek()) {
+            (Some(InlineElement::Text(before)), Some(InlineElement::Text(after))) => {
+                SMART_QUOTES.into_iter().find(|(mark, _, _)| before.ends_with(*mark) && after.starts_with(*mark))
             }
-            _ => {}
+            _ => None,
+        };
+        let (Some(quoted), Some((mark, open, close))) = (quoted, quotes) else {
+            result.push(element);
+            continue;
+        };
+        if let Some(InlineElement::Text(before)) = result.last_mut() {
+            before.pop();
+            before.push(open);
+        }
+        // Replacements are made once over the whole paragraph
+        for element in parse_paragraph_content(&quoted, Substitutions { replacements: false, ..subs }, symbols) {
+            push_inline(&mut result, element);
+        }
+        if let Some(InlineElement::Text(after)) = elements.next() {
+            push_inline(&mut result, InlineElement::Text(format!("{}{}", close, &after[mark.len_utf8()..])));
         }
     }
-    
-    InlineElement::Text(String::new())
+    result
 }
 
-fn parse_link_macro(pair: pest::iterators::Pair<Rule>) -> MacroKind {
-    let mut url = String::new();
-    let mut text = None;
-    
-    for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::url => {
-                url = inner_pair.as_str().to_string();
-            }
-            Rule::link_text => {
-                let link_text = inner_pair.as_str().trim();
-                if !link_text.is_empty() {
-                    text = Some(link_text.to_string());
-                }
-            }
+// Runs of text stay in one element
+fn push_inline(elements: &mut Vec<InlineElement>, element: InlineElement) {
+    match (elements.last_mut(), element) {
+        (Some(InlineElement::Text(last)), InlineElement::Text(text)) => last.push_str(&text),
+        (_, element) => elements.push(element),
+    }
+}
+
+const REPLACEMENTS: [(&str, &str); 8] = [
+    ("(C)", "\u{a9}"),
+    ("(R)", "\u{ae}"),
+    ("(TM)", "\u{2122}"),
+    ("...", "\u{2026}"),
+    ("->", "\u{2192}"),
+    ("=>", "\u{21d2}"),
+    ("<-", "\u{2190}"),
+    ("<=", "\u{21d0}"),
+];
+
+// Text in code is left as written
+fn replace_characters(elements: &mut [InlineElement]) {
+    for element in elements {
+        match element {
+            InlineElement::Text(text) => *text = replacements(text),
+            InlineElement::Formatted { kind: FormattedTextKind::Monospace | FormattedTextKind::LiteralMonospace, .. } => {}
+            InlineElement::Formatted { content, .. } => replace_characters(content),
             _ => {}
         }
     }
-    
-    MacroKind::Link { url, text }
 }
 
-fn parse_image_macro(pair: pest::iterators::Pair<Rule>) -> MacroKind {
-    let mut path = String::new();
-    let mut attributes = None;
+// A backslash in front of one (`\(C)`) keeps the characters as written
+fn replacements(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut index = 0;
+    while let Some(ch) = text[index..].chars().next() {
+        let escaped = ch == '\\';
+        let at = index + usize::from(escaped);
+        if let Some((length, replacement)) = replacement_at(&text[at..], text[..index].chars().next_back()) {
+            result.push_str(if escaped { &text[at..at + length] } else { replacement });
+            index = at + length;
+        } else {
+            result.push(ch);
+            index += ch.len_utf8();
+        }
+    }
+    result
+}
+
+// What `text` starts with stands for, given the character before it. A double hyphen is a
+// dash only between words or with space on both sides, so `--verbose` stays as it is, and an
+// apostrophe only between letters.
+fn replacement_at(text: &str, before: Option<char>) -> Option<(usize, &'static str)> {
+    if let Some(&(from, to)) = REPLACEMENTS.iter().find(|(from, _)| text.starts_with(from)) {
+        return Some((from.len(), to));
+    }
+    let word = |ch: Option<char>| ch.is_some_and(|ch| ch.is_alphanumeric() || ch == '_');
+    let space = |ch: Option<char>| ch.is_none_or(|ch| ch == ' ');
+    if let Some(rest) = text.strip_prefix("--") {
+        let after = rest.chars().next();
+        if (word(before) && word(after)) || (space(before) && space(after)) {
+            return Some((2, "\u{2014}"));
+        }
+    }
+    if let Some(rest) = text.strip_prefix('\'')
+        && before.is_some_and(char::is_alphanumeric)
+        && rest.chars().next().is_some_and(char::is_alphanumeric)
+    {
+        return Some((1, "\u{2019}"));
+    }
+    None
+}
+
+// Super- and subscripts are closed by the next mark only when the text between them is not
+// empty and has no spaces, so `x^2 + y^2` and `~/a ~/b` stay as they are
+fn script_end(text: &str, mark: char) -> Option<usize> {
+    let end = text.find(mark)?;
+    (end > 0 && !text[..end].contains(char::is_whitespace)).then_some(end)
+}
+
+// An attribute list right in front of formatted text, as in `[.role]*text*`, moves onto it,
+// and one in front of `#text#` marks that text
+fn attach_inline_attributes(elements: Vec<InlineElement>, symbols: &mut SymbolPool) -> Vec<InlineElement> {
+    let mut result = Vec::new();
     
-    for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::image_path => {
-                path = inner_pair.as_str().to_string();
-            }
-            Rule::image_attributes => {
-                let attrs = inner_pair.as_str().trim();
-                if !attrs.is_empty() {
-                    attributes = Some(attrs.to_string());
+    for element in elements {
+        match element {
+            InlineElement::Formatted { kind, content, attributes } if attributes.is_empty() => {
+                let mut attributes = attributes;
+                let code = matches!(kind, FormattedTextKind::Monospace | FormattedTextKind::LiteralMonospace);
+                if let Some(InlineElement::Text(text)) = result.last_mut()
+                    && let Some(trailing) = take_trailing_attributes(text, code, symbols)
+                {
+                    attributes = trailing;
+                    if text.is_empty() {
+                        result.pop();
+                    }
                 }
+                result.push(InlineElement::Formatted { kind, content, attributes });
             }
-            _ => {}
+            InlineElement::Text(text) => result.extend(split_marked_text(&text, symbols)),
+            element => result.push(element),
         }
     }
     
-    MacroKind::Image { path, attributes }
+    result
+}
+
+fn take_trailing_attributes(text: &mut String, code: bool, symbols: &mut SymbolPool) -> Option<InlineAttributes> {
+    let inner = text.strip_suffix(']')?;
+    let start = inner.rfind('[')?;
+    let list = &inner[start + 1..];
+    let attributes = if code { InlineAttributes::parse_code_in(list, symbols) } else { InlineAttributes::parse_in(list, symbols) }?;
+    text.truncate(start);
+    Some(attributes)
 }
 
-fn parse_xref_macro(pair: pest::iterators::Pair<Rule>) -> MacroKind {
-    let mut target = String::new();
-    let mut text = None;
+fn split_marked_text(text: &str, symbols: &mut SymbolPool) -> Vec<InlineElement> {
+    let mut elements = Vec::new();
+    let mut rest = text;
+    let mut plain = String::new();
     
-    for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::xref_target => {
-                target = inner_pair.as_str().to_string();
+    while let Some(open) = rest.find('[') {
+        let marked = rest[open + 1..].find("]#").and_then(|close| {
+            let attributes = InlineAttributes::parse_in(&rest[open + 1..open + 1 + close], symbols)?;
+            let content_start = open + 1 + close + 2;
+            let content_end = content_start + rest[content_start..].find('#')?;
+            (content_end > content_start).then_some((attributes, content_start, content_end))
+        });
+        match marked {
+            Some((attributes, content_start, content_end)) => {
+                plain.push_str(&rest[..open]);
+                if !plain.is_empty() {
+                    elements.push(InlineElement::Text(core::mem::take(&mut plain)));
+                }
+                elements.push(InlineElement::Formatted {
+                    kind: FormattedTextKind::Mark,
+                    content: vec![InlineElement::Text(rest[content_start..content_end].to_string())],
+                    attributes,
+                });
+                rest = &rest[content_end + 1..];
             }
-            Rule::xref_text => {
-                text = Some(inner_pair.as_str().to_string());
+            None => {
+                plain.push_str(&rest[..open + 1]);
+                rest = &rest[open + 1..];
             }
-            _ => {}
         }
     }
     
-    MacroKind::CrossReference { target, text }
-}
\ No newline at end of file
+    plain.push_str(rest);
+    if !plain.is_empty() {
+        elements.push(InlineElement::Text(plain));
+    }
+    elements
+}
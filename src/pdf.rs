@@ -0,0 +1,65 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ast::*;
+use crate::trace;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdfOptions {
+    // External typesetting program: pdflatex, xelatex, lualatex or tectonic
+    pub engine: String,
+    // Directory that relative image paths are read from
+    pub base_dir: PathBuf,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        PdfOptions {
+            engine: "pdflatex".to_string(),
+            base_dir: PathBuf::from("."),
+        }
+    }
+}
+
+impl Document {
+    pub fn to_pdf(&self, options: &PdfOptions) -> io::Result<Vec<u8>> {
+        trace::span!(DEBUG, "render", backend = "pdf", engine = options.engine.as_str());
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_err(io::Error::other)?.subsec_nanos();
+        let work_dir = std::env::temp_dir().join(format!("adoc-pdf-{}-{}", std::process::id(), nanos));
+        fs::create_dir_all(&work_dir)?;
+        
+        let result = self.typeset(&work_dir, options);
+        let _ = fs::remove_dir_all(&work_dir);
+        result
+    }
+
+    fn typeset(&self, work_dir: &Path, options: &PdfOptions) -> io::Result<Vec<u8>> {
+        fs::write(work_dir.join("document.tex"), self.to_latex())?;
+        
+        let base_dir = fs::canonicalize(&options.base_dir).unwrap_or_else(|_| options.base_dir.clone());
+        // Tectonic resolves cross references itself, LaTeX engines need a second pass
+        let passes = if options.engine == "tectonic" { 1 } else { 2 };
+        for _ in 0..passes {
+            let mut command = Command::new(&options.engine);
+            if options.engine == "tectonic" {
+                command.arg("-Z").arg(format!("search-path={}", base_dir.display()));
+            } else {
+                command.args(["-interaction=nonstopmode", "-halt-on-error"]);
+                // The trailing separator keeps the engine's default search path
+                command.env("TEXINPUTS", format!("{}//:", base_dir.display()));
+            }
+            let output = command.arg("document.tex").current_dir(work_dir).output()
+                .map_err(|e| io::Error::new(e.kind(), format!("cannot run '{}': {}", options.engine, e)))?;
+            if !output.status.success() {
+                let log = String::from_utf8_lossy(&output.stdout);
+                let message = log.lines().find(|line| line.starts_with('!')).unwrap_or("typesetting failed");
+                return Err(io::Error::other(format!("{}: {}", options.engine, message)));
+            }
+        }
+        
+        fs::read(work_dir.join("document.pdf"))
+    }
+}
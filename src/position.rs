@@ -0,0 +1,490 @@
+use alloc::collections::BTreeMap;
+
+use pest::Parser;
+use pest::iterators::Pair;
+
+use crate::ast::*;
+use crate::intern::Symbol;
+use crate::parser::{AsciiDocParser, Rule, define_attribute, is_attribute_name, resolve_attribute};
+use crate::prelude::*;
+
+// 1-based, with columns counted in characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+// From the first character of a node to just past its last, leaving out the line break after it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    // The end counts as inside, since that is where an editor's cursor sits after typing
+    pub fn contains(&self, position: Position) -> bool {
+        self.start <= position && position <= self.end
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Node<'a> {
+    Block(&'a Block),
+    ListItem(&'a ListItem),
+    Inline(&'a InlineElement),
+}
+
+#[derive(Debug, Clone)]
+pub struct LocatedNode<'a> {
+    pub node: Node<'a>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoldingKind {
+    Section,
+    DelimitedBlock,
+    List,
+}
+
+// Whole lines, as editors fold them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoldingRange {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub kind: FoldingKind,
+}
+
+// A line that sets or unsets an attribute, in the header or the body. `value` is as written
+// and `computed` is what the attribute holds from that line on, with references to
+// attributes set before it resolved; it is none once the attribute is unset. `cycle` is the
+// chain of references that led from the value back to an attribute already being resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeDefinition {
+    pub name: String,
+    pub value: Option<String>,
+    pub computed: Option<String>,
+    pub unset: bool,
+    pub cycle: Option<Vec<String>>,
+    pub span: Span,
+}
+
+// A section for an editor's outline. `span` covers the section with its subsections and
+// `heading` just its title line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentSymbol {
+    pub title: String,
+    pub id: Option<String>,
+    pub level: usize,
+    pub span: Span,
+    pub heading: Span,
+    pub children: Vec<DocumentSymbol>,
+}
+
+impl Document {
+    // The nodes under a cursor in `source`, the text this document was parsed from, from the
+    // outermost block down to the innermost node. Empty when the cursor is not on a block.
+    pub fn node_at_position(&self, source: &str, line: usize, column: usize) -> Vec<LocatedNode<'_>> {
+        let position = Position { line, column };
+        let mut path = Vec::new();
+        let mut nodes = source_tree(self, source);
+        while let Some(index) = nodes.iter().position(|node| node.span.contains(position)) {
+            let SourceNode { node, span, children } = nodes.swap_remove(index);
+            path.push(LocatedNode { node, span });
+            nodes = children;
+        }
+        path
+    }
+    
+    // Sections, delimited blocks and lists that run over more than one line, outermost first
+    pub fn folding_ranges(&self, source: &str) -> Vec<FoldingRange> {
+        let mut ranges = Vec::new();
+        collect_folding_ranges(&source_tree(self, source), &mut ranges);
+        ranges
+    }
+
+    pub fn symbols(&self, source: &str) -> Vec<DocumentSymbol> {
+        section_symbols(&source_tree(self, source), source)
+    }
+    
+    // Every attribute entry in `source` in the order written, so tooling can show where a
+    // value came from
+    pub fn attribute_table(&self, source: &str) -> Vec<AttributeDefinition> {
+        let Ok(pairs) = AsciiDocParser::parse(Rule::document, source) else {
+            return Vec::new();
+        };
+        let mut entries = Vec::new();
+        for pair in pairs {
+            collect_attribute_entries(pair, 0, &mut entries);
+        }
+        let line_starts = line_starts(source);
+        let mut values = BTreeMap::new();
+        entries.into_iter()
+            .map(|EntryToken { name, value, unset, start, end }| {
+                let (computed, cycle) = if unset {
+                    values.remove(name.as_str());
+                    (None, None)
+                } else {
+                    let cycle = define_attribute(&mut values, &Attribute { name: Symbol::new(&name), value: value.clone() });
+                    // Setting an attribute can also make a value set before it, which referred
+                    // to this one, nest too deeply
+                    let cycle = cycle.or_else(|| {
+                        values.iter()
+                            .filter(|(_, value)| value.contains('{'))
+                            .filter_map(|(other, _)| resolve_attribute(other, &values).1)
+                            .find(|chain| chain.iter().any(|link| *link == name.as_str()))
+                    });
+                    let computed = resolve_attribute(&name, &values).0;
+                    (Some(computed), cycle.map(|cycle| cycle.iter().map(|name| name.to_string()).collect()))
+                };
+                let span = Span { start: position(source, &line_starts, start), end: position(source, &line_starts, end) };
+                AttributeDefinition { name, value, computed, unset, cycle, span }
+            })
+            .collect()
+    }
+    
+    // The entry that last set the attribute referenced as `{name}` under the cursor, for an
+    // editor's go-to-definition
+    pub fn attribute_definition_at(&self, source: &str, line: usize, column: usize) -> Option<AttributeDefinition> {
+        let text = source.lines().nth(line.checked_sub(1)?)?;
+        let cursor = text.char_indices().nth(column.checked_sub(1)?).map_or(text.len(), |(index, _)| index);
+        let start = text[..cursor].rfind('{')?;
+        let end = cursor + text[cursor..].find('}')?;
+        let name = &text[start + 1..end];
+        if !is_attribute_name(name) {
+            return None;
+        }
+        let reference = Position { line, column: text[..start].chars().count() + 1 };
+        self.attribute_table(source).into_iter()
+            .rfind(|definition| definition.name == name && definition.span.start < reference)
+            .filter(|definition| !definition.unset)
+    }
+}
+
+fn collect_folding_ranges(nodes: &[SourceNode], ranges: &mut Vec<FoldingRange>) {
+    for node in nodes {
+        let kind = match node.node {
+            Node::Block(Block::Section { .. }) => Some(FoldingKind::Section),
+            Node::Block(Block::DelimitedBlock { .. } | Block::Sidebar { .. } | Block::Table { .. } | Block::Admonition { .. }) => {
+                Some(FoldingKind::DelimitedBlock)
+            }
+            Node::Block(Block::List { .. }) => Some(FoldingKind::List),
+            _ => None,
+        };
+        if let Some(kind) = kind
+            && node.span.end.line > node.span.start.line
+        {
+            ranges.push(FoldingRange { start_line: node.span.start.line, end_line: node.span.end.line, kind });
+        }
+        collect_folding_ranges(&node.children, ranges);
+    }
+}
+
+fn section_symbols(nodes: &[SourceNode], source: &str) -> Vec<DocumentSymbol> {
+    nodes.iter()
+        .filter_map(|node| match node.node {
+            Node::Block(Block::Section { level, title, id, .. }) => {
+                let line = node.span.start.line;
+                let length = source.lines().nth(line - 1).unwrap_or_default().chars().count();
+                Some(DocumentSymbol {
+                    title: title.clone(),
+                    id: id.clone(),
+                    level: *level,
+                    span: node.span,
+                    heading: Span { start: node.span.start, end: Position { line, column: length + 1 } },
+                    children: section_symbols(&node.children, source),
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+pub(crate) struct SourceNode<'a> {
+    pub(crate) node: Node<'a>,
+    pub(crate) span: Span,
+    pub(crate) children: Vec<SourceNode<'a>>,
+}
+
+// An attribute entry with the byte offsets of its line
+struct EntryToken {
+    name: String,
+    value: Option<String>,
+    unset: bool,
+    start: usize,
+    end: usize,
+}
+
+// A role list above the title sets `role`, as the parser reads it
+fn collect_attribute_entries(pair: Pair<Rule>, offset: usize, entries: &mut Vec<EntryToken>) {
+    let span = pair.as_span();
+    let start = offset + span.start();
+    let end = offset + span.start() + span.as_str().trim_end_matches(['\n', '\r']).len();
+    match pair.as_rule() {
+        Rule::header_attribute | Rule::document_attribute => {
+            let mut name = String::new();
+            let mut value = None;
+            let mut unset = false;
+            for inner_pair in pair.into_inner() {
+                match inner_pair.as_rule() {
+                    Rule::attribute_name => name = inner_pair.as_str().to_string(),
+                    Rule::attribute_value => value = Some(inner_pair.as_str().trim().to_string()).filter(|value| !value.is_empty()),
+                    Rule::attribute_unset => unset = true,
+                    _ => {}
+                }
+            }
+            entries.push(EntryToken { name, value, unset, start, end });
+        }
+        Rule::document_roles => {
+            let roles: Vec<&str> = pair.into_inner().map(|role| role.as_str()).collect();
+            entries.push(EntryToken { name: "role".to_string(), value: Some(roles.join(" ")), unset: false, start, end });
+        }
+        Rule::sidebar_content => {
+            if let Ok(pairs) = AsciiDocParser::parse(Rule::nested_body, pair.as_str()) {
+                for pair in pairs {
+                    collect_attribute_entries(pair, start, entries);
+                }
+            }
+        }
+        _ => {
+            for inner_pair in pair.into_inner() {
+                collect_attribute_entries(inner_pair, offset, entries);
+            }
+        }
+    }
+}
+
+// The AST carries no positions, so the source is parsed again and its block rules are matched
+// to the nodes in document order. Rules the tree has no node for, such as an attribute list
+// folded into the block below it, are passed over.
+pub(crate) fn source_tree<'a>(document: &'a Document, source: &str) -> Vec<SourceNode<'a>> {
+    let Ok(pairs) = AsciiDocParser::parse(Rule::document, source) else {
+        return Vec::new();
+    };
+    let mut tokens = Vec::new();
+    for pair in pairs {
+        collect_tokens(pair, 0, &mut tokens);
+    }
+    let line_starts = line_starts(source);
+    
+    let mut locator = Locator { source, line_starts, tokens, next: 0 };
+    locator.locate_blocks(&document.body)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenKind {
+    Section,
+    Paragraph,
+    Delimited,
+    TableOfContents,
+    Image,
+    PageBreak,
+    Comment,
+    Metadata,
+    ListItem,
+}
+
+// Byte offsets of a block rule's text
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+// `offset` is where the text `pair` was parsed from starts in the source
+fn collect_tokens(pair: Pair<Rule>, offset: usize, tokens: &mut Vec<Token>) {
+    let kind = match pair.as_rule() {
+        Rule::section => Some(TokenKind::Section),
+        Rule::paragraph | Rule::list_paragraph => Some(TokenKind::Paragraph),
+        Rule::delimited_block | Rule::list_literal | Rule::literal_paragraph => Some(TokenKind::Delimited),
+        Rule::toc_macro => Some(TokenKind::TableOfContents),
+        Rule::image_block => Some(TokenKind::Image),
+        Rule::page_break => Some(TokenKind::PageBreak),
+        Rule::line_comment | Rule::comment_block => Some(TokenKind::Comment),
+        Rule::block_metadata => Some(TokenKind::Metadata),
+        Rule::list_item => Some(TokenKind::ListItem),
+        _ => None,
+    };
+    if let Some(kind) = kind {
+        let span = pair.as_span();
+        let text = span.as_str();
+        let start = offset + span.start() + text.len() - text.trim_start_matches(['\n', '\r']).len();
+        let end = offset + span.start() + text.trim_end_matches(['\n', '\r']).len();
+        tokens.push(Token { kind, start, end });
+    }
+    
+    // Blocks nest inside lists, and inside sidebars, whose content is parsed on its own as
+    // the parser does; sections are nested after parsing
+    match pair.as_rule() {
+        Rule::document | Rule::body | Rule::block | Rule::list | Rule::list_continuation | Rule::delimited_block | Rule::sidebar_block => {
+            for inner_pair in pair.into_inner() {
+                collect_tokens(inner_pair, offset, tokens);
+            }
+        }
+        Rule::sidebar_content => {
+            let offset = offset + pair.as_span().start();
+            if let Ok(pairs) = AsciiDocParser::parse(Rule::nested_body, pair.as_str()) {
+                for pair in pairs {
+                    collect_tokens(pair, offset, tokens);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+struct Locator<'s> {
+    source: &'s str,
+    line_starts: Vec<usize>,
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Locator<'_> {
+    fn locate_blocks<'a>(&mut self, blocks: &'a [Block]) -> Vec<SourceNode<'a>> {
+        blocks.iter().filter_map(|block| self.locate_block(block)).collect()
+    }
+
+    fn locate_block<'a>(&mut self, block: &'a Block) -> Option<SourceNode<'a>> {
+        let node = Node::Block(block);
+        match block {
+            Block::Section { blocks, .. } => {
+                let (start, end) = self.take(TokenKind::Section)?;
+                let children = self.locate_blocks(blocks);
+                Some(self.node(node, start, end, children))
+            }
+            Block::Admonition { content, blocks, .. } if content.is_empty() => {
+                let (start, end) = self.take(TokenKind::Delimited)?;
+                let children = self.locate_blocks(blocks);
+                Some(self.node(node, start, end, children))
+            }
+            Block::Paragraph { content } | Block::Admonition { content, .. } => {
+                let (start, end) = self.take(TokenKind::Paragraph)?;
+                let children = self.locate_inlines(content, start, end);
+                Some(self.node(node, start, end, children))
+            }
+            // The title and attribute list a listing takes into itself are part of it
+            Block::DelimitedBlock { kind: DelimitedBlockKind::Listing, .. } => {
+                let first = self.next;
+                let (start, end) = self.take(TokenKind::Delimited)?;
+                let start = self.tokens[first..self.next - 1].iter()
+                    .rev()
+                    .take_while(|token| token.kind == TokenKind::Metadata)
+                    .last()
+                    .map_or(start, |token| token.start);
+                Some(self.node(node, start, end, Vec::new()))
+            }
+            // A styled paragraph is turned into a delimited block too
+            Block::DelimitedBlock { .. } => {
+                let (start, end) = self.take_any(&[TokenKind::Delimited, TokenKind::Paragraph])?;
+                Some(self.node(node, start, end, Vec::new()))
+            }
+            Block::Sidebar { blocks, .. } => {
+                let (start, end) = self.take(TokenKind::Delimited)?;
+                let children = self.locate_blocks(blocks);
+                Some(self.node(node, start, end, children))
+            }
+            Block::Table { .. } => {
+                let (start, end) = self.take(TokenKind::Delimited)?;
+                Some(self.node(node, start, end, Vec::new()))
+            }
+            Block::BlockMetadata { .. } => {
+                let (start, end) = self.take(TokenKind::Metadata)?;
+                Some(self.node(node, start, end, Vec::new()))
+            }
+            Block::TableOfContents { .. } => {
+                let (start, end) = self.take(TokenKind::TableOfContents)?;
+                Some(self.node(node, start, end, Vec::new()))
+            }
+            Block::Image { .. } => {
+                let (start, end) = self.take(TokenKind::Image)?;
+                Some(self.node(node, start, end, Vec::new()))
+            }
+            Block::PageBreak => {
+                let (start, end) = self.take(TokenKind::PageBreak)?;
+                Some(self.node(node, start, end, Vec::new()))
+            }
+            Block::Comment { .. } => {
+                let (start, end) = self.take(TokenKind::Comment)?;
+                Some(self.node(node, start, end, Vec::new()))
+            }
+            // A list has no rule of its own once nested, so it spans its items
+            Block::List { items, .. } => {
+                let children: Vec<SourceNode> = items.iter().filter_map(|item| self.locate_item(item)).collect();
+                let span = Span { start: children.first()?.span.start, end: children.last()?.span.end };
+                Some(SourceNode { node, span, children })
+            }
+        }
+    }
+
+    fn locate_item<'a>(&mut self, item: &'a ListItem) -> Option<SourceNode<'a>> {
+        let (start, end) = self.take(TokenKind::ListItem)?;
+        let (content, blocks) = match item {
+            ListItem::Unordered { content, blocks } | ListItem::Ordered { content, blocks } => (content.as_slice(), blocks),
+            ListItem::Description { blocks, .. } => (&[][..], blocks),
+        };
+        let mut children = self.locate_inlines(content, start, end);
+        children.extend(self.locate_blocks(blocks));
+        Some(self.node(Node::ListItem(item), start, end, children))
+    }
+    
+    // Plain text belongs to its block. Formatted text and macros are found by the source they
+    // print back to, and are left out when written some other way.
+    fn locate_inlines<'a>(&self, elements: &'a [InlineElement], start: usize, end: usize) -> Vec<SourceNode<'a>> {
+        let mut nodes = Vec::new();
+        let mut from = start;
+        for element in elements {
+            if matches!(element, InlineElement::Text(_) | InlineElement::LineBreak) {
+                continue;
+            }
+            let text = element.to_asciidoc();
+            let Some(offset) = self.source[from..end].find(&text) else { continue };
+            let element_start = from + offset;
+            from = element_start + text.len();
+            let children = match element {
+                InlineElement::Formatted { content, .. } => self.locate_inlines(content, element_start, from),
+                _ => Vec::new(),
+            };
+            nodes.push(self.node(Node::Inline(element), element_start, from, children));
+        }
+        nodes
+    }
+    
+    // The next token of a kind, skipping any before it
+    fn take(&mut self, kind: TokenKind) -> Option<(usize, usize)> {
+        self.take_any(&[kind])
+    }
+
+    fn take_any(&mut self, kinds: &[TokenKind]) -> Option<(usize, usize)> {
+        let index = self.next + self.tokens[self.next..].iter().position(|token| kinds.contains(&token.kind))?;
+        self.next = index + 1;
+        Some((self.tokens[index].start, self.tokens[index].end))
+    }
+    
+    // A node ends at its own text or at its last child, whichever is further on
+    fn node<'a>(&self, node: Node<'a>, start: usize, end: usize, children: Vec<SourceNode<'a>>) -> SourceNode<'a> {
+        let mut span = Span { start: self.position(start), end: self.position(end) };
+        if let Some(last) = children.last() {
+            span.end = span.end.max(last.span.end);
+        }
+        SourceNode { node, span, children }
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        position(self.source, &self.line_starts, offset)
+    }
+}
+
+fn line_starts(source: &str) -> Vec<usize> {
+    core::iter::once(0)
+        .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+        .collect()
+}
+
+fn position(source: &str, line_starts: &[usize], offset: usize) -> Position {
+    let line = line_starts.partition_point(|&start| start <= offset);
+    let column = source[line_starts[line - 1]..offset].chars().count() + 1;
+    Position { line, column }
+}
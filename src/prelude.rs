@@ -0,0 +1,7 @@
+// The parts of the standard prelude that live in `alloc`, so the core modules read the
+// same with or without `std`
+pub(crate) use alloc::boxed::Box;
+pub(crate) use alloc::format;
+pub(crate) use alloc::string::{String, ToString};
+pub(crate) use alloc::vec;
+pub(crate) use alloc::vec::Vec;
@@ -0,0 +1,325 @@
+use crate::ast::*;
+use crate::prelude::*;
+use crate::parser::is_attribute_name;
+
+impl Document {
+    // Prints the document back to AsciiDoc source that parses to the same tree
+    pub fn to_asciidoc(&self) -> String {
+        let mut source = String::new();
+        
+        if let Some(header) = &self.header {
+            source.push_str(&format!("= {}\n", header.title));
+            for attribute in &header.attributes {
+                match &attribute.value {
+                    Some(value) => source.push_str(&format!(":{}: {}\n", attribute.name, value)),
+                    None => source.push_str(&format!(":{}:\n", attribute.name)),
+                }
+            }
+        }
+        
+        push_blocks(&mut source, &self.body);
+        source
+    }
+    
+    // FNV-1a over the printed source, after includes and substitutions have been applied, so
+    // the hash only changes when the content does and is the same on every platform and run
+    pub fn content_hash(&self) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+        const PRIME: u64 = 0x100000001b3;
+        
+        self.to_asciidoc().bytes().fold(OFFSET_BASIS, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(PRIME))
+    }
+}
+
+impl Block {
+    pub fn to_asciidoc(&self) -> String {
+        match self {
+            Block::Section { level, title, id, blocks, .. } => {
+                let mut source = String::new();
+                if let Some(id) = id {
+                    source.push_str(&format!("[[{}]]\n", id));
+                }
+                source.push_str(&format!("{} {}\n", "=".repeat(*level), title));
+                push_blocks(&mut source, blocks);
+                source
+            }
+            Block::Paragraph { content } => format!("{}\n", inlines_to_asciidoc(content)),
+            Block::Admonition { kind, content, blocks } if !content.is_empty() && blocks.is_empty() => {
+                format!("{}: {}\n", kind.label(), inlines_to_asciidoc(content))
+            }
+            Block::Admonition { kind, blocks, .. } => {
+                let mut content = String::new();
+                push_blocks(&mut content, blocks);
+                format!("[{}]\n====\n{}====\n", kind.label(), content)
+            }
+            Block::DelimitedBlock { kind, content, language, title } => {
+                let delimiter = match kind {
+                    DelimitedBlockKind::Listing => "----".to_string(),
+                    DelimitedBlockKind::Example => "====".to_string(),
+                    // Longer than any line of dots in the content, which would otherwise end it
+                    DelimitedBlockKind::Literal => {
+                        let longest = content.lines()
+                            .filter(|line| !line.is_empty() && line.chars().all(|c| c == '.'))
+                            .map(str::len)
+                            .max()
+                            .unwrap_or(0);
+                        ".".repeat(longest.max(3) + 1)
+                    }
+                    DelimitedBlockKind::Quote(_) => "____".to_string(),
+                };
+                let mut source = String::new();
+                if let Some(title) = title {
+                    source.push_str(&format!(".{}\n", title));
+                }
+                if let DelimitedBlockKind::Quote(Attribution { author, citation }) = kind {
+                    match (author, citation) {
+                        (None, None) => {}
+                        (author, None) => source.push_str(&format!("[quote, {}]\n", author.as_deref().unwrap_or_default())),
+                        (author, Some(citation)) => source.push_str(&format!("[quote, {}, {}]\n", author.as_deref().unwrap_or_default(), citation)),
+                    }
+                }
+                if let Some(language) = language {
+                    source.push_str(&format!("[,{}]\n", language));
+                }
+                source.push_str(&delimiter);
+                source.push('\n');
+                source.push_str(content);
+                if !content.is_empty() && !content.ends_with('\n') {
+                    source.push('\n');
+                }
+                source.push_str(&delimiter);
+                source.push('\n');
+                source
+            }
+            Block::List { items, .. } => items.iter().map(|item| list_item_to_asciidoc(item, [0; 3])).collect(),
+            Block::Sidebar { title, roles, blocks } => {
+                let mut source = String::new();
+                if let Some(title) = title {
+                    source.push_str(&format!(".{}\n", title));
+                }
+                if !roles.is_empty() {
+                    source.push_str(&format!("[.{}]\n", roles.join(".")));
+                }
+                let mut content = String::new();
+                push_blocks(&mut content, blocks);
+                source.push_str(&format!("****\n{}****\n", content));
+                source
+            }
+            // A header row is written alone on the first line with a blank line after it, and
+            // every other row on a line of its own
+            Block::Table { title, header, rows } => {
+                let mut source = String::new();
+                if let Some(title) = title {
+                    source.push_str(&format!(".{}\n", title));
+                }
+                source.push_str("|===\n");
+                for (index, row) in rows.iter().enumerate() {
+                    let cells: Vec<String> = row.iter()
+                        .map(|cell| format!("{}| {}", cell_spec_to_asciidoc(cell), inlines_to_asciidoc(&cell.content).replace('|', "\\|")))
+                        .collect();
+                    source.push_str(cells.join(" ").trim_end());
+                    source.push('\n');
+                    if *header && index == 0 {
+                        source.push('\n');
+                    }
+                }
+                source.push_str("|===\n");
+                source
+            }
+            Block::TableOfContents { levels: Some(levels) } => format!("toc::[levels={}]\n", levels),
+            Block::TableOfContents { levels: None } => "toc::[]\n".to_string(),
+            Block::Image { path, alt, width, height, title } => {
+                let mut source = String::new();
+                if let Some(title) = title {
+                    source.push_str(&format!(".{}\n", title));
+                }
+                let quoted = |value: &str| if value.contains(',') { format!("\"{}\"", value) } else { value.to_string() };
+                let mut attributes: Vec<String> = [alt, width, height].iter().map(|value| value.as_deref().map(quoted).unwrap_or_default()).collect();
+                while attributes.last().is_some_and(String::is_empty) {
+                    attributes.pop();
+                }
+                source.push_str(&format!("image::{}[{}]\n", path, attributes.join(",")));
+                source
+            }
+            Block::PageBreak => "<<<\n".to_string(),
+            Block::Comment { text, delimited: false } => format!("//{}\n", text),
+            Block::Comment { text, delimited: true } => format!("////\n{}////\n", text),
+            Block::BlockMetadata { kind } => match kind {
+                BlockMetadataKind::Title(title) => format!(".{}\n", title),
+                BlockMetadataKind::Attribute(attributes) => {
+                    let attributes: Vec<&str> = attributes.iter().map(|attribute| attribute.as_str()).collect();
+                    format!("[{}]\n", attributes.join(","))
+                }
+                BlockMetadataKind::Anchor(id) => format!("[[{}]]\n", id),
+                BlockMetadataKind::AttributeEntry(attribute) => match &attribute.value {
+                    Some(value) => format!(":{}: {}\n", attribute.name, value),
+                    None => format!(":{}:\n", attribute.name),
+                },
+                BlockMetadataKind::AttributeUnset(name) => format!(":{}!:\n", name),
+            },
+        }
+    }
+}
+
+impl ListItem {
+    // Prints the item as it appears in a list that is not nested in another
+    pub fn to_asciidoc(&self) -> String {
+        list_item_to_asciidoc(self, [0; 3])
+    }
+}
+
+// `nesting` counts the enclosing unordered, ordered and description items. Each enclosing
+// item of the same kind adds a character to the marker, so every list in the chain has a
+// marker of its own when parsed again.
+fn list_item_to_asciidoc(item: &ListItem, mut nesting: [usize; 3]) -> String {
+    let family = match item {
+        ListItem::Unordered { .. } => 0,
+        ListItem::Ordered { .. } => 1,
+        ListItem::Description { .. } => 2,
+    };
+    let depth = nesting[family];
+    let mut source = match item {
+        ListItem::Unordered { content, .. } => format!("{} {}\n", "*".repeat(depth + 1), inlines_to_asciidoc(content)),
+        ListItem::Ordered { content, .. } => format!("{} {}\n", ".".repeat(depth + 1), inlines_to_asciidoc(content)),
+        ListItem::Description { term, description, .. } => {
+            let marker = ":".repeat(depth + 2);
+            match description {
+                Some(description) => format!("{}{} {}\n", term, marker, inlines_to_asciidoc(description)),
+                None => format!("{}{}\n", term, marker),
+            }
+        }
+    };
+    
+    // Nested lists follow the item directly; other blocks are attached with a `+` line
+    nesting[family] += 1;
+    let mut attached = false;
+    for block in item.blocks() {
+        match block {
+            Block::List { items, .. } => {
+                for nested in items {
+                    source.push_str(&list_item_to_asciidoc(nested, nesting));
+                }
+            }
+            _ => {
+                if !attached {
+                    source.push_str("+\n");
+                }
+                source.push_str(&block.to_asciidoc());
+            }
+        }
+        attached = ends_with_block_metadata(block);
+    }
+    source
+}
+
+impl InlineElement {
+    pub fn to_asciidoc(&self) -> String {
+        match self {
+            InlineElement::Text(text) => escape_attribute_references(text),
+            InlineElement::Formatted { kind: FormattedTextKind::LiteralMonospace, content, attributes } => {
+                let text: String = content.iter().map(|element| match element {
+                    InlineElement::Text(text) => text.clone(),
+                    other => other.to_asciidoc(),
+                }).collect();
+                format!("{}`+{}+`", inline_attributes_to_asciidoc(attributes), text)
+            }
+            InlineElement::Formatted { kind, content, attributes } => {
+                let mark = match kind {
+                    FormattedTextKind::Strong => "*",
+                    FormattedTextKind::Emphasis => "_",
+                    FormattedTextKind::Monospace | FormattedTextKind::LiteralMonospace => "`",
+                    FormattedTextKind::Superscript => "^",
+                    FormattedTextKind::Subscript => "~",
+                    FormattedTextKind::Mark => "#",
+                };
+                format!("{}{}{}{}", inline_attributes_to_asciidoc(attributes), mark, inlines_to_asciidoc(content), mark)
+            }
+            InlineElement::Macro { kind } => match kind {
+                MacroKind::Link { url, text } => format!("link:{}[{}]", url, text.as_deref().unwrap_or("")),
+                MacroKind::Image { path, attributes } => format!("image:{}[{}]", path, attributes.as_deref().unwrap_or("")),
+                MacroKind::CrossReference { target, text } => match text {
+                    Some(text) => format!("<<{},{}>>", target, text),
+                    None => format!("<<{}>>", target),
+                },
+                MacroKind::Term { term, .. } => format!("term:[{}]", term),
+            },
+            InlineElement::LineBreak => " +\n".to_string(),
+            InlineElement::Passthrough(content) => format!("+++{}+++", content),
+        }
+    }
+}
+
+fn cell_spec_to_asciidoc(cell: &TableCell) -> String {
+    let mut spec = match (cell.colspan, cell.rowspan) {
+        (1, 1) => String::new(),
+        (columns, 1) => format!("{}+", columns),
+        (1, rows) => format!(".{}+", rows),
+        (columns, rows) => format!("{}.{}+", columns, rows),
+    };
+    spec.push_str(match cell.align {
+        Some(CellAlignment::Left) => "<",
+        Some(CellAlignment::Center) => "^",
+        Some(CellAlignment::Right) => ">",
+        None => "",
+    });
+    spec
+}
+
+// Blocks are separated by a blank line, except that titles, attribute lists and anchors
+// stay directly above the block they apply to. Adjacent lists get a `//-` divider, since a
+// blank line alone would join them into a single list.
+fn push_blocks(source: &mut String, blocks: &[Block]) {
+    let mut attached = false;
+    let mut previous: Option<&Block> = None;
+    for block in blocks {
+        if matches!((previous, block), (Some(Block::List { .. }), Block::List { .. })) {
+            source.push_str("\n//-\n");
+        }
+        if !source.is_empty() && !attached {
+            source.push('\n');
+        }
+        source.push_str(&block.to_asciidoc());
+        attached = ends_with_block_metadata(block);
+        previous = Some(block);
+    }
+}
+
+// Metadata at the end of a section applies to whatever follows the section
+fn ends_with_block_metadata(block: &Block) -> bool {
+    match block {
+        Block::Section { blocks, .. } => blocks.last().is_some_and(ends_with_block_metadata),
+        Block::BlockMetadata { kind } => matches!(
+            kind,
+            BlockMetadataKind::Title(_) | BlockMetadataKind::Attribute(_) | BlockMetadataKind::Anchor(_)
+        ),
+        _ => false,
+    }
+}
+
+// Literal text that reads like `{name}` is escaped so it is not substituted when parsed again
+fn escape_attribute_references(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (index, c) in text.char_indices() {
+        if c == '{'
+            && let Some(end) = text[index + 1..].find('}')
+            && is_attribute_name(&text[index + 1..index + 1 + end])
+        {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn inline_attributes_to_asciidoc(attributes: &InlineAttributes) -> String {
+    if attributes.is_empty() {
+        return String::new();
+    }
+    let id = attributes.id.as_ref().map(|id| format!("#{}", id)).unwrap_or_default();
+    let roles: String = attributes.roles.iter().map(|role| format!(".{}", role)).collect();
+    format!("[{}{}]", id, roles)
+}
+
+fn inlines_to_asciidoc(elements: &[InlineElement]) -> String {
+    elements.iter().map(InlineElement::to_asciidoc).collect()
+}
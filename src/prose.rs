@@ -0,0 +1,178 @@
+use crate::ast::*;
+use crate::parser::is_attribute_name;
+use crate::position::{Node, Position, SourceNode, Span, source_tree};
+use crate::prelude::*;
+
+// A stretch of prose on one line, for spellcheckers and terminology checks
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRun {
+    pub text: String,
+    pub span: Span,
+}
+
+// Targets of these macros are not prose, and neither is anything in their brackets
+const OPAQUE_MACROS: &[&str] = &["image:", "kbd:", "btn:", "menu:", "icon:"];
+// Targets of these are skipped, while the text in their brackets is kept
+const TARGET_PREFIXES: &[&str] = &["https://", "http://", "ftp://", "mailto:", "link:", "xref:", "footnote:", "term:"];
+
+impl Document {
+    // The prose of titles, paragraphs, list items and quote, example and sidebar blocks, with
+    // code, attribute references, anchors, URLs and macro targets left out
+    pub fn prose_runs(&self, source: &str) -> Vec<TextRun> {
+        let lines: Vec<&str> = source.lines().collect();
+        let mut runs = Vec::new();
+        collect_runs(&source_tree(self, source), &lines, &mut runs);
+        runs
+    }
+}
+
+fn collect_runs(nodes: &[SourceNode], lines: &[&str], runs: &mut Vec<TextRun>) {
+    for node in nodes {
+        let (start, end) = (node.span.start.line, node.span.end.line);
+        match node.node {
+            Node::Block(Block::Section { .. }) => {
+                let heading = lines[start - 1];
+                runs_in_line(heading, start, heading.find(' ').map_or(0, |marker| marker + 1), runs);
+            }
+            Node::Block(Block::BlockMetadata { kind: BlockMetadataKind::Title(_) }) => {
+                runs_in_line(lines[start - 1], start, 1, runs);
+            }
+            // Only a `.Title` line gives a listing's title as prose, not `filename=`
+            Node::Block(Block::DelimitedBlock { kind: DelimitedBlockKind::Listing, title: Some(_), .. }) => {
+                let title = (start..end)
+                    .take_while(|&line| !lines[line - 1].starts_with("----"))
+                    .find(|&line| lines[line - 1].starts_with('.'));
+                if let Some(line) = title {
+                    runs_in_line(lines[line - 1], line, 1, runs);
+                }
+            }
+            Node::Block(Block::Paragraph { .. }) => {
+                for line in start..=end {
+                    runs_in_line(lines[line - 1], line, 0, runs);
+                }
+            }
+            Node::Block(Block::DelimitedBlock { kind: DelimitedBlockKind::Quote(_) | DelimitedBlockKind::Example, .. }) => {
+                for line in start + 1..end {
+                    runs_in_line(lines[line - 1], line, 0, runs);
+                }
+            }
+            // The item's own lines, up to the first block attached to it
+            Node::ListItem(_) => {
+                let own_end = node.children.iter()
+                    .find(|child| matches!(child.node, Node::Block(_)))
+                    .map_or(end, |child| child.span.start.line - 1);
+                for line in start..=own_end {
+                    let text = lines[line - 1];
+                    let from = if line == start { item_text_start(text) } else { 0 };
+                    runs_in_line(text, line, from, runs);
+                }
+            }
+            _ => {}
+        }
+        collect_runs(&node.children, lines, runs);
+    }
+}
+
+// Past the marker of `* item`, `. item` or `term:: text`, keeping a description list's term
+fn item_text_start(line: &str) -> usize {
+    let marker = line.len() - line.trim_start_matches(['*', '.', '-']).len();
+    if marker > 0 && line[marker..].starts_with(' ') {
+        return marker + 1;
+    }
+    0
+}
+
+fn runs_in_line(line: &str, number: usize, from: usize, runs: &mut Vec<TextRun>) {
+    if line.trim_start().starts_with("//") {
+        return;
+    }
+    let skipped = skipped_ranges(line, from);
+    
+    let mut run_start = None;
+    let push_run = |start: usize, end: usize, runs: &mut Vec<TextRun>| {
+        let text = &line[start..end];
+        let trimmed = text.trim_start();
+        let start = start + text.len() - trimmed.len();
+        let trimmed = trimmed.trim_end();
+        if trimmed.chars().any(char::is_alphabetic) {
+            let column = |offset: usize| line[..offset].chars().count() + 1;
+            runs.push(TextRun {
+                text: trimmed.to_string(),
+                span: Span {
+                    start: Position { line: number, column: column(start) },
+                    end: Position { line: number, column: column(start + trimmed.len()) },
+                },
+            });
+        }
+    };
+    
+    for (index, c) in line.char_indices() {
+        let prose = index >= from && !skipped.iter().any(|&(start, end)| (start..end).contains(&index)) && !is_markup(line, index, c);
+        match (prose, run_start) {
+            (true, None) => run_start = Some(index),
+            (false, Some(start)) => {
+                push_run(start, index, runs);
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = run_start {
+        push_run(start, line.len(), runs);
+    }
+}
+
+// Byte ranges of code, references and targets in a line
+fn skipped_ranges(line: &str, from: usize) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut index = from;
+    while index < line.len() {
+        let rest = &line[index..];
+        let at_word_start = !line[..index].ends_with(|c: char| c.is_alphanumeric());
+        let length = if let Some(code) = rest.strip_prefix('`') {
+            code.find('`').map(|end| end + 2)
+        } else if rest.starts_with("[[") {
+            rest.find("]]").map(|end| end + 2)
+        } else if rest.starts_with("<<") {
+            // `<<target,text>>` keeps its text
+            rest.find(">>").map(|end| match rest[..end].find(',') {
+                Some(comma) => {
+                    ranges.push((index + end, index + end + 2));
+                    comma + 1
+                }
+                None => end + 2,
+            })
+        } else if rest.starts_with('{') {
+            rest.find('}').filter(|&end| end > 1 && is_attribute_name(&rest[1..end])).map(|end| end + 1)
+        } else if at_word_start && OPAQUE_MACROS.iter().any(|prefix| rest.starts_with(prefix)) {
+            rest.find(']').map(|end| end + 1)
+        } else if at_word_start && TARGET_PREFIXES.iter().any(|prefix| rest.starts_with(prefix)) {
+            Some(rest.find(|c: char| c == '[' || c.is_whitespace()).unwrap_or(rest.len()))
+        } else {
+            None
+        };
+        
+        match length {
+            Some(length) => {
+                ranges.push((index, index + length));
+                index += length;
+            }
+            None => index += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    ranges
+}
+
+// Formatting marks, brackets and a description list's `::`, unless a mark is inside a word
+// like `snake_case`
+fn is_markup(line: &str, index: usize, c: char) -> bool {
+    if matches!(c, '[' | ']') || (c == ':' && (line[index + 1..].starts_with(':') || line[..index].ends_with(':'))) {
+        return true;
+    }
+    if !matches!(c, '*' | '_' | '^' | '~' | '#' | '+') {
+        return false;
+    }
+    let before = line[..index].chars().next_back().is_some_and(char::is_alphanumeric);
+    let after = line[index + c.len_utf8()..].chars().next().is_some_and(char::is_alphanumeric);
+    !(before && after)
+}
@@ -0,0 +1,78 @@
+use core::error::Error;
+
+use crate::prelude::*;
+
+// An Antora-style resource id: `version@component:module:family$path#fragment`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceId {
+    pub version: Option<String>,
+    pub component: Option<String>,
+    pub module: Option<String>,
+    pub family: Option<String>,
+    pub path: String,
+    pub fragment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedXref {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+pub trait ResourceResolver: Send + Sync {
+    fn resolve_xref(&self, id: &ResourceId) -> Option<ResolvedXref>;
+
+    fn read_include(&self, id: &ResourceId) -> Result<String, Box<dyn Error + Send + Sync>> {
+        Err(format!("cannot include '{}'", id.path).into())
+    }
+}
+
+impl ResourceId {
+    pub fn parse(input: &str) -> Option<ResourceId> {
+        let (resource, fragment) = match input.split_once('#') {
+            Some((resource, fragment)) => (resource, non_empty(fragment)),
+            None => (input, None),
+        };
+        let (version, rest) = match resource.split_once('@') {
+            Some((version, rest)) => (non_empty(version), rest),
+            None => (None, resource),
+        };
+        let (coordinates, family_path) = match rest.rsplit_once(':') {
+            Some((coordinates, family_path)) => (Some(coordinates), family_path),
+            None => (None, rest),
+        };
+        let (family, path) = match family_path.split_once('$') {
+            Some((family, path)) => (non_empty(family), path),
+            None => (None, family_path),
+        };
+        // `component::page.adoc` addresses the component's default module
+        let (component, module) = match coordinates {
+            Some(coordinates) => match coordinates.split_once(':') {
+                Some((component, module)) => (non_empty(component), non_empty(module)),
+                None => (None, non_empty(coordinates)),
+            },
+            None => (None, None),
+        };
+        
+        if path.is_empty() || path.contains(char::is_whitespace) {
+            return None;
+        }
+        
+        Some(ResourceId {
+            version,
+            component,
+            module,
+            family,
+            path: path.to_string(),
+            fragment,
+        })
+    }
+
+    pub fn is_qualified(&self) -> bool {
+        self.version.is_some() || self.component.is_some() || self.module.is_some() || self.family.is_some()
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    if value.is_empty() { None } else { Some(value.to_string()) }
+}
@@ -0,0 +1,211 @@
+use crate::ast::*;
+use crate::prelude::*;
+use crate::json::json_string;
+
+// One searchable unit per section: the text directly under its heading, with the titles of
+// the document and the enclosing sections as its breadcrumb
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchEntry {
+    pub document: String,
+    pub id: String,
+    pub title: String,
+    pub breadcrumb: Vec<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchIndex {
+    entries: Vec<SearchEntry>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        SearchIndex::default()
+    }
+    
+    // Text above the first section is indexed under the document title with an empty id
+    pub fn add_document(&mut self, name: &str, document: &Document) {
+        let title = document.header.as_ref().map(|header| header.title.clone());
+        let preamble = blocks_text(&document.body);
+        if !preamble.is_empty() {
+            self.entries.push(SearchEntry {
+                document: name.to_string(),
+                id: String::new(),
+                title: title.clone().unwrap_or_default(),
+                breadcrumb: Vec::new(),
+                text: preamble,
+            });
+        }
+        
+        let mut breadcrumb: Vec<String> = title.into_iter().collect();
+        collect_entries(name, &document.body, &mut breadcrumb, &mut self.entries);
+    }
+
+    pub fn entries(&self) -> &[SearchEntry] {
+        &self.entries
+    }
+    
+    // An array of flat objects, ready to hand to lunr's or elasticlunr's `add`
+    pub fn to_json(&self) -> String {
+        if self.entries.is_empty() {
+            return "[]\n".to_string();
+        }
+        
+        let entries: Vec<String> = self.entries.iter()
+            .map(|entry| {
+                let breadcrumb: Vec<String> = entry.breadcrumb.iter().map(|title| json_string(title)).collect();
+                format!(
+                    "  {{\"document\": {}, \"id\": {}, \"title\": {}, \"breadcrumb\": [{}], \"text\": {}}}",
+                    json_string(&entry.document),
+                    json_string(&entry.id),
+                    json_string(&entry.title),
+                    breadcrumb.join(", "),
+                    json_string(&entry.text),
+                )
+            })
+            .collect();
+        format!("[\n{}\n]\n", entries.join(",\n"))
+    }
+}
+
+impl Document {
+    // The leading prose as plain text, for index pages and meta descriptions. Paragraphs are
+    // read in order, into sections, until there is enough, with runs of whitespace made one
+    // space. Longer text is cut after the last whole word that fits, or mid-word when none
+    // does, and ends with `…`. `max_chars` counts user-perceived characters, so an accent or
+    // an emoji sequence is never split, and includes the ellipsis.
+    pub fn summary(&self, max_chars: usize) -> String {
+        let mut words = Vec::new();
+        let mut length = 0;
+        collect_prose(&self.body, max_chars, &mut words, &mut length);
+        truncate_text(&words.join(" "), max_chars)
+    }
+}
+
+fn collect_prose(blocks: &[Block], max_chars: usize, words: &mut Vec<String>, length: &mut usize) {
+    for block in blocks {
+        if *length > max_chars {
+            return;
+        }
+        match block {
+            Block::Paragraph { content } => {
+                for word in plain_text(content).split_whitespace() {
+                    *length += grapheme_starts(word).len() + 1;
+                    words.push(word.to_string());
+                }
+            }
+            Block::Section { blocks, .. } => collect_prose(blocks, max_chars, words, length),
+            _ => {}
+        }
+    }
+}
+
+fn truncate_text(text: &str, max_chars: usize) -> String {
+    let starts = grapheme_starts(text);
+    if starts.len() <= max_chars {
+        return text.to_string();
+    }
+    if max_chars == 0 {
+        return String::new();
+    }
+    let cut = starts[max_chars - 1];
+    let mut kept = &text[..cut];
+    if !text[cut..].starts_with(' ')
+        && let Some(space) = kept.rfind(' ')
+    {
+        kept = &kept[..space];
+    }
+    let mut summary = kept.trim_end_matches([' ', ',', ';', ':']).to_string();
+    summary.push('…');
+    summary
+}
+
+// Byte offsets where each grapheme cluster starts. Without the Unicode tables this covers the
+// common cases: combining marks, variation selectors, emoji modifiers and tags, ZWJ
+// sequences, flags made of regional indicator pairs and CR LF.
+fn grapheme_starts(text: &str) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut previous: Option<char> = None;
+    let mut regional_indicators = 0;
+    for (offset, c) in text.char_indices() {
+        let regional_indicator = ('\u{1F1E6}'..='\u{1F1FF}').contains(&c);
+        let joined = match previous {
+            None => false,
+            Some('\r') => c == '\n',
+            Some('\u{200D}') => true,
+            Some(_) => extends_grapheme(c) || (regional_indicator && regional_indicators % 2 == 1),
+        };
+        regional_indicators = if regional_indicator { regional_indicators + 1 } else { 0 };
+        if !joined {
+            starts.push(offset);
+        }
+        previous = Some(c);
+    }
+    starts
+}
+
+fn extends_grapheme(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}' | '\u{0483}'..='\u{0489}' | '\u{0591}'..='\u{05BD}' | '\u{0610}'..='\u{061A}'
+        | '\u{064B}'..='\u{065F}' | '\u{0900}'..='\u{0903}' | '\u{093A}'..='\u{094F}' | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}' | '\u{200C}' | '\u{200D}' | '\u{20D0}'..='\u{20FF}' | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}' | '\u{1F3FB}'..='\u{1F3FF}' | '\u{E0020}'..='\u{E007F}' | '\u{E0100}'..='\u{E01EF}'
+    )
+}
+
+fn collect_entries(name: &str, blocks: &[Block], breadcrumb: &mut Vec<String>, entries: &mut Vec<SearchEntry>) {
+    for block in blocks {
+        if let Block::Section { title, id, blocks, .. } = block {
+            entries.push(SearchEntry {
+                document: name.to_string(),
+                id: id.clone().unwrap_or_default(),
+                title: title.clone(),
+                breadcrumb: breadcrumb.clone(),
+                text: blocks_text(blocks),
+            });
+            
+            breadcrumb.push(title.clone());
+            collect_entries(name, blocks, breadcrumb, entries);
+            breadcrumb.pop();
+        }
+    }
+}
+
+// Nested sections get entries of their own, so only the blocks around them count here
+fn blocks_text(blocks: &[Block]) -> String {
+    blocks.iter().filter_map(block_text).collect::<Vec<_>>().join("\n")
+}
+
+fn block_text(block: &Block) -> Option<String> {
+    let text = match block {
+        Block::Paragraph { content } => plain_text(content),
+        Block::Admonition { content, blocks, .. } => {
+            let text = plain_text(content);
+            let nested = blocks_text(blocks);
+            if nested.is_empty() { text } else if text.is_empty() { nested } else { format!("{}\n{}", text, nested) }
+        }
+        Block::DelimitedBlock { content, .. } => content.trim_end().to_string(),
+        Block::List { items, .. } => items.iter()
+            .map(|item| {
+                let text = match item {
+                    ListItem::Unordered { content, .. } | ListItem::Ordered { content, .. } => plain_text(content),
+                    ListItem::Description { term, description: Some(description), .. } => {
+                        format!("{} {}", term, plain_text(description))
+                    }
+                    ListItem::Description { term, description: None, .. } => term.clone(),
+                };
+                let attached = blocks_text(item.blocks());
+                if attached.is_empty() { text } else { format!("{}\n{}", text, attached) }
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Sidebar { blocks, .. } => blocks_text(blocks),
+        Block::Table { rows, .. } => rows.iter()
+            .map(|row| row.iter().map(|cell| plain_text(&cell.content)).collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Block::Image { alt, title, .. } => [title.as_deref(), alt.as_deref()].into_iter().flatten().collect::<Vec<_>>().join("\n"),
+        Block::Section { .. } | Block::BlockMetadata { .. } | Block::TableOfContents { .. } | Block::PageBreak | Block::Comment { .. } => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
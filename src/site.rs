@@ -0,0 +1,287 @@
+use std::collections::BTreeMap;
+
+use crate::ast::*;
+use crate::context::{NavItem, NavLink, PageNavigation};
+use crate::error::ParseError;
+use crate::parser::AsciiDocParser;
+use crate::workspace::DocumentSet;
+
+// A converted document as the site's index and sitemap list it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitePage {
+    pub source: String,
+    // Relative to the output directory, with `/` separators
+    pub path: String,
+    pub title: String,
+}
+
+impl DocumentSet {
+    // One page per document in name order, titled by its header or else its file name
+    pub fn pages(&self) -> Vec<SitePage> {
+        self.documents()
+            .map(|(name, document)| SitePage {
+                source: name.to_string(),
+                path: output_path(name),
+                title: document.header.as_ref()
+                    .map(|header| header.title.clone())
+                    .unwrap_or_else(|| name.rsplit('/').next().unwrap_or(name).to_string()),
+            })
+            .collect()
+    }
+}
+
+// The reading order of a multi-file build, one document path per line with nesting shown by
+// indentation. A line that is not a document path titles the group indented under it, and
+// lines starting with `#` are comments:
+//
+//     index.adoc
+//     Guides
+//       guide/setup.adoc
+//       guide/usage.adoc
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub document: Option<String>,
+    // Set for a group, and otherwise taken from the document
+    pub title: Option<String>,
+    pub children: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn parse(source: &str) -> Manifest {
+        // Each open entry with its indentation, innermost last
+        let mut open: Vec<(usize, ManifestEntry)> = Vec::new();
+        let mut entries = Vec::new();
+        let close = |open: &mut Vec<(usize, ManifestEntry)>, entries: &mut Vec<ManifestEntry>| {
+            let (_, entry) = open.pop().unwrap();
+            match open.last_mut() {
+                Some((_, parent)) => parent.children.push(entry),
+                None => entries.push(entry),
+            }
+        };
+        for line in source.lines() {
+            let text = line.trim();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+            let indent = line.len() - line.trim_start().len();
+            while open.last().is_some_and(|(open_indent, _)| *open_indent >= indent) {
+                close(&mut open, &mut entries);
+            }
+            let entry = if is_document_path(text) {
+                ManifestEntry { document: Some(text.to_string()), title: None, children: Vec::new() }
+            } else {
+                ManifestEntry { document: None, title: Some(text.to_string()), children: Vec::new() }
+            };
+            open.push((indent, entry));
+        }
+        while !open.is_empty() {
+            close(&mut open, &mut entries);
+        }
+        Manifest { entries }
+    }
+    
+    // A navigation document as Antora writes them: nested lists of cross references, each
+    // list optionally titled to group it
+    //
+    //     .Guides
+    //     * xref:guide/setup.adoc[Setup]
+    //     ** xref:guide/usage.adoc[]
+    //     * Reference
+    pub fn parse_nav(source: &str) -> Result<Manifest, ParseError> {
+        Ok(Manifest::from_nav(&AsciiDocParser::parse_document(source)?))
+    }
+
+    pub fn from_nav(document: &Document) -> Manifest {
+        let mut entries = Vec::new();
+        let mut title = None;
+        for block in &document.body {
+            match block {
+                Block::BlockMetadata { kind: BlockMetadataKind::Title(text) } => title = Some(text.trim().to_string()),
+                Block::List { kind: ListKind::Unordered | ListKind::Ordered, items } => {
+                    let children = nav_entries(items);
+                    match title.take() {
+                        Some(title) => entries.push(ManifestEntry { document: None, title: Some(title), children }),
+                        None => entries.extend(children),
+                    }
+                }
+                _ => title = None,
+            }
+        }
+        Manifest { entries }
+    }
+    
+    // Every document in reading order, a parent before its children
+    pub fn documents(&self) -> Vec<&str> {
+        fn collect<'a>(entries: &'a [ManifestEntry], documents: &mut Vec<&'a str>) {
+            for entry in entries {
+                documents.extend(entry.document.as_deref());
+                collect(&entry.children, documents);
+            }
+        }
+        let mut documents = Vec::new();
+        collect(&self.entries, &mut documents);
+        documents
+    }
+}
+
+// An item that is only a cross reference links to that document, titled by the reference
+// text when it has any; any other item is a group titled by its text
+fn nav_entries(items: &[ListItem]) -> Vec<ManifestEntry> {
+    items.iter()
+        .filter_map(|item| {
+            let (ListItem::Unordered { content, blocks } | ListItem::Ordered { content, blocks }) = item else { return None };
+            let children = blocks.iter()
+                .flat_map(|block| match block {
+                    Block::List { items, .. } => nav_entries(items),
+                    _ => Vec::new(),
+                })
+                .collect();
+            let entry = match content.as_slice() {
+                [InlineElement::Macro { kind: MacroKind::CrossReference { target, text } }] => ManifestEntry {
+                    document: Some(target.split('#').next().unwrap_or(target).to_string()),
+                    title: text.clone().filter(|text| !text.is_empty()),
+                    children,
+                },
+                _ => ManifestEntry { document: None, title: Some(plain_text(content)), children },
+            };
+            Some(entry)
+        })
+        .collect()
+}
+
+const DOCUMENT_EXTENSIONS: &[&str] = &[".adoc", ".asciidoc", ".md", ".markdown"];
+
+fn is_document_path(text: &str) -> bool {
+    !text.contains(char::is_whitespace) && DOCUMENT_EXTENSIONS.iter().any(|extension| text.ends_with(extension))
+}
+
+impl DocumentSet {
+    // Previous and next pages, breadcrumbs and the sidebar for each document, by name.
+    // Documents follow the manifest when there is one, leaving out any it does not list, and
+    // otherwise name order without breadcrumbs or a sidebar.
+    pub fn navigation(&self, manifest: Option<&Manifest>) -> BTreeMap<String, PageNavigation> {
+        let pages: BTreeMap<String, SitePage> = self.pages().into_iter().map(|page| (page.source.clone(), page)).collect();
+        // Each document in order with the entries above it
+        let mut order: Vec<(&SitePage, Vec<&ManifestEntry>)> = Vec::new();
+        match manifest {
+            Some(manifest) => flatten(&manifest.entries, &pages, &mut Vec::new(), &mut order),
+            None => order.extend(pages.values().map(|page| (page, Vec::new()))),
+        }
+        
+        let mut navigation = BTreeMap::new();
+        for (index, (page, ancestors)) in order.iter().enumerate() {
+            let link = |other: &SitePage| NavLink { title: other.title.clone(), href: Some(relative_path(&page.path, &other.path)) };
+            let breadcrumbs = ancestors.iter()
+                .map(|entry| match entry.document.as_deref().and_then(|document| pages.get(document)) {
+                    Some(other) => NavLink { title: entry.title.clone().unwrap_or_else(|| other.title.clone()), href: link(other).href },
+                    None => NavLink { title: entry.title.clone().unwrap_or_default(), href: None },
+                })
+                .collect();
+            navigation.insert(page.source.clone(), PageNavigation {
+                previous: index.checked_sub(1).map(|previous| link(order[previous].0)),
+                next: order.get(index + 1).map(|(next, _)| link(next)),
+                breadcrumbs,
+                sidebar: manifest.map(|manifest| sidebar(&manifest.entries, &pages, page)).unwrap_or_default(),
+            });
+        }
+        navigation
+    }
+}
+
+fn flatten<'a>(
+    entries: &'a [ManifestEntry],
+    pages: &'a BTreeMap<String, SitePage>,
+    ancestors: &mut Vec<&'a ManifestEntry>,
+    order: &mut Vec<(&'a SitePage, Vec<&'a ManifestEntry>)>,
+) {
+    for entry in entries {
+        if let Some(page) = entry.document.as_deref().and_then(|document| pages.get(document)) {
+            order.push((page, ancestors.clone()));
+        }
+        ancestors.push(entry);
+        flatten(&entry.children, pages, ancestors, order);
+        ancestors.pop();
+    }
+}
+
+// A manifest document that is not in the set is listed without a link
+fn sidebar(entries: &[ManifestEntry], pages: &BTreeMap<String, SitePage>, current: &SitePage) -> Vec<NavItem> {
+    entries.iter()
+        .map(|entry| {
+            let page = entry.document.as_deref().and_then(|document| pages.get(document));
+            NavItem {
+                title: entry.title.clone()
+                    .or_else(|| page.map(|page| page.title.clone()))
+                    .unwrap_or_else(|| entry.document.clone().unwrap_or_default()),
+                href: page.map(|page| relative_path(&current.path, &page.path)),
+                current: page.is_some_and(|page| page.source == current.source),
+                children: sidebar(&entry.children, pages, current),
+            }
+        })
+        .collect()
+}
+
+// The link from one output page to another, both relative to the output directory:
+// `guide/setup.html` reaches `index.html` as `../index.html`
+pub fn relative_path(from: &str, to: &str) -> String {
+    let from_directories: Vec<&str> = from.rsplit_once('/').map_or(Vec::new(), |(directory, _)| directory.split('/').collect());
+    let to_parts: Vec<&str> = to.split('/').collect();
+    let common = from_directories.iter()
+        .zip(&to_parts[..to_parts.len() - 1])
+        .take_while(|(from, to)| from == to)
+        .count();
+    let mut path = "../".repeat(from_directories.len() - common);
+    path.push_str(&to_parts[common..].join("/"));
+    path
+}
+
+// Where a document's HTML is written, mirroring its source path: `guide/setup.adoc` becomes
+// `guide/setup.html`
+pub fn output_path(name: &str) -> String {
+    let stem = DOCUMENT_EXTENSIONS.iter()
+        .find_map(|extension| name.strip_suffix(extension))
+        .unwrap_or(name);
+    format!("{}.html", stem)
+}
+
+// A standalone page linking to every other one, grouped under their directories
+pub fn index_html(title: &str, pages: &[SitePage]) -> String {
+    let mut html = page_start_html(title, "", "");
+    html.push_str(&format!("<h1>{}</h1>\n<ul class=\"site-index\">\n", escape_html(title)));
+    let mut directory = "";
+    for page in pages {
+        let page_directory = page.path.rsplit_once('/').map_or("", |(directory, _)| directory);
+        if page_directory != directory {
+            if !directory.is_empty() {
+                html.push_str("</ul>\n</li>\n");
+            }
+            if !page_directory.is_empty() {
+                html.push_str(&format!("<li>{}\n<ul>\n", escape_html(page_directory)));
+            }
+            directory = page_directory;
+        }
+        html.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", escape_html(&page.path), escape_html(&page.title)));
+    }
+    if !directory.is_empty() {
+        html.push_str("</ul>\n</li>\n");
+    }
+    html.push_str("</ul>\n");
+    html.push_str(PAGE_END_HTML);
+    html
+}
+
+// Search engines need absolute URLs, so each path is joined to `base_url`
+pub fn sitemap_xml(base_url: &str, pages: &[SitePage]) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for page in pages {
+        xml.push_str(&format!("<url><loc>{}/{}</loc></url>\n", escape_html(base_url), escape_html(&page.path)));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
+use crate::ast::*;
+use crate::parser::{BodyReader, ParseOptions};
+
+// Top-level blocks read from `reader` as they are parsed, for documents too large to hold
+// whole, such as generated logs and reports. The text is parsed a few blocks at a time,
+// so sections come out as their heading alone, a `Block::Section` with no blocks, followed
+// by what they hold; the level of the next heading says where a section ends. Parsed
+// blocks are otherwise the same as `parse_document` gives, except that glossary terms are
+// not linked to their entries.
+pub struct BlockIter<R> {
+    reader: R,
+    body: BodyReader,
+    ready: VecDeque<Block>,
+    // The first line of the next piece, read while finding where the last one ended
+    next_line: Option<String>,
+    // Lines read so far
+    line: usize,
+    done: bool,
+}
+
+impl<R: BufRead> BlockIter<R> {
+    pub fn new(reader: R, options: ParseOptions) -> BlockIter<R> {
+        BlockIter {
+            reader,
+            body: BodyReader::new(options),
+            ready: VecDeque::new(),
+            next_line: None,
+            line: 0,
+            done: false,
+        }
+    }
+    
+    // The document's header, which is read along with the first blocks
+    pub fn header(&mut self) -> io::Result<Option<&Header>> {
+        if !self.body.started() {
+            self.fill()?;
+        }
+        Ok(self.body.header())
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        while self.ready.is_empty() && !self.done {
+            let (piece, line) = self.read_piece()?;
+            if piece.is_empty() {
+                self.done = true;
+                break;
+            }
+            let blocks = self.body.read(&piece, line).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            self.ready.extend(blocks);
+        }
+        Ok(())
+    }
+    
+    // Lines up to a blank one that ends a block at the top level, and the line they start
+    // on. A piece goes on through delimited blocks and conditionals, past a block title or
+    // attribute list, and over any number of blank lines into a line that could carry a
+    // list on.
+    fn read_piece(&mut self) -> io::Result<(String, usize)> {
+        let start = if self.next_line.is_some() { self.line } else { self.line + 1 };
+        let mut piece = String::new();
+        let mut fences: Vec<String> = Vec::new();
+        let mut conditionals = 0usize;
+        let mut blank = false;
+        let mut metadata = false;
+        
+        loop {
+            let line = match self.next_line.take() {
+                Some(line) => line,
+                None => {
+                    let mut line = String::new();
+                    if self.reader.read_line(&mut line)? == 0 {
+                        break;
+                    }
+                    self.line += 1;
+                    line
+                }
+            };
+            let text = line.trim_end_matches(['\n', '\r']);
+            if blank && !text.trim().is_empty() && fences.is_empty() && conditionals == 0 && !metadata && !continues_list(text) {
+                self.next_line = Some(line);
+                break;
+            }
+            
+            if text.starts_with("endif::") {
+                conditionals = conditionals.saturating_sub(1);
+            } else if (text.starts_with("ifdef::") || text.starts_with("ifndef::")) && text.ends_with("[]") {
+                conditionals += 1;
+            }
+            if fences.is_empty() && !text.trim().is_empty() {
+                metadata = is_block_metadata(text);
+            }
+            blank = fences.is_empty() && text.trim().is_empty() && !piece.trim().is_empty();
+            if let Some(fence) = fence(text) {
+                match fences.last() {
+                    Some(open) if open == fence => {
+                        fences.pop();
+                    }
+                    Some(open) if !is_compound(open) => {}
+                    _ => fences.push(fence.to_string()),
+                }
+            }
+            piece.push_str(&line);
+        }
+        Ok((piece, start))
+    }
+}
+
+impl<R: BufRead> Iterator for BlockIter<R> {
+    type Item = io::Result<Block>;
+
+    fn next(&mut self) -> Option<io::Result<Block>> {
+        if let Err(error) = self.fill() {
+            self.done = true;
+            return Some(Err(error));
+        }
+        self.ready.pop_front().map(Ok)
+    }
+}
+
+// A line that opens or closes a delimited block
+fn fence(line: &str) -> Option<&str> {
+    let line = line.trim_end();
+    let first = line.chars().next()?;
+    let delimiter = line.len() >= 4 && "-=*_.+/".contains(first) && line.chars().all(|c| c == first);
+    (delimiter || line == "|===").then_some(line)
+}
+
+// Example, sidebar and quote blocks hold other blocks, so other delimiters open inside them
+fn is_compound(fence: &str) -> bool {
+    matches!(fence, "====" | "****" | "____")
+}
+
+// A block title, attribute list or anchor, which belongs to the block below it
+fn is_block_metadata(line: &str) -> bool {
+    (line.starts_with('[') && line.ends_with(']'))
+        || line.strip_prefix('.').is_some_and(|title| title.starts_with(|c: char| !c.is_whitespace() && c != '.'))
+}
+
+// List items run on past blank lines, and an indented line or `+` can belong to the item
+// above it
+fn continues_list(line: &str) -> bool {
+    if line.starts_with([' ', '\t']) || line == "+" {
+        return true;
+    }
+    let unmarked = line.trim_start_matches(['*', '-', '.']);
+    let numbered = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    (unmarked.len() < line.len() && unmarked.starts_with(' '))
+        || (numbered.len() < line.len() && numbered.starts_with(". "))
+        || line.contains(":: ")
+        || line.contains(";;")
+        || line.ends_with("::")
+}
@@ -0,0 +1,136 @@
+use crate::prelude::*;
+
+// Named regions of a file, marked by `tag::name[]` and `end::name[]` on lines of their own,
+// usually in a comment of the file's language so the file keeps working:
+//
+//     // tag::setup[]
+//     let client = Client::new();
+//     // end::setup[]
+//
+// Regions can nest and a name can be used for several regions. The marker lines are never
+// part of any region's content.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaggedRegion {
+    pub name: String,
+    // 1-based lines of the opening and closing markers. A region left open closes at the last
+    // line.
+    pub start_line: usize,
+    pub end_line: usize,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Marker<'a> {
+    Start(&'a str),
+    End(&'a str),
+}
+
+// `tag::name[]` or `end::name[]` starting a word, with nothing after it on the line but
+// spaces or the end of a comment
+fn marker(line: &str) -> Option<Marker<'_>> {
+    let line = line.trim_end();
+    for (index, _) in line.match_indices("::") {
+        let Some(keyword) = index.checked_sub(3).and_then(|start| line.get(start..index)) else { continue };
+        if !matches!(keyword, "tag" | "end") || line[..index - 3].chars().next_back().is_some_and(char::is_alphanumeric) {
+            continue;
+        }
+        let rest = &line[index + 2..];
+        let Some(end) = rest.find("[]") else { continue };
+        let name = &rest[..end];
+        if name.is_empty() || name.contains(char::is_whitespace) || !rest[end + 2..].trim_start().chars().all(|c| !c.is_alphanumeric()) {
+            continue;
+        }
+        return Some(if keyword == "tag" { Marker::Start(name) } else { Marker::End(name) });
+    }
+    None
+}
+
+// Every region in `source` in the order they open
+pub fn tagged_regions(source: &str) -> Vec<TaggedRegion> {
+    let mut regions: Vec<TaggedRegion> = Vec::new();
+    // Indexes into `regions` of those still open, innermost last
+    let mut open: Vec<usize> = Vec::new();
+    let mut last_line = 0;
+    for (index, line) in source.split_inclusive('\n').enumerate() {
+        last_line = index + 1;
+        match marker(line) {
+            Some(Marker::Start(name)) => {
+                open.push(regions.len());
+                regions.push(TaggedRegion { name: name.to_string(), start_line: index + 1, end_line: 0, content: String::new() });
+            }
+            // An end marker closes the innermost region of its name, and any opened inside it
+            Some(Marker::End(name)) => {
+                if let Some(position) = open.iter().rposition(|&region| regions[region].name == name) {
+                    for region in open.drain(position..) {
+                        regions[region].end_line = index + 1;
+                    }
+                }
+            }
+            None => {
+                for &region in &open {
+                    regions[region].content.push_str(line);
+                }
+            }
+        }
+    }
+    for region in open {
+        regions[region].end_line = last_line;
+    }
+    regions
+}
+
+// The lines of every region named `name`, in order, or None when there is no such region
+pub fn tagged_region(source: &str, name: &str) -> Option<String> {
+    select_tags(source, &[name])
+}
+
+// The lines of the regions named in `tags`, as an include's `tags=a;b` selects them. A name
+// starting with `!` leaves its regions out, listing only names to leave out selects
+// everything else, and `**` selects every line outside the regions left out. Where regions
+// nest, the innermost one named decides. Marker lines are always dropped. None when no
+// named region is found.
+pub fn select_tags(source: &str, tags: &[&str]) -> Option<String> {
+    let included: Vec<&str> = tags.iter().copied().filter(|tag| !tag.starts_with('!') && *tag != "**").collect();
+    let excluded: Vec<&str> = tags.iter().filter_map(|tag| tag.strip_prefix('!')).collect();
+    let everything = tags.contains(&"**") || included.is_empty();
+    
+    let mut selected = String::new();
+    let mut matched = tags.contains(&"**") || (included.is_empty() && !excluded.is_empty());
+    let mut open: Vec<&str> = Vec::new();
+    for line in source.split_inclusive('\n') {
+        match marker(line) {
+            Some(Marker::Start(name)) => {
+                matched |= included.contains(&name) || excluded.contains(&name);
+                open.push(name);
+            }
+            Some(Marker::End(name)) => {
+                if let Some(position) = open.iter().rposition(|open| *open == name) {
+                    open.truncate(position);
+                }
+            }
+            None => {
+                let decided = open.iter().rev().find_map(|name| {
+                    if excluded.contains(name) {
+                        Some(false)
+                    } else if included.contains(name) {
+                        Some(true)
+                    } else {
+                        None
+                    }
+                });
+                if decided.unwrap_or(everything) {
+                    selected.push_str(line);
+                }
+            }
+        }
+    }
+    matched.then_some(selected)
+}
+
+// The names an include's `tag=name` or `tags=a;b` attribute lists
+pub(crate) fn include_tags(attributes: &str) -> Option<Vec<&str>> {
+    attributes.split(',')
+        .map(str::trim)
+        .find_map(|attribute| attribute.strip_prefix("tags=").or_else(|| attribute.strip_prefix("tag=")))
+        .map(|value| value.trim_matches('"').split(';').map(str::trim).filter(|tag| !tag.is_empty()).collect())
+}
@@ -0,0 +1,39 @@
+use crate::prelude::*;
+
+// Stylesheets compiled into the crate, so a standalone page looks finished without any files
+// beside it. `:stylesheet:` or `--theme` chooses one by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Default,
+    Dark,
+    Print,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Default, Theme::Dark, Theme::Print];
+
+    pub fn from_name(name: &str) -> Option<Theme> {
+        Theme::ALL.into_iter().find(|theme| theme.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Default => "default",
+            Theme::Dark => "dark",
+            Theme::Print => "print",
+        }
+    }
+
+    pub fn css(self) -> &'static str {
+        match self {
+            Theme::Default => include_str!("themes/default.css"),
+            Theme::Dark => include_str!("themes/dark.css"),
+            Theme::Print => include_str!("themes/print.css"),
+        }
+    }
+    
+    // The name a linked copy of the stylesheet is written under
+    pub fn file_name(self) -> String {
+        format!("adoc-{}.css", self.name())
+    }
+}
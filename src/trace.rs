@@ -0,0 +1,28 @@
+// Spans and events compile to nothing unless the `tracing` feature is enabled, so the
+// instrumentation costs nothing in default builds. A span stays open until the end of
+// the block it is declared in.
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($level:ident, $($args:tt)+) => {
+        let _span = tracing::span!(tracing::Level::$level, $($args)+).entered();
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($level:ident, $($args:tt)+) => {};
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! event {
+    ($level:ident, $($args:tt)+) => {
+        tracing::event!(tracing::Level::$level, $($args)+)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! event {
+    ($level:ident, $($args:tt)+) => {};
+}
+
+pub(crate) use {event, span};
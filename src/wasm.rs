@@ -0,0 +1,33 @@
+use wasm_bindgen::prelude::*;
+
+use crate::ast::HtmlOptions;
+use crate::parser::AsciiDocParser;
+
+// Entry points for the npm package in `npm/`, which wraps them with typed functions. The
+// tree crosses over as JSON, since a string is far cheaper to pass than a graph of objects.
+
+fn parse(input: &str) -> Result<crate::ast::Document, JsError> {
+    AsciiDocParser::parse_document(input).map_err(|e| JsError::new(&e.to_string()))
+}
+
+#[wasm_bindgen(js_name = parseToJson)]
+pub fn parse_to_json(input: &str) -> Result<String, JsError> {
+    Ok(parse(input)?.to_json())
+}
+
+// Documents from untrusted authors should be converted with `sanitize` set
+#[wasm_bindgen(js_name = convertToHtml)]
+pub fn convert_to_html(input: &str, sanitize: bool) -> Result<String, JsError> {
+    let options = if sanitize { HtmlOptions::untrusted() } else { HtmlOptions::default() };
+    Ok(parse(input)?.to_html_with(&options))
+}
+
+#[wasm_bindgen(js_name = convertToLatex)]
+pub fn convert_to_latex(input: &str) -> Result<String, JsError> {
+    Ok(parse(input)?.to_latex())
+}
+
+#[wasm_bindgen(js_name = formatAsciidoc)]
+pub fn format_asciidoc(input: &str) -> Result<String, JsError> {
+    Ok(parse(input)?.to_asciidoc())
+}
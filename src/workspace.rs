@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::ast::*;
+use crate::catalog::Catalog;
+use crate::diagnostics::{Diagnostic, DiagnosticKind};
+use crate::error::ParseError;
+use crate::include::{IncludeResolver, is_uri, nested_target};
+use crate::intern::SymbolPool;
+use crate::parser::{AsciiDocParser, ParseOptions};
+use crate::trace;
+
+#[derive(Clone, Default)]
+pub struct Workspace {
+    sources: BTreeMap<String, String>,
+    options: ParseOptions,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnresolvedXref {
+    pub document: String,
+    pub target: String,
+}
+
+// Which value a build keeps when its documents set a header attribute differently
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    // The document first by name
+    #[default]
+    FirstWins,
+    LastWins,
+    // Nothing is merged while any attribute is in conflict
+    Error,
+}
+
+// Header attributes that describe their own document rather than the build, which each
+// document keeps as it is
+const DOCUMENT_ATTRIBUTES: &[&str] = &[
+    "author", "authors", "email", "description", "keywords", "revnumber", "revdate", "revremark",
+    "revision", "docdatetime", "role", "docrole", "og-image", "toc", "toc-title",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentSet {
+    documents: BTreeMap<String, Document>,
+    catalog: Catalog,
+    unresolved_xrefs: Vec<UnresolvedXref>,
+}
+
+struct WorkspaceResolver {
+    sources: Arc<BTreeMap<String, String>>,
+    document: String,
+    fallback: Option<Arc<dyn IncludeResolver>>,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Workspace::default()
+    }
+    
+    // Includes that don't name a workspace document are passed to the resolver in `options`
+    pub fn with_options(options: ParseOptions) -> Self {
+        Workspace { sources: BTreeMap::new(), options }
+    }
+
+    pub fn add_document(&mut self, name: impl Into<String>, source: impl Into<String>) {
+        self.sources.insert(name.into(), source.into());
+    }
+    
+    // Attribute names, roles and language ids share one allocation across the documents
+    pub fn parse(&self) -> Result<DocumentSet, ParseError> {
+        let sources = Arc::new(self.sources.clone());
+        let mut documents = BTreeMap::new();
+        let mut symbols = SymbolPool::new();
+        
+        for (name, source) in sources.iter() {
+            let options = ParseOptions {
+                include_resolver: Some(Arc::new(WorkspaceResolver {
+                    sources: Arc::clone(&sources),
+                    document: name.clone(),
+                    fallback: self.options.include_resolver.clone(),
+                })),
+                docfile: Some(name.clone()),
+                ..self.options.clone()
+            };
+            
+            let document = parse_source(name, source, &options, &mut symbols)
+                .map_err(|e| e.in_document(name))?;
+            documents.insert(name.clone(), document);
+        }
+        
+        let mut catalog = Catalog::new();
+        for (name, document) in &documents {
+            catalog.add_document(name, document);
+        }
+        
+        let mut unresolved_xrefs = Vec::new();
+        for (name, document) in documents.iter_mut() {
+            resolve_xrefs(name, document, &catalog, &sources, &mut unresolved_xrefs);
+        }
+        
+        Ok(DocumentSet { documents, catalog, unresolved_xrefs })
+    }
+}
+
+impl DocumentSet {
+    pub fn get(&self, name: &str) -> Option<&Document> {
+        self.documents.get(name)
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = (&str, &Document)> {
+        self.documents.iter().map(|(name, document)| (name.as_str(), document))
+    }
+
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    pub fn unresolved_xrefs(&self) -> &[UnresolvedXref] {
+        &self.unresolved_xrefs
+    }
+    
+    // Gives every document the header attributes any of them sets, so the pages of a build
+    // agree on settings such as `:sectnums:` or `:source-highlighter:`. An attribute set to
+    // different values is settled by `policy` and reported. Under `ConflictPolicy::Error` the
+    // reports come back as the error and the documents are left as they were.
+    pub fn merge_attributes(&mut self, policy: ConflictPolicy) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+        // Each attribute with the documents setting it and their values, in name order
+        let mut settings: Vec<(Attribute, Vec<(&str, &str)>)> = Vec::new();
+        for (name, document) in &self.documents {
+            let Some(header) = &document.header else { continue };
+            for attribute in header.attributes.iter().filter(|attribute| !DOCUMENT_ATTRIBUTES.contains(&attribute.name.as_str())) {
+                let value = attribute.value.as_deref().unwrap_or("");
+                match settings.iter_mut().find(|(merged, _)| merged.name == attribute.name) {
+                    Some((merged, documents)) => {
+                        if policy == ConflictPolicy::LastWins {
+                            *merged = attribute.clone();
+                        }
+                        documents.push((name, value));
+                    }
+                    None => settings.push((attribute.clone(), vec![(name, value)])),
+                }
+            }
+        }
+        
+        let conflicts: Vec<Diagnostic> = settings.iter()
+            .filter(|(_, documents)| documents.iter().any(|(_, value)| *value != documents[0].1))
+            .map(|(attribute, documents)| {
+                let values: Vec<String> = documents.iter().map(|(name, value)| format!("'{}' in {}", value, name)).collect();
+                Diagnostic {
+                    kind: DiagnosticKind::AttributeConflict,
+                    line: None,
+                    message: format!("attribute '{}' is set to {}", attribute.name, values.join(", ")),
+                }
+            })
+            .collect();
+        if policy == ConflictPolicy::Error && !conflicts.is_empty() {
+            return Err(conflicts);
+        }
+        
+        let merged: Vec<Attribute> = settings.into_iter().map(|(attribute, _)| attribute).collect();
+        for document in self.documents.values_mut() {
+            let Some(header) = &mut document.header else { continue };
+            for attribute in &merged {
+                header.attributes.retain(|existing| existing.name != attribute.name);
+                header.attributes.push(attribute.clone());
+            }
+        }
+        Ok(conflicts)
+    }
+}
+
+impl IncludeResolver for WorkspaceResolver {
+    fn resolve(&self, target: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        if !is_uri(target)
+            && let Some(source) = self.sources.get(&join_path(parent_dir(&self.document), target))
+        {
+            return Ok(source.clone());
+        }
+        
+        // The fallback reads from the workspace root, so the target is made relative to it
+        match &self.fallback {
+            Some(fallback) => fallback.resolve(&nested_target(&self.document, target)),
+            None => Err(format!("'{}' is not part of the workspace", target).into()),
+        }
+    }
+}
+
+fn resolve_xrefs(
+    name: &str,
+    document: &mut Document,
+    catalog: &Catalog,
+    sources: &BTreeMap<String, String>,
+    unresolved: &mut Vec<UnresolvedXref>,
+) {
+    let base_dir = parent_dir(name).to_string();
+    
+    document.walk_inlines_mut(&mut |element| {
+        let InlineElement::Macro { kind: MacroKind::CrossReference { target, text } } = element else {
+            return;
+        };
+        
+        let (path, fragment) = match target.split_once('#') {
+            Some((path, "")) => (path, None),
+            Some((path, fragment)) => (path, Some(fragment)),
+            None if target.ends_with(".adoc") || target.ends_with(".md") => (target.as_str(), None),
+            None => ("", Some(target.as_str())),
+        };
+        let document_name = if path.is_empty() { name.to_string() } else { join_path(&base_dir, path) };
+        
+        let title = match fragment {
+            Some(id) => catalog.get(&document_name, id).map(|entry| entry.title.clone()),
+            None if sources.contains_key(&document_name) => Some(None),
+            None => None,
+        };
+        
+        match title {
+            Some(title) => {
+                if text.is_none() {
+                    *text = title;
+                }
+            }
+            None => unresolved.push(UnresolvedXref {
+                document: name.to_string(),
+                target: target.clone(),
+            }),
+        }
+    });
+}
+
+// Markdown is converted on its own, so its symbols join the pool afterwards
+fn parse_source(name: &str, source: &str, options: &ParseOptions, symbols: &mut SymbolPool) -> Result<Document, ParseError> {
+    trace::span!(DEBUG, "document", name);
+    #[cfg(feature = "markdown")]
+    if crate::markdown::is_markdown(name) {
+        let mut document = crate::markdown::MarkdownParser::parse_document(source)?;
+        symbols.intern_document(&mut document);
+        return Ok(document);
+    }
+    #[cfg(not(feature = "markdown"))]
+    let _ = name;
+    
+    AsciiDocParser::parse_document_with_symbols(source, options, symbols)
+}
+
+pub(crate) fn parent_dir(name: &str) -> &str {
+    name.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("")
+}
+
+pub(crate) fn join_path(base_dir: &str, target: &str) -> String {
+    let mut segments: Vec<&str> = if target.starts_with('/') {
+        Vec::new()
+    } else {
+        base_dir.split('/').filter(|segment| !segment.is_empty()).collect()
+    };
+    
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    
+    segments.join("/")
+}
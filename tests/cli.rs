@@ -0,0 +1,124 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Includes are read from `--base-dir` when it's given, and nested ones from the directory
+// of the file that includes them
+#[test]
+fn base_dir_includes() {
+    let dir = env::temp_dir().join(format!("adoc-cli-test-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::create_dir_all(dir.join("partials/sub")).unwrap();
+    fs::write(dir.join("src/index.adoc"), "= Title\n\ninclude::sub/a.adoc[]\n").unwrap();
+    fs::write(dir.join("partials/sub/a.adoc"), "include::b.adoc[]\n").unwrap();
+    fs::write(dir.join("partials/sub/b.adoc"), "From the base directory.\n").unwrap();
+    
+    let output = dir.join("index.html");
+    let status = Command::new(env!("CARGO_BIN_EXE_adoc"))
+        .arg("--base-dir").arg(dir.join("partials"))
+        .arg("-o").arg(&output)
+        .arg(dir.join("src/index.adoc"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let html = fs::read_to_string(&output).unwrap();
+    assert!(html.contains("<p>From the base directory.</p>"), "{}", html);
+    
+    // The other modes that read includes follow the same directory
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_adoc"))
+            .args(args)
+            .arg("--base-dir").arg(dir.join("partials"))
+            .arg(dir.join("src/index.adoc"))
+            .output()
+            .unwrap()
+    };
+    let trace = run(&["--trace-includes"]);
+    assert!(String::from_utf8_lossy(&trace.stdout).ends_with("  sub/a.adoc\n    sub/b.adoc\n"));
+    assert!(run(&["--lint"]).status.success());
+    assert!(String::from_utf8_lossy(&run(&["--search-index"]).stdout).contains("From the base directory."));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// A partial next to a page in a subdirectory is included from there, not from the top
+#[test]
+fn directory_build_nested_partials() {
+    let dir = env::temp_dir().join(format!("adoc-cli-site-test-{}", std::process::id()));
+    fs::create_dir_all(dir.join("in/sub")).unwrap();
+    fs::write(dir.join("in/index.adoc"), "= Home\n\nWelcome.\n").unwrap();
+    fs::write(dir.join("in/sub/page.adoc"), "= Page\n\ninclude::_part.adoc[]\n").unwrap();
+    fs::write(dir.join("in/sub/_part.adoc"), "From the partial.\n").unwrap();
+    
+    let status = Command::new(env!("CARGO_BIN_EXE_adoc"))
+        .arg("-o").arg(dir.join("out"))
+        .arg(dir.join("in"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let html = fs::read_to_string(dir.join("out/sub/page.html")).unwrap();
+    assert!(html.contains("<p>From the partial.</p>"), "{}", html);
+    assert!(!dir.join("out/sub/_part.html").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// `repl` reads snippets until a blank line and prints the tree and HTML of each
+#[test]
+fn repl_subcommand() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_adoc"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"Some *bold* text.\n\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("<p>Some <strong>bold</strong> text.</p>"));
+}
+
+// Input that isn't UTF-8 is refused, whether the file is read or mapped
+#[test]
+fn invalid_utf8_input() {
+    let dir = env::temp_dir().join(format!("adoc-cli-utf8-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("index.adoc"), b"= Title\n\nLatin-1 caf\xe9.\n").unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_adoc"))
+        .arg("-o").arg(dir.join("index.html"))
+        .arg(dir.join("index.adoc"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error reading file"));
+    assert!(!dir.join("index.html").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Only the links the allow and deny lists permit are requested. Nothing listens on the
+// discard port, so a link there that is checked comes out dead.
+#[cfg(feature = "linkcheck")]
+#[test]
+fn check_links_allow_and_deny() {
+    let dir = env::temp_dir().join(format!("adoc-cli-links-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("index.adoc");
+    fs::write(&input, "= Title\n\nSee http://127.0.0.1:9/gone for more.\n").unwrap();
+    
+    let check = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_adoc"))
+            .arg("--check-links")
+            .args(args)
+            .arg(&input)
+            .output()
+            .unwrap()
+    };
+    let output = check(&["--concurrency", "2"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("index.adoc:3: http://127.0.0.1:9/gone"));
+    assert!(check(&["--deny-url", "127.0.0.1"]).status.success());
+    assert!(check(&["--allow-url", "example.org"]).status.success());
+    assert!(!check(&["--allow-url", "http://127.0.0.1:9/"]).status.success());
+    assert!(!check(&["--concurrency", "0"]).status.success());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,57 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub enum Snapshot {
+    Matched,
+    Updated,
+    Missing,
+    Changed(String),
+}
+
+// Compares output with the golden file at `path`. Running with UPDATE_SNAPSHOTS=1
+// rewrites missing or stale golden files instead of failing.
+pub fn check_snapshot(path: &Path, actual: &str) -> Snapshot {
+    let expected = fs::read_to_string(path).ok();
+    if expected.as_deref() == Some(actual) {
+        return Snapshot::Matched;
+    }
+    if env::var_os("UPDATE_SNAPSHOTS").is_some_and(|value| value != "0") {
+        fs::write(path, actual).unwrap();
+        return Snapshot::Updated;
+    }
+    match expected {
+        Some(expected) => Snapshot::Changed(first_difference(&expected, actual)),
+        None => Snapshot::Missing,
+    }
+}
+
+pub fn fixture_files(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(fixture_files(&path, extension));
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for line in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(a), Some(b)) if a == b => continue,
+            (None, None) => break,
+            (a, b) => {
+                return format!("  line {}\n  - {}\n  + {}", line, a.unwrap_or("<end>"), b.unwrap_or("<end>"));
+            }
+        }
+    }
+    // Only the trailing newline differs
+    "  at end of file".to_string()
+}
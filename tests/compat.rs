@@ -0,0 +1,161 @@
+mod common;
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use adoc::*;
+use common::{Snapshot, check_snapshot, fixture_files};
+
+// Runs the vendored corpus in tests/corpus through the parser and compares a structural
+// outline of each document with its recorded .snap file. Run with UPDATE_SNAPSHOTS=1
+// to record the outline of a new file or accept an intended change.
+#[test]
+fn corpus_compatibility() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let files = fixture_files(&corpus, "adoc");
+    
+    let mut scorecard = String::new();
+    let mut parsed = 0;
+    let mut matched = 0;
+    let mut failures = Vec::new();
+    for path in &files {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let source = fs::read_to_string(path).unwrap();
+        let actual = match AsciiDocParser::parse_document(&source) {
+            Ok(document) => {
+                parsed += 1;
+                outline(&document)
+            }
+            Err(e) => format!("error: {}\n", e),
+        };
+        
+        let status = match check_snapshot(&path.with_extension("snap"), &actual) {
+            Snapshot::Matched => {
+                matched += 1;
+                "ok"
+            }
+            Snapshot::Updated => {
+                matched += 1;
+                "updated"
+            }
+            Snapshot::Missing => {
+                failures.push(format!("{}\n  no snapshot recorded", name));
+                "MISSING"
+            }
+            Snapshot::Changed(difference) => {
+                failures.push(format!("{}\n{}", name, difference));
+                "CHANGED"
+            }
+        };
+        let blocks = actual.lines().filter(|line| !line.starts_with(' ') && !line.starts_with("header")).count();
+        writeln!(scorecard, "  {:<24} {:<9} {:>3} top-level blocks", name, status, blocks).unwrap();
+    }
+    
+    println!("compatibility scorecard\n{}", scorecard);
+    println!("{}/{} parsed, {}/{} match their snapshot", parsed, files.len(), matched, files.len());
+    assert!(!files.is_empty(), "no corpus files found in {}", corpus.display());
+    assert!(failures.is_empty(), "structural output changed:\n\n{}", failures.join("\n\n"));
+}
+
+// One line per block with the kinds of its inline elements, so the snapshot captures
+// what the parser recognised without depending on the exact text
+fn outline(document: &Document) -> String {
+    let mut out = String::new();
+    if let Some(header) = &document.header {
+        writeln!(out, "header {:?}", header.title).unwrap();
+        for attribute in &header.attributes {
+            writeln!(out, "  :{}: {:?}", attribute.name, attribute.value.as_deref().unwrap_or("")).unwrap();
+        }
+    }
+    for block in &document.body {
+        outline_block(block, 0, &mut out);
+    }
+    out
+}
+
+fn outline_block(block: &Block, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    match block {
+        Block::Section { level, title, id, number, blocks } => {
+            let number = number.as_ref().map(|number| format!(" {}", number)).unwrap_or_default();
+            writeln!(out, "{}section {} {:?} #{}{}", indent, level, title, id.as_deref().unwrap_or(""), number).unwrap();
+            for block in blocks {
+                outline_block(block, depth + 1, out);
+            }
+        }
+        Block::Paragraph { content } => writeln!(out, "{}paragraph [{}]", indent, inline_kinds(content)).unwrap(),
+        Block::Admonition { kind, content, blocks } => {
+            writeln!(out, "{}admonition {:?} [{}]", indent, kind, inline_kinds(content)).unwrap();
+            for block in blocks {
+                outline_block(block, depth + 1, out);
+            }
+        }
+        Block::DelimitedBlock { kind, content, language, title } => {
+            let language = language.as_ref().map(|language| format!(" {}", language)).unwrap_or_default();
+            let title = title.as_ref().map(|title| format!(" {:?}", title)).unwrap_or_default();
+            writeln!(out, "{}{:?}{}{} ({} lines)", indent, kind, language, title, content.lines().count()).unwrap();
+        }
+        Block::Sidebar { title, roles, blocks } => {
+            writeln!(out, "{}sidebar {:?} {:?}", indent, title, roles).unwrap();
+            for block in blocks {
+                outline_block(block, depth + 1, out);
+            }
+        }
+        Block::List { kind, items } => {
+            writeln!(out, "{}list {:?}", indent, kind).unwrap();
+            for item in items {
+                match item {
+                    ListItem::Unordered { content, .. } | ListItem::Ordered { content, .. } => {
+                        writeln!(out, "{}  item [{}]", indent, inline_kinds(content)).unwrap();
+                    }
+                    ListItem::Description { term, description, .. } => {
+                        let description = description.as_deref().map(inline_kinds).unwrap_or_default();
+                        writeln!(out, "{}  term {:?} [{}]", indent, term, description).unwrap();
+                    }
+                }
+                for block in item.blocks() {
+                    outline_block(block, depth + 2, out);
+                }
+            }
+        }
+        Block::Table { title, header, rows } => {
+            writeln!(out, "{}table {:?} header {}", indent, title, header).unwrap();
+            for row in rows {
+                let cells: Vec<String> = row.iter().map(|cell| format!("[{}]", inline_kinds(&cell.content))).collect();
+                writeln!(out, "{}  row {}", indent, cells.join(" ")).unwrap();
+            }
+        }
+        Block::BlockMetadata { kind } => writeln!(out, "{}metadata {:?}", indent, kind).unwrap(),
+        Block::TableOfContents { levels } => writeln!(out, "{}toc {:?}", indent, levels).unwrap(),
+        Block::Image { path, title, .. } => writeln!(out, "{}image {:?} {:?}", indent, path, title).unwrap(),
+        Block::PageBreak => writeln!(out, "{}page-break", indent).unwrap(),
+        Block::Comment { text, delimited } => writeln!(out, "{}comment {:?} delimited {}", indent, text, delimited).unwrap(),
+    }
+}
+
+fn inline_kinds(elements: &[InlineElement]) -> String {
+    let kinds: Vec<&str> = elements.iter()
+        .map(|element| match element {
+            InlineElement::Text(_) => "text",
+            InlineElement::Formatted { kind, .. } => match kind {
+                FormattedTextKind::Strong => "strong",
+                FormattedTextKind::Emphasis => "emphasis",
+                FormattedTextKind::Monospace => "monospace",
+                FormattedTextKind::LiteralMonospace => "literal monospace",
+                FormattedTextKind::Superscript => "superscript",
+                FormattedTextKind::Subscript => "subscript",
+                FormattedTextKind::Mark => "mark",
+            },
+            InlineElement::Macro { kind } => match kind {
+                MacroKind::Link { .. } => "link",
+                MacroKind::Image { .. } => "image",
+                MacroKind::CrossReference { .. } => "xref",
+                MacroKind::Term { .. } => "term",
+            },
+            InlineElement::LineBreak => "break",
+            InlineElement::Passthrough(_) => "passthrough",
+        })
+        .collect();
+    kinds.join(" ")
+}
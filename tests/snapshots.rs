@@ -0,0 +1,106 @@
+mod common;
+
+use std::path::Path;
+
+use adoc::*;
+use common::{Snapshot, check_snapshot, fixture_files};
+
+type Render = fn(&Document) -> String;
+
+// Every fixture in tests/fixtures/<feature>/ is rendered by each backend and compared with
+// the golden file next to it. Add a .adoc file and run with UPDATE_SNAPSHOTS=1 to record
+// its output, then review the new files before committing them.
+const BACKENDS: &[(&str, Render)] = &[
+    ("html", |document| document.to_html()),
+    ("chunked.html", chunked_html),
+    ("tex", |document| document.to_latex()),
+    ("asciidoc", |document| document.to_asciidoc()),
+];
+
+fn chunked_html(document: &Document) -> String {
+    document.to_chunked_html(1).iter()
+        .map(|chunk| format!("--- {} ---\n{}", chunk.file_name, chunk.html))
+        .collect()
+}
+
+#[test]
+fn fixture_snapshots() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let files = fixture_files(&fixtures, "adoc");
+    assert!(!files.is_empty(), "no fixtures found in {}", fixtures.display());
+    
+    let mut failures = Vec::new();
+    for path in &files {
+        let name = path.strip_prefix(&fixtures).unwrap().display();
+        let source = std::fs::read_to_string(path).unwrap();
+        let document = match AsciiDocParser::parse_document(&source) {
+            Ok(document) => document,
+            Err(e) => {
+                failures.push(format!("{}: parse error: {}", name, e));
+                continue;
+            }
+        };
+        
+        for (extension, render) in BACKENDS {
+            let snapshot_path = path.with_extension(extension);
+            match check_snapshot(&snapshot_path, &render(&document)) {
+                Snapshot::Matched | Snapshot::Updated => {}
+                Snapshot::Missing => failures.push(format!("{}: no .{} snapshot recorded", name, extension)),
+                Snapshot::Changed(difference) => failures.push(format!("{}: .{} output changed\n{}", name, extension, difference)),
+            }
+        }
+    }
+    
+    assert!(
+        failures.is_empty(),
+        "{} snapshot failures (rerun with UPDATE_SNAPSHOTS=1 to accept the new output):\n\n{}",
+        failures.len(),
+        failures.join("\n\n"),
+    );
+}
+
+// Reading a fixture a few blocks at a time gives what parsing it whole does, with sections
+// flattened into their heading followed by what they hold. Glossary terms are the one
+// difference, since a streamed term can't link to an entry further on.
+#[test]
+fn fixture_block_iter() {
+    fn flatten(blocks: Vec<Block>, flat: &mut Vec<Block>) {
+        for mut block in blocks {
+            match &mut block {
+                Block::Section { blocks, .. } => {
+                    let blocks = std::mem::take(blocks);
+                    flat.push(block);
+                    flatten(blocks, flat);
+                }
+                _ => flat.push(block),
+            }
+        }
+    }
+    
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut failures = Vec::new();
+    for path in fixture_files(&fixtures, "adoc") {
+        let name = path.strip_prefix(&fixtures).unwrap().display().to_string();
+        let source = std::fs::read_to_string(&path).unwrap();
+        let options = ParseOptions::default();
+        let mut document = AsciiDocParser::parse_document_with_options(&source, &options).unwrap();
+        document.walk_inlines_mut(&mut |element| {
+            if let InlineElement::Macro { kind: MacroKind::Term { target, .. } } = element {
+                *target = None;
+            }
+        });
+        let mut expected = Vec::new();
+        flatten(document.body, &mut expected);
+        
+        match BlockIter::new(source.as_bytes(), options).collect::<Result<Vec<Block>, _>>() {
+            Ok(blocks) if blocks == expected => {}
+            Ok(blocks) => {
+                let index = blocks.iter().zip(&expected).position(|(block, expected)| block != expected).unwrap_or(blocks.len().min(expected.len()));
+                failures.push(format!("{}: block {} differs\n  streamed: {:?}\n  expected: {:?}", name, index, blocks.get(index), expected.get(index)));
+            }
+            Err(e) => failures.push(format!("{}: {}", name, e)),
+        }
+    }
+    
+    assert!(failures.is_empty(), "{} fixtures stream differently:\n\n{}", failures.len(), failures.join("\n\n"));
+}